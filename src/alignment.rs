@@ -1,27 +1,36 @@
-use crate::io::parameters::AlignmentParameters;
+use crate::io::parameters::{AlignmentMode, AlignmentParameters};
 use crate::models::score_matrix::MatrixType::{Ix, Iy, M};
 use crate::models::score_matrix::{MatrixType, Pointer};
-use crate::models::AlignGrid;
-use crate::utils::{Epsilon, CHUNK_SIZE};
+use crate::models::{
+    AlignGrid, AlignedPair, Alignment, AlignmentOp, AlignmentResult, Residue, StartInfo,
+    TokenFormat, TracebackLimit, Truncation,
+};
+use crate::utils::Epsilon;
 use num_traits::Zero;
-use std::collections::HashSet;
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
 use std::fmt::Display;
-use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io;
 use std::str::FromStr;
 
-
-fn find_traceback_start<T: Copy + Display + Epsilon + FromStr + PartialOrd + Zero>(
+/// Optimal score and the cells a traceback starts from, sorted by row, column and
+/// matrix
+pub(crate) fn find_traceback_start<
+    T: Copy + Display + Epsilon + FromStr + PartialOrd + Zero,
+    S: Residue,
+>(
     align_grid: &AlignGrid<T>,
-    alignment_parameters: &AlignmentParameters<T>,
-) -> (T, HashSet<Pointer>) {
+    alignment_parameters: &AlignmentParameters<T, S>,
+) -> (T, Vec<Pointer>) {
     let mut max_val;
     let mut max_loc = HashSet::new();
 
     if alignment_parameters.global_alignment {
-        let max_row = align_grid.m_matrix.nrow - 1;
-        let max_col = align_grid.m_matrix.ncol - 1;
+        // A global alignment ends with the residues, leaving out any padding after them
+        let max_row = alignment_parameters.sequences.orig_len_a();
+        let max_col = alignment_parameters.sequences.orig_len_b();
 
         let m = align_grid.m_matrix.get_score(max_row, max_col);
         max_val = m;
@@ -45,16 +54,23 @@ fn find_traceback_start<T: Copy + Display + Epsilon + FromStr + PartialOrd + Zer
             max_loc.insert((Iy, max_row, max_col));
         }
     } else {
-        // Local alignment: search entire M matrix
+        // Local alignment: search entire M matrix, short of any padding, whose cells only
+        // repeat the scores before them. Only positive cells can start an alignment; when
+        // nothing scores above zero there is no local alignment, rather than one empty
+        // alignment per cell.
         max_val = T::zero();
         let m_matrix = &align_grid.m_matrix;
+        let sequences = &alignment_parameters.sequences;
 
         // Pre-allocate with reasonable capacity
         max_loc.reserve(16);
 
-        for row in 0..m_matrix.nrow {
-            for col in 0..m_matrix.ncol {
+        for row in 1..=sequences.orig_len_a() {
+            for col in 1..=sequences.orig_len_b() {
                 let val = m_matrix.get_score(row, col);
+                if val <= T::zero() || T::fuzzy_equals(val, T::zero()) {
+                    continue;
+                }
                 if val > max_val && !T::fuzzy_equals(val, max_val) {
                     max_val = val;
                     max_loc.clear();
@@ -66,144 +82,901 @@ fn find_traceback_start<T: Copy + Display + Epsilon + FromStr + PartialOrd + Zer
         }
     }
 
+    let mut max_loc: Vec<Pointer> = max_loc.into_iter().collect();
+    max_loc.sort_by_key(|&(matrix, row, col)| (row, col, matrix));
     (max_val, max_loc)
 }
 
-/// Perform traceback from a specific position using parent pointers
-fn traceback_from_position<T: Clone + Copy + Display + FromStr + Zero>(
-    align_grid: &AlignGrid<T>,
-    alignment_parameters: &AlignmentParameters<T>,
-    writer: &mut BufWriter<File>,
-    start_matrix: MatrixType,
-    start_row: usize,
-    start_col: usize,
-) -> Result<(), Box<dyn Error>> {
-    // Store nodes with parent indices to avoid cloning paths
-    struct Node {
-        pointer: Pointer,
-        parent: Option<usize>,
-    }
-
-    let mut nodes = Vec::with_capacity(1024);
-    let mut stack = Vec::with_capacity(256);
-    let mut leaf_nodes = Vec::with_capacity(256);
-
-    // Add initial node
-    nodes.push(Node {
-        pointer: (start_matrix, start_row, start_col),
-        parent: None,
-    });
-    stack.push(0);
+/// Bounds on how much of the pointer DAG a traceback enumerates. Unset limits do not
+/// apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TracebackLimits {
+    /// Trace back from at most this many start cells, the first in row-major order
+    pub max_starts: Option<usize>,
+    /// Enumerate at most this many paths from each start cell
+    pub max_paths_per_start: Option<usize>,
+    /// Stop once this many alignments have been built in total
+    pub max_alignments: Option<usize>,
+}
 
-    // Build traceback tree using iterative DFS
-    while let Some(node_idx) = stack.pop() {
-        let (matrix, row, col) = nodes[node_idx].pointer;
+/// Receives the alignments of a traceback one by one, as they are built, so that they
+/// can be written out without holding them all in memory
+pub trait AlignmentSink<T> {
+    /// Called once with the optimal score, before any alignment
+    fn begin(&mut self, _score: T) -> io::Result<()> {
+        Ok(())
+    }
 
-        let pointers = match matrix {
-            M => align_grid.m_matrix.get_pointers(row, col),
-            Ix => align_grid.ix_matrix.get_pointers(row, col),
-            Iy => align_grid.iy_matrix.get_pointers(row, col),
-        };
+    fn push(&mut self, alignment: Alignment) -> io::Result<()>;
 
-        if pointers.is_empty() {
-            leaf_nodes.push(node_idx);
-            continue;
-        }
+    /// Called once after the last alignment, e.g. to flush a writer
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
 
-        for &pointer in pointers {
-            let new_node_idx = nodes.len();
-            nodes.push(Node {
-                pointer,
-                parent: Some(node_idx),
-            });
-            stack.push(new_node_idx);
+impl<T, K: AlignmentSink<T> + ?Sized> AlignmentSink<T> for &mut K {
+    fn begin(&mut self, score: T) -> io::Result<()> {
+        (**self).begin(score)
+    }
+
+    fn push(&mut self, alignment: Alignment) -> io::Result<()> {
+        (**self).push(alignment)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        (**self).finish()
+    }
+}
+
+impl<T, K: AlignmentSink<T> + ?Sized> AlignmentSink<T> for Box<K> {
+    fn begin(&mut self, score: T) -> io::Result<()> {
+        (**self).begin(score)
+    }
+
+    fn push(&mut self, alignment: Alignment) -> io::Result<()> {
+        (**self).push(alignment)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        (**self).finish()
+    }
+}
+
+impl<T> AlignmentSink<T> for Vec<Alignment> {
+    fn push(&mut self, alignment: Alignment) -> io::Result<()> {
+        Vec::push(self, alignment);
+        Ok(())
+    }
+}
+
+/// Pointer DAG explored during a traceback.
+///
+/// Cells reachable along several tie paths are expanded once and shared, so exploring
+/// the DAG is linear in the number of reachable cells; only the path enumeration grows
+/// with the number of co-optimal alignments. The cache lives for one call to
+/// [`traceback`] and is shared between its start cells.
+struct TracebackDag {
+    index: HashMap<Pointer, usize>,
+    pointers: Vec<Pointer>,
+    children: Vec<Vec<usize>>,
+}
+
+impl TracebackDag {
+    fn new() -> Self {
+        Self {
+            index: HashMap::new(),
+            pointers: Vec::with_capacity(1024),
+            children: Vec::with_capacity(1024),
         }
     }
 
-    // Process in chunks to balance memory and I/O
+    fn insert(&mut self, pointer: Pointer) -> usize {
+        let idx = self.pointers.len();
+        self.index.insert(pointer, idx);
+        self.pointers.push(pointer);
+        self.children.push(Vec::new());
+        idx
+    }
+
+    /// Node for `start`, expanding every cell reachable from it that is not cached yet
+    fn expand<T: Copy + Display + Zero>(
+        &mut self,
+        align_grid: &AlignGrid<T>,
+        start: Pointer,
+    ) -> usize {
+        if let Some(&idx) = self.index.get(&start) {
+            return idx;
+        }
+        let root = self.insert(start);
+        let mut stack = vec![root];
+
+        while let Some(idx) = stack.pop() {
+            let (matrix, row, col) = self.pointers[idx];
+            let pointers = match matrix {
+                M => align_grid.m_matrix.get_pointers(row, col),
+                Ix => align_grid.ix_matrix.get_pointers(row, col),
+                Iy => align_grid.iy_matrix.get_pointers(row, col),
+            };
 
+            let mut children = Vec::with_capacity(pointers.len());
+            for pointer in pointers {
+                let child = match self.index.get(&pointer) {
+                    Some(&child) => child,
+                    None => {
+                        let child = self.insert(pointer);
+                        stack.push(child);
+                        child
+                    }
+                };
+                children.push(child);
+            }
+            self.children[idx] = children;
+        }
+        root
+    }
+}
+
+/// Build the alignment for one traceback path, given from its first column to the start cell
+fn path_alignment<T: Copy + FromStr + Zero, S: Residue>(
+    alignment_parameters: &AlignmentParameters<T, S>,
+    token_format: &TokenFormat,
+    path: impl Iterator<Item = Pointer>,
+) -> Alignment {
     let seq_a_chars = &alignment_parameters.sequences.seq_a;
     let seq_b_chars = &alignment_parameters.sequences.seq_b;
+    let gap_penalties = &alignment_parameters.gap_penalties;
 
     // Pre-allocate alignment buffers
     let estimated_len = seq_a_chars.len().max(seq_b_chars.len()) + 100;
+    let mut row_a = Vec::with_capacity(estimated_len);
+    let mut row_b = Vec::with_capacity(estimated_len);
+    let mut ops = Vec::with_capacity(estimated_len);
+    let mut start = None;
 
-    for chunk in leaf_nodes.chunks(CHUNK_SIZE) {
-        for &leaf_idx in chunk {
-            // Reconstruct path by walking back through parents
-            let mut path_len = 0;
-            let mut current_idx = Some(leaf_idx);
-            while let Some(idx) = current_idx {
-                path_len += 1;
-                current_idx = nodes[idx].parent;
+    let sequences = &alignment_parameters.sequences;
+    for (m, r, c) in path {
+        // The leaf is the first column. Cell (r, c) follows r residues of A and c of B, less
+        // the one each of them consumes.
+        start.get_or_insert(match m {
+            M => (r - 1, c - 1),
+            Ix => (r - 1, c),
+            Iy => (r, c - 1),
+        });
+        match m {
+            M => {
+                row_a.push(Some(&seq_a_chars[r - 1]));
+                row_b.push(Some(&seq_b_chars[c - 1]));
+                ops.push(AlignmentOp::Match);
+            }
+            Ix => {
+                if !gap_penalties.is_free_a_end(c, sequences.orig_len_b() + 1) {
+                    row_a.push(Some(&seq_a_chars[r - 1]));
+                    row_b.push(None);
+                    ops.push(AlignmentOp::Deletion);
+                }
             }
+            Iy => {
+                if !gap_penalties.is_free_b_end(r, sequences.orig_len_a() + 1) {
+                    row_a.push(None);
+                    row_b.push(Some(&seq_b_chars[c - 1]));
+                    ops.push(AlignmentOp::Insertion);
+                }
+            }
+        }
+    }
 
-            // Build alignment strings directly
-            let mut align_a = String::with_capacity(estimated_len);
-            let mut align_b = String::with_capacity(estimated_len);
+    let (start_a, start_b) = start.unwrap_or_default();
+    let mut alignment = Alignment::new(
+        token_format.render(&row_a),
+        token_format.render(&row_b),
+        start_a,
+        start_b,
+        ops,
+    );
+    if alignment_parameters.mode() == AlignmentMode::SoftClipA {
+        alignment.soft_clip_a = (start_a, sequences.orig_len_a() - alignment.end_a());
+    }
+    alignment
+}
 
-            current_idx = Some(leaf_idx);
-            while let Some(idx) = current_idx {
-                let (m, r, c) = nodes[idx].pointer;
+/// Builds alignments from traceback paths under one set of parameters and token format
+pub struct AlignmentEngine<'a, T: Copy + FromStr + Zero, S: Residue> {
+    alignment_parameters: &'a AlignmentParameters<T, S>,
+    token_format: &'a TokenFormat,
+}
 
-                match m {
-                    M => {
-                        align_a.push(seq_a_chars[r]);
-                        align_b.push(seq_b_chars[c]);
-                    }
-                    Ix => {
-                        if c < align_grid.ix_matrix.ncol - 1 {
-                            align_a.push(seq_a_chars[r]);
-                            align_b.push('_');
-                        }
-                    }
-                    Iy => {
-                        if r < align_grid.iy_matrix.nrow - 1 {
-                            align_a.push('_');
-                            align_b.push(seq_b_chars[c]);
-                        }
-                    }
-                }
+impl<'a, T: Copy + FromStr + Zero, S: Residue> AlignmentEngine<'a, T, S> {
+    pub fn new(
+        alignment_parameters: &'a AlignmentParameters<T, S>,
+        token_format: &'a TokenFormat,
+    ) -> Self {
+        Self {
+            alignment_parameters,
+            token_format,
+        }
+    }
 
-                current_idx = nodes[idx].parent;
-            }
+    /// The alignment along one traceback path with the columns where each row has a gap.
+    ///
+    /// `path` runs from the alignment's first column to the start cell of the traceback,
+    /// as the traceback itself walks it back.
+    pub fn path_to_aligned_pair(&self, path: Vec<Pointer>) -> AlignedPair {
+        AlignedPair::from(path_alignment(
+            self.alignment_parameters,
+            self.token_format,
+            path.into_iter(),
+        ))
+    }
+
+    /// The rows of the alignment along one traceback path and the columns, 0-based, where
+    /// each row has a gap, as `(seq_a, gaps_a, seq_b, gaps_b)`; see
+    /// [`path_to_aligned_pair`](Self::path_to_aligned_pair)
+    pub fn path_to_alignment_with_gaps(
+        &self,
+        path: Vec<Pointer>,
+    ) -> (String, Vec<usize>, String, Vec<usize>) {
+        self.path_to_aligned_pair(path).into_parts()
+    }
+}
 
-            // Write alignment
-            writer.write_all(b"\n")?;
-            writer.write_all(align_a.as_bytes())?;
-            writer.write_all(b"\n")?;
-            writer.write_all(align_b.as_bytes())?;
-            writer.write_all(b"\n")?;
+/// Perform traceback from a specific position, passing the paths through the DAG to
+/// `sink` until `max_paths` have been built. Returns whether it stopped at `max_paths`
+/// and how many paths it built.
+fn traceback_from_position<T: Clone + Copy + Display + FromStr + Zero, S: Residue>(
+    align_grid: &AlignGrid<T>,
+    alignment_parameters: &AlignmentParameters<T, S>,
+    token_format: &TokenFormat,
+    dag: &mut TracebackDag,
+    sink: &mut dyn AlignmentSink<T>,
+    start: Pointer,
+    max_paths: Option<usize>,
+) -> Result<(bool, usize), Box<dyn Error>> {
+    let root = dag.expand(align_grid, start);
+    let mut paths = 0;
+
+    // Iterative DFS over paths: each frame is a node and the next child to visit
+    let mut stack = vec![(root, 0)];
+    while let Some(&(node, next)) = stack.last() {
+        let children = &dag.children[node];
+        if children.is_empty() {
+            sink.push(path_alignment(
+                alignment_parameters,
+                token_format,
+                stack.iter().rev().map(|&(idx, _)| dag.pointers[idx]),
+            ))?;
+            paths += 1;
+            if max_paths == Some(paths) {
+                return Ok((true, paths));
+            }
+            stack.pop();
+        } else if next < children.len() {
+            if let Some(frame) = stack.last_mut() {
+                frame.1 += 1;
+            }
+            stack.push((children[next], 0));
+        } else {
+            stack.pop();
         }
     }
 
-    Ok(())
+    Ok((false, paths))
 }
 
-/// Perform traceback to generate alignments
-pub fn traceback<T: Copy + FromStr + Display + Epsilon + PartialOrd + Zero>(
+/// Perform traceback to generate alignments, in the canonical order of
+/// [`AlignmentResult::sort_canonical`]
+pub fn traceback<T: Copy + FromStr + Display + Epsilon + PartialOrd + Zero, S: Residue>(
     align_grid: &AlignGrid<T>,
-    alignment_parameters: &AlignmentParameters<T>,
-    output_file: &str
-) -> Result<(), Box<dyn Error>> {
-    let (max_val, max_loc) = find_traceback_start(align_grid, alignment_parameters);
+    alignment_parameters: &AlignmentParameters<T, S>,
+    token_format: &TokenFormat,
+) -> Result<AlignmentResult<T>, Box<dyn Error>> {
+    traceback_with_limits(
+        align_grid,
+        alignment_parameters,
+        token_format,
+        &TracebackLimits::default(),
+    )
+}
+
+/// Perform traceback within `limits`, recording in the result each limit that dropped
+/// start cells or alignments.
+///
+/// Start cells are traced in row-major order and the paths of each in a fixed order,
+/// so the alignments kept are the same on every run; they are returned in the canonical
+/// order of [`AlignmentResult::sort_canonical`]. How many were dropped is only counted,
+/// with [`count_optimal_paths`]' recurrence, once a limit has been reached.
+pub fn traceback_with_limits<
+    T: Copy + FromStr + Display + Epsilon + PartialOrd + Zero,
+    S: Residue,
+>(
+    align_grid: &AlignGrid<T>,
+    alignment_parameters: &AlignmentParameters<T, S>,
+    token_format: &TokenFormat,
+    limits: &TracebackLimits,
+) -> Result<AlignmentResult<T>, Box<dyn Error>> {
+    let (max_val, starts) = find_traceback_start(align_grid, alignment_parameters);
+    let mut alignments = Vec::new();
+    let truncated = traceback_starts_into(
+        align_grid,
+        alignment_parameters,
+        token_format,
+        limits,
+        &mut alignments,
+        max_val,
+        &starts,
+    )?;
+    let mut result = AlignmentResult::new(max_val, alignments);
+    result.truncated = truncated;
+    result.starts = starts.into_iter().map(StartInfo::from).collect();
+    result.sort_canonical();
+    Ok(result)
+}
+
+/// Perform traceback within `limits` as [`traceback_with_limits`] does, but hand each
+/// alignment to `sink` as soon as it is built rather than collecting them. Returns the
+/// optimal score and the limits that dropped start cells or alignments.
+///
+/// The sink receives the alignments in traversal order, which is the same on every run
+/// but not the canonical order of [`AlignmentResult::sort_canonical`].
+pub fn traceback_into<T: Copy + FromStr + Display + Epsilon + PartialOrd + Zero, S: Residue>(
+    align_grid: &AlignGrid<T>,
+    alignment_parameters: &AlignmentParameters<T, S>,
+    token_format: &TokenFormat,
+    limits: &TracebackLimits,
+    sink: &mut dyn AlignmentSink<T>,
+) -> Result<(T, Vec<Truncation>), Box<dyn Error>> {
+    let (max_val, starts) = find_traceback_start(align_grid, alignment_parameters);
+    let truncated = traceback_starts_into(
+        align_grid,
+        alignment_parameters,
+        token_format,
+        limits,
+        sink,
+        max_val,
+        &starts,
+    )?;
+    Ok((max_val, truncated))
+}
 
-    let file = File::create(output_file)?;
-    let mut writer = BufWriter::with_capacity(65536, file);
+/// Trace back from `starts`, the cells scoring `max_val` found by
+/// [`find_traceback_start`], as [`traceback_into`] does
+fn traceback_starts_into<T: Copy + FromStr + Display + Epsilon + PartialOrd + Zero, S: Residue>(
+    align_grid: &AlignGrid<T>,
+    alignment_parameters: &AlignmentParameters<T, S>,
+    token_format: &TokenFormat,
+    limits: &TracebackLimits,
+    sink: &mut dyn AlignmentSink<T>,
+    max_val: T,
+    starts: &[Pointer],
+) -> Result<Vec<Truncation>, Box<dyn Error>> {
+    sink.begin(max_val)?;
 
-    writeln!(writer, "{}", max_val)?;
+    let mut truncated = Vec::new();
+    let mut starts = starts;
+    if let Some(max_starts) = limits.max_starts.filter(|&max| starts.len() > max) {
+        truncated.push(Truncation {
+            limit: TracebackLimit::MaxStarts,
+            kept: max_starts as u128,
+            total: starts.len() as u128,
+        });
+        starts = &starts[..max_starts];
+    }
 
-    for (matrix, row, col) in max_loc {
-        traceback_from_position(
+    let mut built = 0;
+    let mut dag = TracebackDag::new();
+    let mut stopped = false;
+    for &start in starts {
+        let budget = limits.max_alignments.map(|max| max.saturating_sub(built));
+        if budget == Some(0) {
+            stopped = true;
+            break;
+        }
+        let max_paths = match (limits.max_paths_per_start, budget) {
+            (Some(per_start), Some(budget)) => Some(per_start.min(budget)),
+            (per_start, budget) => per_start.or(budget),
+        };
+        let (stopped_here, paths) = traceback_from_position(
             align_grid,
             alignment_parameters,
-            &mut writer,
-            matrix,
-            row,
-            col
+            token_format,
+            &mut dag,
+            sink,
+            start,
+            max_paths,
         )?;
+        stopped |= stopped_here;
+        built += paths;
+    }
+
+    if stopped {
+        // A limit was reached, but it only dropped alignments if more paths remained
+        let counts = PathCounts::new(align_grid);
+        let per_start = |start: &Pointer| {
+            let paths = counts.get(*start);
+            let max = limits
+                .max_paths_per_start
+                .map_or(u128::MAX, |max| max as u128);
+            (paths, paths.min(max))
+        };
+        let (total, capped) = starts.iter().map(per_start).fold((0u128, 0u128), |acc, p| {
+            (acc.0.saturating_add(p.0), acc.1.saturating_add(p.1))
+        });
+        if capped < total {
+            truncated.push(Truncation {
+                limit: TracebackLimit::MaxPathsPerStart,
+                kept: capped,
+                total,
+            });
+        }
+        let kept = built as u128;
+        if kept < capped {
+            truncated.push(Truncation {
+                limit: TracebackLimit::MaxAlignments,
+                kept,
+                total: capped,
+            });
+        }
     }
 
-    writer.flush()?;
-    Ok(())
-}
\ No newline at end of file
+    sink.finish()?;
+    Ok(truncated)
+}
+
+/// Perform traceback as [`traceback`] does, tracing the start cells in parallel.
+///
+/// Each start cell gets its own pointer DAG, so cells reachable from several starts are
+/// expanded once per start rather than once in all; this pays off when there are many
+/// start cells with many paths each. The alignments come out in the same canonical order
+/// as from [`traceback`], however many threads trace them.
+pub fn traceback_parallel<T, S>(
+    align_grid: &AlignGrid<T>,
+    alignment_parameters: &AlignmentParameters<T, S>,
+    token_format: &TokenFormat,
+) -> Result<AlignmentResult<T>, Box<dyn Error>>
+where
+    T: Copy + FromStr + Display + Epsilon + PartialOrd + Zero + Send + Sync,
+    S: Residue + Sync,
+{
+    let (max_val, starts) = find_traceback_start(align_grid, alignment_parameters);
+    let per_start = starts
+        .par_iter()
+        .map(|&start| {
+            let mut alignments = Vec::new();
+            traceback_from_position(
+                align_grid,
+                alignment_parameters,
+                token_format,
+                &mut TracebackDag::new(),
+                &mut alignments,
+                start,
+                None,
+            )
+            .map(|_| alignments)
+            .map_err(|e| e.to_string())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut result = AlignmentResult::new(max_val, per_start.concat());
+    result.starts = starts.into_iter().map(StartInfo::from).collect();
+    result.sort_canonical();
+    Ok(result)
+}
+
+/// Alignments traced from one optimal cell by [`traceback_per_cell`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellTraceback {
+    pub row: usize,
+    pub col: usize,
+    /// Number of alignments passed to the cell's sink
+    pub alignments: usize,
+    /// Whether a start reached `max_paths_per_start`, so that paths may have been left out
+    pub truncated: bool,
+}
+
+/// Trace back each optimal cell in its own rayon task, handing its alignments to a sink
+/// of its own that `open` creates from the cell's row and column, e.g. a file per cell.
+///
+/// The start cells of all three matrices at one row and column share a task and a
+/// sink, so a global alignment gets a single sink. Each sink receives the optimal score,
+/// then the cell's alignments in traversal order, at most `max_paths_per_start` of them
+/// from each start, and is dropped once finished; only one alignment per task is held in
+/// memory at a time. Returns the optimal score and the cells in row-major order.
+pub fn traceback_per_cell<T, S, W, F>(
+    align_grid: &AlignGrid<T>,
+    alignment_parameters: &AlignmentParameters<T, S>,
+    token_format: &TokenFormat,
+    max_paths_per_start: Option<usize>,
+    open: F,
+) -> Result<(T, Vec<CellTraceback>), Box<dyn Error>>
+where
+    T: Copy + FromStr + Display + Epsilon + PartialOrd + Zero + Send + Sync,
+    S: Residue,
+    W: AlignmentSink<T>,
+    F: Fn(usize, usize) -> io::Result<W> + Sync,
+{
+    let (max_val, starts) = find_traceback_start(align_grid, alignment_parameters);
+    let cells: Vec<&[Pointer]> = starts
+        .chunk_by(|&(_, r1, c1), &(_, r2, c2)| (r1, c1) == (r2, c2))
+        .collect();
+    let mut outcomes: Vec<Result<CellTraceback, String>> = vec![Err(String::new()); cells.len()];
+    let open = &open;
+    rayon::scope(|scope| {
+        for (&cell_starts, outcome) in cells.iter().zip(outcomes.iter_mut()) {
+            scope.spawn(move |_| {
+                let (_, row, col) = cell_starts[0];
+                let trace = || -> Result<CellTraceback, Box<dyn Error>> {
+                    let mut sink = open(row, col)?;
+                    sink.begin(max_val)?;
+                    let mut dag = TracebackDag::new();
+                    let mut cell = CellTraceback {
+                        row,
+                        col,
+                        alignments: 0,
+                        truncated: false,
+                    };
+                    for &start in cell_starts {
+                        let (stopped, paths) = traceback_from_position(
+                            align_grid,
+                            alignment_parameters,
+                            token_format,
+                            &mut dag,
+                            &mut sink,
+                            start,
+                            max_paths_per_start,
+                        )?;
+                        cell.alignments += paths;
+                        cell.truncated |= stopped;
+                    }
+                    sink.finish()?;
+                    Ok(cell)
+                };
+                *outcome = trace().map_err(|e| e.to_string());
+            });
+        }
+    });
+    let cells = outcomes.into_iter().collect::<Result<Vec<_>, _>>()?;
+    Ok((max_val, cells))
+}
+
+/// Trace back only the co-optimal path with the fewest steps.
+///
+/// Every step is one column of the alignment, counting the free end gaps that are not
+/// written out. A breadth-first search from the traceback starts over the pointer DAG reaches the
+/// leaf closest to a start first, and stops there, so at most one alignment is built
+/// however many co-optimal paths there are. Starts are visited in row-major order so
+/// ties between equally short paths resolve the same way on every run.
+pub fn traceback_shortest_path<
+    T: Copy + FromStr + Display + Epsilon + PartialOrd + Zero,
+    S: Residue,
+>(
+    align_grid: &AlignGrid<T>,
+    alignment_parameters: &AlignmentParameters<T, S>,
+    token_format: &TokenFormat,
+) -> AlignmentResult<T> {
+    let (max_val, starts) = find_traceback_start(align_grid, alignment_parameters);
+
+    let mut parents: HashMap<Pointer, Option<Pointer>> =
+        starts.iter().map(|&start| (start, None)).collect();
+    let mut alignments = Vec::new();
+    let mut queue: VecDeque<Pointer> = starts.iter().copied().collect();
+    while let Some(cell) = queue.pop_front() {
+        let (matrix, row, col) = cell;
+        let pointers = match matrix {
+            M => align_grid.m_matrix.get_pointers(row, col),
+            Ix => align_grid.ix_matrix.get_pointers(row, col),
+            Iy => align_grid.iy_matrix.get_pointers(row, col),
+        };
+        if pointers.is_empty() {
+            let path = std::iter::successors(Some(cell), |pointer| parents[pointer]);
+            let alignment = path_alignment(alignment_parameters, token_format, path);
+            alignments.push(alignment);
+            break;
+        }
+        for pointer in pointers {
+            parents.entry(pointer).or_insert_with(|| {
+                queue.push_back(pointer);
+                Some(cell)
+            });
+        }
+    }
+    let mut result = AlignmentResult::new(max_val, alignments);
+    result.starts = starts.into_iter().map(StartInfo::from).collect();
+    result
+}
+
+/// The aligned rows of the co-optimal path with the fewest steps, rendered with the
+/// default token format; see [`traceback_shortest_path`]
+pub fn traceback_shortest<T: Copy + FromStr + Display + Epsilon + PartialOrd + Zero, S: Residue>(
+    align_grid: &AlignGrid<T>,
+    alignment_parameters: &AlignmentParameters<T, S>,
+) -> Option<(String, String)> {
+    traceback_shortest_path(align_grid, alignment_parameters, &TokenFormat::default())
+        .alignments
+        .pop()
+        .map(|pair| {
+            let alignment = pair.into_alignment();
+            (alignment.align_a, alignment.align_b)
+        })
+}
+
+/// The `k` best local alignments, whether or not they share the optimal score.
+///
+/// Every cell of M scoring above `min_score` (and zero) is a candidate end, taken in
+/// decreasing order of score and row-major order among ties. The first path back from
+/// each candidate is traced, following the first pointer of every cell, and kept unless
+/// it passes through a cell of an alignment already kept: otherwise the same hit would
+/// come back once for every cell it can be extended by. The covered cells are kept in a
+/// mask over the grid, so unlike Waterman-Eggert nothing is refilled, and a hit whose
+/// best path runs through a stronger one is dropped rather than rescored without it.
+///
+/// The result's score is that of the best alignment, and
+/// [`AlignmentResult::alignment_scores`] holds the score of each.
+pub fn traceback_top_hits<T: Copy + FromStr + Display + Epsilon + PartialOrd + Zero, S: Residue>(
+    align_grid: &AlignGrid<T>,
+    alignment_parameters: &AlignmentParameters<T, S>,
+    token_format: &TokenFormat,
+    k: usize,
+    min_score: T,
+) -> AlignmentResult<T> {
+    let m_matrix = &align_grid.m_matrix;
+    let threshold = if min_score > T::zero() {
+        min_score
+    } else {
+        T::zero()
+    };
+    let sequences = &alignment_parameters.sequences;
+    let mut candidates: Vec<(T, Pointer)> = Vec::new();
+    for row in 1..=sequences.orig_len_a() {
+        for col in 1..=sequences.orig_len_b() {
+            let score = m_matrix.get_score(row, col);
+            if score > threshold && !T::fuzzy_equals(score, threshold) {
+                candidates.push((score, (M, row, col)));
+            }
+        }
+    }
+    // Stable, so ties stay in row-major order
+    candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+    let mut covered = vec![false; m_matrix.nrow * m_matrix.ncol];
+    let mut alignments = Vec::new();
+    let mut scores = Vec::new();
+    for (score, start) in candidates {
+        if alignments.len() == k {
+            break;
+        }
+        let path: Vec<Pointer> = std::iter::successors(Some(start), |&(matrix, row, col)| {
+            let pointers = match matrix {
+                M => align_grid.m_matrix.get_pointers(row, col),
+                Ix => align_grid.ix_matrix.get_pointers(row, col),
+                Iy => align_grid.iy_matrix.get_pointers(row, col),
+            };
+            pointers.first().copied()
+        })
+        .collect();
+        if path
+            .iter()
+            .any(|&(_, row, col)| covered[row * m_matrix.ncol + col])
+        {
+            continue;
+        }
+        for &(_, row, col) in &path {
+            covered[row * m_matrix.ncol + col] = true;
+        }
+        alignments.push(path_alignment(
+            alignment_parameters,
+            token_format,
+            path.into_iter().rev(),
+        ));
+        scores.push(score);
+    }
+
+    let best = scores.first().copied().unwrap_or_else(T::zero);
+    let mut result = AlignmentResult::new(best, alignments);
+    result.alignment_scores = Some(scores);
+    result.sort_canonical();
+    result
+}
+
+/// Number of pointer paths from each cell to a leaf.
+///
+/// Every pointer moves to a cell with a smaller `row + col`, so visiting cells in
+/// row-major order is a topological order of the pointer DAG. A cell without pointers
+/// ends one path; any other cell has as many paths as its pointer targets combined.
+/// Counts saturate at `u128::MAX`.
+struct PathCounts {
+    ncol: usize,
+    /// counts[matrix][row * ncol + col]
+    counts: [Vec<u128>; 3],
+}
+
+impl PathCounts {
+    fn new<T: Copy + Display + Zero>(align_grid: &AlignGrid<T>) -> Self {
+        let (nrow, ncol) = (align_grid.m_matrix.nrow, align_grid.m_matrix.ncol);
+        let mut path_counts = Self {
+            ncol,
+            counts: [
+                vec![0u128; nrow * ncol],
+                vec![0; nrow * ncol],
+                vec![0; nrow * ncol],
+            ],
+        };
+
+        for row in 0..nrow {
+            for col in 0..ncol {
+                for matrix in [M, Ix, Iy] {
+                    let pointers = match matrix {
+                        M => align_grid.m_matrix.get_pointers(row, col),
+                        Ix => align_grid.ix_matrix.get_pointers(row, col),
+                        Iy => align_grid.iy_matrix.get_pointers(row, col),
+                    };
+                    let count = if pointers.is_empty() {
+                        1
+                    } else {
+                        pointers.iter().fold(0u128, |total, &pointer| {
+                            total.saturating_add(path_counts.get(pointer))
+                        })
+                    };
+                    path_counts.counts[matrix as usize][row * ncol + col] = count;
+                }
+            }
+        }
+        path_counts
+    }
+
+    fn get(&self, (matrix, row, col): Pointer) -> u128 {
+        self.counts[matrix as usize][row * self.ncol + col]
+    }
+}
+
+/// Count the co-optimal alignments a traceback would produce, without enumerating
+/// them. Counts saturate at `u128::MAX`.
+pub fn count_optimal_paths<
+    T: Copy + FromStr + Display + Epsilon + PartialOrd + Zero,
+    S: Residue,
+>(
+    align_grid: &AlignGrid<T>,
+    alignment_parameters: &AlignmentParameters<T, S>,
+) -> u128 {
+    estimate_alignments(
+        align_grid,
+        alignment_parameters,
+        &TracebackLimits::default(),
+    )
+}
+
+/// Count the alignments a traceback under `limits` would enumerate before any
+/// `max_alignments` cap: the paths from the first `max_starts` start cells, at most
+/// `max_paths_per_start` from each.
+///
+/// The counts follow the same pointers as the traceback, so the estimate is exact
+/// unless it saturates at `u128::MAX`.
+pub fn estimate_alignments<
+    T: Copy + FromStr + Display + Epsilon + PartialOrd + Zero,
+    S: Residue,
+>(
+    align_grid: &AlignGrid<T>,
+    alignment_parameters: &AlignmentParameters<T, S>,
+    limits: &TracebackLimits,
+) -> u128 {
+    let (_, starts) = find_traceback_start(align_grid, alignment_parameters);
+    let counts = PathCounts::new(align_grid);
+    let max_paths = limits
+        .max_paths_per_start
+        .map_or(u128::MAX, |max| max as u128);
+    starts
+        .iter()
+        .take(limits.max_starts.unwrap_or(usize::MAX))
+        .fold(0u128, |total, &start| {
+            total.saturating_add(counts.get(start).min(max_paths))
+        })
+}
+
+/// Where gaps are placed when several placements describe the same event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GapCanonicalForm {
+    /// Shift every gap as far left as it can go
+    #[default]
+    Left,
+    /// Shift every gap as far right as it can go
+    Right,
+}
+
+impl FromStr for GapCanonicalForm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "left" => Ok(GapCanonicalForm::Left),
+            "right" => Ok(GapCanonicalForm::Right),
+            _ => Err(format!("Unknown canonical form: {}", s)),
+        }
+    }
+}
+
+/// Shift each gap run in `gapped` left while the residue it passes over in the other
+/// row equals the one it uncovers, so the set of aligned columns is unchanged
+fn shift_gaps_left(gapped: &mut [char], other: &[char], gap: char) -> bool {
+    let mut moved = false;
+    let mut col = 0;
+    while col < gapped.len() {
+        if gapped[col] != gap {
+            col += 1;
+            continue;
+        }
+        let (mut start, mut end) = (col, col);
+        while end < gapped.len() && gapped[end] == gap {
+            end += 1;
+        }
+        col = end;
+        while start > 0
+            && gapped[start - 1] != gap
+            && other[start - 1] != gap
+            && other[start - 1] == other[end - 1]
+        {
+            gapped.swap(start - 1, end - 1);
+            start -= 1;
+            end -= 1;
+            moved = true;
+        }
+    }
+    moved
+}
+
+/// Normalize gap placement in a pair of aligned rows.
+///
+/// A gap run inside a repeat, such as `A_A` against `AAA`, can sit anywhere in the
+/// repeat without changing which residues are aligned or the score. Each run is moved
+/// as a whole to its leftmost or rightmost such position, so alignments that differ
+/// only in where the gap sits within the repeat become identical. `gap` is the character
+/// the rows write gaps with.
+pub fn canonical_alignment(
+    seq_a: &str,
+    seq_b: &str,
+    gap: char,
+    form: GapCanonicalForm,
+) -> (String, String) {
+    let mut row_a: Vec<char> = seq_a.chars().collect();
+    let mut row_b: Vec<char> = seq_b.chars().collect();
+    if form == GapCanonicalForm::Right {
+        row_a.reverse();
+        row_b.reverse();
+    }
+
+    // Shifting gaps in one row can uncover further shifts in the other
+    while shift_gaps_left(&mut row_a, &row_b, gap) | shift_gaps_left(&mut row_b, &row_a, gap) {}
+
+    if form == GapCanonicalForm::Right {
+        row_a.reverse();
+        row_b.reverse();
+    }
+    (row_a.into_iter().collect(), row_b.into_iter().collect())
+}
+
+/// Put every character alignment, its rows writing gaps as `gap`, in canonical form and
+/// drop the duplicates this exposes, keeping the first occurrence of each
+pub fn dedup_canonical(alignments: &mut Vec<AlignedPair>, gap: char, form: GapCanonicalForm) {
+    let mut seen = HashSet::new();
+    alignments.retain_mut(|pair| {
+        pair.modify(|alignment| canonicalize(alignment, gap, form));
+        seen.insert((
+            pair.align_a.clone(),
+            pair.align_b.clone(),
+            pair.start_a,
+            pair.start_b,
+        ))
+    });
+}
+
+fn canonicalize(alignment: &mut Alignment, gap: char, form: GapCanonicalForm) {
+    let (align_a, align_b) = canonical_alignment(&alignment.align_a, &alignment.align_b, gap, form);
+    alignment.ops = align_a
+        .chars()
+        .zip(align_b.chars())
+        .map(|(a, b)| match (a == gap, b == gap) {
+            (true, _) => AlignmentOp::Insertion,
+            (_, true) => AlignmentOp::Deletion,
+            _ => AlignmentOp::Match,
+        })
+        .collect();
+    alignment.align_a = align_a;
+    alignment.align_b = align_b;
+}
@@ -0,0 +1,492 @@
+use crate::alignment::traceback;
+use crate::distance::{DistanceMetric, PairDistances};
+use crate::error::AlignmentError;
+use crate::fast_path::trivial_alignment;
+use crate::io::fasta::FastaRecord;
+use crate::io::parameters::AlignmentParameters;
+use crate::kmer::{KmerFilter, KmerSet};
+use crate::models::{AlignGrid, AlignmentResult, Sequences, TokenFormat};
+use crate::score_only::{global_score, local_score};
+use crate::stats::CoverageFilter;
+use ndarray::Array2;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::io;
+use std::io::{BufRead, Write};
+
+/// Result of aligning one pair of records in a batch
+#[derive(Debug, Clone)]
+pub struct PairResult {
+    /// Index of the first record
+    pub index_a: usize,
+    /// Index of the second record
+    pub index_b: usize,
+    /// `None` if the pair was skipped by the k-mer prefilter
+    pub result: Option<AlignmentResult>,
+    /// Distances from the first reported alignment, if there is one
+    pub distances: Option<PairDistances>,
+    /// Fractions of each record spanned by the first reported alignment; see
+    /// [`crate::models::Alignment::coverage`]
+    pub coverage_a: Option<f64>,
+    pub coverage_b: Option<f64>,
+}
+
+impl PairResult {
+    /// A pair the prefilter skipped, without a result
+    fn skipped(index_a: usize, index_b: usize) -> Self {
+        Self {
+            index_a,
+            index_b,
+            result: None,
+            distances: None,
+            coverage_a: None,
+            coverage_b: None,
+        }
+    }
+
+    /// Drop the result and distances of a pair whose alignment spans too little of
+    /// either record, keeping its coverage to show why. Returns whether the pair passed.
+    pub fn apply_coverage_filter(&mut self, filter: &CoverageFilter) -> bool {
+        let passes = filter.passes((self.coverage_a, self.coverage_b));
+        if !passes {
+            self.result = None;
+            self.distances = None;
+        }
+        passes
+    }
+}
+
+/// Pairs aligned in parallel before their results are passed on in order
+const PAIR_CHUNK: usize = 1024;
+
+/// Columns of the pairs TSV written by [`write_pairs_tsv`]
+const PAIRS_TSV_HEADER: &str = "id_a\tid_b\tscore\tcompared\tmismatches\tp_distance\t\
+     jc_distance\tscore_distance\tcoverage_a\tcoverage_b";
+
+fn align_pair(
+    records: &[FastaRecord],
+    template: &AlignmentParameters<f64>,
+    index_a: usize,
+    index_b: usize,
+) -> Result<PairResult, AlignmentError> {
+    align_records(
+        &records[index_a],
+        &records[index_b],
+        template,
+        index_a,
+        index_b,
+    )
+}
+
+fn align_records(
+    record_a: &FastaRecord,
+    record_b: &FastaRecord,
+    template: &AlignmentParameters<f64>,
+    index_a: usize,
+    index_b: usize,
+) -> Result<PairResult, AlignmentError> {
+    let parameters = AlignmentParameters {
+        sequences: Sequences::from_string(record_a.sequence.clone(), record_b.sequence.clone()),
+        ..template.clone()
+    };
+    let token_format = TokenFormat::default();
+    match trivial_alignment(&parameters, &token_format) {
+        Some(result) => Ok(result),
+        None => {
+            let mut grid = AlignGrid::new(parameters.len_a(), parameters.len_b());
+            grid.populate_score_matrices(&parameters)
+                .and_then(|_| traceback(&grid, &parameters, &token_format))
+        }
+    }
+    .map(|result| {
+        let first = result.alignments.first();
+        let distances = first.map(|alignment| {
+            PairDistances::from_alignment(alignment, result.score_ratio(&parameters))
+        });
+        let (coverage_a, coverage_b) = first.map_or((None, None), |alignment| {
+            alignment.coverage(parameters.len_a(), parameters.len_b())
+        });
+        PairResult {
+            index_a,
+            index_b,
+            result: Some(result),
+            distances,
+            coverage_a,
+            coverage_b,
+        }
+    })
+    .map_err(|e| AlignmentError::InvalidInput(format!("{} vs {}: {}", record_a.id, record_b.id, e)))
+}
+
+/// Align every pair of records for which `skip` is false, passing each result to `sink`.
+///
+/// Pairs are taken in order `(0, 1), (0, 2), ..., (n-2, n-1)` and aligned in parallel a
+/// chunk at a time; `sink` sees the results in that same order, so it can write them
+/// out as they arrive. With a `prefilter`, each record's k-mers are collected once and
+/// pairs that share too few are passed on without a result instead of being aligned.
+pub fn for_each_pair<K, F>(
+    records: &[FastaRecord],
+    template: &AlignmentParameters<f64>,
+    prefilter: Option<&KmerFilter>,
+    skip: K,
+    mut sink: F,
+) -> Result<(), AlignmentError>
+where
+    K: Fn(usize, usize) -> bool,
+    F: FnMut(PairResult) -> io::Result<()>,
+{
+    let kmers: Vec<KmerSet> = prefilter.map_or_else(Vec::new, |filter| {
+        records
+            .par_iter()
+            .map(|record| filter.kmers(&record.sequence))
+            .collect()
+    });
+
+    let n = records.len();
+    let mut pairs = (0..n)
+        .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+        .filter(|&(index_a, index_b)| !skip(index_a, index_b));
+    let mut chunk = Vec::with_capacity(PAIR_CHUNK);
+    loop {
+        chunk.clear();
+        chunk.extend(pairs.by_ref().take(PAIR_CHUNK));
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        let results = chunk
+            .par_iter()
+            .map(|&(index_a, index_b)| {
+                if prefilter.is_some_and(|filter| !filter.passes(&kmers[index_a], &kmers[index_b]))
+                {
+                    return Ok(PairResult::skipped(index_a, index_b));
+                }
+                align_pair(records, template, index_a, index_b)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for result in results {
+            sink(result)?;
+        }
+    }
+}
+
+/// Align records `(0, 1), (2, 3), ...` of a FASTA stream as pairs, passing each result
+/// to `sink` with its two records.
+///
+/// Pairs are read and aligned in parallel a chunk at a time, so only one chunk of records
+/// is held in memory; `sink` sees the results in file order. The [`PairResult`] indices
+/// are those of the records in the file. With a `prefilter`, pairs that share too few
+/// k-mers are passed on without a result. Fails after passing on every complete pair if
+/// the last record has no partner.
+pub fn for_each_consecutive_pair<I, F>(
+    records: I,
+    template: &AlignmentParameters<f64>,
+    prefilter: Option<&KmerFilter>,
+    mut sink: F,
+) -> Result<(), AlignmentError>
+where
+    I: IntoIterator<Item = io::Result<FastaRecord>>,
+    F: FnMut(&FastaRecord, &FastaRecord, PairResult) -> io::Result<()>,
+{
+    let mut records = records.into_iter();
+    let mut chunk: Vec<(FastaRecord, FastaRecord)> = Vec::with_capacity(PAIR_CHUNK);
+    let mut read = 0;
+    loop {
+        chunk.clear();
+        let mut unpaired = None;
+        while chunk.len() < PAIR_CHUNK {
+            let Some(record_a) = records.next().transpose()? else {
+                break;
+            };
+            match records.next().transpose()? {
+                Some(record_b) => chunk.push((record_a, record_b)),
+                None => {
+                    unpaired = Some(record_a);
+                    break;
+                }
+            }
+        }
+        let first = read;
+        read += 2 * chunk.len();
+        let results = chunk
+            .par_iter()
+            .enumerate()
+            .map(|(i, (record_a, record_b))| {
+                let (index_a, index_b) = (first + 2 * i, first + 2 * i + 1);
+                if let Some(filter) = prefilter {
+                    let (kmers_a, kmers_b) = (
+                        filter.kmers(&record_a.sequence),
+                        filter.kmers(&record_b.sequence),
+                    );
+                    if !filter.passes(&kmers_a, &kmers_b) {
+                        return Ok(PairResult::skipped(index_a, index_b));
+                    }
+                }
+                align_records(record_a, record_b, template, index_a, index_b)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for ((record_a, record_b), result) in chunk.iter().zip(results) {
+            sink(record_a, record_b, result)?;
+        }
+        if let Some(record) = unpaired {
+            return Err(AlignmentError::InvalidInput(format!(
+                "the FASTA file has an odd number of records; {} (record {}) has no partner",
+                record.id,
+                read + 1
+            )));
+        }
+        if chunk.len() < PAIR_CHUNK {
+            return Ok(());
+        }
+    }
+}
+
+/// Align every pair of records with the scoring model of `template`.
+///
+/// Pairs are aligned in parallel and returned in order `(0, 1), (0, 2), ..., (n-2, n-1)`;
+/// see [`for_each_pair`] for the prefilter.
+pub fn all_vs_all(
+    records: &[FastaRecord],
+    template: &AlignmentParameters<f64>,
+    prefilter: Option<&KmerFilter>,
+) -> Result<Vec<PairResult>, AlignmentError> {
+    let mut results = Vec::new();
+    for_each_pair(
+        records,
+        template,
+        prefilter,
+        |_, _| false,
+        |result| {
+            results.push(result);
+            Ok(())
+        },
+    )?;
+    Ok(results)
+}
+
+/// Optimal score of every pair of records, and of each record against itself on the
+/// diagonal, computed in parallel with the score-only recurrences of
+/// [`crate::score_only`], without traceback or pointers.
+///
+/// Only pairs `i <= j` are aligned, record `i` as A against record `j` as B, and each
+/// score is copied below the diagonal, so a scoring model that treats A and B differently
+/// gives the score of the earlier record as A. Fails for a global alignment with charged
+/// end gaps, which the score-only recurrences do not handle.
+pub fn score_table(
+    records: &[FastaRecord],
+    template: &AlignmentParameters<f64>,
+) -> Result<Array2<f64>, AlignmentError> {
+    let gaps = &template.gap_penalties;
+    if template.global_alignment
+        && (gaps.a_end_penalties().is_some() || gaps.b_end_penalties().is_some())
+    {
+        return Err(AlignmentError::IncompatibleParameters(
+            "a score table needs free end gaps in global alignments".to_string(),
+        ));
+    }
+    let sequences: Vec<Vec<char>> = records
+        .iter()
+        .map(|record| record.sequence.chars().collect())
+        .collect();
+    let n = records.len();
+    let pairs: Vec<(usize, usize)> = (0..n).flat_map(|i| (i..n).map(move |j| (i, j))).collect();
+    let scores: Vec<f64> = pairs
+        .par_iter()
+        .map(|&(i, j)| {
+            let score = if template.global_alignment {
+                global_score
+            } else {
+                local_score
+            };
+            score(&sequences[i], &sequences[j], &template.match_matrix, gaps)
+        })
+        .collect();
+
+    let mut table = Array2::zeros((n, n));
+    for (&(i, j), score) in pairs.iter().zip(scores) {
+        table[[i, j]] = score;
+        table[[j, i]] = score;
+    }
+    Ok(table)
+}
+
+/// Write a [`score_table`] as a tab-separated matrix, with the record names heading the
+/// columns and starting each row
+pub fn write_score_table<W: Write>(
+    writer: &mut W,
+    names: &[String],
+    table: &Array2<f64>,
+) -> io::Result<()> {
+    for name in names {
+        write!(writer, "\t{}", name)?;
+    }
+    writeln!(writer)?;
+    for (name, row) in names.iter().zip(table.rows()) {
+        write!(writer, "{}", name)?;
+        for score in row {
+            write!(writer, "\t{}", score)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Square matrix of one distance metric, indexed by record
+pub fn distance_matrix(
+    n: usize,
+    pairs: &[PairResult],
+    metric: DistanceMetric,
+) -> Vec<Vec<Option<f64>>> {
+    let mut matrix = vec![vec![None; n]; n];
+    for pair in pairs {
+        let distance = pair.distances.and_then(|d| d.get(metric));
+        matrix[pair.index_a][pair.index_b] = distance;
+        matrix[pair.index_b][pair.index_a] = distance;
+    }
+    matrix
+}
+
+fn format_distance(distance: Option<f64>) -> String {
+    distance.map_or_else(|| "NA".to_string(), |d| format!("{:.6}", d))
+}
+
+fn format_coverage(coverage: Option<f64>) -> String {
+    coverage.map_or_else(|| "NA".to_string(), |c| format!("{:.4}", c))
+}
+
+pub fn write_pairs_header<W: Write>(writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "{}", PAIRS_TSV_HEADER)
+}
+
+/// Write the row for one pair; undefined distances and coverage, and the score of a pair
+/// skipped by the prefilter or the coverage filter, are written as `NA`
+pub fn write_pair_row<W: Write>(
+    writer: &mut W,
+    records: &[FastaRecord],
+    pair: &PairResult,
+) -> io::Result<()> {
+    write_pair_row_ids(
+        writer,
+        &records[pair.index_a].id,
+        &records[pair.index_b].id,
+        pair,
+    )
+}
+
+/// Write the row for one pair of records with IDs `id_a` and `id_b`; see
+/// [`write_pair_row`]
+pub fn write_pair_row_ids<W: Write>(
+    writer: &mut W,
+    id_a: &str,
+    id_b: &str,
+    pair: &PairResult,
+) -> io::Result<()> {
+    let distances = pair.distances;
+    writeln!(
+        writer,
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        id_a,
+        id_b,
+        pair.result
+            .as_ref()
+            .map_or_else(|| "NA".to_string(), |result| result.score.to_string()),
+        distances.map_or(0, |d| d.compared),
+        distances.map_or(0, |d| d.mismatches),
+        format_distance(distances.and_then(|d| d.p_distance)),
+        format_distance(distances.and_then(|d| d.jukes_cantor)),
+        format_distance(distances.map(|d| d.score_distance)),
+        format_coverage(pair.coverage_a),
+        format_coverage(pair.coverage_b),
+    )
+}
+
+/// Write a header and one tab-separated row per pair
+pub fn write_pairs_tsv<W: Write>(
+    writer: &mut W,
+    records: &[FastaRecord],
+    pairs: &[PairResult],
+) -> io::Result<()> {
+    write_pairs_header(writer)?;
+    for pair in pairs {
+        write_pair_row(writer, records, pair)?;
+    }
+    Ok(())
+}
+
+/// Pairs already present in a pairs TSV, for resuming an interrupted batch
+#[derive(Debug, Clone, Default)]
+pub struct CompletedPairs {
+    /// Distances of each completed pair, keyed by the two record IDs
+    pub pairs: BTreeMap<(String, String), Option<PairDistances>>,
+    /// Length in bytes of the file up to the end of the last complete row
+    pub complete_len: u64,
+}
+
+fn parse_distance(field: &str) -> Result<Option<f64>, String> {
+    if field == "NA" {
+        return Ok(None);
+    }
+    field.parse().map(Some).map_err(|e| format!("{}", e))
+}
+
+/// Parse one complete pairs TSV row into its IDs and distances
+fn parse_pair_row(line: &str) -> Result<((String, String), Option<PairDistances>), String> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    let [id_a, id_b, _score, compared, mismatches, p_distance, jc_distance, score_distance, _, _] =
+        fields[..]
+    else {
+        return Err(format!("expected 10 fields, got {}", fields.len()));
+    };
+    // Only pairs without an alignment have no score distance
+    let distances = match parse_distance(score_distance)? {
+        Some(score_distance) => Some(PairDistances {
+            compared: compared.parse().map_err(|e| format!("{}", e))?,
+            mismatches: mismatches.parse().map_err(|e| format!("{}", e))?,
+            p_distance: parse_distance(p_distance)?,
+            jukes_cantor: parse_distance(jc_distance)?,
+            score_distance,
+        }),
+        None => None,
+    };
+    Ok(((id_a.to_string(), id_b.to_string()), distances))
+}
+
+/// Scan a pairs TSV left by an interrupted batch run.
+///
+/// A row counts as complete only if it ends in a newline, so a row cut off mid-write is
+/// left out of the result and of [`CompletedPairs::complete_len`]. The file is read one
+/// line at a time.
+pub fn read_completed_pairs<R: BufRead>(mut reader: R) -> io::Result<CompletedPairs> {
+    let mut completed = CompletedPairs::default();
+    let mut line = Vec::new();
+    let mut line_number = 0;
+
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 || line.last() != Some(&b'\n') {
+            return Ok(completed);
+        }
+        line_number += 1;
+        let text = std::str::from_utf8(&line[..read - 1])
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+            .trim_end_matches('\r');
+
+        if line_number == 1 {
+            if text != PAIRS_TSV_HEADER {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Existing output does not start with the batch TSV header",
+                ));
+            }
+        } else {
+            let (ids, distances) = parse_pair_row(text).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid batch row on line {}: {}", line_number, e),
+                )
+            })?;
+            completed.pairs.insert(ids, distances);
+        }
+        completed.complete_len += read as u64;
+    }
+}
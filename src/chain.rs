@@ -0,0 +1,145 @@
+use crate::models::{Alignment, GapPenalties};
+use std::cmp::Ordering;
+use std::io;
+use std::io::Write;
+
+/// A local alignment with the score it was found with
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoredLocalAlignment {
+    pub alignment: Alignment,
+    pub score: f64,
+}
+
+/// Colinear hits chosen by [`chain`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chain {
+    /// Indices of the chained hits, in order along both sequences
+    pub hits: Vec<usize>,
+    /// Sum of the hit scores less the cost of each link between them
+    pub score: f64,
+}
+
+impl Chain {
+    /// Residues of A and of B covered by the chained hits
+    pub fn covered(&self, hits: &[ScoredLocalAlignment]) -> (usize, usize) {
+        self.hits.iter().fold((0, 0), |(a, b), &i| {
+            let alignment = &hits[i].alignment;
+            (
+                a + alignment.end_a() - alignment.start_a,
+                b + alignment.end_b() - alignment.start_b,
+            )
+        })
+    }
+}
+
+/// Cost of following hit `previous` with hit `next`: the gap that takes the chain from
+/// the diagonal `previous` ends on to the one `next` starts on. Residues of both
+/// sequences between the two hits are otherwise left unaligned for free.
+pub fn link_cost(previous: &Alignment, next: &Alignment, gaps: &GapPenalties<f64>) -> f64 {
+    let skipped_a = next.start_a - previous.end_a();
+    let skipped_b = next.start_b - previous.end_b();
+    let affine = |open: f64, extend: f64, length: usize| open + (length - 1) as f64 * extend;
+    match skipped_a.cmp(&skipped_b) {
+        Ordering::Equal => 0.0,
+        // B residues left over are a gap in A
+        Ordering::Less => affine(gaps.dx, gaps.ex, skipped_b - skipped_a),
+        Ordering::Greater => affine(gaps.dy, gaps.ey, skipped_a - skipped_b),
+    }
+}
+
+/// Whether `next` starts after `previous` ends in both sequences
+fn follows(previous: &Alignment, next: &Alignment) -> bool {
+    previous.end_a() <= next.start_a && previous.end_b() <= next.start_b
+}
+
+/// The best colinear chain of local alignments: the hits, in order along both sequences
+/// and not overlapping in either, with the highest total score less the [`link_cost`] of
+/// each link. Quadratic in the number of hits. Ties go to the chain whose hits start
+/// earliest; no hits give an empty chain with score 0.
+pub fn chain(hits: &[ScoredLocalAlignment], gaps: &GapPenalties<f64>) -> Chain {
+    let mut order: Vec<usize> = (0..hits.len()).collect();
+    order.sort_by_key(|&i| (hits[i].alignment.start_a, hits[i].alignment.start_b, i));
+
+    // best[k] is the best chain ending with hit order[k], linked through previous[k]
+    let mut best: Vec<f64> = Vec::with_capacity(order.len());
+    let mut previous: Vec<Option<usize>> = Vec::with_capacity(order.len());
+    for (k, &next) in order.iter().enumerate() {
+        let next_alignment = &hits[next].alignment;
+        let mut link = None;
+        let mut linked = 0.0;
+        for (j, &prev) in order[..k].iter().enumerate() {
+            let prev_alignment = &hits[prev].alignment;
+            if !follows(prev_alignment, next_alignment) {
+                continue;
+            }
+            let score = best[j] - link_cost(prev_alignment, next_alignment, gaps);
+            if score > linked {
+                linked = score;
+                link = Some(j);
+            }
+        }
+        best.push(hits[next].score + linked);
+        previous.push(link);
+    }
+
+    let mut end = None;
+    let mut score = 0.0;
+    for (k, &chained) in best.iter().enumerate() {
+        if end.is_none() || chained > score {
+            end = Some(k);
+            score = chained;
+        }
+    }
+    let mut chained = Vec::new();
+    while let Some(k) = end {
+        chained.push(order[k]);
+        end = previous[k];
+    }
+    chained.reverse();
+    Chain {
+        hits: chained,
+        score,
+    }
+}
+
+/// Write a chain as TSV: comments with the chained score and the fraction of each
+/// sequence covered, then one line per hit with its index in `hits` and its ranges
+/// (1-based, inclusive)
+pub fn write_chain_tsv<W: Write>(
+    writer: &mut W,
+    chain: &Chain,
+    hits: &[ScoredLocalAlignment],
+    len_a: usize,
+    len_b: usize,
+) -> io::Result<()> {
+    let (covered_a, covered_b) = chain.covered(hits);
+    let fraction = |covered: usize, len: usize| {
+        if len > 0 {
+            covered as f64 / len as f64
+        } else {
+            0.0
+        }
+    };
+    writeln!(writer, "# chained score: {}", chain.score)?;
+    writeln!(
+        writer,
+        "# covered: A {:.4}, B {:.4}",
+        fraction(covered_a, len_a),
+        fraction(covered_b, len_b)
+    )?;
+    writeln!(writer, "alignment\ta_start\ta_end\tb_start\tb_end\tscore")?;
+    for &i in &chain.hits {
+        let alignment = &hits[i].alignment;
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            i + 1,
+            alignment.start_a + 1,
+            alignment.end_a(),
+            alignment.start_b + 1,
+            alignment.end_b(),
+            hits[i].score
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,1277 @@
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::error::ErrorKind;
+use clap::{ArgAction, Args, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use sequence_alignment::alignment::{GapCanonicalForm, TracebackLimits};
+use sequence_alignment::consensus::{ConsensusRule, GapPolicy, MismatchRule};
+use sequence_alignment::distance::DistanceMetric;
+use sequence_alignment::generate::AlphabetKind;
+use sequence_alignment::io::format::OutputFormat;
+use sequence_alignment::io::interleaved::DEFAULT_INTERLEAVED_WIDTH;
+use sequence_alignment::merge::MergeCriteria;
+use sequence_alignment::models::builtin::BUILTIN_MATRICES;
+#[cfg(feature = "mmap")]
+use sequence_alignment::models::score_store::MatrixStorage;
+use sequence_alignment::models::{
+    CodonAwareGapPenalties, EndGaps, GapConstraints, GapPenalties, GAP_CHAR,
+};
+use sequence_alignment::preset::{Preset, PRESET_NAMES};
+use sequence_alignment::run_summary::Algorithm;
+use sequence_alignment::score_type::ScoreType;
+use sequence_alignment::search::HitFilter;
+use sequence_alignment::stats::{CoverageFilter, KarlinAltschul};
+use std::env;
+use std::ffi::OsString;
+use std::io::Write;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::process;
+use std::process::ExitCode;
+use std::str::FromStr;
+
+const INPUT_FORMAT: &str = "Input file format, one item per line:
+  <sequence_A>
+  <sequence_B>
+  <alignment_type>                  0 for global, 1 for local
+  <dx> <ex> <dy> <ey>               gap open and extend penalties for gaps in A and in B
+  <alphabet_A_length>
+  <alphabet_A>
+  <alphabet_B_length>
+  <alphabet_B>
+  <index_a> <index_b> <char_a> <char_b> <score>   one line per pair of residues
+  [karlin-altschul <lambda> <K>]
+
+A sequence line starting with `base64:` is decoded before use. With --tokens, sequences
+and alphabets are whitespace-separated tokens. Values given on the command line take
+precedence over those in the input file.";
+
+/// Pairwise sequence alignment with affine gap penalties
+#[derive(Debug, Parser)]
+#[command(
+    name = "align",
+    version,
+    subcommand_required = true,
+    arg_required_else_help = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Subcommands,
+    /// Worker threads for parallel work (default: one per core)
+    #[arg(long, global = true, value_parser = positive)]
+    pub threads: Option<usize>,
+    /// Sync each output file to disk once it is written
+    #[arg(long, global = true)]
+    pub durable: bool,
+    /// Report only errors on stderr
+    #[arg(long, short, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Also report parameters and timings on stderr; repeat (`-vv`) for trace output
+    #[arg(long, short, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+    /// Output format of `align` and `convert`
+    #[arg(
+        long,
+        global = true,
+        ignore_case = true,
+        value_parser = named::<OutputFormat>(OutputFormat::ALL.map(|format| format.as_str()))
+    )]
+    format: Option<OutputFormat>,
+    /// Whether the arguments were given in the old form without a subcommand
+    #[arg(skip)]
+    pub legacy: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum Subcommands {
+    /// Align the two sequences of an input file
+    #[command(after_help = INPUT_FORMAT)]
+    Align(Box<Options>),
+    /// Align every pair of records in a FASTA file
+    Batch(BatchOptions),
+    /// Search one query against every record of a FASTA database
+    Search(SearchOptions),
+    /// Recompute identity and coverage of the alignments in a result file
+    Stats(StatsOptions),
+    /// Re-emit an existing result file in another format, or convert an NCBI matrix
+    Convert(ConvertOptions),
+    /// Compare two result files
+    Diff(DiffOptions),
+    /// Write a random sequence and a mutated copy as an input file or FASTA pair
+    Generate(GenerateOptions),
+    /// Merge paired-end reads across the overlap of each pair
+    Merge(MergeOptions),
+    /// Print a shell completion script to stdout
+    #[command(hide = true)]
+    Completions {
+        /// Shell to complete for: bash, zsh, fish, elvish or powershell
+        shell: Shell,
+    },
+}
+
+/// Subcommand selected on the command line
+#[derive(Debug, Clone)]
+pub enum Command {
+    Align(Box<Options>),
+    Batch(BatchOptions),
+    Search(SearchOptions),
+    Stats(StatsOptions),
+    Convert(ConvertOptions),
+    Diff(DiffOptions),
+    Generate(GenerateOptions),
+    Merge(MergeOptions),
+    Completions(Shell),
+}
+
+/// Parser accepting one of `names`, which `--help` and shell completions list
+fn named<T>(names: impl IntoIterator<Item = &'static str>) -> impl TypedValueParser<Value = T>
+where
+    T: FromStr<Err = String> + Clone + Send + Sync + 'static,
+{
+    PossibleValuesParser::new(names).try_map(|name| name.parse::<T>())
+}
+
+/// Parse a rate between zero and one
+fn fraction(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(f) if (0.0..=1.0).contains(&f) => Ok(f),
+        Ok(_) => Err("must be between 0 and 1".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Parse a count that must be at least one
+fn positive(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(0) => Err("must be positive".to_string()),
+        Ok(n) => Ok(n),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Parse a minimum identity and a window width, `P:W`
+fn identity_window(s: &str) -> Result<(f64, usize), String> {
+    let (identity, window) = s
+        .split_once(':')
+        .ok_or_else(|| "expected P:W, e.g. 0.5:10".to_string())?;
+    let identity: f64 = identity.parse().map_err(|e| format!("{}", e))?;
+    if !(0.0..=1.0).contains(&identity) {
+        return Err("identity must be between 0 and 1".to_string());
+    }
+    Ok((identity, positive(window)?))
+}
+
+/// Parse an `open,extend` pair of gap penalties
+fn penalty_pair(s: &str) -> Result<(f64, f64), String> {
+    let (open, extend) = s
+        .split_once(',')
+        .ok_or_else(|| "expected OPEN,EXTEND, e.g. 1,0.5".to_string())?;
+    let penalty = |value: &str| value.trim().parse::<f64>().map_err(|e| format!("{}", e));
+    Ok((penalty(open)?, penalty(extend)?))
+}
+
+/// Parse `in,out,extend` codon-aware gap penalties
+fn codon_penalties(s: &str) -> Result<CodonAwareGapPenalties, String> {
+    let penalties = s
+        .split(',')
+        .map(|value| value.trim().parse::<f64>().map_err(|e| format!("{}", e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    match penalties[..] {
+        [in_frame_open, out_of_frame_open, extend] => Ok(CodonAwareGapPenalties::new(
+            in_frame_open,
+            out_of_frame_open,
+            extend,
+        )),
+        _ => Err("expected IN,OUT,EXTEND, e.g. 2,6,1".to_string()),
+    }
+}
+
+/// Parse a 1-based inclusive range of residues, `start:end`, into a 0-based range with an
+/// exclusive end
+fn residue_range(s: &str) -> Result<Range<usize>, String> {
+    let (start, end) = s
+        .split_once(':')
+        .ok_or_else(|| "expected START:END, e.g. 10:25".to_string())?;
+    let (start, end) = (positive(start)?, positive(end)?);
+    if start > end {
+        return Err(format!("start {} is after end {}", start, end));
+    }
+    Ok(start - 1..end)
+}
+
+/// Parse a size in bytes, with an optional binary `K`, `M` or `G` suffix
+fn memory_size(s: &str) -> Result<usize, String> {
+    let (number, unit) = match s.char_indices().last() {
+        Some((i, suffix)) if suffix.is_ascii_alphabetic() => {
+            let unit = match suffix.to_ascii_uppercase() {
+                'K' => 1 << 10,
+                'M' => 1 << 20,
+                'G' => 1 << 30,
+                _ => return Err(format!("unknown size suffix {}; use K, M or G", suffix)),
+            };
+            (&s[..i], unit)
+        }
+        _ => (s, 1),
+    };
+    let number: f64 = number.parse().map_err(|e| format!("{}", e))?;
+    if !number.is_finite() || number < 0.0 {
+        return Err("must be a non-negative size".to_string());
+    }
+    Ok((number * unit as f64) as usize)
+}
+
+/// Command-line options for aligning an input file
+#[derive(Debug, Clone, Args)]
+pub struct Options {
+    /// Input file; may be replaced by --seq-a and --seq-b
+    #[arg(required_unless_present = "seq_a", conflicts_with = "seq_a")]
+    pub input_file: Option<String>,
+    /// Output file; may be left out with --count-paths and --benchmark
+    #[arg(
+        default_value = "",
+        hide_default_value = true,
+        required_unless_present_any = ["count_paths", "benchmark", "output", "dry_run"]
+    )]
+    pub output_file: String,
+    /// Output file, `-` for standard output, in place of the positional output file
+    #[arg(short, long, value_name = "FILE", conflicts_with = "output_file")]
+    output: Option<String>,
+    /// Sequence A, aligned without an input file
+    #[arg(
+        long,
+        value_name = "SEQ",
+        requires = "seq_b",
+        conflicts_with = "merge_file"
+    )]
+    pub seq_a: Option<String>,
+    /// Sequence B, aligned without an input file
+    #[arg(long, value_name = "SEQ", requires = "seq_a")]
+    pub seq_b: Option<String>,
+    #[arg(skip)]
+    pub format: OutputFormat,
+    /// Columns per line of the interleaved format
+    #[arg(long, default_value_t = DEFAULT_INTERLEAVED_WIDTH, value_parser = positive)]
+    pub width: usize,
+    /// Numeric type of the DP grid; the integer types need whole-number scores, and
+    /// i16 and i8 only suit short sequences
+    #[arg(
+        long,
+        default_value_t,
+        ignore_case = true,
+        value_parser = named::<ScoreType>(ScoreType::ALL.map(|score_type| score_type.as_str())),
+        conflicts_with_all = ["log_space", "count_paths", "dump_dot", "shortest_path"]
+    )]
+    pub score_type: ScoreType,
+    /// Read sequences and alphabets as whitespace-separated tokens
+    #[arg(long)]
+    pub tokens: bool,
+    /// Replace the match scores with pair scores read from a file
+    #[arg(long = "scores", value_name = "FILE")]
+    pub scores_file: Option<String>,
+    /// Separator between aligned tokens
+    #[arg(long)]
+    pub separator: Option<String>,
+    /// Gap symbol in aligned output; must not be a residue of either sequence
+    #[arg(long, visible_alias = "gap-char")]
+    pub gap: Option<String>,
+    /// Force global alignment with free end gaps
+    #[arg(long, conflicts_with_all = ["local", "semiglobal", "soft_clip_a"])]
+    pub global: bool,
+    /// Force local alignment
+    #[arg(long, conflicts_with_all = ["semiglobal", "soft_clip_a"])]
+    pub local: bool,
+    /// Align all of A against part of B: end gaps are free for B only
+    #[arg(long, conflicts_with_all = ["score_only", "log_space", "soft_clip_a"])]
+    pub semiglobal: bool,
+    /// Align part of A against all of B: A's overhangs are free and soft-clipped (`S`)
+    #[arg(long, conflicts_with_all = ["score_only", "log_space"])]
+    pub soft_clip_a: bool,
+    /// Gap penalties `dx,ex,dy,ey`, replacing those of the input file
+    #[arg(
+        long,
+        value_name = "DX,EX,DY,EY",
+        allow_hyphen_values = true,
+        conflicts_with_all = [
+            "gap_open", "gap_extend", "gap_open_a", "gap_extend_a", "gap_open_b", "gap_extend_b"
+        ]
+    )]
+    pub gaps: Option<GapPenalties<f64>>,
+    /// Gap open penalty for gaps in both sequences
+    #[arg(long, value_name = "F", conflicts_with_all = ["gap_open_a", "gap_open_b"])]
+    pub gap_open: Option<f64>,
+    /// Gap extension penalty for gaps in both sequences
+    #[arg(long, value_name = "F", conflicts_with_all = ["gap_extend_a", "gap_extend_b"])]
+    pub gap_extend: Option<f64>,
+    /// Gap open penalty for gaps in sequence A (dx)
+    #[arg(long, value_name = "F")]
+    pub gap_open_a: Option<f64>,
+    /// Gap extension penalty for gaps in sequence A (ex)
+    #[arg(long, value_name = "F")]
+    pub gap_extend_a: Option<f64>,
+    /// Gap open penalty for gaps in sequence B (dy)
+    #[arg(long, value_name = "F")]
+    pub gap_open_b: Option<f64>,
+    /// Gap extension penalty for gaps in sequence B (ey)
+    #[arg(long, value_name = "F")]
+    pub gap_extend_b: Option<f64>,
+    /// Charge residues of A left unaligned at its ends as a gap with these penalties,
+    /// rather than as a free or an internal gap
+    #[arg(
+        long,
+        value_name = "OPEN,EXTEND",
+        value_parser = penalty_pair,
+        conflicts_with_all = ["local", "soft_clip_a", "score_only", "log_space"]
+    )]
+    pub terminal_gaps_a: Option<(f64, f64)>,
+    /// Charge residues of B left unaligned at its ends as a gap with these penalties,
+    /// rather than as a free or an internal gap
+    #[arg(
+        long,
+        value_name = "OPEN,EXTEND",
+        value_parser = penalty_pair,
+        conflicts_with_all = ["local", "semiglobal", "score_only", "log_space"]
+    )]
+    pub terminal_gaps_b: Option<(f64, f64)>,
+    /// Take this off the open penalty of every end gap, charging free ends as internal
+    /// gaps less the bonus
+    #[arg(
+        long,
+        value_name = "F",
+        allow_hyphen_values = true,
+        conflicts_with_all = ["local", "score_only", "log_space"]
+    )]
+    pub end_gap_bonus: Option<f64>,
+    /// Score lower-case (soft-masked) residues of A at this fraction of their upper-case
+    /// scores, 0 masking them fully; the case of B is ignored
+    #[arg(long, value_name = "F", value_parser = fraction, conflicts_with = "log_space")]
+    pub soft_mask: Option<f64>,
+    /// Write only the optimal score, skipping traceback
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "verify", "profile_file", "dna_stats", "count_paths", "benchmark", "canonical",
+            "max_alignments", "first_only", "vcf_file", "consensus_file", "chain_file",
+            "sensitivity", "shuffles", "dump_dot", "shortest_path", "max_starts",
+            "max_paths_per_start"
+        ]
+    )]
+    pub score_only: bool,
+    /// Report the score relative to the better self-alignment score
+    #[arg(long)]
+    pub score_ratio: bool,
+    /// Check every reported alignment against its score
+    #[arg(long)]
+    pub verify: bool,
+    /// Take sequence B from a second input file with the same match scores and gap
+    /// penalties
+    #[arg(long = "merge", value_name = "INPUT_FILE")]
+    pub merge_file: Option<String>,
+    /// Use the match scores and gap penalties of a preset for the type of sequence,
+    /// unless given by other flags
+    #[arg(
+        long,
+        value_name = "NAME",
+        ignore_case = true,
+        value_parser = PossibleValuesParser::new(PRESET_NAMES)
+    )]
+    pub preset: Option<String>,
+    /// Use a builtin substitution matrix instead of the file's match scores
+    #[arg(
+        long,
+        value_name = "NAME",
+        ignore_case = true,
+        value_parser = PossibleValuesParser::new(BUILTIN_MATRICES)
+    )]
+    pub matrix: Option<String>,
+    /// Score identical residues this, replacing the match scores (requires --mismatch)
+    #[arg(
+        id = "match",
+        long = "match",
+        value_name = "F",
+        allow_hyphen_values = true,
+        requires = "mismatch",
+        conflicts_with_all = ["matrix", "scores_file"]
+    )]
+    pub match_score: Option<f64>,
+    /// Score differing residues this, replacing the match scores (requires --match)
+    #[arg(long, value_name = "F", allow_hyphen_values = true, requires = "match")]
+    pub mismatch: Option<f64>,
+    /// Fail if any score differs from that of the reversed pair, listing the pairs
+    #[arg(long)]
+    pub strict_symmetry: bool,
+    /// Fail if the match matrix leaves out any pair of residues of the two alphabets,
+    /// rather than warning that it scores zero
+    #[arg(long)]
+    pub strict_matrix: bool,
+    /// Check the layout of the input files line by line before parsing them, and fail on
+    /// any structural problem
+    #[arg(long)]
+    pub strict: bool,
+    /// Log gap penalties scaled to the average match score of the sequences, without
+    /// using them
+    #[arg(long)]
+    pub suggest_penalties: bool,
+    /// Karlin-Altschul lambda for bit scores and E-values
+    #[arg(long, requires = "karlin_k")]
+    pub lambda: Option<f64>,
+    /// Karlin-Altschul K for bit scores and E-values
+    #[arg(long, requires = "lambda")]
+    pub karlin_k: Option<f64>,
+    /// Fail unless the E-value is at most this
+    #[arg(long, value_name = "F")]
+    pub max_evalue: Option<f64>,
+    /// Estimate significance from this many shuffles of sequence B
+    #[arg(long, value_name = "N")]
+    pub shuffles: Option<usize>,
+    /// Seed for the shuffles
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+    /// Treat scores and penalties as probabilities and align in log space
+    #[arg(long)]
+    pub log_space: bool,
+    /// Write a windowed identity profile to this file
+    #[arg(long = "profile", value_name = "FILE")]
+    pub profile_file: Option<String>,
+    /// Profile window length
+    #[arg(long, default_value_t = 50, value_parser = positive)]
+    pub window: usize,
+    /// Profile window step
+    #[arg(long, default_value_t = 10, value_parser = positive)]
+    pub step: usize,
+    /// Vary each penalty by DELTA over STEPS steps and report how the score responds
+    #[arg(id = "sensitivity", long, num_args = 2, value_names = ["DELTA", "STEPS"])]
+    sensitivity_args: Vec<String>,
+    #[arg(skip)]
+    pub sensitivity: Option<(f64, usize)>,
+    /// Report substitution statistics for nucleotide alignments
+    #[arg(long)]
+    pub dna_stats: bool,
+    /// Treat the alphabets as nucleotides whatever their symbols
+    #[arg(long)]
+    pub nucleotide: bool,
+    /// Count co-optimal alignments without enumerating them
+    #[arg(long)]
+    pub count_paths: bool,
+    /// Time the DP fill and log a table to stderr
+    #[arg(long)]
+    pub benchmark: bool,
+    /// Validate the input and print the problem size and memory estimate without
+    /// aligning
+    #[arg(long, conflicts_with_all = ["count_paths", "benchmark"])]
+    pub dry_run: bool,
+    /// Benchmark runs
+    #[arg(long, default_value_t = 5, value_parser = positive)]
+    pub runs: usize,
+    /// Shift gaps left or right and drop alignments that become duplicates
+    #[arg(long)]
+    pub canonical: Option<GapCanonicalForm>,
+    /// Keep only the first co-optimal alignment
+    #[arg(long, conflicts_with = "max_alignments")]
+    pub first_only: bool,
+    /// Report only the co-optimal alignment with the fewest traceback steps
+    #[arg(
+        long,
+        conflicts_with_all = ["first_only", "max_alignments", "max_starts", "max_paths_per_start"]
+    )]
+    pub shortest_path: bool,
+    /// Report the K best local alignments, not only the co-optimal ones, skipping any
+    /// that runs through a better one
+    #[arg(
+        long,
+        value_name = "K",
+        value_parser = positive,
+        conflicts_with_all = [
+            "score_only", "score_type", "log_space", "shortest_path", "canonical",
+            "first_only", "max_alignments", "max_starts", "max_paths_per_start",
+            "max_evalue", "shuffles", "lambda"
+        ]
+    )]
+    pub top_hits: Option<usize>,
+    /// With --top-hits, report only alignments scoring above this
+    #[arg(
+        long,
+        value_name = "F",
+        allow_hyphen_values = true,
+        requires = "top_hits"
+    )]
+    pub min_score: Option<f64>,
+    /// Allow no gap longer than this many columns; 0 allows no gaps
+    #[arg(
+        long,
+        value_name = "L",
+        conflicts_with_all = [
+            "score_only", "score_type", "log_space", "check_scores", "check_consistency",
+            "trace_execution", "count_paths", "benchmark"
+        ]
+    )]
+    pub max_gap_length: Option<usize>,
+    /// Allow no gap in B against residues START to END of A (1-based, inclusive), nor
+    /// any gap in A between them
+    #[arg(
+        long,
+        value_name = "START:END",
+        value_parser = residue_range,
+        conflicts_with_all = [
+            "score_only", "score_type", "log_space", "check_scores", "check_consistency",
+            "trace_execution", "count_paths", "benchmark"
+        ]
+    )]
+    pub no_gap_region_a: Option<Range<usize>>,
+    /// Charge internal gaps as coding sequence gaps: opening on a codon boundary of A
+    /// costs IN and within a codon OUT, and every further residue EXTEND
+    #[arg(
+        long,
+        value_name = "IN,OUT,EXTEND",
+        value_parser = codon_penalties,
+        conflicts_with_all = [
+            "score_only", "score_type", "log_space", "check_consistency", "trace_execution",
+            "count_paths", "benchmark", "verify", "max_gap_length", "no_gap_region_a"
+        ]
+    )]
+    pub codon_gaps: Option<CodonAwareGapPenalties>,
+    /// Position within its codon, 0, 1 or 2, of the first residue of A
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        value_parser = clap::value_parser!(u8).range(0..=2),
+        requires = "codon_gaps"
+    )]
+    pub frame_offset: u8,
+    /// Keep at most this many co-optimal alignments
+    #[arg(long, value_name = "N", value_parser = positive)]
+    pub max_alignments: Option<usize>,
+    /// Drop leading and trailing columns with a gap in either sequence from the reported
+    /// alignments
+    #[arg(long, conflicts_with = "soft_clip_a")]
+    pub trim_end_gaps: bool,
+    /// Trim each end while the W columns there have an identity below P
+    #[arg(
+        long,
+        value_name = "P:W",
+        value_parser = identity_window,
+        conflicts_with = "soft_clip_a"
+    )]
+    pub trim_below_identity: Option<(f64, usize)>,
+    /// Trace back from at most this many optimal cells, the first in row-major order
+    #[arg(long, value_name = "N", value_parser = positive)]
+    pub max_starts: Option<usize>,
+    /// Enumerate at most this many co-optimal paths from each optimal cell
+    #[arg(long, value_name = "N", value_parser = positive)]
+    pub max_paths_per_start: Option<usize>,
+    /// Refuse to allocate a DP grid larger than this many bytes (suffixes K, M and G)
+    #[arg(long, value_name = "SIZE", value_parser = memory_size)]
+    pub max_memory: Option<usize>,
+    /// Write the traceback pointers as a Graphviz DOT graph to this file (`-` for
+    /// standard output); sequences of at most 50 residues
+    #[arg(long, value_name = "FILE")]
+    pub dump_dot: Option<String>,
+    /// Write the M, Ix and Iy scores as NumPy arrays PREFIX_m.npy, PREFIX_ix.npy and
+    /// PREFIX_iy.npy
+    #[cfg(feature = "numpy")]
+    #[arg(long, value_name = "PREFIX", conflicts_with_all = ["score_only", "score_type"])]
+    pub export_npy: Option<String>,
+    /// Keep the M, Ix and Iy matrices on the heap, or on disk in memory-mapped temporary
+    /// files that the OS pages in and out, for grids larger than RAM
+    #[cfg(feature = "mmap")]
+    #[arg(
+        long,
+        default_value_t,
+        ignore_case = true,
+        value_parser = named::<MatrixStorage>(MatrixStorage::ALL.map(|storage| storage.as_str())),
+        conflicts_with_all = ["score_only", "score_type", "log_space", "max_memory", "benchmark"]
+    )]
+    pub matrix_storage: MatrixStorage,
+    /// Directory for the temporary files of --matrix-storage disk (default: the system
+    /// temporary directory)
+    #[cfg(feature = "mmap")]
+    #[arg(long, value_name = "PATH")]
+    pub tmpdir: Option<PathBuf>,
+    /// Fail at the first DP cell whose score is NaN or infinite, as debug builds always do
+    #[arg(long, conflicts_with_all = ["log_space", "score_only", "score_type"])]
+    pub check_scores: bool,
+    /// After the fill, check that the stored traceback pointers match those recomputed
+    /// from the scores, sampling grids over a million cells
+    #[arg(long, conflicts_with_all = ["log_space", "score_only", "score_type"])]
+    pub check_consistency: bool,
+    /// Log every cell of the DP fill, step by step, to this file: JSON if it ends in
+    /// `.json`, otherwise TSV. Slow and large; meant for small teaching examples
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["log_space", "score_only"])]
+    pub trace_execution: Option<String>,
+    /// Write FASTA output as the traceback builds each alignment, in the order it finds
+    /// them rather than the canonical order, holding only one alignment in memory
+    #[arg(long)]
+    pub stream: bool,
+    /// Trace back each optimal cell in a parallel task of its own, writing its alignments
+    /// as text to ROW_COL.txt in --output-dir. The output file gets only the score and a
+    /// manifest of those files
+    #[arg(
+        long,
+        requires = "output_dir",
+        conflicts_with_all = ["stream", "format", "max_alignments", "max_starts"]
+    )]
+    pub parallel_output: bool,
+    /// Directory for the files of --parallel-output, created if missing
+    #[arg(long, value_name = "DIR", requires = "parallel_output")]
+    pub output_dir: Option<PathBuf>,
+    /// Remove `_` and `-` from the input sequences before aligning
+    #[arg(long)]
+    pub strip_gaps: bool,
+    /// Pad both sequences for the fill until each length is a multiple of N, the layout
+    /// a vectorized fill needs. The padding scores 0 and is left out of the results
+    #[arg(long, value_name = "N", value_parser = positive)]
+    pub pad_to: Option<usize>,
+    /// Residue --pad-to pads the sequences with
+    #[arg(long, value_name = "C", default_value_t = '*', requires = "pad_to")]
+    pub pad_char: char,
+    /// Write the differences between the sequences as VCF to this file
+    #[arg(long = "vcf", value_name = "FILE")]
+    pub vcf_file: Option<String>,
+    /// Reference and sample names for the VCF
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["A", "B"],
+        default_values = ["A", "B"]
+    )]
+    pub names: Vec<String>,
+    /// Write the best colinear chain of the local alignments to this file
+    #[arg(long = "chain", value_name = "FILE")]
+    pub chain_file: Option<String>,
+    /// Write the consensus of each alignment to this file
+    #[arg(long = "consensus", value_name = "FILE")]
+    pub consensus_file: Option<String>,
+    /// Consensus residue for mismatches: a, b or iupac
+    #[arg(long = "consensus-rule", value_name = "RULE")]
+    consensus_mismatch: Option<MismatchRule>,
+    /// Consensus handling of gaps: keep or drop
+    #[arg(long = "consensus-gaps", value_name = "POLICY", default_value_t)]
+    consensus_gaps: GapPolicy,
+    #[arg(skip)]
+    pub consensus_rule: ConsensusRule,
+}
+
+/// K-mer prefilter settings shared by `batch` and `search`
+#[derive(Debug, Clone, Copy, Default, Args)]
+pub struct PrefilterOptions {
+    /// Skip pairs sharing too few k-mers to be worth aligning
+    #[arg(long = "prefilter")]
+    pub enabled: bool,
+    /// K-mer length (default 8 for nucleotides, 4 for proteins)
+    #[arg(long = "kmer", value_name = "K", requires = "enabled", value_parser = positive)]
+    pub k: Option<usize>,
+    /// Shared k-mers needed to align a pair
+    #[arg(long, value_name = "N", requires = "enabled")]
+    pub min_shared: Option<usize>,
+}
+
+/// Coverage thresholds shared by `batch` and `search`
+#[derive(Debug, Clone, Copy, Default, Args)]
+pub struct CoverageOptions {
+    /// Report only alignments spanning at least this fraction of sequence A (the query
+    /// in `search`)
+    #[arg(long, value_name = "P", value_parser = fraction)]
+    pub min_coverage_a: Option<f64>,
+    /// Report only alignments spanning at least this fraction of sequence B (the
+    /// database record in `search`)
+    #[arg(long, value_name = "P", value_parser = fraction)]
+    pub min_coverage_b: Option<f64>,
+}
+
+impl CoverageOptions {
+    pub fn filter(&self) -> CoverageFilter {
+        CoverageFilter {
+            min_a: self.min_coverage_a,
+            min_b: self.min_coverage_b,
+        }
+    }
+}
+
+/// Command-line options for aligning every pair of records in a FASTA file
+#[derive(Debug, Clone, Args)]
+pub struct BatchOptions {
+    /// Input file supplying the scoring model; its sequences are ignored
+    pub input_file: String,
+    pub fasta_file: String,
+    pub output_file: String,
+    /// Write a PHYLIP distance matrix to this file
+    #[arg(long = "phylip", value_name = "FILE")]
+    pub phylip_file: Option<String>,
+    /// Distance for the PHYLIP matrix: p, jc or score
+    #[arg(long, default_value_t)]
+    pub metric: DistanceMetric,
+    /// Write the optimal score of every pair of records, and of each against itself, to
+    /// this file as a tab-separated matrix
+    #[arg(long = "score-table", value_name = "FILE")]
+    pub score_table_file: Option<String>,
+    #[command(flatten)]
+    pub prefilter: PrefilterOptions,
+    #[command(flatten)]
+    pub coverage: CoverageOptions,
+    /// Keep the complete rows of an existing output file and align only the other pairs
+    #[arg(long)]
+    pub resume: bool,
+    /// Align records 1 and 2, 3 and 4 and so on as pairs instead of every pair, reading
+    /// the FASTA file a chunk at a time
+    #[arg(long, conflicts_with_all = ["phylip_file", "score_table_file", "resume"])]
+    pub pairs_fasta: bool,
+}
+
+const DEFAULT_TOP_HITS: usize = 10;
+
+/// Command-line options for searching a query against a FASTA database
+#[derive(Debug, Clone, Args)]
+pub struct SearchOptions {
+    /// Input file supplying the scoring model; its sequences are ignored
+    pub input_file: String,
+    pub query_file: String,
+    pub database_file: String,
+    pub output_file: String,
+    /// Write the first alignment of each hit to this file
+    #[arg(long = "alignments", value_name = "FILE")]
+    pub alignments_file: Option<String>,
+    /// Report at most this many hits (default 10 when no threshold is given)
+    #[arg(long, value_name = "N")]
+    top: Option<usize>,
+    /// Report only hits scoring at least this
+    #[arg(long, value_name = "F")]
+    min_score: Option<f64>,
+    /// Report only hits with at most this E-value
+    #[arg(long, value_name = "F")]
+    max_evalue: Option<f64>,
+    #[arg(skip)]
+    pub filter: HitFilter,
+    /// Karlin-Altschul lambda for bit scores and E-values
+    #[arg(long, requires = "karlin_k")]
+    pub lambda: Option<f64>,
+    /// Karlin-Altschul K for bit scores and E-values
+    #[arg(long, requires = "lambda")]
+    pub karlin_k: Option<f64>,
+    #[command(flatten)]
+    pub prefilter: PrefilterOptions,
+    #[command(flatten)]
+    pub coverage: CoverageOptions,
+}
+
+/// Command-line options for recomputing statistics of an existing result file
+#[derive(Debug, Clone, Args)]
+pub struct StatsOptions {
+    pub result_file: String,
+    /// Output file (default: standard output)
+    pub output_file: Option<String>,
+    /// Input file whose sequence lengths give the coverage of each alignment
+    #[arg(long = "parameters", value_name = "INPUT_FILE")]
+    pub parameters_file: Option<String>,
+    /// Gap character the result rows were written with, as given to `align --gap`
+    #[arg(long, visible_alias = "gap-char", default_value_t = GAP_CHAR)]
+    pub gap: char,
+}
+
+/// Command-line options for re-emitting an existing result file
+#[derive(Debug, Clone, Args)]
+pub struct ConvertOptions {
+    /// Result file, NCBI matrix file with --ncbi-matrix or diffs listing with --apply
+    pub result_file: String,
+    pub output_file: String,
+    #[arg(skip)]
+    pub format: OutputFormat,
+    /// Input file whose alphabets the result must use
+    #[arg(long = "parameters", value_name = "INPUT_FILE")]
+    pub parameters_file: Option<String>,
+    /// Convert an NCBI substitution matrix to the alphabet and match score lines of an
+    /// input file
+    #[arg(long, conflicts_with = "parameters_file")]
+    pub ncbi_matrix: bool,
+    /// Rebuild sequence B from a diffs listing and sequence A, the first record of this
+    /// FASTA file, writing the part of B each alignment covers as FASTA
+    #[arg(long, value_name = "FASTA", conflicts_with_all = ["parameters_file", "ncbi_matrix"])]
+    pub apply: Option<String>,
+    /// Gap character the result rows were written with, as given to `align --gap`
+    #[arg(long, visible_alias = "gap-char", default_value_t = GAP_CHAR)]
+    pub gap: char,
+}
+
+/// Command-line options for comparing two result files
+#[derive(Debug, Clone, Args)]
+pub struct DiffOptions {
+    pub first_file: String,
+    pub second_file: String,
+    /// Write the comparison as JSON
+    #[arg(long)]
+    pub json: bool,
+    /// Input file whose alphabets both results must use
+    #[arg(long = "parameters", value_name = "INPUT_FILE")]
+    pub parameters_file: Option<String>,
+    /// Gap character the result rows were written with, as given to `align --gap`
+    #[arg(long, visible_alias = "gap-char", default_value_t = GAP_CHAR)]
+    pub gap: char,
+}
+
+/// Command-line options for generating a related pair of sequences
+#[derive(Debug, Clone, Args)]
+pub struct GenerateOptions {
+    /// Output file (default: standard output)
+    pub output_file: Option<String>,
+    /// Residues in the random sequence
+    #[arg(long, default_value_t = 100, value_parser = positive)]
+    pub length: usize,
+    /// Chance that a residue of the copy matches the original
+    #[arg(long, value_name = "P", default_value_t = 0.9, value_parser = fraction)]
+    pub identity: f64,
+    /// Chance per residue of an insertion or deletion
+    #[arg(long, value_name = "R", default_value_t = 0.02, value_parser = fraction)]
+    pub indel_rate: f64,
+    /// Residues to draw from
+    #[arg(
+        long,
+        default_value_t,
+        ignore_case = true,
+        value_parser = named::<AlphabetKind>(AlphabetKind::ALL.map(|kind| kind.as_str()))
+    )]
+    pub alphabet: AlphabetKind,
+    /// Seed of the random generator
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+    /// Write the input file for a local alignment
+    #[arg(long)]
+    pub local: bool,
+    /// Write the two sequences as FASTA instead of an input file
+    #[arg(long, conflicts_with = "local")]
+    pub fasta: bool,
+}
+
+/// Command-line options for merging paired-end reads
+#[derive(Debug, Clone, Args)]
+pub struct MergeOptions {
+    /// Input file supplying the scoring model; its sequences are ignored
+    pub input_file: String,
+    /// First reads of the pairs, as FASTA or FASTQ
+    pub reads_1: String,
+    /// Second reads of the pairs, in the order of their mates
+    pub reads_2: String,
+    /// Merged reads, as FASTQ when both reads of a pair have qualities and FASTA
+    /// otherwise
+    pub output_file: String,
+    /// Merge only across an overlap of at least this many columns
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    pub min_overlap: usize,
+    /// Merge only across an overlap with at least this fraction of identical columns
+    #[arg(long, value_name = "F", default_value_t = 0.9, value_parser = fraction)]
+    pub min_overlap_identity: f64,
+}
+
+impl MergeOptions {
+    pub fn criteria(&self) -> MergeCriteria {
+        MergeCriteria {
+            min_overlap: self.min_overlap,
+            min_identity: self.min_overlap_identity,
+        }
+    }
+}
+
+impl Cli {
+    /// Parse the process arguments. The old form without a subcommand,
+    /// `align INPUT OUTPUT [OPTIONS]`, is still accepted as `align align ...`.
+    pub fn parse_args() -> Self {
+        let mut args: Vec<OsString> = env::args_os().collect();
+        let legacy = is_legacy_invocation(&args);
+        if legacy {
+            args.insert(1, "align".into());
+        }
+        let cli = Self::try_parse_from(args).unwrap_or_else(|error| exit_with(error));
+        Self { legacy, ..cli }
+    }
+
+    /// The selected subcommand, with derived option values filled in. Exits with a
+    /// usage error if the options are inconsistent.
+    pub fn command(self) -> Command {
+        let format = self.format;
+        let no_format = |name: &str| match format {
+            Some(_) => Err(format!("--format does not apply to `{}`", name)),
+            None => Ok(()),
+        };
+        let command = match self.command {
+            Subcommands::Align(mut options) => {
+                options.format = format.unwrap_or_default();
+                options
+                    .finish()
+                    .map(|options| Command::Align(Box::new(options)))
+            }
+            Subcommands::Batch(options) => no_format("batch").map(|_| Command::Batch(options)),
+            Subcommands::Search(options) => {
+                no_format("search").map(|_| Command::Search(options.finish()))
+            }
+            Subcommands::Stats(options) => no_format("stats").map(|_| Command::Stats(options)),
+            Subcommands::Convert(options) if options.ncbi_matrix => {
+                no_format("convert --ncbi-matrix").map(|_| Command::Convert(options))
+            }
+            Subcommands::Convert(options) if options.apply.is_some() => {
+                no_format("convert --apply").map(|_| Command::Convert(options))
+            }
+            Subcommands::Convert(mut options) => {
+                options.format = format.unwrap_or_default();
+                Ok(Command::Convert(options))
+            }
+            Subcommands::Diff(options) => no_format("diff").map(|_| Command::Diff(options)),
+            Subcommands::Generate(options) => {
+                no_format("generate").map(|_| Command::Generate(options))
+            }
+            Subcommands::Merge(options) => no_format("merge").map(|_| Command::Merge(options)),
+            Subcommands::Completions { shell } => {
+                no_format("completions").map(|_| Command::Completions(shell))
+            }
+        };
+        command.unwrap_or_else(|message| {
+            exit_with(<Cli as CommandFactory>::command().error(ErrorKind::ValueValidation, message))
+        })
+    }
+
+    /// Write the completion script for `shell`
+    pub fn write_completions(shell: Shell, writer: &mut dyn Write) {
+        clap_complete::generate(
+            shell,
+            &mut <Cli as CommandFactory>::command(),
+            "align",
+            writer,
+        );
+    }
+
+    /// Most detailed level of messages to report on stderr
+    pub fn log_level(&self) -> log::LevelFilter {
+        if self.quiet {
+            log::LevelFilter::Error
+        } else {
+            match self.verbose {
+                0 => log::LevelFilter::Info,
+                1 => log::LevelFilter::Debug,
+                _ => log::LevelFilter::Trace,
+            }
+        }
+    }
+}
+
+/// Process exit statuses, so scripts can tell failures apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    Success = 0,
+    /// Invalid command line
+    Usage = 1,
+    /// Input that cannot be read or parsed
+    Input = 2,
+    /// Input too large, or a resource such as disk space ran out
+    Limit = 3,
+    /// `--max-evalue` or the search filters left nothing to report
+    NoHits = 4,
+}
+
+impl From<ExitStatus> for ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
+
+/// Print a clap error and exit: with success for `--help` and `--version`, otherwise
+/// with the usage status
+fn exit_with(error: clap::Error) -> ! {
+    let _ = error.print();
+    let status = if error.use_stderr() {
+        ExitStatus::Usage
+    } else {
+        ExitStatus::Success
+    };
+    process::exit(status as i32)
+}
+
+/// Whether the arguments use the old form: the first argument that is not a top-level
+/// option names no subcommand
+fn is_legacy_invocation(args: &[OsString]) -> bool {
+    let mut args = args.iter().skip(1).map(|arg| arg.to_string_lossy());
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            "--threads" | "--format" => {
+                args.next();
+            }
+            "-q" | "--quiet" | "-v" | "--verbose" | "--durable" | "-h" | "--help" | "-V"
+            | "--version" => {}
+            arg if arg.starts_with("--threads=") || arg.starts_with("--format=") => {}
+            arg if arg.starts_with("-v") && arg[1..].chars().all(|c| c == 'v') => {}
+            arg => {
+                return arg != "help"
+                    && <Cli as CommandFactory>::command()
+                        .find_subcommand(arg)
+                        .is_none()
+            }
+        }
+    }
+    false
+}
+
+impl Options {
+    fn finish(mut self) -> Result<Self, String> {
+        if let [delta, steps] = &self.sensitivity_args[..] {
+            let delta = delta
+                .parse()
+                .map_err(|e| format!("Invalid --sensitivity delta: {}", e))?;
+            let steps = steps
+                .parse()
+                .map_err(|e| format!("Invalid --sensitivity steps: {}", e))?;
+            self.sensitivity = Some((delta, steps));
+        }
+        if self.first_only {
+            self.max_alignments = Some(1);
+        }
+        if let Some(output) = self.output.take() {
+            self.output_file = output;
+        }
+        if self.stream && self.format != OutputFormat::Fasta {
+            return Err("--stream needs --format fasta".to_string());
+        }
+        if self.seq_a.is_some() && self.preset.is_none() {
+            if !self.sets_scores() {
+                return Err(
+                    "--seq-a and --seq-b need scores: give --preset, --matrix, --match and \
+                     --mismatch, or --scores"
+                        .to_string(),
+                );
+            }
+            if self.gap_penalties([f64::NAN; 4]).iter().any(|p| p.is_nan()) {
+                return Err(
+                    "--seq-a and --seq-b need all four gap penalties: give --preset, --gaps, \
+                     or --gap-open and --gap-extend"
+                        .to_string(),
+                );
+            }
+        }
+        self.consensus_rule = ConsensusRule {
+            mismatch: self.consensus_mismatch.take().unwrap_or_default(),
+            gaps: self.consensus_gaps,
+        };
+        Ok(self)
+    }
+
+    /// Alignment type forced on the command line, `true` for global
+    pub fn global_alignment(&self) -> Option<bool> {
+        match (self.global, self.local) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Sequences A and B given on the command line instead of an input file
+    pub fn sequences(&self) -> Option<(&str, &str)> {
+        Some((self.seq_a.as_deref()?, self.seq_b.as_deref()?))
+    }
+
+    /// The preset named by `--preset`
+    pub fn preset(&self) -> Option<&'static Preset> {
+        Preset::find(self.preset.as_deref()?)
+    }
+
+    /// Whether a flag other than `--preset` replaces the match scores
+    pub fn sets_scores(&self) -> bool {
+        self.matrix.is_some() || self.match_score.is_some() || self.scores_file.is_some()
+    }
+
+    /// Match and mismatch scores given on the command line
+    pub fn simple_scores(&self) -> Option<(f64, f64)> {
+        Some((self.match_score?, self.mismatch?))
+    }
+
+    /// Whether any flag overrides the alignment type or gap penalties of the input file
+    pub fn overrides_parameters(&self) -> bool {
+        let gap_flags = [
+            self.gap_open,
+            self.gap_extend,
+            self.gap_open_a,
+            self.gap_extend_a,
+            self.gap_open_b,
+            self.gap_extend_b,
+        ];
+        self.global_alignment().is_some()
+            || self.preset.is_some()
+            || self.semiglobal
+            || self.soft_clip_a
+            || self.gaps.is_some()
+            || self.terminal_gaps_a.is_some()
+            || self.terminal_gaps_b.is_some()
+            || self.end_gap_bonus.is_some()
+            || gap_flags.iter().any(Option::is_some)
+    }
+
+    /// Gap penalties `[dx, ex, dy, ey]` after applying the command-line overrides to
+    /// those of the input file
+    pub fn gap_penalties(&self, [dx, ex, dy, ey]: [f64; 4]) -> [f64; 4] {
+        if let Some(gaps) = &self.gaps {
+            return [gaps.dx, gaps.ex, gaps.dy, gaps.ey];
+        }
+        [
+            self.gap_open_a.or(self.gap_open).unwrap_or(dx),
+            self.gap_extend_a.or(self.gap_extend).unwrap_or(ex),
+            self.gap_open_b.or(self.gap_open).unwrap_or(dy),
+            self.gap_extend_b.or(self.gap_extend).unwrap_or(ey),
+        ]
+    }
+
+    /// How the ends of A and of B are charged, `--terminal-gaps-a` and
+    /// `--terminal-gaps-b` replacing the treatment in `gaps`
+    pub fn end_gaps(&self, gaps: &GapPenalties<f64>) -> (EndGaps<f64>, EndGaps<f64>) {
+        let terminal = |flag: Option<(f64, f64)>, current| {
+            flag.map_or(current, |(open, extend)| EndGaps::Terminal { open, extend })
+        };
+        (
+            terminal(self.terminal_gaps_a, gaps.a_end_gaps()),
+            terminal(self.terminal_gaps_b, gaps.b_end_gaps()),
+        )
+    }
+
+    /// Karlin–Altschul parameters given on the command line
+    pub fn karlin_altschul(&self) -> Option<KarlinAltschul> {
+        Some(KarlinAltschul::new(self.lambda?, self.karlin_k?))
+    }
+
+    /// Whether `--export-npy` was given
+    #[cfg(feature = "numpy")]
+    pub fn exports_npy(&self) -> bool {
+        self.export_npy.is_some()
+    }
+
+    #[cfg(not(feature = "numpy"))]
+    pub fn exports_npy(&self) -> bool {
+        false
+    }
+
+    /// Whether `--matrix-storage disk` was given
+    #[cfg(feature = "mmap")]
+    pub fn stores_on_disk(&self) -> bool {
+        self.matrix_storage == MatrixStorage::Disk
+    }
+
+    #[cfg(not(feature = "mmap"))]
+    pub fn stores_on_disk(&self) -> bool {
+        false
+    }
+
+    /// Constraints on gap placement from `--max-gap-length` and `--no-gap-region-a`
+    pub fn gap_constraints(&self) -> GapConstraints {
+        GapConstraints {
+            max_gap_length: self.max_gap_length,
+            no_gap_region_a: self.no_gap_region_a.clone(),
+        }
+    }
+
+    /// Whether to write FASTA output as the traceback builds each alignment: only with
+    /// `--stream`, and unless another option needs every alignment, or a grid other than
+    /// the plain one, before anything is written.
+    pub fn streams_output(&self) -> bool {
+        self.stream
+            && self.format == OutputFormat::Fasta
+            && !self.parallel_output
+            && self.traces_plain_grid()
+    }
+
+    /// Whether the alignments are written as they are, traced back from a grid filled
+    /// without constraints, so that they may be written as the traceback builds them
+    pub fn traces_plain_grid(&self) -> bool {
+        !self.tokens
+            && !self.score_only
+            && !self.verify
+            && !self.dna_stats
+            && !self.log_space
+            && !self.shortest_path
+            && self.top_hits.is_none()
+            && self.gap_constraints().is_empty()
+            && self.codon_gaps.is_none()
+            && !self.trim_end_gaps
+            && self.trim_below_identity.is_none()
+            && self.canonical.is_none()
+            && self.profile_file.is_none()
+            && self.vcf_file.is_none()
+            && self.consensus_file.is_none()
+            && self.chain_file.is_none()
+            && self.dump_dot.is_none()
+            && !self.exports_npy()
+            && !self.check_consistency
+            && !self.check_scores
+            && self.trace_execution.is_none()
+            && self.sensitivity.is_none()
+            && self.max_evalue.is_none()
+            && self.shuffles.is_none()
+    }
+
+    /// How the DP runs for these options
+    pub fn algorithm(&self) -> Algorithm {
+        if self.score_only {
+            Algorithm::ScoreOnly
+        } else if self.log_space {
+            Algorithm::LogSpace
+        } else {
+            Algorithm::FillAndTraceback
+        }
+    }
+
+    /// Options that would make a large run cheaper and still give what it is asked to
+    /// write. `--score-only` needs free end gaps in global alignments, so
+    /// `charges_global_ends` rules it out.
+    pub fn cheaper_modes(&self, charges_global_ends: bool) -> Vec<&'static str> {
+        let needs_alignments = self.verify
+            || self.profile_file.is_some()
+            || self.dna_stats
+            || self.count_paths
+            || self.canonical.is_some()
+            || self.max_alignments.is_some()
+            || self.first_only
+            || self.vcf_file.is_some()
+            || self.consensus_file.is_some()
+            || self.chain_file.is_some()
+            || self.sensitivity.is_some()
+            || self.shuffles.is_some()
+            || self.dump_dot.is_some()
+            || self.shortest_path
+            || self.max_starts.is_some()
+            || self.max_paths_per_start.is_some();
+        let needs_full_grid = self.semiglobal
+            || self.soft_clip_a
+            || self.terminal_gaps_a.is_some()
+            || self.terminal_gaps_b.is_some()
+            || self.end_gap_bonus.is_some()
+            || self.log_space
+            || charges_global_ends;
+        let mut modes = Vec::new();
+        if !self.score_only && !needs_alignments && !needs_full_grid {
+            modes.push("--score-only, which keeps two rows of the grid");
+        }
+        modes
+    }
+
+    /// Limits for the traceback. `--max-alignments` is applied after `--canonical`
+    /// drops duplicates instead, so it cannot cut the traceback short there.
+    pub fn traceback_limits(&self) -> TracebackLimits {
+        TracebackLimits {
+            max_starts: self.max_starts,
+            max_paths_per_start: self.max_paths_per_start,
+            max_alignments: self.max_alignments.filter(|_| self.canonical.is_none()),
+        }
+    }
+}
+
+impl SearchOptions {
+    fn finish(mut self) -> Self {
+        // Without a threshold, report the ten best hits
+        let top = match (self.top, self.min_score, self.max_evalue) {
+            (None, None, None) => Some(DEFAULT_TOP_HITS),
+            (top, _, _) => top,
+        };
+        self.filter = HitFilter {
+            top,
+            min_score: self.min_score,
+            max_evalue: self.max_evalue,
+            coverage: self.coverage.filter(),
+        };
+        self
+    }
+
+    /// Karlin–Altschul parameters given on the command line
+    pub fn karlin_altschul(&self) -> Option<KarlinAltschul> {
+        Some(KarlinAltschul::new(self.lambda?, self.karlin_k?))
+    }
+}
@@ -0,0 +1,127 @@
+use crate::models::{Alignment, AlignmentOp};
+use std::fmt;
+use std::str::FromStr;
+
+/// How a column with two different residues is resolved
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum MismatchRule {
+    /// Take the residue from sequence A
+    #[default]
+    PreferA,
+    /// Take the residue from sequence B
+    PreferB,
+    /// IUPAC ambiguity code for the two bases, `N` if either is not A, C, G or T
+    Iupac,
+    /// Take the residue with the higher Phred quality, A on ties. Qualities are indexed
+    /// by position in the full sequences, like FASTQ quality strings.
+    Quality {
+        quality_a: Vec<u8>,
+        quality_b: Vec<u8>,
+    },
+}
+
+impl FromStr for MismatchRule {
+    type Err = String;
+
+    /// Parse the rules that need no extra data: `a`, `b` or `iupac`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "a" => Ok(MismatchRule::PreferA),
+            "b" => Ok(MismatchRule::PreferB),
+            "iupac" => Ok(MismatchRule::Iupac),
+            _ => Err(format!("Unknown consensus rule: {}", s)),
+        }
+    }
+}
+
+/// What to do with a column where one sequence has a gap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GapPolicy {
+    /// Keep the residue from the sequence without the gap
+    #[default]
+    Keep,
+    /// Leave the column out of the consensus
+    Drop,
+}
+
+impl FromStr for GapPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "keep" => Ok(GapPolicy::Keep),
+            "drop" => Ok(GapPolicy::Drop),
+            _ => Err(format!("Unknown gap policy: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for GapPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            GapPolicy::Keep => "keep",
+            GapPolicy::Drop => "drop",
+        })
+    }
+}
+
+/// Rules for building a consensus from a pairwise alignment
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ConsensusRule {
+    pub mismatch: MismatchRule,
+    pub gaps: GapPolicy,
+}
+
+/// IUPAC code for an unordered pair of distinct bases
+fn iupac_code(a: char, b: char) -> char {
+    let (a, b) = (a.to_ascii_uppercase(), b.to_ascii_uppercase());
+    let (low, high) = if a < b { (a, b) } else { (b, a) };
+    match (low, high) {
+        ('A', 'G') => 'R',
+        ('C', 'T') => 'Y',
+        ('C', 'G') => 'S',
+        ('A', 'T') => 'W',
+        ('G', 'T') => 'K',
+        ('A', 'C') => 'M',
+        _ => 'N',
+    }
+}
+
+/// Build a consensus string from a character alignment
+pub fn consensus(alignment: &Alignment, rule: &ConsensusRule) -> String {
+    let mut out = String::with_capacity(alignment.len());
+    let (mut pos_a, mut pos_b) = (alignment.start_a, alignment.start_b);
+
+    for (op, (a, b)) in alignment
+        .ops
+        .iter()
+        .zip(alignment.align_a.chars().zip(alignment.align_b.chars()))
+    {
+        match op {
+            AlignmentOp::Match if a == b => out.push(a),
+            AlignmentOp::Match => out.push(match &rule.mismatch {
+                MismatchRule::PreferA => a,
+                MismatchRule::PreferB => b,
+                MismatchRule::Iupac => iupac_code(a, b),
+                MismatchRule::Quality {
+                    quality_a,
+                    quality_b,
+                } => {
+                    let q_a = quality_a.get(pos_a).copied().unwrap_or_default();
+                    let q_b = quality_b.get(pos_b).copied().unwrap_or_default();
+                    if q_b > q_a {
+                        b
+                    } else {
+                        a
+                    }
+                }
+            }),
+            AlignmentOp::Deletion if rule.gaps == GapPolicy::Keep => out.push(a),
+            AlignmentOp::Insertion if rule.gaps == GapPolicy::Keep => out.push(b),
+            _ => {}
+        }
+        pos_a += usize::from(op.consumes_a());
+        pos_b += usize::from(op.consumes_b());
+    }
+    out
+}
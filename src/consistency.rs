@@ -0,0 +1,304 @@
+use crate::alignment::traceback;
+use crate::error::AlignmentError;
+use crate::io::parameters::AlignmentParameters;
+use crate::models::score_matrix::MatrixType::{Ix, Iy, M};
+use crate::models::score_matrix::{MatrixType, Pointer, ScoreMatrix};
+use crate::models::{AlignGrid, AlignmentResult, Residue, TokenFormat};
+use crate::score_type::{cast_score, ScoreType};
+use crate::utils::{clamp_to_zero, max, Epsilon};
+use num_traits::Zero;
+use std::fmt;
+use std::fmt::Display;
+use std::ops::Sub;
+use std::str::FromStr;
+
+/// Grids of up to this many cells per matrix are checked in full; larger ones are
+/// sampled down to about this many
+pub const FULL_CHECK_CELLS: usize = 1 << 20;
+
+/// One way of reaching a cell: a predecessor and the step from it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Candidate<T> {
+    pub from: Pointer,
+    pub from_score: T,
+    /// Match score added or gap penalty subtracted
+    pub step: T,
+    /// Score the cell would take from this predecessor
+    pub value: T,
+}
+
+/// A cell whose stored score or pointers differ from those recomputed from the scores of
+/// its predecessors. Cells are in grid coordinates, row and column 0 being the boundary.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellMismatch<T> {
+    pub matrix: MatrixType,
+    pub row: usize,
+    pub col: usize,
+    pub stored_score: T,
+    pub recomputed_score: T,
+    pub stored_pointers: Vec<Pointer>,
+    pub expected_pointers: Vec<Pointer>,
+    /// Every predecessor considered, whether or not it attains the score
+    pub candidates: Vec<Candidate<T>>,
+}
+
+fn format_cell((matrix, row, col): Pointer) -> String {
+    format!("{}[{},{}]", matrix.as_str(), row, col)
+}
+
+fn format_pointers(pointers: &[Pointer]) -> String {
+    let cells: Vec<String> = pointers
+        .iter()
+        .map(|&pointer| format_cell(pointer))
+        .collect();
+    format!("[{}]", cells.join(", "))
+}
+
+impl<T: Display> fmt::Display for CellMismatch<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: score {} (recomputed {}), pointers {} (expected {})",
+            format_cell((self.matrix, self.row, self.col)),
+            self.stored_score,
+            self.recomputed_score,
+            format_pointers(&self.stored_pointers),
+            format_pointers(&self.expected_pointers)
+        )?;
+        for candidate in &self.candidates {
+            write!(
+                f,
+                "; from {} = {} with step {} gives {}",
+                format_cell(candidate.from),
+                candidate.from_score,
+                candidate.step,
+                candidate.value
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of [`check_consistency`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsistencyReport<T> {
+    /// Cells checked in each of M, Ix and Iy
+    pub checked: usize,
+    /// Cells that did not match, over all three matrices
+    pub mismatched: usize,
+    /// The first mismatching cells in fill order, up to the number asked for
+    pub first: Vec<CellMismatch<T>>,
+}
+
+impl<T> ConsistencyReport<T> {
+    pub fn is_consistent(&self) -> bool {
+        self.mismatched == 0
+    }
+}
+
+/// Check a filled grid against its own scores: recompute each cell's score from its
+/// predecessors under the recurrence, and the pointers to the predecessors that attain
+/// it, and compare both with what the fill stored.
+///
+/// Traceback follows the stored pointers alone, so a fill whose pointers drift from its
+/// scores gives wrong alignments without any error; this finds the cells where that
+/// happened. Grids of up to [`FULL_CHECK_CELLS`] cells are checked in full, larger ones at
+/// evenly spaced cells. The first `max_reported` mismatches are kept with every
+/// candidate predecessor.
+pub fn check_consistency<T, S>(
+    grid: &AlignGrid<T>,
+    alignment_parameters: &AlignmentParameters<T, S>,
+    max_reported: usize,
+) -> ConsistencyReport<T>
+where
+    T: Copy + Display + Epsilon + FromStr + PartialOrd + Sub<Output = T> + Zero,
+    S: Residue,
+{
+    let (rows, columns) = (grid.m_matrix.nrow - 1, grid.m_matrix.ncol - 1);
+    let cells = rows * columns;
+    let stride = cells.div_ceil(FULL_CHECK_CELLS).max(1);
+    let mut report = ConsistencyReport {
+        checked: 0,
+        mismatched: 0,
+        first: Vec::new(),
+    };
+    for cell in (0..cells).step_by(stride) {
+        let (row, col) = (cell / columns + 1, cell % columns + 1);
+        report.checked += 1;
+        for matrix in [M, Ix, Iy] {
+            if let Some(mismatch) = check_cell(grid, alignment_parameters, matrix, row, col) {
+                report.mismatched += 1;
+                if report.first.len() < max_reported {
+                    report.first.push(mismatch);
+                }
+            }
+        }
+    }
+    report
+}
+
+fn check_cell<T, S>(
+    grid: &AlignGrid<T>,
+    alignment_parameters: &AlignmentParameters<T, S>,
+    matrix: MatrixType,
+    row: usize,
+    col: usize,
+) -> Option<CellMismatch<T>>
+where
+    T: Copy + Display + Epsilon + FromStr + PartialOrd + Sub<Output = T> + Zero,
+    S: Residue,
+{
+    let scores = matrix_scores(grid, matrix);
+    let stored_score = scores.get_score(row, col);
+    let mut stored_pointers = scores.get_pointers(row, col).to_vec();
+    stored_pointers.sort();
+
+    let local = !alignment_parameters.global_alignment;
+    let candidates = candidates(grid, alignment_parameters, matrix, row, col);
+    let best = candidates
+        .iter()
+        .map(|candidate| candidate.value)
+        .reduce(max);
+    let recomputed_score = match best {
+        Some(best) if local => clamp_to_zero(best),
+        Some(best) => best,
+        // The first row of Ix and first column of Iy are set from the end gaps
+        None => stored_score,
+    };
+    // A path ends at the boundary, and in a local alignment wherever the score is zero
+    let ends_path =
+        (matrix == M && (row == 1 || col == 1)) || (local && stored_score <= T::epsilon());
+    let mut expected_pointers: Vec<Pointer> = if ends_path {
+        Vec::new()
+    } else {
+        candidates
+            .iter()
+            .filter(|candidate| T::fuzzy_equals(candidate.value, stored_score))
+            .filter(|candidate| !local || candidate.from_score > T::epsilon())
+            .map(|candidate| candidate.from)
+            .collect()
+    };
+    expected_pointers.sort();
+
+    if T::fuzzy_equals(stored_score, recomputed_score) && stored_pointers == expected_pointers {
+        return None;
+    }
+    Some(CellMismatch {
+        matrix,
+        row,
+        col,
+        stored_score,
+        recomputed_score,
+        stored_pointers,
+        expected_pointers,
+        candidates,
+    })
+}
+
+fn matrix_scores<T>(grid: &AlignGrid<T>, matrix: MatrixType) -> &ScoreMatrix<T> {
+    match matrix {
+        M => &grid.m_matrix,
+        Ix => &grid.ix_matrix,
+        Iy => &grid.iy_matrix,
+    }
+}
+
+/// The predecessors of a cell under the affine recurrence: the diagonal in every matrix
+/// for M, and opening from M or extending for a gap
+fn candidates<T, S>(
+    grid: &AlignGrid<T>,
+    alignment_parameters: &AlignmentParameters<T, S>,
+    matrix: MatrixType,
+    row: usize,
+    col: usize,
+) -> Vec<Candidate<T>>
+where
+    T: Copy + Display + Epsilon + FromStr + PartialOrd + Sub<Output = T> + Zero,
+    S: Residue,
+{
+    let gaps = &alignment_parameters.gap_penalties;
+    let local = !alignment_parameters.global_alignment;
+    let candidate = |from: MatrixType, from_row: usize, from_col: usize, step: T, add: bool| {
+        let from_score = matrix_scores(grid, from).get_score(from_row, from_col);
+        Candidate {
+            from: (from, from_row, from_col),
+            from_score,
+            step,
+            value: if add {
+                from_score + step
+            } else {
+                from_score - step
+            },
+        }
+    };
+    match matrix {
+        M => {
+            let sequences = &alignment_parameters.sequences;
+            let score = if sequences.is_padding(row - 1, col - 1) {
+                T::zero()
+            } else {
+                alignment_parameters
+                    .match_matrix
+                    .get_score(&sequences.seq_a[row - 1], &sequences.seq_b[col - 1])
+            };
+            [M, Ix, Iy]
+                .into_iter()
+                .map(|from| candidate(from, row - 1, col - 1, score, true))
+                .collect()
+        }
+        Ix if row > 1 => {
+            let (open, extend) = if local {
+                (gaps.dy, gaps.ey)
+            } else {
+                gaps.y_penalties(col, alignment_parameters.sequences.orig_len_b() + 1)
+            };
+            vec![
+                candidate(M, row - 1, col, open, false),
+                candidate(Ix, row - 1, col, extend, false),
+            ]
+        }
+        Iy if col > 1 => {
+            let (open, extend) = if local {
+                (gaps.dx, gaps.ex)
+            } else {
+                gaps.x_penalties(row, alignment_parameters.sequences.orig_len_a() + 1)
+            };
+            vec![
+                candidate(M, row, col - 1, open, false),
+                candidate(Iy, row, col - 1, extend, false),
+            ]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Whether an input aligns the same with `f64` and with `i32` scores: the optimal scores
+/// within a relative tolerance `rtol`, and the same alignments in the same order.
+///
+/// Both grids run the same generic fill and traceback, so any disagreement on an input
+/// with integer scores points to arithmetic or comparisons in them that depend on the
+/// score type, such as an epsilon applied to one type and not the other. Fails if the
+/// input does not parse or has a score or penalty that is not an integer.
+pub fn scores_agree_int_vs_float(params_str: &str, rtol: f64) -> Result<bool, AlignmentError> {
+    let float_parameters = AlignmentParameters::<f64>::load_from_str(params_str)?;
+    let int_parameters =
+        float_parameters.try_map_scores(|score| cast_score::<i32>(score, ScoreType::I32))?;
+    let float_result = align(&float_parameters)?;
+    let int_result = align(&int_parameters)?;
+
+    let (float_score, int_score) = (float_result.score, f64::from(int_result.score));
+    let scores_agree =
+        (float_score - int_score).abs() <= rtol * float_score.abs().max(int_score.abs());
+    Ok(scores_agree && float_result.alignments == int_result.alignments)
+}
+
+fn align<T>(parameters: &AlignmentParameters<T>) -> Result<AlignmentResult<T>, AlignmentError>
+where
+    T: Copy + Display + Epsilon + FromStr + PartialOrd + Sub<Output = T> + Zero,
+    <T as FromStr>::Err: Display,
+{
+    let mut grid = AlignGrid::new(parameters.len_a(), parameters.len_b());
+    grid.populate_score_matrices(parameters)
+        .and_then(|_| traceback(&grid, parameters, &TokenFormat::default()))
+        .map_err(|e| AlignmentError::InvalidInput(e.to_string()))
+}
@@ -0,0 +1,168 @@
+use crate::models::{Alignment, AlignmentResult};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::io::Write;
+
+/// Identifies an alignment by where it starts and how its columns are laid out
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct AlignmentKey {
+    pub start_a: usize,
+    pub start_b: usize,
+    pub cigar: String,
+}
+
+impl AlignmentKey {
+    pub fn of(alignment: &Alignment) -> Self {
+        Self {
+            start_a: alignment.start_a,
+            start_b: alignment.start_b,
+            cigar: alignment.cigar(),
+        }
+    }
+}
+
+/// Column agreement between two different alignments covering some of the same A positions
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnAgreement {
+    /// Index of the alignment in the first result
+    pub first: usize,
+    /// Index of the alignment in the second result
+    pub second: usize,
+    /// Positions of A covered by both alignments
+    pub shared_positions: usize,
+    /// Shared positions aligned to the same B position (or to a gap) in both
+    pub agreeing_positions: usize,
+    pub percent_agreement: f64,
+}
+
+/// Differences between two alignment results of the same sequence pair
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffReport {
+    pub score_first: f64,
+    pub score_second: f64,
+    pub score_delta: f64,
+    /// Number of alignments present in both results
+    pub shared: usize,
+    pub only_in_first: Vec<AlignmentKey>,
+    pub only_in_second: Vec<AlignmentKey>,
+    /// Best-matching overlapping counterpart for each alignment unique to the first result
+    pub agreements: Vec<ColumnAgreement>,
+}
+
+/// Compare two results, matching alignments by start coordinates and CIGAR
+pub fn diff_results(first: &AlignmentResult<f64>, second: &AlignmentResult<f64>) -> DiffReport {
+    let keys_first: Vec<AlignmentKey> = first
+        .alignments
+        .iter()
+        .map(|alignment| AlignmentKey::of(alignment))
+        .collect();
+    let keys_second: Vec<AlignmentKey> = second
+        .alignments
+        .iter()
+        .map(|alignment| AlignmentKey::of(alignment))
+        .collect();
+    let set_first: HashSet<&AlignmentKey> = keys_first.iter().collect();
+    let set_second: HashSet<&AlignmentKey> = keys_second.iter().collect();
+
+    let unique_first: Vec<usize> = (0..keys_first.len())
+        .filter(|&i| !set_second.contains(&keys_first[i]))
+        .collect();
+    let unique_second: Vec<usize> = (0..keys_second.len())
+        .filter(|&j| !set_first.contains(&keys_second[j]))
+        .collect();
+
+    let agreements = unique_first
+        .iter()
+        .filter_map(|&i| {
+            unique_second
+                .iter()
+                .filter_map(|&j| {
+                    column_agreement(&first.alignments[i], &second.alignments[j]).map(
+                        |(shared_positions, agreeing_positions)| ColumnAgreement {
+                            first: i,
+                            second: j,
+                            shared_positions,
+                            agreeing_positions,
+                            percent_agreement: 100.0 * agreeing_positions as f64
+                                / shared_positions as f64,
+                        },
+                    )
+                })
+                .max_by_key(|a| (a.agreeing_positions, a.shared_positions))
+        })
+        .collect();
+
+    DiffReport {
+        score_first: first.score,
+        score_second: second.score,
+        score_delta: second.score - first.score,
+        shared: set_first.intersection(&set_second).count(),
+        only_in_first: unique_first
+            .iter()
+            .map(|&i| keys_first[i].clone())
+            .collect(),
+        only_in_second: unique_second
+            .iter()
+            .map(|&j| keys_second[j].clone())
+            .collect(),
+        agreements,
+    }
+}
+
+/// Count A positions covered by both alignments and how many of them map to the same
+/// B position. Returns `None` when the alignments do not overlap on A.
+pub fn column_agreement(first: &Alignment, second: &Alignment) -> Option<(usize, usize)> {
+    let projected: HashMap<usize, Option<usize>> = second.project_onto_a().into_iter().collect();
+    let (shared, agreeing) = first
+        .project_onto_a()
+        .into_iter()
+        .filter_map(|(pos_a, pos_b)| projected.get(&pos_a).map(|other| pos_b == *other))
+        .fold((0, 0), |(shared, agreeing), same| {
+            (shared + 1, agreeing + usize::from(same))
+        });
+    (shared > 0).then_some((shared, agreeing))
+}
+
+impl DiffReport {
+    /// Write a human-readable summary
+    pub fn write_text<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "score: {} -> {} (delta {})",
+            self.score_first, self.score_second, self.score_delta
+        )?;
+        writeln!(writer, "shared alignments: {}", self.shared)?;
+        for (label, keys) in [
+            ("only in first", &self.only_in_first),
+            ("only in second", &self.only_in_second),
+        ] {
+            writeln!(writer, "{}: {}", label, keys.len())?;
+            for key in keys {
+                writeln!(
+                    writer,
+                    "  a={} b={} {}",
+                    key.start_a + 1,
+                    key.start_b + 1,
+                    key.cigar
+                )?;
+            }
+        }
+        for agreement in &self.agreements {
+            writeln!(
+                writer,
+                "first #{} vs second #{}: {}/{} shared positions agree ({:.1}%)",
+                agreement.first + 1,
+                agreement.second + 1,
+                agreement.agreeing_positions,
+                agreement.shared_positions,
+                agreement.percent_agreement
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
@@ -0,0 +1,125 @@
+use crate::models::{Alignment, ColumnKind};
+use serde::Serialize;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::str::FromStr;
+
+/// Written in place of a distance that is not defined, such as Jukes–Cantor at p ≥ 0.75
+pub const UNDEFINED_DISTANCE: f64 = -1.0;
+
+/// Distances between two sequences derived from their alignment.
+///
+/// Gaps use pairwise deletion: any column with a gap in either row is left out, so the
+/// distances only reflect columns where both sequences have a residue. A distance is
+/// `None` when it is undefined.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PairDistances {
+    /// Aligned columns without a gap in either row
+    pub compared: usize,
+    pub mismatches: usize,
+    /// Mismatches over compared columns
+    pub p_distance: Option<f64>,
+    /// `-3/4 ln(1 - 4p/3)`, meaningful for nucleotides and undefined for p ≥ 0.75
+    pub jukes_cantor: Option<f64>,
+    /// `1 - score / self-score`, using the larger of the two self-alignment scores
+    pub score_distance: f64,
+}
+
+impl PairDistances {
+    /// Compute distances for one alignment, given the pair's score ratio
+    pub fn from_alignment(alignment: &Alignment, score_ratio: f64) -> Self {
+        let (compared, mismatches) = alignment
+            .columns()
+            .filter(|column| !column.kind.is_gap())
+            .fold((0, 0), |(compared, mismatches), column| {
+                let mismatch = column.kind == ColumnKind::Mismatch;
+                (compared + 1, mismatches + usize::from(mismatch))
+            });
+
+        let p_distance = (compared > 0).then(|| mismatches as f64 / compared as f64);
+        let jukes_cantor = p_distance
+            .filter(|&p| p < 0.75)
+            .map(|p| 0.75 * (3.0 / (3.0 - 4.0 * p)).ln());
+
+        Self {
+            compared,
+            mismatches,
+            p_distance,
+            jukes_cantor,
+            score_distance: 1.0 - score_ratio,
+        }
+    }
+
+    pub fn get(&self, metric: DistanceMetric) -> Option<f64> {
+        match metric {
+            DistanceMetric::PDistance => self.p_distance,
+            DistanceMetric::JukesCantor => self.jukes_cantor,
+            DistanceMetric::Score => Some(self.score_distance),
+        }
+    }
+}
+
+/// Distance written to the PHYLIP matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DistanceMetric {
+    #[default]
+    PDistance,
+    JukesCantor,
+    Score,
+}
+
+impl DistanceMetric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DistanceMetric::PDistance => "p",
+            DistanceMetric::JukesCantor => "jc",
+            DistanceMetric::Score => "score",
+        }
+    }
+}
+
+impl fmt::Display for DistanceMetric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DistanceMetric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "p" => Ok(DistanceMetric::PDistance),
+            "jc" => Ok(DistanceMetric::JukesCantor),
+            "score" => Ok(DistanceMetric::Score),
+            _ => Err(format!("Unknown distance metric: {}", s)),
+        }
+    }
+}
+
+/// Write a square distance matrix in relaxed PHYLIP format.
+///
+/// Each row starts with the full name padded to 10 characters. `distances[i][j]` is
+/// read for `i != j`; the diagonal is written as zero and undefined distances as
+/// [`UNDEFINED_DISTANCE`].
+pub fn write_phylip<W: Write>(
+    writer: &mut W,
+    names: &[String],
+    distances: &[Vec<Option<f64>>],
+) -> io::Result<()> {
+    writeln!(writer, "{}", names.len())?;
+    for (i, (name, row)) in names.iter().zip(distances).enumerate() {
+        write!(writer, "{:<10}", name)?;
+        for (j, distance) in row.iter().enumerate() {
+            let distance = if i == j {
+                0.0
+            } else {
+                distance.unwrap_or(UNDEFINED_DISTANCE)
+            };
+            write!(writer, " {:.6}", distance)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
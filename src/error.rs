@@ -0,0 +1,73 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Errors raised while preparing or running an alignment
+#[derive(Debug)]
+pub enum AlignmentError {
+    /// Two parameter sets that cannot be combined
+    IncompatibleParameters(String),
+    /// Input that is well-formed but fails validation
+    InvalidInput(String),
+    /// Input too large for the requested operation
+    LimitExceeded(String),
+    /// Failure reading a file, with the 1-based line it was found on when known. The path
+    /// is empty for errors on a stream that is not a named file.
+    Io {
+        source: io::Error,
+        path: PathBuf,
+        line: Option<usize>,
+    },
+}
+
+impl fmt::Display for AlignmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlignmentError::IncompatibleParameters(msg) => {
+                write!(f, "Incompatible parameters: {}", msg)
+            }
+            AlignmentError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
+            AlignmentError::LimitExceeded(msg) => write!(f, "Limit exceeded: {}", msg),
+            AlignmentError::Io { source, path, .. } if path.as_os_str().is_empty() => {
+                write!(f, "I/O error: {}", source)
+            }
+            AlignmentError::Io {
+                source,
+                path,
+                line: Some(line),
+            } => write!(f, "{}:{}: {}", path.display(), line, source),
+            AlignmentError::Io {
+                source,
+                path,
+                line: None,
+            } => write!(f, "{}: {}", path.display(), source),
+        }
+    }
+}
+
+impl Error for AlignmentError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AlignmentError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl AlignmentError {
+    /// Attach the file, and the line when known, to an I/O error
+    pub fn io(source: io::Error, path: impl Into<PathBuf>, line: Option<usize>) -> Self {
+        AlignmentError::Io {
+            source,
+            path: path.into(),
+            line,
+        }
+    }
+}
+
+impl From<io::Error> for AlignmentError {
+    fn from(e: io::Error) -> Self {
+        AlignmentError::io(e, PathBuf::new(), None)
+    }
+}
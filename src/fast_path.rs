@@ -0,0 +1,122 @@
+use crate::io::parameters::{AlignmentMode, AlignmentParameters};
+use crate::models::score_matrix::MatrixType::{Ix, Iy, M};
+use crate::models::{Alignment, AlignmentOp, AlignmentResult, Residue, StartInfo, TokenFormat};
+use crate::utils::Epsilon;
+use std::collections::BTreeSet;
+
+/// The result [`AlignGrid::populate_score_matrices`](crate::models::AlignGrid) and
+/// [`traceback`](crate::alignment::traceback) would give, without filling a grid, for
+/// sequences that are identical, or of which one starts the other in a global alignment
+/// where the longer one's ends are free. `None` whenever the shortcut cannot be sure of
+/// giving exactly that result, in which case the DP has to run.
+///
+/// The alignment pairs each residue of the shorter sequence with the same residue of the
+/// other and scores the sum of those pairs, added up in the order the fill adds them. It
+/// is the only optimal alignment when:
+///
+/// - every residue of the shorter sequence scores above epsilon against itself and more
+///   than epsilon higher than against any other residue of the longer one,
+/// - no gap penalty, end gaps and any end-gap bonus included, is negative, and
+/// - for a prefix, gaps in the shorter sequence cost more than epsilon to open and it
+///   occurs nowhere else in the longer one.
+///
+/// Any other alignment then leaves a residue of the shorter sequence unaligned, pairs one
+/// with a worse residue or opens a gap within it, and scores lower by more than the
+/// epsilon within which the traceback takes scores as tied.
+pub fn trivial_alignment<S: Residue>(
+    parameters: &AlignmentParameters<f64, S>,
+    token_format: &TokenFormat,
+) -> Option<AlignmentResult> {
+    let (seq_a, seq_b) = (&parameters.sequences.seq_a, &parameters.sequences.seq_b);
+    let (len_a, len_b) = (seq_a.len(), seq_b.len());
+    let n = len_a.min(len_b);
+    if n == 0 || seq_a[..n] != seq_b[..n] {
+        return None;
+    }
+
+    let gaps = &parameters.gap_penalties;
+    gaps.check_end_gap_bonus(parameters.global_alignment).ok()?;
+    let end_penalties = [gaps.a_end_penalties(), gaps.b_end_penalties()]
+        .into_iter()
+        .flatten()
+        .flat_map(|(open, extend)| [open, extend]);
+    if ![gaps.dx, gaps.ex, gaps.dy, gaps.ey]
+        .into_iter()
+        .chain(end_penalties)
+        .all(|penalty| penalty >= 0.0)
+    {
+        return None;
+    }
+
+    let shorter_is_a = len_a <= len_b;
+    let (shorter, longer) = if shorter_is_a {
+        (seq_a, seq_b)
+    } else {
+        (seq_b, seq_a)
+    };
+    let start = if len_a == len_b {
+        StartInfo::from((M, len_a, len_b))
+    } else {
+        // The longer sequence's overhang follows the last pair in the last row or column
+        let (longer_ends_free, inner_open, matrix) = if shorter_is_a {
+            (gaps.has_free_b_ends(), gaps.dx, Iy)
+        } else {
+            (gaps.has_free_a_ends(), gaps.dy, Ix)
+        };
+        if !parameters.global_alignment
+            || !longer_ends_free
+            || inner_open <= f64::epsilon()
+            || longer
+                .windows(n)
+                .skip(1)
+                .any(|window| window == &shorter[..])
+        {
+            return None;
+        }
+        StartInfo::from((matrix, len_a, len_b))
+    };
+
+    // Scores of a residue of the shorter sequence against one of the longer, as A and B
+    let matrix = &parameters.match_matrix;
+    let score = |x: &S, y: &S| {
+        if shorter_is_a {
+            matrix.get_score(x, y)
+        } else {
+            matrix.get_score(y, x)
+        }
+    };
+    let longer_residues: BTreeSet<&S> = longer.iter().collect();
+    for x in shorter.iter().collect::<BTreeSet<_>>() {
+        let own = score(x, x);
+        if !own.is_finite() || own <= f64::epsilon() {
+            return None;
+        }
+        if longer_residues
+            .iter()
+            .filter(|&&y| y != x)
+            .any(|y| !score(x, y).is_finite() || own - score(x, y) <= f64::epsilon())
+        {
+            return None;
+        }
+    }
+
+    let total = seq_a[..n]
+        .iter()
+        .zip(&seq_b[..n])
+        .fold(0.0, |total, (a, b)| total + matrix.get_score(a, b));
+    let row_a: Vec<_> = seq_a[..n].iter().map(Some).collect();
+    let row_b: Vec<_> = seq_b[..n].iter().map(Some).collect();
+    let mut alignment = Alignment::new(
+        token_format.render(&row_a),
+        token_format.render(&row_b),
+        0,
+        0,
+        vec![AlignmentOp::Match; n],
+    );
+    if parameters.mode() == AlignmentMode::SoftClipA {
+        alignment.soft_clip_a = (0, len_a - n);
+    }
+    let mut result = AlignmentResult::new(total, vec![alignment]);
+    result.starts = vec![start];
+    Some(result)
+}
@@ -0,0 +1,141 @@
+use crate::io::parameters::AlignmentParameters;
+use crate::models::{Alphabet, GapPenalties, MatchMatrix, Sequences};
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::fmt;
+use std::str::FromStr;
+
+const DNA_RESIDUES: &str = "ACGT";
+const PROTEIN_RESIDUES: &str = "ARNDCQEGHILKMFPSTWYV";
+
+/// Residues of generated sequences
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlphabetKind {
+    #[default]
+    Dna,
+    Protein,
+}
+
+impl AlphabetKind {
+    pub const ALL: [AlphabetKind; 2] = [AlphabetKind::Dna, AlphabetKind::Protein];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlphabetKind::Dna => "dna",
+            AlphabetKind::Protein => "protein",
+        }
+    }
+
+    /// The four bases, or the twenty standard amino acids
+    pub fn residues(&self) -> &'static str {
+        match self {
+            AlphabetKind::Dna => DNA_RESIDUES,
+            AlphabetKind::Protein => PROTEIN_RESIDUES,
+        }
+    }
+
+    /// Scoring for generated inputs: +1/-1 with gaps `dx = 3, ex = 1` for DNA, and
+    /// BLOSUM62 with the BLAST default gap costs 11/1 (`dx = 12, ex = 1`) for proteins
+    fn scoring(&self) -> (MatchMatrix<f64>, GapPenalties<f64>) {
+        let residues: Vec<char> = self.residues().chars().collect();
+        match self {
+            AlphabetKind::Dna => (
+                MatchMatrix::simple(&residues, &residues, 1.0, -1.0),
+                GapPenalties::new(3.0, 1.0, 3.0, 1.0),
+            ),
+            AlphabetKind::Protein => (
+                MatchMatrix::builtin("blosum62").unwrap_or_default(),
+                GapPenalties::new(12.0, 1.0, 12.0, 1.0),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for AlphabetKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AlphabetKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dna" => Ok(AlphabetKind::Dna),
+            "protein" => Ok(AlphabetKind::Protein),
+            _ => Err(format!("Unknown alphabet: {}", s)),
+        }
+    }
+}
+
+/// Rates at which [`generate_pair`] mutates the first sequence into the second
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MutationRates {
+    /// Chance that a residue kept in B is the same as in A
+    pub identity: f64,
+    /// Chance per residue of A of an indel: half are deletions of that residue, half
+    /// insertions of a random residue before it
+    pub indel_rate: f64,
+}
+
+/// A random sequence of `length` residues and a mutated copy of it.
+///
+/// Substitutions always change the residue, so the identity of the aligned residues
+/// is close to `rates.identity` for long sequences. The same seed gives the same pair.
+pub fn generate_pair(
+    kind: AlphabetKind,
+    length: usize,
+    rates: MutationRates,
+    seed: u64,
+) -> (String, String) {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let residues: Vec<char> = kind.residues().chars().collect();
+    let random_residue = |rng: &mut ChaCha8Rng| *residues.choose(rng).unwrap_or(&'A');
+
+    let seq_a: String = (0..length).map(|_| random_residue(&mut rng)).collect();
+    let mut seq_b = String::with_capacity(length + length / 10);
+    for a in seq_a.chars() {
+        let indel = rng.random::<f64>();
+        if indel < rates.indel_rate / 2.0 {
+            continue;
+        }
+        if indel < rates.indel_rate {
+            seq_b.push(random_residue(&mut rng));
+        }
+        if rng.random::<f64>() < rates.identity {
+            seq_b.push(a);
+        } else {
+            let substitute = loop {
+                let b = random_residue(&mut rng);
+                if b != a {
+                    break b;
+                }
+            };
+            seq_b.push(substitute);
+        }
+    }
+    (seq_a, seq_b)
+}
+
+/// Parameters for aligning a generated pair, with the scoring of `kind` over its full
+/// alphabet
+pub fn generated_parameters(
+    kind: AlphabetKind,
+    seq_a: String,
+    seq_b: String,
+    global_alignment: bool,
+) -> AlignmentParameters<f64> {
+    let (match_matrix, gap_penalties) = kind.scoring();
+    let mut parameters = AlignmentParameters::new(
+        Sequences::from_string(seq_a, seq_b),
+        global_alignment,
+        gap_penalties,
+        Alphabet::from_string(kind.residues()),
+        Alphabet::from_string(kind.residues()),
+        match_matrix,
+    );
+    parameters.set_global_alignment(global_alignment);
+    parameters
+}
@@ -0,0 +1,309 @@
+use crate::error::AlignmentError;
+use crate::models::{Alignment, AlignmentOp, AlignmentResult, CoveredRange};
+use std::fmt::Display;
+use std::io;
+use std::io::{BufRead, Write};
+
+const DIFFS_HEADER: &str = "alignment\tpos_a\tpos_b\ta\tb";
+
+/// One difference between the rows of an alignment, in 1-based coordinates.
+///
+/// A mismatch has one residue on each side. An indel covers a whole run of gap columns,
+/// with its residues on one side and nothing on the other; its position on the gapped
+/// side is that of the residue before the gap, or 0 at the start of the sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    pub pos_a: usize,
+    pub pos_b: usize,
+    /// Residues of A replaced or deleted, empty for an insertion
+    pub residues_a: String,
+    /// Residues of B substituted or inserted, empty for a deletion
+    pub residues_b: String,
+}
+
+/// List the mismatches and indels of a character alignment, with one character per
+/// column in each row.
+pub fn differences(alignment: &Alignment) -> Vec<Difference> {
+    let row_a: Vec<char> = alignment.align_a.chars().collect();
+    let row_b: Vec<char> = alignment.align_b.chars().collect();
+    let (mut pos_a, mut pos_b) = (alignment.start_a, alignment.start_b);
+    let mut differences: Vec<Difference> = Vec::new();
+    let mut previous = None;
+
+    for (col, &op) in alignment.ops.iter().enumerate() {
+        match op {
+            AlignmentOp::Match => {
+                pos_a += 1;
+                pos_b += 1;
+                if row_a[col] != row_b[col] {
+                    differences.push(Difference {
+                        pos_a,
+                        pos_b,
+                        residues_a: row_a[col].to_string(),
+                        residues_b: row_b[col].to_string(),
+                    });
+                }
+            }
+            AlignmentOp::Deletion => {
+                pos_a += 1;
+                match differences.last_mut() {
+                    Some(last) if previous == Some(op) => last.residues_a.push(row_a[col]),
+                    _ => differences.push(Difference {
+                        pos_a,
+                        pos_b,
+                        residues_a: row_a[col].to_string(),
+                        residues_b: String::new(),
+                    }),
+                }
+            }
+            AlignmentOp::Insertion => {
+                pos_b += 1;
+                match differences.last_mut() {
+                    Some(last) if previous == Some(op) => last.residues_b.push(row_b[col]),
+                    _ => differences.push(Difference {
+                        pos_a,
+                        pos_b,
+                        residues_a: String::new(),
+                        residues_b: row_b[col].to_string(),
+                    }),
+                }
+            }
+        }
+        previous = Some(op);
+    }
+    differences
+}
+
+/// Differences of one alignment read back from a listing, with the ranges it covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlignmentDiffs {
+    pub range: CoveredRange,
+    pub differences: Vec<Difference>,
+}
+
+/// Rebuild the part of B an alignment covers from the part of A it covers,
+/// `seq_a[start_a..end_a]`, and the alignment's [`differences`]. This is the inverse of
+/// the listing, so it returns B's residues `start_b..end_b` unchanged.
+///
+/// Fails if the differences are out of order, fall outside the range or name residues of
+/// A that are not in `seq_a`.
+pub fn apply_differences(
+    seq_a: &[char],
+    start_a: usize,
+    end_a: usize,
+    differences: &[Difference],
+) -> Result<String, AlignmentError> {
+    if start_a > end_a || end_a > seq_a.len() {
+        return Err(AlignmentError::InvalidInput(format!(
+            "range {}-{} does not fit sequence A of length {}",
+            start_a + 1,
+            end_a,
+            seq_a.len()
+        )));
+    }
+    let mut seq_b = String::with_capacity(end_a - start_a);
+    let mut next_a = start_a;
+    for difference in differences {
+        // Residues of A before the difference are unchanged
+        let kept_until = if difference.residues_a.is_empty() {
+            difference.pos_a
+        } else {
+            difference.pos_a.saturating_sub(1)
+        };
+        let replaced = difference.residues_a.chars().count();
+        if kept_until < next_a || kept_until + replaced > end_a {
+            return Err(AlignmentError::InvalidInput(format!(
+                "difference at position {} of A is out of order or outside {}-{}",
+                difference.pos_a,
+                start_a + 1,
+                end_a
+            )));
+        }
+        if !difference
+            .residues_a
+            .chars()
+            .eq(seq_a[kept_until..kept_until + replaced].iter().copied())
+        {
+            return Err(AlignmentError::InvalidInput(format!(
+                "difference at position {} of A names '{}', but A has '{}' there",
+                difference.pos_a,
+                difference.residues_a,
+                seq_a[kept_until..kept_until + replaced]
+                    .iter()
+                    .collect::<String>()
+            )));
+        }
+        seq_b.extend(&seq_a[next_a..kept_until]);
+        seq_b.push_str(&difference.residues_b);
+        next_a = kept_until + replaced;
+    }
+    seq_b.extend(&seq_a[next_a..end_a]);
+    Ok(seq_b)
+}
+
+/// Reconstruct the part of B an alignment covers from sequence A, so that only A and the
+/// alignment need be kept for a sequence similar to it.
+///
+/// `seq_a` must be the whole sequence the alignment was made against. Fails if the
+/// alignment's operations run past the end of A or its row A does not match A.
+pub fn apply_alignment(seq_a: &[char], alignment: &Alignment) -> Result<String, AlignmentError> {
+    if alignment.end_a() > seq_a.len() {
+        return Err(AlignmentError::InvalidInput(format!(
+            "alignment covers A up to position {}, but A has {} residues",
+            alignment.end_a(),
+            seq_a.len()
+        )));
+    }
+    if alignment.align_a.chars().count() != alignment.len()
+        || alignment.align_b.chars().count() != alignment.len()
+    {
+        return Err(AlignmentError::InvalidInput(
+            "applying an alignment needs one character per alignment column".to_string(),
+        ));
+    }
+    // Identical columns are not listed, so check row A against A here
+    let covered = &seq_a[alignment.start_a..alignment.end_a()];
+    let row_a = alignment
+        .align_a
+        .chars()
+        .zip(&alignment.ops)
+        .filter(|(_, op)| op.consumes_a())
+        .map(|(a, _)| a);
+    if !row_a.eq(covered.iter().copied()) {
+        return Err(AlignmentError::InvalidInput(format!(
+            "row A of the alignment does not match A at {}-{}",
+            alignment.start_a + 1,
+            alignment.end_a()
+        )));
+    }
+    apply_differences(
+        seq_a,
+        alignment.start_a,
+        alignment.end_a(),
+        &differences(alignment),
+    )
+}
+
+/// Read a listing written by [`write_diffs_to`], one entry per alignment
+pub fn read_diffs_from<R: BufRead>(reader: R) -> io::Result<Vec<AlignmentDiffs>> {
+    let invalid = |line: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("not a line of a diffs listing: {}", line),
+        )
+    };
+    let mut alignments: Vec<AlignmentDiffs> = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if let Some(header) = line.strip_prefix("# alignment ") {
+            let range = parse_alignment_header(header).ok_or_else(|| invalid(&line))?;
+            alignments.push(AlignmentDiffs {
+                range,
+                differences: Vec::new(),
+            });
+            continue;
+        }
+        if line.starts_with('#') || line.trim().is_empty() || line == DIFFS_HEADER {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [index, pos_a, pos_b, residues_a, residues_b] = fields[..] else {
+            return Err(invalid(&line));
+        };
+        let number = |field: &str| field.parse::<usize>().map_err(|_| invalid(&line));
+        let alignment = number(index)?
+            .checked_sub(1)
+            .and_then(|i| alignments.get_mut(i))
+            .ok_or_else(|| invalid(&line))?;
+        let residues = |field: &str| if field == "-" { "" } else { field }.to_string();
+        alignment.differences.push(Difference {
+            pos_a: number(pos_a)?,
+            pos_b: number(pos_b)?,
+            residues_a: residues(residues_a),
+            residues_b: residues(residues_b),
+        });
+    }
+    Ok(alignments)
+}
+
+/// Ranges of a `k: A s-e, B s-e, CIGAR` alignment comment
+fn parse_alignment_header(header: &str) -> Option<CoveredRange> {
+    let (_, ranges) = header.split_once(": ")?;
+    let mut fields = ranges.split(", ");
+    let mut range = |label: &str| -> Option<(usize, usize)> {
+        let (start, end) = fields.next()?.strip_prefix(label)?.split_once('-')?;
+        Some((
+            start.parse::<usize>().ok()?.checked_sub(1)?,
+            end.parse().ok()?,
+        ))
+    };
+    let (start_a, end_a) = range("A ")?;
+    let (start_b, end_b) = range("B ")?;
+    Some(CoveredRange {
+        start_a,
+        end_a,
+        start_b,
+        end_b,
+    })
+}
+
+/// Write a result as the differences of each alignment, one tab-separated line per
+/// mismatch or indel and `-` for the empty side of an indel, after a comment giving the
+/// ranges each alignment covers.
+///
+/// Expects character alignments, with one character per column in each row.
+pub fn write_diffs_to<W: Write, T: Display>(
+    writer: &mut W,
+    result: &AlignmentResult<T>,
+) -> io::Result<()> {
+    writeln!(writer, "# score: {}", result.score)?;
+    for truncation in &result.truncated {
+        writeln!(writer, "# truncated: {}", truncation)?;
+    }
+    for alignment in &result.alignments {
+        if alignment.align_a.chars().count() != alignment.len()
+            || alignment.align_b.chars().count() != alignment.len()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Diffs output needs one character per alignment column",
+            ));
+        }
+    }
+    for (i, alignment) in result.alignments.iter().enumerate() {
+        writeln!(
+            writer,
+            "# alignment {}: A {}-{}, B {}-{}, {}",
+            i + 1,
+            alignment.start_a + 1,
+            alignment.end_a(),
+            alignment.start_b + 1,
+            alignment.end_b(),
+            alignment.cigar()
+        )?;
+    }
+    writeln!(writer, "{}", DIFFS_HEADER)?;
+    for (i, alignment) in result.alignments.iter().enumerate() {
+        for difference in differences(alignment) {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}",
+                i + 1,
+                difference.pos_a,
+                difference.pos_b,
+                or_gap(&difference.residues_a),
+                or_gap(&difference.residues_b)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// `-` for the empty side of an indel
+fn or_gap(residues: &str) -> &str {
+    if residues.is_empty() {
+        "-"
+    } else {
+        residues
+    }
+}
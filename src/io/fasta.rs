@@ -0,0 +1,76 @@
+use std::io;
+use std::io::BufRead;
+
+/// A named sequence read from a FASTA file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FastaRecord {
+    /// First word of the header line, without the leading `>`
+    pub id: String,
+    pub sequence: String,
+}
+
+/// Streaming FASTA reader yielding one record at a time.
+///
+/// Sequence lines may be wrapped; blank lines and `\r\n` line endings are ignored.
+pub struct FastaReader<R> {
+    lines: io::Lines<R>,
+    next_id: Option<String>,
+}
+
+impl<R: BufRead> FastaReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+            next_id: None,
+        }
+    }
+
+    fn header_id(line: &str) -> String {
+        line[1..]
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    }
+}
+
+impl<R: BufRead> Iterator for FastaReader<R> {
+    type Item = io::Result<FastaRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Find the first header if this is the start of the file
+        while self.next_id.is_none() {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            if !line.starts_with('>') {
+                return Some(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Expected a FASTA header, got: {}", line),
+                )));
+            }
+            self.next_id = Some(Self::header_id(line));
+        }
+
+        let id = self.next_id.take().unwrap_or_default();
+        let mut sequence = String::new();
+        for line in self.lines.by_ref() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            let line = line.trim_end();
+            if line.starts_with('>') {
+                self.next_id = Some(Self::header_id(line));
+                break;
+            }
+            sequence.push_str(line.trim());
+        }
+        Some(Ok(FastaRecord { id, sequence }))
+    }
+}
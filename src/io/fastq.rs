@@ -0,0 +1,129 @@
+use std::io;
+use std::io::BufRead;
+
+/// Offset of the Phred+33 encoding of FASTQ quality strings
+pub const PHRED_OFFSET: u8 = 33;
+
+/// A named sequence with per-base qualities read from a FASTQ file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FastqRecord {
+    /// First word of the header line, without the leading `@`
+    pub id: String,
+    pub sequence: String,
+    /// Phred quality of each base, decoded from Phred+33
+    pub quality: Vec<u8>,
+}
+
+impl FastqRecord {
+    /// The quality string of the record, encoded as Phred+33
+    pub fn quality_string(&self) -> String {
+        encode_quality(&self.quality)
+    }
+}
+
+/// Phred qualities as a Phred+33 quality string, capped at the highest printable one
+pub fn encode_quality(quality: &[u8]) -> String {
+    quality
+        .iter()
+        .map(|&q| char::from(q.min(b'~' - PHRED_OFFSET) + PHRED_OFFSET))
+        .collect()
+}
+
+/// Streaming FASTQ reader yielding one record at a time.
+///
+/// Each record is four lines: the `@` header, the sequence, a `+` separator and the
+/// quality string, which must be as long as the sequence. Blank lines between records
+/// and `\r\n` line endings are ignored; wrapped sequence and quality lines are not
+/// supported.
+pub struct FastqReader<R> {
+    lines: io::Lines<R>,
+}
+
+impl<R: BufRead> FastqReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+        }
+    }
+
+    fn invalid(message: String) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, message)
+    }
+
+    /// The next line of the record with header `id`, failing at the end of the file
+    fn record_line(&mut self, id: &str, what: &str) -> io::Result<String> {
+        match self.lines.next() {
+            Some(line) => Ok(line?.trim_end().to_string()),
+            None => Err(Self::invalid(format!(
+                "FASTQ record {} ends before its {}",
+                id, what
+            ))),
+        }
+    }
+
+    fn read_record(&mut self, header: &str) -> io::Result<FastqRecord> {
+        let id = header[1..]
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let sequence = self.record_line(&id, "sequence")?;
+        let separator = self.record_line(&id, "+ line")?;
+        if !separator.starts_with('+') {
+            return Err(Self::invalid(format!(
+                "FASTQ record {}: expected a + line, got: {}",
+                id, separator
+            )));
+        }
+        let quality_string = self.record_line(&id, "quality string")?;
+        if quality_string.len() != sequence.chars().count() {
+            return Err(Self::invalid(format!(
+                "FASTQ record {}: {} qualities for {} bases",
+                id,
+                quality_string.len(),
+                sequence.chars().count()
+            )));
+        }
+        let quality = quality_string
+            .bytes()
+            .map(|byte| {
+                byte.checked_sub(PHRED_OFFSET).ok_or_else(|| {
+                    Self::invalid(format!(
+                        "FASTQ record {}: invalid quality character {:?}",
+                        id,
+                        char::from(byte)
+                    ))
+                })
+            })
+            .collect::<io::Result<Vec<u8>>>()?;
+        Ok(FastqRecord {
+            id,
+            sequence,
+            quality,
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for FastqReader<R> {
+    type Item = io::Result<FastqRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            if !line.starts_with('@') {
+                return Some(Err(Self::invalid(format!(
+                    "Expected a FASTQ header, got: {}",
+                    line
+                ))));
+            }
+            return Some(self.read_record(line));
+        }
+    }
+}
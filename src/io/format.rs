@@ -0,0 +1,64 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Supported layouts for writing alignment results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Score line followed by blank-line-separated pairs of aligned strings
+    #[default]
+    Text,
+    /// One FASTA record per aligned sequence
+    Fasta,
+    /// One tab-separated row per alignment with coordinates and statistics
+    Tsv,
+    /// A JSON object with the score and one record per alignment
+    Json,
+    /// Rows of each alignment in alternating fixed-width blocks with position counters
+    Interleaved,
+    /// One tab-separated line per mismatch or indel, for near-identical sequences
+    Diffs,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 6] = [
+        OutputFormat::Text,
+        OutputFormat::Fasta,
+        OutputFormat::Tsv,
+        OutputFormat::Json,
+        OutputFormat::Interleaved,
+        OutputFormat::Diffs,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutputFormat::Text => "text",
+            OutputFormat::Fasta => "fasta",
+            OutputFormat::Tsv => "tsv",
+            OutputFormat::Json => "json",
+            OutputFormat::Interleaved => "interleaved",
+            OutputFormat::Diffs => "diffs",
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "fasta" => Ok(OutputFormat::Fasta),
+            "tsv" => Ok(OutputFormat::Tsv),
+            "json" => Ok(OutputFormat::Json),
+            "interleaved" => Ok(OutputFormat::Interleaved),
+            "diffs" => Ok(OutputFormat::Diffs),
+            _ => Err(format!("Unknown output format: {}", s)),
+        }
+    }
+}
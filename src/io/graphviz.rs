@@ -0,0 +1,117 @@
+use crate::alignment::find_traceback_start;
+use crate::error::AlignmentError;
+use crate::io::parameters::AlignmentParameters;
+use crate::models::score_matrix::MatrixType::{Ix, Iy, M};
+use crate::models::score_matrix::{MatrixType, Pointer};
+use crate::models::{AlignGrid, Residue};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// Largest number of residues of either sequence drawn by [`alignment_dag_to_dot`]
+pub const MAX_DOT_LENGTH: usize = 50;
+
+/// Node for a cell, named by the residues it follows so that the boundary does not show
+fn node_id((matrix, row, col): Pointer) -> String {
+    format!("{}_{}_{}", matrix.as_str(), row - 1, col - 1)
+}
+
+fn edge_color(matrix: MatrixType) -> &'static str {
+    match matrix {
+        M => "green",
+        Ix => "red",
+        Iy => "blue",
+    }
+}
+
+fn pointers(grid: &AlignGrid<f64>, (matrix, row, col): Pointer) -> Vec<Pointer> {
+    let pointers = match matrix {
+        M => grid.m_matrix.get_pointers(row, col),
+        Ix => grid.ix_matrix.get_pointers(row, col),
+        Iy => grid.iy_matrix.get_pointers(row, col),
+    };
+    pointers.into_vec()
+}
+
+fn score(grid: &AlignGrid<f64>, (matrix, row, col): Pointer) -> f64 {
+    match matrix {
+        M => grid.m_matrix.get_score(row, col),
+        Ix => grid.ix_matrix.get_score(row, col),
+        Iy => grid.iy_matrix.get_score(row, col),
+    }
+}
+
+/// Render the traceback pointers of a populated grid as a Graphviz DOT digraph, for
+/// viewing with e.g. `dot -Tpng`.
+///
+/// Every cell with a pointer, or pointed to, becomes a node labelled `M(r,c)`, `Ix(r,c)`
+/// or `Iy(r,c)` with its score, and each pointer an edge coloured by the matrix it
+/// leaves: green for M, red for Ix and blue for Iy. Nodes and edges on an optimal path
+/// are drawn bold. Fails for sequences longer than [`MAX_DOT_LENGTH`].
+pub fn alignment_dag_to_dot<S: Residue>(
+    grid: &AlignGrid<f64>,
+    params: &AlignmentParameters<f64, S>,
+) -> Result<String, AlignmentError> {
+    let (rows, columns) = (grid.m_matrix.nrow - 1, grid.m_matrix.ncol - 1);
+    if rows > MAX_DOT_LENGTH || columns > MAX_DOT_LENGTH {
+        return Err(AlignmentError::LimitExceeded(format!(
+            "DOT output is limited to {0} x {0} grids, got {1} x {2}",
+            MAX_DOT_LENGTH, rows, columns
+        )));
+    }
+
+    // Cells on an optimal path are those reachable from a traceback start
+    let (_, starts) = find_traceback_start(grid, params);
+    let mut optimal: HashSet<Pointer> = HashSet::new();
+    let mut stack: Vec<Pointer> = starts.into_iter().collect();
+    while let Some(cell) = stack.pop() {
+        if optimal.insert(cell) {
+            stack.extend(pointers(grid, cell));
+        }
+    }
+
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    for row in 1..=rows {
+        for col in 1..=columns {
+            for matrix in [M, Ix, Iy] {
+                let cell = (matrix, row, col);
+                for target in pointers(grid, cell) {
+                    nodes.push(cell);
+                    nodes.push(target);
+                    edges.push((cell, target));
+                }
+            }
+        }
+    }
+    nodes.sort_by_key(|&(matrix, row, col)| (row, col, matrix.as_str()));
+    nodes.dedup();
+
+    let bold = |optimal_part: bool| if optimal_part { ", style=bold" } else { "" };
+    let mut dot =
+        String::from("digraph alignment {\n    node [shape=box, fontname=\"monospace\"];\n");
+    for cell in nodes {
+        let (matrix, row, col) = cell;
+        let _ = writeln!(
+            dot,
+            "    {} [label=\"{}({},{})\\n{}\"{}];",
+            node_id(cell),
+            matrix.as_str(),
+            row - 1,
+            col - 1,
+            score(grid, cell),
+            bold(optimal.contains(&cell))
+        );
+    }
+    for (from, to) in edges {
+        let _ = writeln!(
+            dot,
+            "    {} -> {} [color={}{}];",
+            node_id(from),
+            node_id(to),
+            edge_color(from.0),
+            bold(optimal.contains(&from))
+        );
+    }
+    dot.push_str("}\n");
+    Ok(dot)
+}
@@ -0,0 +1,111 @@
+use crate::models::{Alignment, AlignmentResult, Column};
+use std::fmt::Display;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use unicode_width::UnicodeWidthChar;
+
+/// Columns per line when no width is given
+pub const DEFAULT_INTERLEAVED_WIDTH: usize = 60;
+
+/// Terminal cells a character of a row takes, at least one so that a zero-width or
+/// control character still gets a column of its own
+fn display_width(c: char) -> usize {
+    c.width().unwrap_or(0).max(1)
+}
+
+/// Write the rows of one alignment in blocks of `width` columns, each row prefixed with
+/// the position of its first residue and followed by the position of its last, 1-based
+fn write_alignment_blocks<W: Write>(
+    writer: &mut W,
+    alignment: &Alignment,
+    width: usize,
+) -> io::Result<()> {
+    let row_a: Vec<char> = alignment.align_a.chars().collect();
+    let row_b: Vec<char> = alignment.align_b.chars().collect();
+    let columns: Vec<Column> = alignment.columns().collect();
+    // Residues of each sequence before the block, the last before it for a line of gaps
+    let (mut before_a, mut before_b) = (alignment.start_a, alignment.start_b);
+
+    for (block, block_columns) in columns.chunks(width).enumerate() {
+        let range = block * width..block * width + block_columns.len();
+        // Pad each column to its wider character, so that a double-width character
+        // opposite a narrow one or a gap leaves the rest of the rows in line
+        let cell_widths: Vec<usize> = range
+            .clone()
+            .map(|i| display_width(row_a[i]).max(display_width(row_b[i])))
+            .collect();
+        let residues = |pos: fn(&Column) -> Option<usize>| {
+            let first = block_columns.iter().find_map(pos);
+            let last = block_columns.iter().rev().find_map(pos);
+            first.zip(last)
+        };
+        for (label, row, before, residues) in [
+            ("A", &row_a, &mut before_a, residues(|column| column.a_pos)),
+            ("B", &row_b, &mut before_b, residues(|column| column.b_pos)),
+        ] {
+            let (start, end) = match residues {
+                Some((first, last)) => (first + 1, last + 1),
+                None => (*before, *before),
+            };
+            *before = end;
+            let mut chunk = String::with_capacity(cell_widths.iter().sum());
+            for (&c, &cell_width) in row[range.clone()].iter().zip(&cell_widths) {
+                chunk.push(c);
+                chunk.extend(std::iter::repeat_n(' ', cell_width - display_width(c)));
+            }
+            writeln!(writer, "{:<6} {:>8} {} {}", label, start, chunk, end)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Write a result with the two rows of each alignment interleaved in blocks of `width`
+/// columns, as the EMBOSS `water` and `needle` tools do.
+///
+/// Expects character alignments, with one character per column in each row. A column
+/// holding a double-width character, such as a CJK ideograph, is padded in the other
+/// row so that the rows stay in line on a terminal.
+pub fn write_interleaved_to<W: Write, T: Display>(
+    writer: &mut W,
+    result: &AlignmentResult<T>,
+    width: usize,
+) -> io::Result<()> {
+    if width == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Interleaved width must be positive",
+        ));
+    }
+    writeln!(writer, "# Score: {}", result.score)?;
+    for truncation in &result.truncated {
+        writeln!(writer, "# Truncated: {}", truncation)?;
+    }
+    for (i, alignment) in result.alignments.iter().enumerate() {
+        if alignment.align_a.chars().count() != alignment.len()
+            || alignment.align_b.chars().count() != alignment.len()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Interleaved output needs one character per alignment column",
+            ));
+        }
+        writeln!(writer, "#")?;
+        writeln!(writer, "# Alignment {}: {}", i + 1, alignment.cigar())?;
+        writeln!(writer)?;
+        write_alignment_blocks(writer, alignment, width)?;
+    }
+    Ok(())
+}
+
+/// Write a result in the interleaved layout to a file
+pub fn write_interleaved<T: Display>(
+    path: &str,
+    result: &AlignmentResult<T>,
+    width: usize,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_interleaved_to(&mut writer, result, width)?;
+    writer.flush()
+}
@@ -1 +1,10 @@
+pub mod diffs;
+pub mod fasta;
+pub mod fastq;
+pub mod format;
+pub mod graphviz;
+pub mod interleaved;
+pub mod ncbi;
 pub mod parameters;
+pub mod streaming;
+pub mod structure;
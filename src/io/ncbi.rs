@@ -0,0 +1,90 @@
+use crate::models::{Alphabet, MatchMatrix};
+use std::io;
+use std::io::{BufRead, Write};
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn parse_residue(field: &str) -> io::Result<char> {
+    let mut chars = field.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(invalid(format!("Invalid residue: {}", field))),
+    }
+}
+
+/// Read a substitution matrix in the NCBI layout used for BLOSUM and PAM files: `#`
+/// comment lines, a header row of column residues, then one row per residue starting
+/// with the residue itself.
+///
+/// Returns the row residues as alphabet A, the column residues as alphabet B, and the
+/// scores.
+pub fn read_ncbi_matrix<R: BufRead>(
+    reader: R,
+) -> io::Result<(Alphabet, Alphabet, MatchMatrix<f64>)> {
+    let mut lines = reader
+        .lines()
+        .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty() || l.starts_with('#')));
+    let header = lines
+        .next()
+        .ok_or_else(|| invalid("Missing header row".to_string()))??;
+    let columns = header
+        .split_whitespace()
+        .map(parse_residue)
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let mut rows = Vec::new();
+    let mut match_matrix = MatchMatrix::new();
+    for line in lines {
+        let line = line?;
+        let mut fields = line.split_whitespace();
+        let a = parse_residue(fields.next().unwrap_or_default())?;
+        let scores: Vec<&str> = fields.collect();
+        if scores.len() != columns.len() {
+            return Err(invalid(format!(
+                "Row {} has {} scores, expected {}",
+                a,
+                scores.len(),
+                columns.len()
+            )));
+        }
+        for (&b, field) in columns.iter().zip(scores) {
+            let score = field
+                .parse()
+                .map_err(|e| invalid(format!("Invalid score for ({}, {}): {}", a, b, e)))?;
+            match_matrix.set_score(a, b, score);
+        }
+        rows.push(a);
+    }
+    Ok((Alphabet::new(rows), Alphabet::new(columns), match_matrix))
+}
+
+/// Write the alphabet and match score lines of an input file
+pub fn write_match_block<W: Write>(
+    writer: &mut W,
+    alphabet_a: &Alphabet,
+    alphabet_b: &Alphabet,
+    match_matrix: &MatchMatrix<f64>,
+) -> io::Result<()> {
+    for alphabet in [alphabet_a, alphabet_b] {
+        writeln!(writer, "{}", alphabet.len())?;
+        writeln!(writer, "{}", alphabet.symbols().iter().collect::<String>())?;
+    }
+    for (i, a) in alphabet_a.symbols().iter().enumerate() {
+        for (j, b) in alphabet_b.symbols().iter().enumerate() {
+            if match_matrix.has_score(a, b) {
+                writeln!(
+                    writer,
+                    "{} {} {} {} {}",
+                    i + 1,
+                    j + 1,
+                    a,
+                    b,
+                    match_matrix.get_score(a, b)
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
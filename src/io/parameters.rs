@@ -1,33 +1,148 @@
-use crate::models::{Alphabet, GapPenalties, MatchMatrix, Sequences};
+use crate::error::AlignmentError;
+use crate::io::ncbi::write_match_block;
+use crate::models::{
+    Alphabet, EndGaps, GapPenalties, MatchMatrix, Residue, Sequences, SoftMaskMode,
+};
+use crate::score_type::{cast_score, ScoreType};
+use crate::stats::KarlinAltschul;
+use log::warn;
 use num_traits::Zero;
+use std::cmp::Ordering;
+use std::fmt;
 use std::fmt::Display;
 use std::fs::File;
 use std::io;
-use std::io::{BufRead, BufReader, Lines};
+use std::io::{BufRead, BufReader, Write};
 use std::str::FromStr;
 
+/// Lines of an input file, counting how many have been read
+struct NumberedLines<I> {
+    lines: I,
+    read: usize,
+    exhausted: bool,
+}
+
+impl<I> NumberedLines<I> {
+    fn new(lines: I) -> Self {
+        Self {
+            lines,
+            read: 0,
+            exhausted: false,
+        }
+    }
+
+    /// 1-based number of the last line read, or `None` once the file has run out or
+    /// before any line was read
+    fn current_line(&self) -> Option<usize> {
+        Some(self.read).filter(|&read| read > 0 && !self.exhausted)
+    }
+}
+
+impl<I: Iterator<Item = io::Result<String>>> Iterator for NumberedLines<I> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.lines.next();
+        match line {
+            Some(_) => self.read += 1,
+            None => self.exhausted = true,
+        }
+        line
+    }
+}
+
 /// Alignment parameters loaded from input file
 #[derive(Debug, Clone)]
-pub struct AlignmentParameters<T: FromStr + Copy> {
-    pub sequences: Sequences,
+pub struct AlignmentParameters<T: FromStr + Copy, S: Residue = char> {
+    pub sequences: Sequences<S>,
     pub global_alignment: bool,
     pub gap_penalties: GapPenalties<T>,
-    pub alphabet_a: Alphabet,
-    pub alphabet_b: Alphabet,
-    pub match_matrix: MatchMatrix<T>,
+    pub alphabet_a: Alphabet<S>,
+    pub alphabet_b: Alphabet<S>,
+    pub match_matrix: MatchMatrix<T, S>,
+    /// Karlin–Altschul parameters given in the input file, if any
+    pub karlin_altschul: Option<KarlinAltschul>,
+    /// Position within its codon, 0, 1 or 2, of A's first residue, for
+    /// [`AlignGrid::populate_codon_aware`](crate::models::AlignGrid::populate_codon_aware)
+    pub frame_offset: u8,
+}
+
+/// Which residues at the ends of the sequences may be left unaligned for free
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlignmentMode {
+    /// Global alignment with free end gaps in both sequences
+    Global,
+    Local,
+    /// All of A against part of B; see [`AlignmentParameters::set_semi_global`]
+    SemiGlobal,
+    /// Part of A against all of B; see [`AlignmentParameters::set_soft_clip_a`]
+    SoftClipA,
+}
+
+impl AlignmentMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlignmentMode::Global => "global",
+            AlignmentMode::Local => "local",
+            AlignmentMode::SemiGlobal => "semi-global",
+            AlignmentMode::SoftClipA => "soft-clip-a",
+        }
+    }
+}
+
+impl fmt::Display for AlignmentMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// `gaps` with the ends of A and of B charged as `a_ends` and `b_ends`, except those
+/// that already have terminal penalties
+fn keeping_terminal<T: Copy + FromStr + Zero>(
+    gaps: &GapPenalties<T>,
+    a_ends: EndGaps<T>,
+    b_ends: EndGaps<T>,
+) -> GapPenalties<T> {
+    gaps.with_end_gaps(
+        a_ends.unless_terminal(gaps.a_end_gaps()),
+        b_ends.unless_terminal(gaps.b_end_gaps()),
+    )
 }
 
-impl<T: Copy + FromStr + Zero> AlignmentParameters<T>
+impl<T: Copy + FromStr + Zero, S: Residue> AlignmentParameters<T, S> {
+    /// Whether this is semi-global alignment as set up by [`Self::set_semi_global`]
+    pub fn is_semi_global(&self) -> bool {
+        self.mode() == AlignmentMode::SemiGlobal
+    }
+
+    /// The alignment mode the end-gap treatment amounts to. Global parameters without
+    /// any free end gaps count as [`AlignmentMode::Global`].
+    pub fn mode(&self) -> AlignmentMode {
+        let gaps = &self.gap_penalties;
+        match (
+            self.global_alignment,
+            gaps.has_free_a_ends(),
+            gaps.has_free_b_ends(),
+        ) {
+            (false, _, _) => AlignmentMode::Local,
+            (true, false, true) => AlignmentMode::SemiGlobal,
+            (true, true, false) => AlignmentMode::SoftClipA,
+            (true, _, _) => AlignmentMode::Global,
+        }
+    }
+}
+
+impl<T: Copy + FromStr + Zero, S: Residue> AlignmentParameters<T, S>
 where
     <T as FromStr>::Err: Display,
 {
     pub fn new(
-        sequences: Sequences,
+        sequences: Sequences<S>,
         global_alignment: bool,
         gap_penalties: GapPenalties<T>,
-        alphabet_a: Alphabet,
-        alphabet_b: Alphabet,
-        match_matrix: MatchMatrix<T>,
+        alphabet_a: Alphabet<S>,
+        alphabet_b: Alphabet<S>,
+        match_matrix: MatchMatrix<T, S>,
     ) -> Self {
         Self {
             sequences,
@@ -36,10 +151,55 @@ where
             alphabet_a,
             alphabet_b,
             match_matrix,
+            karlin_altschul: None,
+            frame_offset: 0,
         }
     }
 
-    fn read_alignment_type(lines: &mut Lines<BufReader<File>>) -> io::Result<bool> {
+    /// Parameters for aligning `sequences` without an input file: a global alignment over
+    /// the residues the sequences contain, with zero gap penalties and no match scores,
+    /// for the caller to fill in.
+    pub fn from_sequences(sequences: Sequences<S>) -> Self {
+        let alphabet_a = Alphabet::of_residues(&sequences.seq_a);
+        let alphabet_b = Alphabet::of_residues(&sequences.seq_b);
+        Self::new(
+            sequences,
+            true,
+            GapPenalties::free_end_gaps(T::zero(), T::zero(), T::zero(), T::zero()),
+            alphabet_a,
+            alphabet_b,
+            MatchMatrix::new(),
+        )
+    }
+
+    /// Parse an optional `karlin-altschul <lambda> <K>` line following the match scores
+    fn read_karlin_altschul(line: Option<&str>) -> io::Result<Option<KarlinAltschul>> {
+        let mut fields = line.unwrap_or_default().split_whitespace();
+        if fields.next() != Some("karlin-altschul") {
+            return Ok(None);
+        }
+        let mut value = |name: &str| -> io::Result<f64> {
+            fields
+                .next()
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("Missing {}", name))
+                })?
+                .parse()
+                .map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Invalid {}: {}", name, e),
+                    )
+                })
+        };
+        let lambda = value("lambda")?;
+        let k = value("K")?;
+        Ok(Some(KarlinAltschul::new(lambda, k)))
+    }
+
+    fn read_alignment_type(
+        lines: &mut impl Iterator<Item = io::Result<String>>,
+    ) -> io::Result<bool> {
         let alignment_type: i32 = lines
             .next()
             .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing alignment type"))??
@@ -53,36 +213,160 @@ where
         Ok(alignment_type == 0)
     }
 
-    pub fn load_from_file(input_file: &str) -> io::Result<Self> {
-        let file = File::open(input_file)?;
-        let reader = BufReader::new(file);
-        let mut lines = reader.lines();
+    /// Read an input file. Errors name the file and, when the problem is on a line that
+    /// was read, the line. Each pair of the alphabets the match scores leave out is logged
+    /// as a warning, as it scores zero; see [`MatchMatrix::validate_completeness`].
+    pub fn load_from_file(input_file: &str) -> Result<Self, AlignmentError> {
+        let file = File::open(input_file).map_err(|e| AlignmentError::io(e, input_file, None))?;
+        let mut lines = NumberedLines::new(BufReader::new(file).lines());
+        Self::read_sections(&mut lines)
+            .map_err(|e| AlignmentError::io(e, input_file, lines.current_line()))
+    }
+
+    /// Read an input file held in memory, laid out as on disk. Errors name the line,
+    /// when the problem is on a line that was read.
+    pub fn load_from_str(input: &str) -> Result<Self, AlignmentError> {
+        let mut lines = NumberedLines::new(input.lines().map(|line| Ok(line.to_string())));
+        Self::read_sections(&mut lines).map_err(|e| match lines.current_line() {
+            Some(line) => AlignmentError::InvalidInput(format!("line {}: {}", line, e)),
+            None => AlignmentError::InvalidInput(e.to_string()),
+        })
+    }
 
+    fn read_sections(lines: &mut impl Iterator<Item = io::Result<String>>) -> io::Result<Self> {
         // Read sequences
-        let sequences = Sequences::load_sequences(&mut lines)?;
+        let sequences = Sequences::load_sequences(lines)?;
 
         // Read alignment type
-        let global_alignment = Self::read_alignment_type(&mut lines)?;
+        let global_alignment = Self::read_alignment_type(lines)?;
 
         // Read gap penalties
-        let gaps = GapPenalties::<T>::read_gap_penalties(&mut lines)?;
+        let mut gaps = GapPenalties::<T>::read_gap_penalties(lines)?;
+        if global_alignment {
+            gaps = keeping_terminal(&gaps, EndGaps::Free, EndGaps::Free);
+        }
 
         // Read alphabet A
-        let alphabet_a = Alphabet::read_alphabet(&mut lines)?;
+        let alphabet_a = Alphabet::read_alphabet(lines)?;
         // Read alphabet B
-        let alphabet_b = Alphabet::read_alphabet(&mut lines)?;
+        let alphabet_b = Alphabet::read_alphabet(lines)?;
 
         // Read match scores
-        let match_matrix = MatchMatrix::<T>::read_match_matrix(&mut lines)?;
+        let (match_matrix, trailer) = MatchMatrix::<T, S>::read_match_matrix_until(lines)?;
+        if let Err(missing) = match_matrix.validate_completeness(&alphabet_a, &alphabet_b) {
+            for (a, b) in &missing {
+                warn!("# missing score: ({}, {}) scores zero", a, b);
+            }
+        }
 
-        Ok(Self::new(
-            sequences,
-            global_alignment,
-            gaps,
-            alphabet_a,
-            alphabet_b,
-            match_matrix,
-        ))
+        // Optional Karlin–Altschul override
+        let karlin_altschul = Self::read_karlin_altschul(trailer.as_deref())?;
+
+        Ok(Self {
+            karlin_altschul,
+            ..Self::new(
+                sequences,
+                global_alignment,
+                gaps,
+                alphabet_a,
+                alphabet_b,
+                match_matrix,
+            )
+        })
+    }
+
+    /// Convert the match scores and gap penalties with `f`, for aligning with another
+    /// score type
+    pub fn try_map_scores<U: Copy + FromStr + Zero, E>(
+        &self,
+        mut f: impl FnMut(T) -> Result<U, E>,
+    ) -> Result<AlignmentParameters<U, S>, E> {
+        Ok(AlignmentParameters {
+            sequences: self.sequences.clone(),
+            global_alignment: self.global_alignment,
+            gap_penalties: self.gap_penalties.try_map(&mut f)?,
+            alphabet_a: self.alphabet_a.clone(),
+            alphabet_b: self.alphabet_b.clone(),
+            match_matrix: self.match_matrix.try_map(&mut f)?,
+            karlin_altschul: self.karlin_altschul,
+            frame_offset: self.frame_offset,
+        })
+    }
+
+    /// These parameters with sequence A and sequence B exchanged, along with their
+    /// alphabets, the residues of each match score and the gap penalties and end-gap
+    /// treatment of each, so that aligning them gives the mirror image of the alignment
+    /// of these. `frame_offset`, which places A within its codons, is reset to 0.
+    pub fn swap_sequences(&self) -> Self {
+        AlignmentParameters {
+            sequences: Sequences::new(self.sequences.seq_b.clone(), self.sequences.seq_a.clone()),
+            global_alignment: self.global_alignment,
+            gap_penalties: self.gap_penalties.swapped(),
+            alphabet_a: self.alphabet_b.clone(),
+            alphabet_b: self.alphabet_a.clone(),
+            match_matrix: self.match_matrix.transpose(),
+            karlin_altschul: self.karlin_altschul,
+            frame_offset: 0,
+        }
+    }
+
+    /// Switch between global and local alignment, giving global alignment free end gaps
+    /// just as an alignment type of `0` in the input file does. Terminal penalties for
+    /// the ends of either sequence are kept, here and when switching to any other mode
+    /// that does not make those ends free.
+    pub fn set_global_alignment(&mut self, global: bool) {
+        let ends = if global {
+            EndGaps::Free
+        } else {
+            EndGaps::Charged
+        };
+        self.gap_penalties = keeping_terminal(&self.gap_penalties, ends, ends);
+        self.global_alignment = global;
+    }
+
+    /// Switch to semi-global alignment of all of A against part of B, with free end gaps
+    /// for B only
+    pub fn set_semi_global(&mut self) {
+        let gaps = &self.gap_penalties;
+        self.gap_penalties = gaps.with_end_gaps(
+            EndGaps::Charged.unless_terminal(gaps.a_end_gaps()),
+            EndGaps::Free,
+        );
+        self.global_alignment = true;
+    }
+
+    /// Switch to soft-clipping A: part of A against all of B, with free end gaps for A
+    /// only, as when placing a read that overhangs a reference
+    pub fn set_soft_clip_a(&mut self) {
+        let gaps = &self.gap_penalties;
+        self.gap_penalties = gaps.with_end_gaps(
+            EndGaps::Free,
+            EndGaps::Charged.unless_terminal(gaps.b_end_gaps()),
+        );
+        self.global_alignment = true;
+    }
+
+    /// Switch to `mode`, keeping the gap penalties
+    pub fn set_mode(&mut self, mode: AlignmentMode) {
+        match mode {
+            AlignmentMode::Global => self.set_global_alignment(true),
+            AlignmentMode::Local => self.set_global_alignment(false),
+            AlignmentMode::SemiGlobal => self.set_semi_global(),
+            AlignmentMode::SoftClipA => self.set_soft_clip_a(),
+        }
+    }
+
+    /// Replace the gap penalties, keeping the end-gap treatment of the alignment type
+    pub fn set_gap_penalties(&mut self, dx: T, ex: T, dy: T, ey: T) {
+        self.gap_penalties = self.gap_penalties.with_penalties(dx, ex, dy, ey);
+    }
+
+    /// These parameters with the gap penalties replaced by those parsed from `s`, a
+    /// `dx ex dy ey` string as on the gap line of an input file
+    pub fn with_gap_str(mut self, s: &str) -> Result<Self, AlignmentError> {
+        let gaps: GapPenalties<T> = s.parse()?;
+        self.set_gap_penalties(gaps.dx, gaps.ex, gaps.dy, gaps.ey);
+        Ok(self)
     }
 
     pub fn len_a(&self) -> usize {
@@ -93,3 +377,195 @@ where
         self.sequences.len_b()
     }
 }
+
+impl<T: Copy + FromStr + PartialEq + Zero, S: Residue> AlignmentParameters<T, S> {
+    /// Combine sequence A and the scoring model from `self` with sequence B from `other`.
+    ///
+    /// The match matrix and gap penalties of both parameter sets must agree, and the
+    /// match matrix must define a score for every pair drawn from the two alphabets.
+    pub fn merge(self, other: Self) -> Result<Self, AlignmentError> {
+        if self.match_matrix != other.match_matrix {
+            return Err(AlignmentError::IncompatibleParameters(
+                "match matrices differ".to_string(),
+            ));
+        }
+        if self.gap_penalties != other.gap_penalties {
+            return Err(AlignmentError::IncompatibleParameters(
+                "gap penalties differ".to_string(),
+            ));
+        }
+
+        let merged = Self {
+            sequences: Sequences::new(self.sequences.seq_a, other.sequences.seq_b),
+            global_alignment: self.global_alignment,
+            gap_penalties: self.gap_penalties,
+            alphabet_a: self.alphabet_a,
+            alphabet_b: other.alphabet_b,
+            match_matrix: self.match_matrix,
+            karlin_altschul: self.karlin_altschul.or(other.karlin_altschul),
+            frame_offset: self.frame_offset,
+        };
+        merged.check_match_scores()?;
+        Ok(merged)
+    }
+
+    /// Fail unless the match matrix defines a score for every pair drawn from the two
+    /// alphabets
+    pub fn check_match_scores(&self) -> Result<(), AlignmentError> {
+        self.match_matrix
+            .validate_completeness(&self.alphabet_a, &self.alphabet_b)
+            .map_err(|missing| {
+                let (a, b) = &missing[0];
+                AlignmentError::InvalidInput(format!(
+                    "match matrix has no score for ({}, {})",
+                    a, b
+                ))
+            })
+    }
+
+    /// Check that the symbol written for gaps in aligned rows is not itself a residue of
+    /// either alphabet or sequence, which would make those rows ambiguous
+    pub fn check_gap_symbol(&self, gap: &str) -> Result<(), AlignmentError> {
+        let Some(symbol) = S::parse_token(gap) else {
+            return Ok(());
+        };
+        let sequences = [
+            ("A", &self.sequences.seq_a, &self.alphabet_a),
+            ("B", &self.sequences.seq_b, &self.alphabet_b),
+        ];
+        for (name, sequence, alphabet) in sequences {
+            if alphabet.contains(&symbol) || sequence.contains(&symbol) {
+                return Err(AlignmentError::IncompatibleParameters(format!(
+                    "gap symbol '{}' is also a residue of sequence {}",
+                    gap, name
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: Copy + Display + FromStr + PartialOrd + Zero, S: Residue> AlignmentParameters<T, S> {
+    /// Check everything the DP fill relies on without filling a grid: both sequences
+    /// are non-empty and drawn from their alphabets, the match matrix scores every pair
+    /// of the alphabets, no gap penalty is negative or NaN, and any end-gap bonus has a
+    /// charged end gap to discount.
+    pub fn validate(&self) -> Result<(), AlignmentError> {
+        let sequences = [
+            ("A", &self.sequences.seq_a, &self.alphabet_a),
+            ("B", &self.sequences.seq_b, &self.alphabet_b),
+        ];
+        for (name, sequence, alphabet) in sequences {
+            if sequence.is_empty() {
+                return Err(AlignmentError::InvalidInput(format!(
+                    "sequence {} is empty",
+                    name
+                )));
+            }
+            if let Some((i, residue)) = sequence
+                .iter()
+                .enumerate()
+                .find(|(_, residue)| !alphabet.contains(residue))
+            {
+                return Err(AlignmentError::InvalidInput(format!(
+                    "residue {} at position {} of sequence {} is not in alphabet {}",
+                    residue,
+                    i + 1,
+                    name,
+                    name
+                )));
+            }
+        }
+        self.check_match_scores()?;
+
+        let gaps = &self.gap_penalties;
+        let penalties = [
+            ("dx", gaps.dx),
+            ("ex", gaps.ex),
+            ("dy", gaps.dy),
+            ("ey", gaps.ey),
+        ];
+        match penalties.iter().find(|(_, penalty)| {
+            !matches!(
+                penalty.partial_cmp(&T::zero()),
+                Some(Ordering::Greater | Ordering::Equal)
+            )
+        }) {
+            Some((name, penalty)) => Err(AlignmentError::InvalidInput(format!(
+                "gap penalty {} = {} must be a non-negative number",
+                name, penalty
+            ))),
+            None => gaps.check_end_gap_bonus(self.global_alignment),
+        }
+    }
+}
+
+impl<S: Residue> AlignmentParameters<f64, S> {
+    /// Copy of these parameters with gap penalties scaled by the sequence lengths; see
+    /// [`GapPenalties::scale_by_length`]
+    pub fn with_length_scaled_penalties(&self) -> Self {
+        Self {
+            gap_penalties: self
+                .gap_penalties
+                .scale_by_length(self.len_a(), self.len_b()),
+            ..self.clone()
+        }
+    }
+
+    /// Down-weight the soft-masked residues of A, those written in lower case, scoring
+    /// each at `mode.penalty_factor` times its upper-case form, while the case of B is
+    /// ignored; see [`MatchMatrix::with_soft_mask`]. The lower-case forms of their
+    /// symbols are added to both alphabets, so mixed-case sequences validate.
+    pub fn apply_soft_mask(&mut self, mode: SoftMaskMode) {
+        self.match_matrix = self.match_matrix.with_soft_mask(mode);
+        self.alphabet_a = self.alphabet_a.upper_case().with_lower_case();
+        self.alphabet_b = self.alphabet_b.upper_case().with_lower_case();
+    }
+}
+
+impl<S: Residue> AlignmentParameters<i8, S> {
+    /// Read an input file for an `i8` grid. Scores are read as `f64`, so `4.0` is
+    /// accepted, and the file is rejected if one is not a whole number, does not fit in
+    /// `i8`, or could overflow the grid for these sequences.
+    pub fn load_from_file_i8(input_file: &str) -> Result<Self, AlignmentError> {
+        let parameters = AlignmentParameters::<f64, S>::load_from_file(input_file)?;
+        ScoreType::I8.check_parameters(&parameters)?;
+        parameters.try_map_scores(|score| cast_score::<i8>(score, ScoreType::I8))
+    }
+}
+
+impl AlignmentParameters<f64> {
+    /// Write these parameters in the input file format read by [`Self::load_from_file`].
+    ///
+    /// Semi-global parameters are written as global, which the file format cannot
+    /// tell apart.
+    pub fn write_input_file<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "{}",
+            self.sequences.seq_a.iter().collect::<String>()
+        )?;
+        writeln!(
+            writer,
+            "{}",
+            self.sequences.seq_b.iter().collect::<String>()
+        )?;
+        writeln!(writer, "{}", if self.global_alignment { 0 } else { 1 })?;
+        let gaps = &self.gap_penalties;
+        writeln!(writer, "{} {} {} {}", gaps.dx, gaps.ex, gaps.dy, gaps.ey)?;
+        write_match_block(
+            writer,
+            &self.alphabet_a,
+            &self.alphabet_b,
+            &self.match_matrix,
+        )?;
+        if let Some(karlin_altschul) = &self.karlin_altschul {
+            writeln!(
+                writer,
+                "karlin-altschul {} {}",
+                karlin_altschul.lambda, karlin_altschul.k
+            )?;
+        }
+        Ok(())
+    }
+}
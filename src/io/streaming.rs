@@ -0,0 +1,98 @@
+use crate::alignment::AlignmentSink;
+use crate::models::{write_fasta_alignment, Alignment};
+use std::fmt::Display;
+use std::io;
+use std::io::Write;
+
+/// Writes each alignment of a traceback as FASTA as soon as it is built, so that a
+/// traceback with very many co-optimal paths runs in the memory of one alignment rather
+/// than of all of them.
+///
+/// The records are those of [`OutputFormat::Fasta`]; pass the writer to
+/// [`traceback_into`] in place of a result.
+///
+/// [`OutputFormat::Fasta`]: crate::io::format::OutputFormat::Fasta
+/// [`traceback_into`]: crate::alignment::traceback_into
+pub struct StreamingTracebackWriter<W: Write, T> {
+    writer: W,
+    score: Option<T>,
+    written: usize,
+}
+
+impl<W: Write, T> StreamingTracebackWriter<W, T> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            score: None,
+            written: 0,
+        }
+    }
+
+    /// Number of alignments written so far
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write, T: Copy + Display> AlignmentSink<T> for StreamingTracebackWriter<W, T> {
+    fn begin(&mut self, score: T) -> io::Result<()> {
+        self.score = Some(score);
+        Ok(())
+    }
+
+    fn push(&mut self, alignment: Alignment) -> io::Result<()> {
+        let score = self.score.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "alignment before the score")
+        })?;
+        self.written += 1;
+        write_fasta_alignment(&mut self.writer, self.written, score, &alignment)
+    }
+}
+
+/// Writes each alignment of a traceback as plain text as soon as it is built: the
+/// optimal score on the first line, then each alignment as its two aligned rows after a
+/// blank line, as in [`OutputFormat::Text`] without the comment lines.
+///
+/// [`OutputFormat::Text`]: crate::io::format::OutputFormat::Text
+pub struct TextTracebackWriter<W: Write> {
+    writer: W,
+    written: usize,
+}
+
+impl<W: Write> TextTracebackWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer, written: 0 }
+    }
+
+    /// Number of alignments written so far
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write, T: Display> AlignmentSink<T> for TextTracebackWriter<W> {
+    fn begin(&mut self, score: T) -> io::Result<()> {
+        writeln!(self.writer, "{}", score)
+    }
+
+    fn push(&mut self, alignment: Alignment) -> io::Result<()> {
+        self.written += 1;
+        self.writer.write_all(b"\n")?;
+        self.writer.write_all(alignment.align_a.as_bytes())?;
+        self.writer.write_all(b"\n")?;
+        self.writer.write_all(alignment.align_b.as_bytes())?;
+        self.writer.write_all(b"\n")
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,171 @@
+use crate::error::AlignmentError;
+use std::fs;
+
+/// Line-level layout of an input file, found without parsing any scores
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FileReport {
+    pub total_lines: usize,
+    /// Size of alphabet A times size of alphabet B, when both sizes could be read
+    pub expected_match_entries: usize,
+    pub found_match_entries: usize,
+    /// Structural problems, each naming the 1-based line it was found on
+    pub issues: Vec<String>,
+}
+
+/// Sections of an input file, in order
+const SECTIONS: [&str; 8] = [
+    "sequence A",
+    "sequence B",
+    "alignment type",
+    "gap penalties",
+    "alphabet A length",
+    "alphabet A",
+    "alphabet B length",
+    "alphabet B",
+];
+
+/// Scan an input file section by section and report where its layout departs from the
+/// format, so that a truncated or padded file is caught before the parser misreads the
+/// sections that follow.
+///
+/// Only the number and shape of the lines are checked: that the alignment type is `0` or
+/// `1`, that there are four gap penalties, that each alphabet has as many symbols as its
+/// length line gives (counting characters or whitespace-separated tokens), that each
+/// match line has five fields with indices inside the alphabets and that nothing but an
+/// optional `karlin-altschul` line follows the match scores.
+pub fn validate_file_structure(path: &str) -> Result<FileReport, AlignmentError> {
+    let contents = fs::read_to_string(path).map_err(|e| AlignmentError::io(e, path, None))?;
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut report = FileReport {
+        total_lines: lines.len(),
+        ..FileReport::default()
+    };
+    if lines.len() < SECTIONS.len() {
+        report.issues.push(format!(
+            "file ends after line {}, before the {} line",
+            lines.len(),
+            SECTIONS[lines.len()]
+        ));
+    }
+
+    for (i, (line, section)) in lines.iter().zip(SECTIONS).enumerate() {
+        if line.trim().is_empty() {
+            report
+                .issues
+                .push(format!("line {}: {} is blank", i + 1, section));
+        }
+    }
+    if let Some(line) = lines.get(2) {
+        if !matches!(line.trim(), "0" | "1") {
+            report.issues.push(format!(
+                "line 3: alignment type must be 0 or 1, got '{}'",
+                line.trim()
+            ));
+        }
+    }
+    if let Some(line) = lines.get(3) {
+        let fields = line.split_whitespace().count();
+        if fields != 4 {
+            report.issues.push(format!(
+                "line 4: expected 4 gap penalties, got {} fields",
+                fields
+            ));
+        }
+    }
+    let alphabet_a = alphabet_size(&lines, 4, &mut report.issues);
+    let alphabet_b = alphabet_size(&lines, 6, &mut report.issues);
+    if let (Some(len_a), Some(len_b)) = (alphabet_a, alphabet_b) {
+        report.expected_match_entries = len_a * len_b;
+    }
+
+    let mut rest = lines
+        .iter()
+        .enumerate()
+        .skip(SECTIONS.len())
+        .map(|(i, line)| (i + 1, line.split_whitespace().collect::<Vec<_>>()));
+    let mut trailer = None;
+    for (number, fields) in rest.by_ref() {
+        if fields.len() < 5 {
+            trailer = Some((number, fields));
+            break;
+        }
+        report.found_match_entries += 1;
+        if fields.len() > 5 {
+            report.issues.push(format!(
+                "line {}: expected 5 fields in a match score, got {}",
+                number,
+                fields.len()
+            ));
+        }
+        for (field, name, len) in [(fields[0], "A", alphabet_a), (fields[1], "B", alphabet_b)] {
+            let in_range = match (field.parse::<usize>(), len) {
+                (Ok(index), Some(len)) => (1..=len).contains(&index),
+                (Ok(_), None) => true,
+                (Err(_), _) => false,
+            };
+            if !in_range {
+                report.issues.push(format!(
+                    "line {}: '{}' is not an index into alphabet {}",
+                    number, field, name
+                ));
+            }
+        }
+    }
+    if let Some((number, fields)) = trailer {
+        if !fields.is_empty() && fields[0] != "karlin-altschul" {
+            report.issues.push(format!(
+                "line {}: expected a match score or a karlin-altschul line, got {} fields",
+                number,
+                fields.len()
+            ));
+        }
+    }
+    if let Some((number, _)) = rest.find(|(_, fields)| !fields.is_empty()) {
+        report.issues.push(format!(
+            "line {}: unexpected content after the match scores",
+            number
+        ));
+    }
+
+    if alphabet_a.is_some()
+        && alphabet_b.is_some()
+        && report.found_match_entries != report.expected_match_entries
+    {
+        report.issues.push(format!(
+            "expected {} match scores for the two alphabets, found {}",
+            report.expected_match_entries, report.found_match_entries
+        ));
+    }
+    Ok(report)
+}
+
+/// Size of the alphabet whose length line is at `index` (0-based), if the length parses
+/// and agrees with the symbols on the following line
+fn alphabet_size(lines: &[&str], index: usize, issues: &mut Vec<String>) -> Option<usize> {
+    let len: usize = match lines.get(index)?.trim().parse() {
+        Ok(len) => len,
+        Err(_) => {
+            issues.push(format!(
+                "line {}: {} is not a number",
+                index + 1,
+                SECTIONS[index]
+            ));
+            return None;
+        }
+    };
+    let symbols = lines.get(index + 1)?;
+    let chars = symbols.chars().count();
+    let tokens = symbols.split_whitespace().count();
+    if len != chars && len != tokens {
+        issues.push(format!(
+            "line {}: {} has {} symbols but line {} gives a length of {}",
+            index + 2,
+            SECTIONS[index + 1],
+            if tokens > 1 { tokens } else { chars },
+            index + 1,
+            len
+        ));
+        return None;
+    }
+    Some(len)
+}
@@ -0,0 +1,110 @@
+/// Groups of amino acids that are treated as the same letter when building protein
+/// k-mers (Murphy et al. 2000, 10-letter alphabet). Residues in no group stand alone.
+const PROTEIN_GROUPS: [&str; 10] = ["LVIM", "C", "A", "G", "ST", "P", "FYW", "EDNQ", "KR", "H"];
+
+/// Default k-mer length for nucleotide sequences
+pub const DEFAULT_NUCLEOTIDE_K: usize = 8;
+
+/// Default k-mer length for protein sequences over the reduced alphabet
+pub const DEFAULT_PROTEIN_K: usize = 4;
+
+/// Prefilter that skips pairs sharing too few k-mers to be worth aligning.
+///
+/// Protein residues are first mapped to a reduced alphabet so that conservative
+/// substitutions still produce shared k-mers. The filter trades sensitivity for speed:
+/// a real but highly diverged alignment can share no k-mer at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KmerFilter {
+    pub k: usize,
+    /// Pairs sharing fewer distinct k-mers than this are skipped
+    pub min_shared: usize,
+    /// Whether residues are nucleotides (compared exactly, U as T) or amino acids
+    /// (compared over the reduced alphabet)
+    pub nucleotide: bool,
+}
+
+impl KmerFilter {
+    /// Default k for the sequence type, keeping any pair with a shared k-mer
+    pub fn new(nucleotide: bool) -> Self {
+        Self {
+            k: if nucleotide {
+                DEFAULT_NUCLEOTIDE_K
+            } else {
+                DEFAULT_PROTEIN_K
+            },
+            min_shared: 1,
+            nucleotide,
+        }
+    }
+
+    fn code(&self, residue: char) -> u64 {
+        let residue = residue.to_ascii_uppercase();
+        if self.nucleotide {
+            return u64::from(if residue == 'U' { 'T' } else { residue });
+        }
+        PROTEIN_GROUPS
+            .iter()
+            .position(|group| group.contains(residue))
+            .map_or(u64::from(residue) + PROTEIN_GROUPS.len() as u64, |group| {
+                group as u64
+            })
+    }
+
+    /// Distinct k-mers of a sequence, hashed and sorted
+    pub fn kmers(&self, sequence: &str) -> KmerSet {
+        let codes: Vec<u64> = sequence.chars().map(|c| self.code(c)).collect();
+        let mut hashes: Vec<u64> = if self.k == 0 {
+            Vec::new()
+        } else {
+            codes
+                .windows(self.k)
+                .map(|window| {
+                    window.iter().fold(0u64, |hash, &code| {
+                        hash.wrapping_mul(1_000_003).wrapping_add(code)
+                    })
+                })
+                .collect()
+        };
+        hashes.sort_unstable();
+        hashes.dedup();
+        KmerSet { hashes }
+    }
+
+    /// Whether two sequences share enough k-mers to be aligned
+    pub fn passes(&self, a: &KmerSet, b: &KmerSet) -> bool {
+        a.shared(b) >= self.min_shared
+    }
+}
+
+/// Distinct k-mers of one sequence, built once and compared against many others
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KmerSet {
+    hashes: Vec<u64>,
+}
+
+impl KmerSet {
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Number of distinct k-mers in both sets
+    pub fn shared(&self, other: &KmerSet) -> usize {
+        let (mut i, mut j, mut shared) = (0, 0, 0);
+        while i < self.hashes.len() && j < other.hashes.len() {
+            match self.hashes[i].cmp(&other.hashes[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    shared += 1;
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        shared
+    }
+}
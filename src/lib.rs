@@ -0,0 +1,25 @@
+pub mod alignment;
+pub mod batch;
+pub mod chain;
+pub mod consensus;
+pub mod consistency;
+pub mod diff;
+pub mod distance;
+pub mod error;
+pub mod fast_path;
+pub mod generate;
+pub mod io;
+pub mod kmer;
+pub mod merge;
+pub mod models;
+pub mod preset;
+pub mod profile;
+pub mod run_summary;
+pub mod score_only;
+pub mod score_type;
+pub mod search;
+pub mod stats;
+pub mod trace_logger;
+pub mod utils;
+pub mod variants;
+pub mod verify;
@@ -1,29 +1,1673 @@
-mod alignment;
-mod io;
-mod models;
-mod utils;
-
-use crate::alignment::traceback;
-use crate::io::parameters::AlignmentParameters;
-use crate::models::AlignGrid;
-use std::env;
+mod cli;
+
+use crate::cli::{
+    BatchOptions, Cli, Command, ConvertOptions, DiffOptions, ExitStatus, GenerateOptions,
+    MergeOptions, Options, PrefilterOptions, SearchOptions, StatsOptions,
+};
+use log::{debug, error, info, warn};
+use sequence_alignment::alignment::{
+    count_optimal_paths, dedup_canonical, estimate_alignments, traceback_shortest_path,
+    traceback_top_hits, traceback_with_limits, AlignmentSink,
+};
+use sequence_alignment::batch::{
+    for_each_consecutive_pair, for_each_pair, read_completed_pairs, score_table, write_pair_row,
+    write_pair_row_ids, write_pairs_header, write_score_table, CompletedPairs,
+};
+use sequence_alignment::chain::{chain, write_chain_tsv, ScoredLocalAlignment};
+use sequence_alignment::consensus::consensus;
+use sequence_alignment::consistency::check_consistency;
+use sequence_alignment::diff::diff_results;
+use sequence_alignment::distance::write_phylip;
+use sequence_alignment::error::AlignmentError;
+use sequence_alignment::fast_path::trivial_alignment;
+use sequence_alignment::generate::{generate_pair, generated_parameters, MutationRates};
+use sequence_alignment::io::diffs::{apply_differences, read_diffs_from};
+use sequence_alignment::io::fasta::FastaReader;
+use sequence_alignment::io::format::OutputFormat;
+use sequence_alignment::io::graphviz::alignment_dag_to_dot;
+use sequence_alignment::io::interleaved::{write_interleaved_to, DEFAULT_INTERLEAVED_WIDTH};
+use sequence_alignment::io::ncbi::{read_ncbi_matrix, write_match_block};
+use sequence_alignment::io::parameters::AlignmentParameters;
+use sequence_alignment::io::streaming::{StreamingTracebackWriter, TextTracebackWriter};
+use sequence_alignment::io::structure::validate_file_structure;
+use sequence_alignment::kmer::KmerFilter;
+use sequence_alignment::merge::{for_each_read_pair, write_merged_read, ReadRecords};
+use sequence_alignment::models::{
+    AlignGrid, AlignmentResult, EndGaps, LogSpaceAlignGrid, LogSpaceMode, MatchMatrix, Residue,
+    Sequences, SoftMaskMode, TokenFormat, TracebackLimit, Truncation, GAP_CHAR,
+};
+use sequence_alignment::preset::{detect_sequence_type, Preset};
+use sequence_alignment::profile::{window_profile, write_profile_tsv};
+use sequence_alignment::run_summary::{Algorithm, RunSummary};
+use sequence_alignment::score_only::optimal_score;
+use sequence_alignment::score_type::{new_grid, AlignGridDyn, ScoreType};
+use sequence_alignment::search::{search, write_hit_alignments, write_hits_tsv};
+use sequence_alignment::stats::{
+    is_nucleotide_alphabet, sensitivity_analysis, shuffle_significance, suggest_gap_penalties,
+    write_summaries_tsv, AlignmentSummary, CoverageFilter, DnaStats, KarlinAltschul,
+    SensitivityReport,
+};
+use sequence_alignment::trace_logger::StepTracer;
+use sequence_alignment::variants::{variants_from_alignment, write_vcf};
+use sequence_alignment::verify::verify;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+/// Whether flushing an output file also syncs it to disk, set by `--durable`
+static DURABLE: AtomicBool = AtomicBool::new(false);
+
+/// File opened for output. Flushing it also syncs its data to disk under `--durable`, so
+/// every writer's final `flush()` is the point the output is known to be stored.
+struct OutputFile(File);
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()?;
+        if DURABLE.load(Ordering::Relaxed) {
+            self.0.sync_data()?;
+        }
+        Ok(())
+    }
+}
+
+/// Create an output file, buffered
+fn create_file(path: impl AsRef<Path>) -> io::Result<BufWriter<OutputFile>> {
+    Ok(BufWriter::new(OutputFile(File::create(path)?)))
+}
+
+/// Open an output file, `-` meaning standard output
+fn create_output(output_file: &str) -> io::Result<Box<dyn Write>> {
+    if output_file == "-" {
+        Ok(Box::new(io::stdout().lock()))
+    } else {
+        Ok(Box::new(OutputFile(File::create(output_file)?)))
+    }
+}
+
+fn write_result<T: Copy + Display + Serialize>(
+    result: &AlignmentResult<T>,
+    output_file: &str,
+    format: OutputFormat,
+    width: usize,
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::with_capacity(65536, create_output(output_file)?);
+    if format == OutputFormat::Interleaved {
+        write_interleaved_to(&mut writer, result, width)?;
+    } else {
+        result.write_to(&mut writer, format)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Abort on any structural issue found by the line-by-line scan of an input file
+fn check_file_structure(path: &str) -> Result<(), AlignmentError> {
+    let report = validate_file_structure(path)?;
+    debug!(
+        "{}: {} lines, {} of {} match scores",
+        path, report.total_lines, report.found_match_entries, report.expected_match_entries
+    );
+    if report.issues.is_empty() {
+        Ok(())
+    } else {
+        Err(AlignmentError::InvalidInput(format!(
+            "{}: {}",
+            path,
+            report.issues.join("; ")
+        )))
+    }
+}
+
+/// Load the input file, or take the sequences from the command line, and apply the
+/// options that change the scoring model
+fn load_parameters<S: Residue>(
+    options: &Options,
+) -> Result<AlignmentParameters<f64, S>, Box<dyn Error>> {
+    if options.strict {
+        for path in options.input_file.iter().chain(&options.merge_file) {
+            check_file_structure(path)?;
+        }
+    }
+    let mut parameters = match (&options.input_file, options.sequences()) {
+        (Some(input_file), _) => AlignmentParameters::<f64, S>::load_from_file(input_file)?,
+        (None, Some((seq_a, seq_b))) => {
+            AlignmentParameters::from_sequences(Sequences::from_lines(seq_a, seq_b)?)
+        }
+        (None, None) => return Err("Please specify an input file or --seq-a and --seq-b".into()),
+    };
+    if let Some(merge_file) = &options.merge_file {
+        parameters = parameters.merge(AlignmentParameters::load_from_file(merge_file)?)?;
+    }
+    if options.soft_mask.is_some() {
+        // Score residues by their upper-case forms; the mask adds the lower-case ones back
+        parameters.alphabet_a = parameters.alphabet_a.upper_case();
+        parameters.alphabet_b = parameters.alphabet_b.upper_case();
+    }
+    let residues = || {
+        parameters
+            .sequences
+            .seq_a
+            .iter()
+            .chain(&parameters.sequences.seq_b)
+    };
+    match options.preset() {
+        Some(preset) => {
+            preset.check_sequences(residues())?;
+            preset.apply(&mut parameters);
+        }
+        // Suggest a preset only when nothing else scores the residues
+        None if !options.sets_scores() && parameters.match_matrix.is_empty() => {
+            if let Some(sequence_type) = detect_sequence_type(residues()) {
+                let preset = Preset::for_type(sequence_type);
+                warn!(
+                    "# the sequences look like {}; --preset {} scores them with {}",
+                    sequence_type, preset.name, preset.description
+                );
+            }
+        }
+        None => {}
+    }
+    if let Some(scores_file) = &options.scores_file {
+        parameters.match_matrix = MatchMatrix::load_pair_scores(scores_file)?;
+    }
+    if let Some(name) = &options.matrix {
+        parameters.match_matrix = MatchMatrix::builtin(name)
+            .ok_or_else(|| format!("Unknown builtin matrix: {}", name))?;
+    }
+    if let Some((match_score, mismatch_score)) = options.simple_scores() {
+        parameters.match_matrix = MatchMatrix::simple(
+            parameters.alphabet_a.symbols(),
+            parameters.alphabet_b.symbols(),
+            match_score,
+            mismatch_score,
+        );
+    }
+    if options.strict_symmetry {
+        check_symmetry(&parameters.match_matrix)?;
+    }
+    if options.input_file.is_none() {
+        // Without a file there is no alphabet to check the scores against beforehand
+        parameters.check_match_scores()?;
+    } else if options.strict_matrix {
+        check_completeness(&parameters)?;
+    }
+    if let Some(penalty_factor) = options.soft_mask {
+        parameters.apply_soft_mask(SoftMaskMode { penalty_factor });
+    }
+    if options.strip_gaps {
+        strip_gaps(&mut parameters.sequences);
+    }
+    if let Some(global) = options.global_alignment() {
+        parameters.set_global_alignment(global);
+    }
+    if options.semiglobal {
+        parameters.set_semi_global();
+    }
+    if options.soft_clip_a {
+        parameters.set_soft_clip_a();
+    }
+    if options.suggest_penalties {
+        let suggested = suggest_gap_penalties(
+            &parameters.sequences.seq_a,
+            &parameters.sequences.seq_b,
+            &parameters.match_matrix,
+        );
+        info!(
+            "# suggested gaps: --gaps {:.2},{:.2},{:.2},{:.2}",
+            suggested.dx, suggested.ex, suggested.dy, suggested.ey
+        );
+    }
+    let gaps = &parameters.gap_penalties;
+    let [dx, ex, dy, ey] = options.gap_penalties([gaps.dx, gaps.ex, gaps.dy, gaps.ey]);
+    parameters.set_gap_penalties(dx, ex, dy, ey);
+    let (a_ends, b_ends) = options.end_gaps(&parameters.gap_penalties);
+    parameters.gap_penalties = parameters.gap_penalties.with_end_gaps(a_ends, b_ends);
+    if let Some(bonus) = options.end_gap_bonus {
+        parameters.gap_penalties = parameters.gap_penalties.with_end_gap_bonus(bonus);
+        parameters
+            .gap_penalties
+            .check_end_gap_bonus(parameters.global_alignment)?;
+    }
+    parameters.frame_offset = options.frame_offset;
+    let summary = RunSummary::for_parameters(&parameters, options.algorithm());
+    let effective = format!(
+        "# parameters: {}, gaps dx={} ex={} dy={} ey={}",
+        summary, dx, ex, dy, ey
+    );
+    // Echo the values that actually ran so that parameter sweeps can check them
+    if options.overrides_parameters() {
+        info!("{}", effective);
+    } else {
+        debug!("{}", effective);
+    }
+    let gaps = &parameters.gap_penalties;
+    let charges_global_ends = parameters.global_alignment
+        && (gaps.a_end_penalties().is_some() || gaps.b_end_penalties().is_some());
+    if let Some(suggestion) = summary.suggestion(&options.cheaper_modes(charges_global_ends)) {
+        warn!("# {}", suggestion);
+    }
+    if options.log_space {
+        parameters.match_matrix = parameters.match_matrix.to_log_probabilities();
+        parameters.gap_penalties = parameters.gap_penalties.to_log_costs();
+    }
+    Ok(parameters)
+}
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let args: Vec<String> = env::args().collect();
+/// Where the match scores come from, in the order [`load_parameters`] applies them
+fn matrix_source(options: &Options) -> String {
+    let input = || match &options.input_file {
+        Some(input_file) => input_file.clone(),
+        None => "--seq-a/--seq-b".to_string(),
+    };
+    if let Some((match_score, mismatch_score)) = options.simple_scores() {
+        format!("--match {} --mismatch {}", match_score, mismatch_score)
+    } else if let Some(name) = &options.matrix {
+        format!("builtin {}", name)
+    } else if let Some(scores_file) = &options.scores_file {
+        format!("pair scores from {}", scores_file)
+    } else if let Some(preset) = options.preset() {
+        format!("preset {} ({})", preset.name, preset.description)
+    } else if let Some(merge_file) = &options.merge_file {
+        format!("{} merged with {}", input(), merge_file)
+    } else {
+        input()
+    }
+}
 
-    if args.len() != 3 {
-        eprintln!("Please specify an input file and an output file as args.");
-        eprintln!("Usage: {} <input_file> <output_file>", args[0]);
-        std::process::exit(1);
+/// Load and validate the parameters and print what a run would do, without filling a
+/// grid
+fn dry_run<S: Residue>(options: &Options) -> Result<(), Box<dyn Error>> {
+    let parameters = load_parameters::<S>(options)?;
+    if options.log_space {
+        LogSpaceAlignGrid::check_parameters(&parameters)?;
     }
+    parameters.validate()?;
+    options.score_type.check_parameters(&parameters)?;
+    check_memory(options, &parameters)?;
 
-    let input_file = &args[1];
-    let output_file = &args[2];
+    let (len_a, len_b) = (parameters.len_a(), parameters.len_b());
+    let gaps = &parameters.gap_penalties;
+    let describe_ends = |ends: EndGaps<f64>, sequence: &str| match ends {
+        EndGaps::Charged => String::new(),
+        EndGaps::Free => format!(", free end gaps in {}", sequence),
+        EndGaps::Terminal { open, extend } => {
+            format!(
+                ", terminal gaps in {} open={} extend={}",
+                sequence, open, extend
+            )
+        }
+    };
+    let mut free_ends = if gaps.has_free_a_ends() && gaps.has_free_b_ends() {
+        ", free end gaps".to_string()
+    } else {
+        describe_ends(gaps.a_end_gaps(), "A") + &describe_ends(gaps.b_end_gaps(), "B")
+    };
+    if gaps.end_gap_bonus != 0.0 {
+        free_ends += &format!(", end-gap bonus {}", gaps.end_gap_bonus);
+    }
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "alignment: {}", parameters.mode())?;
+    writeln!(stdout, "sequences: {} x {} residues", len_a, len_b)?;
+    writeln!(
+        stdout,
+        "gaps: dx={} ex={} dy={} ey={}{}",
+        gaps.dx, gaps.ex, gaps.dy, gaps.ey, free_ends
+    )?;
+    writeln!(stdout, "matrix: {}", matrix_source(options))?;
+    if let Some(penalty_factor) = options.soft_mask {
+        writeln!(
+            stdout,
+            "soft mask: lower-case residues of A score {} of their upper-case scores",
+            penalty_factor
+        )?;
+    }
+    writeln!(stdout, "score type: {}", options.score_type)?;
+    writeln!(
+        stdout,
+        "run: {}",
+        RunSummary::for_parameters(&parameters, options.algorithm())
+    )?;
+    writeln!(
+        stdout,
+        "memory: {:.3} MiB (scores and traceback pointers)",
+        options.score_type.estimate_memory_bytes(len_a, len_b) as f64 / (1024.0 * 1024.0)
+    )?;
+    if !options.score_only {
+        let grid = fill_grid(options, &parameters)?;
+        let estimate = if options.shortest_path {
+            1.min(count_optimal_paths(&grid, &parameters))
+        } else {
+            estimate_alignments(&grid, &parameters, &options.traceback_limits())
+        };
+        let kept = options
+            .max_alignments
+            .map_or(estimate, |max| estimate.min(max as u128));
+        writeln!(
+            stdout,
+            "alignments: {} ({} kept)",
+            format_count(estimate),
+            format_count(kept)
+        )?;
+    }
+    Ok(())
+}
+
+/// Warn about each pair scored differently from its reverse, failing if there are any
+fn check_symmetry<S: Residue>(match_matrix: &MatchMatrix<f64, S>) -> Result<(), Box<dyn Error>> {
+    let Err(asymmetric) = match_matrix.validate_symmetry() else {
+        return Ok(());
+    };
+    for (a, b, forward, reverse) in &asymmetric {
+        warn!(
+            "# asymmetric scores: ({}, {}) = {} but ({}, {}) = {}",
+            a, b, forward, b, a, reverse
+        );
+    }
+    Err(format!("match matrix is asymmetric for {} pairs", asymmetric.len()).into())
+}
 
-    let parameters = AlignmentParameters::<f64>::load_from_file(input_file)?;
+/// Fail if the match matrix leaves out any pair of the alphabets. Pairs missing from the
+/// input file were logged as it was read, but the matrix may since have been replaced.
+fn check_completeness<S: Residue>(
+    parameters: &AlignmentParameters<f64, S>,
+) -> Result<(), Box<dyn Error>> {
+    let Err(missing) = parameters
+        .match_matrix
+        .validate_completeness(&parameters.alphabet_a, &parameters.alphabet_b)
+    else {
+        return Ok(());
+    };
+    Err(AlignmentError::InvalidInput(format!(
+        "match matrix has no score for {} pairs of the alphabets, the first ({}, {})",
+        missing.len(),
+        missing[0].0,
+        missing[0].1
+    ))
+    .into())
+}
+
+/// Remove the gap characters of pre-aligned input, reporting how many went
+fn strip_gaps<S: Residue>(sequences: &mut Sequences<S>) {
+    let (mut stripped_a, mut stripped_b) = (0, 0);
+    for gap_char in [GAP_CHAR, '-'] {
+        let (a, b) = sequences.strip_gaps(gap_char);
+        stripped_a += a;
+        stripped_b += b;
+    }
+    for (stripped, name) in [(stripped_a, "seq_a"), (stripped_b, "seq_b")] {
+        if stripped > 0 {
+            warn!("# stripped {} gap characters from {}", stripped, name);
+        }
+    }
+}
+
+/// Fail if the DP grid for these parameters would exceed `--max-memory`
+fn check_memory<S: Residue>(
+    options: &Options,
+    parameters: &AlignmentParameters<f64, S>,
+) -> Result<(), AlignmentError> {
+    let Some(max_memory) = options.max_memory else {
+        return Ok(());
+    };
+    let needed = options
+        .score_type
+        .estimate_memory_bytes(parameters.len_a(), parameters.len_b());
+    if needed > max_memory {
+        return Err(AlignmentError::LimitExceeded(format!(
+            "the {} x {} grid needs {} bytes, over --max-memory of {} bytes",
+            parameters.len_a(),
+            parameters.len_b(),
+            needed,
+            max_memory
+        )));
+    }
+    Ok(())
+}
+
+/// The parameters to fill the grid with: `parameters` with both sequences padded to a
+/// multiple of `--pad-to`, or `parameters` themselves without it. The rest of a run works
+/// with the unpadded sequences, which the alignments refer to either way.
+fn fill_parameters<'a, S: Residue>(
+    options: &Options,
+    parameters: &'a AlignmentParameters<f64, S>,
+) -> Result<Cow<'a, AlignmentParameters<f64, S>>, AlignmentError> {
+    let Some(n) = options.pad_to else {
+        return Ok(Cow::Borrowed(parameters));
+    };
+    let mut padded = parameters.clone();
+    padded.sequences = parameters.sequences.pad_to_multiple(n, options.pad_char)?;
+    debug!(
+        "padding: {} x {} residues filled as {} x {}",
+        parameters.len_a(),
+        parameters.len_b(),
+        padded.len_a(),
+        padded.len_b()
+    );
+    Ok(Cow::Owned(padded))
+}
+
+/// An empty grid of `--score-type`, within `--max-memory`
+fn allocate_grid<S: Residue + 'static>(
+    options: &Options,
+    parameters: &AlignmentParameters<f64, S>,
+) -> Result<Box<dyn AlignGridDyn<S>>, Box<dyn Error>> {
+    check_memory(options, parameters)?;
+    Ok(new_grid(
+        options.score_type,
+        parameters.len_a(),
+        parameters.len_b(),
+    ))
+}
+
+/// An empty `f64` grid, on disk with `--matrix-storage disk`
+#[cfg(feature = "mmap")]
+fn new_f64_grid(
+    options: &Options,
+    rows: usize,
+    columns: usize,
+) -> Result<AlignGrid<f64>, AlignmentError> {
+    if !options.stores_on_disk() {
+        return Ok(AlignGrid::new(rows, columns));
+    }
+    let dir = options.tmpdir.clone().unwrap_or_else(std::env::temp_dir);
+    AlignGrid::new_on_disk(rows, columns, &dir).map_err(|e| AlignmentError::io(e, dir, None))
+}
+
+#[cfg(not(feature = "mmap"))]
+fn new_f64_grid(
+    _options: &Options,
+    rows: usize,
+    columns: usize,
+) -> Result<AlignGrid<f64>, AlignmentError> {
+    Ok(AlignGrid::new(rows, columns))
+}
+
+fn fill_grid<S: Residue>(
+    options: &Options,
+    parameters: &AlignmentParameters<f64, S>,
+) -> Result<AlignGrid<f64>, Box<dyn Error>> {
+    check_memory(options, parameters)?;
+    if options.log_space {
+        let mut grid =
+            LogSpaceAlignGrid::new(parameters.len_a(), parameters.len_b(), LogSpaceMode::Max);
+        grid.populate_score_matrices(parameters)?;
+        Ok(grid.into_grid())
+    } else {
+        let mut grid = new_f64_grid(options, parameters.len_a(), parameters.len_b())?;
+        let constraints = options.gap_constraints();
+        if let Some(codon_gaps) = &options.codon_gaps {
+            if options.check_scores {
+                grid.populate_codon_aware_checked(parameters, codon_gaps)?;
+            } else {
+                grid.populate_codon_aware(parameters, codon_gaps)?;
+            }
+        } else if !constraints.is_empty() {
+            grid.populate_constrained(parameters, &constraints)?;
+        } else if options.check_scores {
+            grid.populate_score_matrices_checked(parameters)?;
+        } else if parameters.is_semi_global() {
+            grid.populate_semi_global_free_b_end(parameters)?;
+        } else {
+            grid.populate_score_matrices(parameters)?;
+        }
+        Ok(grid)
+    }
+}
+
+/// Fill an `f64` grid, logging every cell to `trace_file`
+fn fill_grid_traced<S: Residue>(
+    options: &Options,
+    parameters: &AlignmentParameters<f64, S>,
+    trace_file: &str,
+) -> Result<AlignGrid<f64>, Box<dyn Error>> {
+    check_memory(options, parameters)?;
     let mut grid = AlignGrid::new(parameters.len_a(), parameters.len_b());
-    let _ = grid.populate_score_matrices(&parameters)?;
-    let _ = traceback(&grid, &parameters, output_file)?;
+    let mut tracer = StepTracer::new();
+    grid.populate_score_matrices_traced(parameters, &mut tracer)?;
+    tracer.write_trace(trace_file)?;
+    debug!(
+        "trace: {} steps written to {}",
+        tracer.updates.len(),
+        trace_file
+    );
+    Ok(grid)
+}
+
+/// Mismatching cells logged by `--check-consistency`
+const MAX_REPORTED_MISMATCHES: usize = 5;
+
+/// Fail if the stored traceback pointers disagree with the scores of the filled grid
+fn check_grid_consistency<S: Residue>(
+    grid: &AlignGrid<f64>,
+    parameters: &AlignmentParameters<f64, S>,
+) -> Result<(), Box<dyn Error>> {
+    let report = check_consistency(grid, parameters, MAX_REPORTED_MISMATCHES);
+    if report.is_consistent() {
+        info!("consistency: {} cells checked", report.checked);
+        return Ok(());
+    }
+    for mismatch in &report.first {
+        error!("consistency: {}", mismatch);
+    }
+    Err(format!(
+        "Consistency check failed for {} cells of M, Ix and Iy ({} cells checked in each)",
+        report.mismatched, report.checked
+    )
+    .into())
+}
+
+/// Print the number of co-optimal alignments instead of writing them
+fn count_paths<S: Residue>(options: &Options) -> Result<(), Box<dyn Error>> {
+    let parameters = load_parameters::<S>(options)?;
+    let parameters = fill_parameters(options, &parameters)?;
+    let grid = fill_grid(options, &parameters)?;
+    println!("{}", count_optimal_paths(&grid, &parameters));
+    Ok(())
+}
+
+/// Time the DP fill alone over `--runs` runs and log fill rate and grid memory
+fn benchmark<S: Residue + 'static>(options: &Options) -> Result<(), Box<dyn Error>> {
+    let parameters = load_parameters::<S>(options)?;
+    let parameters = fill_parameters(options, &parameters)?;
+    check_memory(options, &parameters)?;
+    let (len_a, len_b) = (parameters.len_a(), parameters.len_b());
+
+    let mut times = Vec::with_capacity(options.runs);
+    let mut memory = 0;
+    for _ in 0..options.runs {
+        let (elapsed, grid_memory) = if options.log_space {
+            let mut grid = LogSpaceAlignGrid::new(len_a, len_b, LogSpaceMode::Max);
+            let start = Instant::now();
+            grid.populate_score_matrices(&parameters)?;
+            (start.elapsed(), grid.grid().memory_bytes())
+        } else {
+            let mut grid = new_grid(options.score_type, len_a, len_b);
+            let start = Instant::now();
+            grid.populate_score_matrices_dyn(&parameters)?;
+            (start.elapsed(), grid.memory_bytes())
+        };
+        times.push(elapsed.as_secs_f64());
+        memory = grid_memory;
+    }
+
+    let summary = RunSummary::for_parameters(&parameters, options.algorithm());
+    let cells = summary.cells() as f64;
+    let total: f64 = times.iter().sum();
+    let rate = |seconds: f64| {
+        if seconds > 0.0 {
+            format!("{:.3e}", cells / seconds)
+        } else {
+            "inf".to_string()
+        }
+    };
+
+    info!("benchmark: {}, {} runs", summary, options.runs);
+    info!("{:<8} {:>12} {:>12}", "run", "seconds", "cells/s");
+    for (i, &seconds) in times.iter().enumerate() {
+        info!("{:<8} {:>12.6} {:>12}", i + 1, seconds, rate(seconds));
+    }
+    let mean = total / options.runs as f64;
+    info!("{:<8} {:>12.6} {:>12}", "mean", mean, rate(mean));
+    info!("{:<8} {:>12.6}", "total", total);
+    info!(
+        "{:<8} {:>12.3} MiB (scores and traceback pointers)",
+        "memory",
+        memory as f64 / (1024.0 * 1024.0)
+    );
+    Ok(())
+}
+
+/// Warn once, before the traceback, when it would produce more alignments than
+/// `--max-alignments` keeps
+fn warn_if_over_max_alignments(options: &Options, estimate: u128) {
+    if let Some(max_alignments) = options.max_alignments.filter(|&max| estimate > max as u128) {
+        warn!(
+            "# {} co-optimal alignments; --max-alignments keeps the first {}",
+            format_count(estimate),
+            max_alignments
+        );
+    }
+}
+
+/// A path count, marking counts that saturated
+fn format_count(count: u128) -> String {
+    if count == u128::MAX {
+        format!("at least {}", count)
+    } else {
+        count.to_string()
+    }
+}
+
+/// Log how long the fill since `start` took for the cells of the run, and with which
+/// score type if the grid was chosen by `--score-type`
+fn log_fill_time<S: Residue>(
+    start: Instant,
+    options: &Options,
+    parameters: &AlignmentParameters<f64, S>,
+    score_type: Option<ScoreType>,
+) {
+    let cells = RunSummary::for_parameters(parameters, options.algorithm()).cells();
+    let seconds = start.elapsed().as_secs_f64();
+    match score_type {
+        Some(score_type) => debug!(
+            "fill: {:.3}s for {} DP cells with {} scores",
+            seconds, cells, score_type
+        ),
+        None => debug!("fill: {:.3}s for {} DP cells", seconds, cells),
+    }
+}
+
+/// Load the parameters and settle the token format the alignments are written in
+fn prepare<S: Residue>(
+    options: &Options,
+    default_format: TokenFormat,
+) -> Result<(AlignmentParameters<f64, S>, TokenFormat), Box<dyn Error>> {
+    let parameters = load_parameters::<S>(options)?;
+
+    let token_format = TokenFormat::new(
+        options
+            .separator
+            .as_deref()
+            .unwrap_or(&default_format.separator),
+        options.gap.as_deref().unwrap_or(&default_format.gap),
+    );
+    parameters
+        .check_gap_symbol(&token_format.gap)
+        .map_err(|e| format!("{}; choose another gap symbol with --gap-char", e))?;
+    Ok((parameters, token_format))
+}
+
+fn align<S: Residue + 'static>(
+    options: &Options,
+    default_format: TokenFormat,
+) -> Result<(AlignmentParameters<f64, S>, AlignmentResult), Box<dyn Error>> {
+    let (parameters, token_format) = prepare::<S>(options, default_format)?;
+
+    let gaps = &parameters.gap_penalties;
+    let charges_ends = gaps.a_end_penalties().is_some() || gaps.b_end_penalties().is_some();
+    if options.score_only && parameters.global_alignment && charges_ends {
+        return Err("--score-only needs free end gaps in global alignments".into());
+    }
+    // Only the fill and traceback see any padding
+    let grid_parameters = fill_parameters(options, &parameters)?;
+    let mut result = if options.score_only {
+        AlignmentResult::new(optimal_score(&grid_parameters), Vec::new())
+    } else {
+        let start = Instant::now();
+        let result = if options.log_space
+            || options.dump_dot.is_some()
+            || options.shortest_path
+            || options.top_hits.is_some()
+            || !options.gap_constraints().is_empty()
+            || options.codon_gaps.is_some()
+            || options.trace_execution.is_some()
+            || options.exports_npy()
+            || options.stores_on_disk()
+            || options.check_consistency
+            || options.check_scores
+        {
+            if options.top_hits.is_some() && parameters.global_alignment {
+                return Err("--top-hits is only supported for local alignments".into());
+            }
+            let grid = match &options.trace_execution {
+                Some(trace_file) => fill_grid_traced(options, &grid_parameters, trace_file)?,
+                None => fill_grid(options, &grid_parameters)?,
+            };
+            log_fill_time(start, options, &grid_parameters, None);
+            if options.check_consistency {
+                check_grid_consistency(&grid, &grid_parameters)?;
+            }
+            if let Some(dot_file) = &options.dump_dot {
+                let mut writer = create_output(dot_file)?;
+                writer.write_all(alignment_dag_to_dot(&grid, &grid_parameters)?.as_bytes())?;
+                writer.flush()?;
+            }
+            #[cfg(feature = "numpy")]
+            if let Some(prefix) = &options.export_npy {
+                grid.export_npy(
+                    &format!("{}_m.npy", prefix),
+                    &format!("{}_ix.npy", prefix),
+                    &format!("{}_iy.npy", prefix),
+                )?;
+            }
+            if let Some(k) = options.top_hits {
+                let min_score = options.min_score.unwrap_or(0.0);
+                traceback_top_hits(&grid, &grid_parameters, &token_format, k, min_score)
+            } else if options.shortest_path {
+                traceback_shortest_path(&grid, &grid_parameters, &token_format)
+            } else {
+                if options.max_alignments.is_some() {
+                    let limits = options.traceback_limits();
+                    warn_if_over_max_alignments(
+                        options,
+                        estimate_alignments(&grid, &grid_parameters, &limits),
+                    );
+                }
+                traceback_with_limits(
+                    &grid,
+                    &grid_parameters,
+                    &token_format,
+                    &options.traceback_limits(),
+                )?
+            }
+        } else if let Some(result) = (options.score_type == ScoreType::F64)
+            .then(|| trivial_alignment(&parameters, &token_format))
+            .flatten()
+        {
+            check_memory(options, &parameters)?;
+            debug!("fill: skipped, the optimal alignment pairs the sequences residue by residue");
+            result
+        } else {
+            let mut grid = allocate_grid(options, &grid_parameters)?;
+            grid.populate_score_matrices_dyn(&grid_parameters)?;
+            log_fill_time(start, options, &grid_parameters, Some(grid.score_type()));
+            if options.max_alignments.is_some() {
+                let estimate = grid.estimate_alignments_dyn(&options.traceback_limits())?;
+                warn_if_over_max_alignments(options, estimate);
+            }
+            grid.traceback_dyn(&token_format, &options.traceback_limits())?
+        };
+        debug!(
+            "traceback: {:.3}s, {} alignments",
+            start.elapsed().as_secs_f64(),
+            result.n_alignments()
+        );
+        if result.is_empty() && !parameters.global_alignment {
+            info!("no local alignment found: no pair of residues scores above zero");
+        }
+        result
+    };
+    if let Some(form) = options.canonical {
+        let mut gap = token_format.gap.chars();
+        let (Some(gap), None, true) = (gap.next(), gap.next(), token_format.separator.is_empty())
+        else {
+            return Err("--canonical needs character alignments with a one-character gap".into());
+        };
+        dedup_canonical(&mut result.alignments, gap, form);
+        // Moving the gaps changes the CIGAR strings the canonical order compares
+        result.sort_canonical();
+    }
+    if let Some(max_alignments) = options
+        .max_alignments
+        .filter(|&max| result.n_alignments() > max)
+    {
+        result.truncated.push(Truncation {
+            limit: TracebackLimit::MaxAlignments,
+            kept: max_alignments as u128,
+            total: result.n_alignments() as u128,
+        });
+        result.alignments.truncate(max_alignments);
+    }
+    for truncation in &result.truncated {
+        warn!("# truncated: {}", truncation);
+    }
+    if options.score_ratio {
+        result.score_ratio = Some(result.score_ratio(&parameters));
+    }
+    apply_significance(options, &parameters, &mut result)?;
+    if let Some((delta, steps)) = options.sensitivity {
+        write_sensitivity(&sensitivity_analysis(&parameters, delta, steps))?;
+    }
+    Ok((parameters, result))
+}
+
+/// Write the sensitivity CSV to stdout and log a per-parameter summary
+fn write_sensitivity(report: &SensitivityReport) -> Result<(), Box<dyn Error>> {
+    let mut stdout = io::stdout().lock();
+    report.write_csv(&mut stdout)?;
+    for parameter in &report.parameters {
+        let boundary = |offset: Option<f64>| offset.map_or("none".to_string(), |o| o.to_string());
+        info!(
+            "sensitivity: {} d(score)/d(penalty) = {}, optimum changes at -{} / +{}",
+            parameter.parameter.as_str(),
+            parameter.derivative,
+            boundary(parameter.switch_below),
+            boundary(parameter.switch_above)
+        );
+    }
+    Ok(())
+}
+
+/// Attach bit scores, E-values and shuffle statistics to local results and apply
+/// `--max-evalue`
+fn apply_significance<S: Residue>(
+    options: &Options,
+    parameters: &AlignmentParameters<f64, S>,
+    result: &mut AlignmentResult,
+) -> Result<(), Box<dyn Error>> {
+    if parameters.global_alignment {
+        if options.max_evalue.is_some() || options.shuffles.is_some() {
+            return Err(
+                "--max-evalue and --shuffles are only supported for local alignments".into(),
+            );
+        }
+        return Ok(());
+    }
+    // A bit score and E-value are shared by every alignment, so they cannot describe
+    // top hits of differing scores
+    if result.alignment_scores.is_some() {
+        return Ok(());
+    }
+
+    if let Some(shuffles) = options.shuffles {
+        result.shuffle_significance = Some(shuffle_significance(
+            result.score,
+            parameters,
+            shuffles,
+            options.seed,
+        ));
+    }
+
+    let karlin_altschul = options
+        .karlin_altschul()
+        .or_else(|| KarlinAltschul::from_parameters(parameters));
+    let Some(karlin_altschul) = karlin_altschul else {
+        if options.max_evalue.is_some() {
+            return Err(
+                "--max-evalue needs Karlin-Altschul parameters for this scoring system; \
+                        pass --lambda and --karlin-k"
+                    .into(),
+            );
+        }
+        return Ok(());
+    };
+
+    let significance =
+        karlin_altschul.significance(result.score, parameters.len_a(), parameters.len_b());
+    if options
+        .max_evalue
+        .is_some_and(|max_evalue| significance.evalue > max_evalue)
+    {
+        result.alignments.clear();
+    }
+    result.significance = Some(significance);
     Ok(())
 }
+
+fn run_tokens(options: &Options) -> Result<(), Box<dyn Error>> {
+    if options.verify
+        || options.profile_file.is_some()
+        || options.dna_stats
+        || options.canonical.is_some()
+        || options.trim_end_gaps
+        || options.trim_below_identity.is_some()
+    {
+        return Err(
+            "--verify, --profile, --dna-stats, --canonical and trimming are only supported for \
+             character sequences"
+                .into(),
+        );
+    }
+    let (_, result) = align::<String>(options, TokenFormat::tokens())?;
+    write_result(&result, &options.output_file, options.format, options.width)?;
+    no_hits_unless(
+        options.max_evalue.is_none() || !result.is_empty(),
+        "--max-evalue",
+    )
+}
+
+/// Align and write each alignment as FASTA as soon as the traceback builds it, so that
+/// the alignments are never all held in memory
+fn run_streaming(options: &Options) -> Result<(), Box<dyn Error>> {
+    let (parameters, token_format) = prepare::<char>(options, TokenFormat::default())?;
+    let parameters = fill_parameters(options, &parameters)?;
+    let start = Instant::now();
+    let mut grid = allocate_grid(options, &parameters)?;
+    grid.populate_score_matrices_dyn(&parameters)?;
+    log_fill_time(start, options, &parameters, Some(grid.score_type()));
+    let limits = options.traceback_limits();
+    if options.max_alignments.is_some() {
+        warn_if_over_max_alignments(options, grid.estimate_alignments_dyn(&limits)?);
+    }
+
+    let output = BufWriter::with_capacity(65536, create_output(&options.output_file)?);
+    let mut writer = StreamingTracebackWriter::new(output);
+    let (_, truncated) = grid.traceback_into_dyn(&token_format, &limits, &mut writer)?;
+    debug!(
+        "traceback: {:.3}s, {} alignments written",
+        start.elapsed().as_secs_f64(),
+        writer.written()
+    );
+    if writer.written() == 0 && !parameters.global_alignment {
+        info!("no local alignment found: no pair of residues scores above zero");
+    }
+    for truncation in &truncated {
+        warn!("# truncated: {}", truncation);
+    }
+    writer.into_inner().flush()?;
+    Ok(())
+}
+
+/// Write the alignments of each optimal cell to a file of its own in `output_dir`,
+/// tracing the cells in parallel, and the score and a manifest of the files to the output
+fn run_parallel_output(options: &Options, output_dir: &Path) -> Result<(), Box<dyn Error>> {
+    if !options.traces_plain_grid() {
+        return Err(AlignmentError::IncompatibleParameters(
+            "--parallel-output writes the alignments as the traceback builds them, which the \
+             other options given do not allow"
+                .to_string(),
+        )
+        .into());
+    }
+    let (parameters, token_format) = prepare::<char>(options, TokenFormat::default())?;
+    let parameters = fill_parameters(options, &parameters)?;
+    let start = Instant::now();
+    let mut grid = allocate_grid(options, &parameters)?;
+    grid.populate_score_matrices_dyn(&parameters)?;
+    log_fill_time(start, options, &parameters, Some(grid.score_type()));
+
+    std::fs::create_dir_all(output_dir)
+        .map_err(|e| AlignmentError::io(e, output_dir.to_path_buf(), None))?;
+    let cell_file = |row: usize, col: usize| output_dir.join(format!("{}_{}.txt", row, col));
+    let open = |row, col| -> io::Result<Box<dyn AlignmentSink<f64> + Send>> {
+        let path = cell_file(row, col);
+        let file = File::create(&path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        Ok(Box::new(TextTracebackWriter::new(BufWriter::new(file))))
+    };
+    let (score, cells) =
+        grid.traceback_per_cell_dyn(&token_format, options.max_paths_per_start, &open)?;
+    debug!(
+        "traceback: {:.3}s, {} cells traced",
+        start.elapsed().as_secs_f64(),
+        cells.len()
+    );
+    if cells.is_empty() && !parameters.global_alignment {
+        info!("no local alignment found: no pair of residues scores above zero");
+    }
+
+    let mut output = BufWriter::new(create_output(&options.output_file)?);
+    writeln!(output, "{}", score)?;
+    for cell in &cells {
+        if cell.truncated {
+            warn!(
+                "# cell ({}, {}) reached --max-paths-per-start; paths may have been left out",
+                cell.row, cell.col
+            );
+        }
+        writeln!(
+            output,
+            "{}\t{}",
+            cell_file(cell.row, cell.col).display(),
+            cell.alignments
+        )?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    if let Some(output_dir) = &options.output_dir {
+        return run_parallel_output(options, output_dir);
+    }
+    if options.streams_output() {
+        return run_streaming(options);
+    }
+    let (parameters, mut result) = align::<char>(options, TokenFormat::default())?;
+    if options.verify {
+        let discrepancies = verify(&result, &parameters);
+        if !discrepancies.is_empty() {
+            for discrepancy in &discrepancies {
+                error!("verify: {}", discrepancy);
+            }
+            return Err(format!(
+                "Verification failed for {} of {} alignments",
+                discrepancies.len(),
+                result.n_alignments()
+            )
+            .into());
+        }
+    }
+    if options.dna_stats {
+        let nucleotide = is_nucleotide_alphabet(&parameters.alphabet_a)
+            && is_nucleotide_alphabet(&parameters.alphabet_b);
+        if !nucleotide && !options.nucleotide {
+            return Err(
+                "--dna-stats needs nucleotide alphabets; pass --nucleotide to override".into(),
+            );
+        }
+        result.dna_stats = Some(
+            result
+                .alignments
+                .iter()
+                .map(|alignment| DnaStats::from_alignment(alignment))
+                .collect(),
+        );
+    }
+    if let Some(vcf_file) = &options.vcf_file {
+        let seq_a = &parameters.sequences.seq_a;
+        let variants = result
+            .alignments
+            .first()
+            .map(|alignment| variants_from_alignment(alignment, seq_a))
+            .unwrap_or_default();
+        let (reference_name, sample_name) = (&options.names[0], &options.names[1]);
+        let mut writer = create_file(vcf_file)?;
+        write_vcf(
+            &mut writer,
+            reference_name,
+            seq_a.len(),
+            sample_name,
+            &variants,
+        )?;
+        writer.flush()?;
+    }
+    if let Some(consensus_file) = &options.consensus_file {
+        let mut writer = create_file(consensus_file)?;
+        for (i, alignment) in result.alignments.iter().enumerate() {
+            writeln!(writer, ">consensus_{}", i + 1)?;
+            writeln!(writer, "{}", consensus(alignment, &options.consensus_rule))?;
+        }
+        writer.flush()?;
+    }
+    if let Some(chain_file) = &options.chain_file {
+        if parameters.global_alignment {
+            return Err("--chain is only supported for local alignments".into());
+        }
+        let hits: Vec<ScoredLocalAlignment> = result
+            .alignments
+            .iter()
+            .enumerate()
+            .map(|(i, pair)| ScoredLocalAlignment {
+                alignment: pair.alignment().clone(),
+                score: result.alignment_score(i),
+            })
+            .collect();
+        let chained = chain(&hits, &parameters.gap_penalties);
+        info!(
+            "chain: {} of {} alignments, score {}",
+            chained.hits.len(),
+            hits.len(),
+            chained.score
+        );
+        let mut writer = create_file(chain_file)?;
+        write_chain_tsv(
+            &mut writer,
+            &chained,
+            &hits,
+            parameters.len_a(),
+            parameters.len_b(),
+        )?;
+        writer.flush()?;
+    }
+    if let Some(profile_file) = &options.profile_file {
+        let profiles: Vec<_> = result
+            .alignments
+            .iter()
+            .map(|alignment| window_profile(alignment, &parameters, options.window, options.step))
+            .collect();
+        let mut writer = create_file(profile_file)?;
+        write_profile_tsv(&mut writer, &profiles)?;
+        writer.flush()?;
+    }
+    // Trim last, so that every statistic above describes the untrimmed optimum
+    let gap = options
+        .gap
+        .as_deref()
+        .unwrap_or(&TokenFormat::default().gap)
+        .to_string();
+    for pair in &mut result.alignments {
+        pair.modify(|alignment| {
+            if let Some((min_identity, window)) = options.trim_below_identity {
+                alignment.trim_below_identity(min_identity, window, &gap);
+            }
+            if options.trim_end_gaps {
+                alignment.trim_end_gaps(&gap);
+            }
+        });
+    }
+    write_result(&result, &options.output_file, options.format, options.width)?;
+    no_hits_unless(
+        options.max_evalue.is_none() || !result.is_empty(),
+        "--max-evalue",
+    )
+}
+
+/// K-mer prefilter for the template's sequence type, if `--prefilter` was given
+fn kmer_filter(
+    options: &PrefilterOptions,
+    template: &AlignmentParameters<f64>,
+) -> Option<KmerFilter> {
+    if !options.enabled {
+        return None;
+    }
+    let nucleotide = is_nucleotide_alphabet(&template.alphabet_a)
+        && is_nucleotide_alphabet(&template.alphabet_b);
+    let mut filter = KmerFilter::new(nucleotide);
+    filter.k = options.k.unwrap_or(filter.k);
+    filter.min_shared = options.min_shared.unwrap_or(filter.min_shared);
+    Some(filter)
+}
+
+fn report_prefilter(filter: &KmerFilter, skipped: usize, total: usize, what: &str) {
+    info!(
+        "prefilter: skipped {} of {} {} sharing fewer than {} {}-mers",
+        skipped, total, what, filter.min_shared, filter.k
+    );
+}
+
+/// Log the size of a pair aligned in batch mode. The first pair large enough to be worth
+/// it also logs the cheaper options the batch could use, after which `suggested` is set.
+fn log_batch_pair(
+    options: &BatchOptions,
+    template: &AlignmentParameters<f64>,
+    (id_a, sequence_a): (&str, &str),
+    (id_b, sequence_b): (&str, &str),
+    suggested: &mut bool,
+) {
+    let summary = RunSummary::new(
+        template.mode(),
+        Algorithm::FillAndTraceback,
+        sequence_a.chars().count(),
+        sequence_b.chars().count(),
+    );
+    debug!("pair {} vs {}: {}", id_a, id_b, summary);
+    if *suggested {
+        return;
+    }
+    let mut cheaper = Vec::new();
+    if !options.prefilter.enabled {
+        cheaper.push("--prefilter, which skips pairs sharing few k-mers");
+    }
+    if let Some(suggestion) = summary.suggestion(&cheaper) {
+        warn!("# pair {} vs {}: {}", id_a, id_b, suggestion);
+        *suggested = true;
+    }
+}
+
+fn report_coverage(filter: &CoverageFilter, dropped: usize, total: usize) {
+    if filter.is_set() {
+        info!(
+            "coverage: dropped {} of {} pairs below the minimum coverage",
+            dropped, total
+        );
+    }
+}
+
+/// Open the pairs TSV for writing, keeping its complete rows when resuming
+fn open_pairs_output(
+    options: &BatchOptions,
+) -> Result<(BufWriter<OutputFile>, CompletedPairs), Box<dyn Error>> {
+    let path = Path::new(&options.output_file);
+    if !options.resume || !path.exists() {
+        let mut writer = create_file(path)?;
+        write_pairs_header(&mut writer)?;
+        return Ok((writer, CompletedPairs::default()));
+    }
+
+    let completed = read_completed_pairs(BufReader::new(File::open(path)?))?;
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    // Drop a row that was cut off mid-write
+    file.set_len(completed.complete_len)?;
+    file.seek(SeekFrom::End(0))?;
+    let mut writer = BufWriter::new(OutputFile(file));
+    if completed.complete_len == 0 {
+        write_pairs_header(&mut writer)?;
+    }
+    Ok((writer, completed))
+}
+
+fn batch(options: &BatchOptions) -> Result<(), Box<dyn Error>> {
+    let template = AlignmentParameters::<f64>::load_from_file(&options.input_file)?;
+    if options.pairs_fasta {
+        return batch_consecutive_pairs(options, &template);
+    }
+    let records = FastaReader::new(BufReader::new(File::open(&options.fasta_file)?))
+        .collect::<io::Result<Vec<_>>>()?;
+    let prefilter = kmer_filter(&options.prefilter, &template);
+    let n = records.len();
+    if let Some(score_table_file) = &options.score_table_file {
+        // Scores every pair regardless of the prefilter, which only limits the alignments
+        let table = score_table(&records, &template)?;
+        let names: Vec<String> = records.iter().map(|record| record.id.clone()).collect();
+        let mut writer = create_file(score_table_file)?;
+        write_score_table(&mut writer, &names, &table)?;
+        writer.flush()?;
+    }
+
+    let mut index: HashMap<&str, usize> = HashMap::with_capacity(n);
+    for (i, record) in records.iter().enumerate() {
+        if index.insert(&record.id, i).is_some() && options.resume {
+            return Err(format!("--resume needs unique record IDs; {} repeats", record.id).into());
+        }
+    }
+
+    let (mut writer, completed) = open_pairs_output(options)?;
+    let mut distances = vec![vec![None; n]; n];
+    let mut done = HashSet::with_capacity(completed.pairs.len());
+    for ((id_a, id_b), pair_distances) in &completed.pairs {
+        let (Some(&i), Some(&j)) = (index.get(id_a.as_str()), index.get(id_b.as_str())) else {
+            return Err(format!(
+                "{} holds pair {} vs {}, which is not in {}",
+                options.output_file, id_a, id_b, options.fasta_file
+            )
+            .into());
+        };
+        let distance = pair_distances.and_then(|d| d.get(options.metric));
+        distances[i][j] = distance;
+        distances[j][i] = distance;
+        done.insert((i, j));
+    }
+    if options.resume {
+        info!("resume: {} pairs already complete", done.len());
+    }
+
+    let coverage = options.coverage.filter();
+    let (mut aligned, mut skipped, mut uncovered) = (0, 0, 0);
+    let mut suggested = false;
+    for_each_pair(
+        &records,
+        &template,
+        prefilter.as_ref(),
+        |i, j| done.contains(&(i, j)),
+        |mut pair| {
+            if pair.result.is_some() {
+                let (record_a, record_b) = (&records[pair.index_a], &records[pair.index_b]);
+                log_batch_pair(
+                    options,
+                    &template,
+                    (&record_a.id, &record_a.sequence),
+                    (&record_b.id, &record_b.sequence),
+                    &mut suggested,
+                );
+            }
+            uncovered +=
+                usize::from(pair.result.is_some() && !pair.apply_coverage_filter(&coverage));
+            write_pair_row(&mut writer, &records, &pair)?;
+            // Flush every row so an interrupted run can be resumed from the file
+            writer.flush()?;
+            let distance = pair.distances.and_then(|d| d.get(options.metric));
+            distances[pair.index_a][pair.index_b] = distance;
+            distances[pair.index_b][pair.index_a] = distance;
+            aligned += 1;
+            skipped += usize::from(pair.result.is_none());
+            Ok(())
+        },
+    )?;
+    if let Some(filter) = &prefilter {
+        report_prefilter(filter, skipped - uncovered, aligned, "pairs");
+    }
+    report_coverage(&coverage, uncovered, aligned);
+
+    if let Some(phylip_file) = &options.phylip_file {
+        let names: Vec<String> = records.iter().map(|record| record.id.clone()).collect();
+        let mut writer = create_file(phylip_file)?;
+        write_phylip(&mut writer, &names, &distances)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Align consecutive records of the FASTA file as pairs, writing each row as it arrives
+fn batch_consecutive_pairs(
+    options: &BatchOptions,
+    template: &AlignmentParameters<f64>,
+) -> Result<(), Box<dyn Error>> {
+    let records = FastaReader::new(BufReader::new(File::open(&options.fasta_file)?));
+    let prefilter = kmer_filter(&options.prefilter, template);
+    let mut writer = create_file(&options.output_file)?;
+    write_pairs_header(&mut writer)?;
+    let coverage = options.coverage.filter();
+    let (mut aligned, mut skipped, mut uncovered) = (0, 0, 0);
+    let mut suggested = false;
+    let outcome = for_each_consecutive_pair(
+        records,
+        template,
+        prefilter.as_ref(),
+        |record_a, record_b, mut pair| {
+            if pair.result.is_some() {
+                log_batch_pair(
+                    options,
+                    template,
+                    (&record_a.id, &record_a.sequence),
+                    (&record_b.id, &record_b.sequence),
+                    &mut suggested,
+                );
+            }
+            uncovered +=
+                usize::from(pair.result.is_some() && !pair.apply_coverage_filter(&coverage));
+            write_pair_row_ids(&mut writer, &record_a.id, &record_b.id, &pair)?;
+            writer.flush()?;
+            aligned += 1;
+            skipped += usize::from(pair.result.is_none());
+            Ok(())
+        },
+    );
+    // The rows of the complete pairs are kept even if the last record has no partner
+    outcome?;
+    if let Some(filter) = &prefilter {
+        report_prefilter(filter, skipped - uncovered, aligned, "pairs");
+    }
+    report_coverage(&coverage, uncovered, aligned);
+    Ok(())
+}
+
+fn search_database(options: &SearchOptions) -> Result<(), Box<dyn Error>> {
+    let template = AlignmentParameters::<f64>::load_from_file(&options.input_file)?;
+    let mut queries = FastaReader::new(BufReader::new(File::open(&options.query_file)?));
+    let query = match (queries.next(), queries.next()) {
+        (Some(query), None) => query?,
+        _ => return Err("The query FASTA file must contain exactly one record".into()),
+    };
+    let database = FastaReader::new(BufReader::new(File::open(&options.database_file)?));
+    let karlin_altschul = options
+        .karlin_altschul()
+        .or_else(|| KarlinAltschul::from_parameters(&template));
+    let prefilter = kmer_filter(&options.prefilter, &template);
+    let results = search(
+        &query,
+        database,
+        &template,
+        &options.filter,
+        karlin_altschul,
+        prefilter.as_ref(),
+    )?;
+    if let Some(filter) = &prefilter {
+        report_prefilter(filter, results.prefiltered, results.searched, "records");
+    }
+    let hits = results.hits;
+
+    let mut writer = create_file(&options.output_file)?;
+    write_hits_tsv(&mut writer, &query.id, &hits)?;
+    writer.flush()?;
+
+    if let Some(alignments_file) = &options.alignments_file {
+        let mut writer = create_file(alignments_file)?;
+        write_hit_alignments(&mut writer, &hits)?;
+        writer.flush()?;
+    }
+    no_hits_unless(!hits.is_empty(), "the search filters")
+}
+
+fn read_result(result_file: &str, gap: char) -> Result<AlignmentResult, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(result_file)?);
+    Ok(AlignmentResult::read_from(reader, gap)?)
+}
+
+fn stats(options: &StatsOptions) -> Result<(), Box<dyn Error>> {
+    let result = read_result(&options.result_file, options.gap)?;
+    let lengths = match &options.parameters_file {
+        Some(parameters_file) => {
+            let parameters = AlignmentParameters::<f64>::load_from_file(parameters_file)?;
+            Some((parameters.len_a(), parameters.len_b()))
+        }
+        None => None,
+    };
+    let summaries: Vec<AlignmentSummary> = result
+        .alignments
+        .iter()
+        .map(|alignment| AlignmentSummary::from_alignment(alignment))
+        .collect();
+    let mut writer = BufWriter::new(create_output(
+        options.output_file.as_deref().unwrap_or("-"),
+    )?);
+    write_summaries_tsv(&mut writer, &summaries, lengths)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn convert_ncbi_matrix(options: &ConvertOptions) -> Result<(), Box<dyn Error>> {
+    let reader = BufReader::new(File::open(&options.result_file)?);
+    let (alphabet_a, alphabet_b, match_matrix) = read_ncbi_matrix(reader)?;
+    let mut writer = BufWriter::new(create_output(&options.output_file)?);
+    write_match_block(&mut writer, &alphabet_a, &alphabet_b, &match_matrix)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Rebuild the parts of B covered by the alignments of a diffs listing from sequence A
+fn convert_apply(options: &ConvertOptions, fasta_file: &str) -> Result<(), Box<dyn Error>> {
+    let seq_a: Vec<char> = FastaReader::new(BufReader::new(File::open(fasta_file)?))
+        .next()
+        .ok_or_else(|| AlignmentError::InvalidInput(format!("{} has no records", fasta_file)))??
+        .sequence
+        .chars()
+        .collect();
+    let listing = read_diffs_from(BufReader::new(File::open(&options.result_file)?))?;
+    let mut writer = BufWriter::new(create_output(&options.output_file)?);
+    for (i, alignment) in listing.iter().enumerate() {
+        let range = alignment.range;
+        let seq_b = apply_differences(&seq_a, range.start_a, range.end_a, &alignment.differences)?;
+        if seq_b.chars().count() != range.end_b - range.start_b {
+            return Err(AlignmentError::InvalidInput(format!(
+                "alignment {} rebuilds {} residues of B, but covers B {}-{}",
+                i + 1,
+                seq_b.chars().count(),
+                range.start_b + 1,
+                range.end_b
+            ))
+            .into());
+        }
+        writeln!(
+            writer,
+            ">alignment_{}_b start={} end={}\n{}",
+            i + 1,
+            range.start_b + 1,
+            range.end_b,
+            seq_b
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn convert(options: &ConvertOptions) -> Result<(), Box<dyn Error>> {
+    if options.ncbi_matrix {
+        return convert_ncbi_matrix(options);
+    }
+    if let Some(fasta_file) = &options.apply {
+        return convert_apply(options, fasta_file);
+    }
+    let result = read_result(&options.result_file, options.gap)?;
+    if let Some(parameters_file) = &options.parameters_file {
+        let parameters = AlignmentParameters::<f64>::load_from_file(parameters_file)?;
+        result.validate_symbols(&parameters.alphabet_a, &parameters.alphabet_b, options.gap)?;
+    }
+    write_result(
+        &result,
+        &options.output_file,
+        options.format,
+        DEFAULT_INTERLEAVED_WIDTH,
+    )
+}
+
+fn diff(options: &DiffOptions) -> Result<(), Box<dyn Error>> {
+    let mut first = read_result(&options.first_file, options.gap)?;
+    let mut second = read_result(&options.second_file, options.gap)?;
+    if let Some(parameters_file) = &options.parameters_file {
+        let parameters = AlignmentParameters::<f64>::load_from_file(parameters_file)?;
+        let sequences = &parameters.sequences;
+        for pair in first
+            .alignments
+            .iter_mut()
+            .chain(second.alignments.iter_mut())
+        {
+            if !pair.modify(|alignment| {
+                alignment.locate(&sequences.seq_a, &sequences.seq_b, options.gap)
+            }) {
+                return Err("Alignment rows do not occur in the input sequences".into());
+            }
+        }
+    }
+
+    let report = diff_results(&first, &second);
+    let mut stdout = io::stdout().lock();
+    if options.json {
+        writeln!(stdout, "{}", report.to_json())?;
+    } else {
+        report.write_text(&mut stdout)?;
+    }
+    Ok(())
+}
+
+fn generate(options: &GenerateOptions) -> Result<(), Box<dyn Error>> {
+    let rates = MutationRates {
+        identity: options.identity,
+        indel_rate: options.indel_rate,
+    };
+    let (seq_a, seq_b) = generate_pair(options.alphabet, options.length, rates, options.seed);
+    let mut writer = BufWriter::new(create_output(
+        options.output_file.as_deref().unwrap_or("-"),
+    )?);
+    if options.fasta {
+        writeln!(writer, ">a\n{}\n>b\n{}", seq_a, seq_b)?;
+    } else {
+        generated_parameters(options.alphabet, seq_a, seq_b, !options.local)
+            .write_input_file(&mut writer)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Open a file of reads, FASTA or FASTQ
+fn open_reads(path: &str) -> Result<ReadRecords<BufReader<File>>, AlignmentError> {
+    File::open(path)
+        .and_then(|file| ReadRecords::new(BufReader::new(file)))
+        .map_err(|e| AlignmentError::io(e, path, None))
+}
+
+fn merge_reads(options: &MergeOptions) -> Result<(), Box<dyn Error>> {
+    let template = AlignmentParameters::<f64>::load_from_file(&options.input_file)?;
+    let reads_1 = open_reads(&options.reads_1)?;
+    let reads_2 = open_reads(&options.reads_2)?;
+    let mut writer = create_file(&options.output_file)?;
+    let (mut pairs, mut merged) = (0, 0);
+    for_each_read_pair(
+        reads_1,
+        reads_2,
+        &template,
+        &options.criteria(),
+        |read_1, _, outcome| {
+            pairs += 1;
+            match outcome {
+                Ok(read) => {
+                    merged += 1;
+                    write_merged_read(&mut writer, &read)
+                }
+                Err(reason) => {
+                    info!("# not merged: {}: {}", read_1.id, reason);
+                    Ok(())
+                }
+            }
+        },
+    )?;
+    writer.flush()?;
+    info!("# merged {} of {} pairs", merged, pairs);
+    no_hits_unless(merged > 0, "the overlap thresholds")
+}
+
+/// Raised after writing the results of a filtered run that found nothing to report
+#[derive(Debug)]
+struct NoHits(&'static str);
+
+impl fmt::Display for NoHits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no hits passed {}", self.0)
+    }
+}
+
+impl Error for NoHits {}
+
+fn no_hits_unless(found: bool, filter: &'static str) -> Result<(), Box<dyn Error>> {
+    if found {
+        Ok(())
+    } else {
+        Err(NoHits(filter).into())
+    }
+}
+
+/// Exit status for an error: failures to read input are input errors, other I/O
+/// failures such as a full disk are resource errors
+fn exit_status(e: &(dyn Error + 'static)) -> ExitStatus {
+    let io_status = |e: &io::Error| match e.kind() {
+        io::ErrorKind::InvalidData
+        | io::ErrorKind::InvalidInput
+        | io::ErrorKind::UnexpectedEof
+        | io::ErrorKind::NotFound
+        | io::ErrorKind::PermissionDenied => ExitStatus::Input,
+        _ => ExitStatus::Limit,
+    };
+    if e.is::<NoHits>() {
+        ExitStatus::NoHits
+    } else if e.is::<rayon::ThreadPoolBuildError>() {
+        ExitStatus::Limit
+    } else if let Some(e) = e.downcast_ref::<io::Error>() {
+        io_status(e)
+    } else if let Some(e) = e.downcast_ref::<AlignmentError>() {
+        match e {
+            AlignmentError::LimitExceeded(_) => ExitStatus::Limit,
+            AlignmentError::Io { source, .. } => io_status(source),
+            _ => ExitStatus::Input,
+        }
+    } else {
+        ExitStatus::Input
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse_args();
+    env_logger::Builder::new()
+        .filter_level(cli.log_level())
+        .format(|buf, record| writeln!(buf, "{}", record.args()))
+        .init();
+    if cli.legacy {
+        warn!("# `align INPUT OUTPUT` without a subcommand is deprecated; use `align align INPUT OUTPUT`");
+    }
+    match dispatch(cli) {
+        Ok(()) => ExitStatus::Success.into(),
+        Err(e) => {
+            error!("error: {}", e);
+            exit_status(e.as_ref()).into()
+        }
+    }
+}
+
+fn dispatch(cli: Cli) -> Result<(), Box<dyn Error>> {
+    DURABLE.store(cli.durable, Ordering::Relaxed);
+    if let Some(threads) = cli.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
+    }
+
+    match cli.command() {
+        Command::Align(options) if options.dry_run && options.tokens => dry_run::<String>(&options),
+        Command::Align(options) if options.dry_run => dry_run::<char>(&options),
+        Command::Align(options) if options.count_paths && options.tokens => {
+            count_paths::<String>(&options)
+        }
+        Command::Align(options) if options.count_paths => count_paths::<char>(&options),
+        Command::Align(options) if options.benchmark && options.tokens => {
+            benchmark::<String>(&options)
+        }
+        Command::Align(options) if options.benchmark => benchmark::<char>(&options),
+        Command::Align(options) if options.tokens => run_tokens(&options),
+        Command::Align(options) => run(&options),
+        Command::Batch(options) => batch(&options),
+        Command::Search(options) => search_database(&options),
+        Command::Stats(options) => stats(&options),
+        Command::Convert(options) => convert(&options),
+        Command::Diff(options) => diff(&options),
+        Command::Generate(options) => generate(&options),
+        Command::Merge(options) => merge_reads(&options),
+        Command::Completions(shell) => {
+            Cli::write_completions(shell, &mut io::stdout());
+            Ok(())
+        }
+    }
+}
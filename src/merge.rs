@@ -0,0 +1,356 @@
+use crate::alignment::traceback;
+use crate::consensus::{consensus, ConsensusRule, GapPolicy, MismatchRule};
+use crate::error::AlignmentError;
+use crate::io::fasta::{FastaReader, FastaRecord};
+use crate::io::fastq::{encode_quality, FastqReader, FastqRecord};
+use crate::io::parameters::AlignmentParameters;
+use crate::models::{
+    AlignGrid, Alignment, AlignmentOp, ColumnKind, EndGaps, Sequences, TokenFormat,
+};
+use crate::stats::AlignmentSummary;
+use rayon::prelude::*;
+use std::fmt;
+use std::io;
+use std::io::{BufRead, Write};
+
+/// Read pairs merged in parallel at a time
+const MERGE_CHUNK: usize = 1024;
+
+/// A sequencing read, with the Phred quality of each base when it came from FASTQ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadRecord {
+    pub id: String,
+    pub sequence: String,
+    pub quality: Option<Vec<u8>>,
+}
+
+impl From<FastaRecord> for ReadRecord {
+    fn from(record: FastaRecord) -> Self {
+        Self {
+            id: record.id,
+            sequence: record.sequence,
+            quality: None,
+        }
+    }
+}
+
+impl From<FastqRecord> for ReadRecord {
+    fn from(record: FastqRecord) -> Self {
+        Self {
+            id: record.id,
+            sequence: record.sequence,
+            quality: Some(record.quality),
+        }
+    }
+}
+
+impl ReadRecord {
+    /// The read of the opposite strand: bases complemented in reverse order, and the
+    /// qualities reversed with them
+    pub fn reverse_complement(&self) -> Self {
+        Self {
+            id: self.id.clone(),
+            sequence: self.sequence.chars().rev().map(complement).collect(),
+            quality: self
+                .quality
+                .as_ref()
+                .map(|quality| quality.iter().rev().copied().collect()),
+        }
+    }
+}
+
+/// Complementary base of a DNA or RNA base or IUPAC ambiguity code, keeping its case.
+/// `U` pairs with `A`, which pairs with `T`; anything else, such as `N`, is its own
+/// complement.
+pub fn complement(base: char) -> char {
+    let complemented = match base.to_ascii_uppercase() {
+        'A' => 'T',
+        'T' | 'U' => 'A',
+        'C' => 'G',
+        'G' => 'C',
+        'R' => 'Y',
+        'Y' => 'R',
+        'K' => 'M',
+        'M' => 'K',
+        'B' => 'V',
+        'V' => 'B',
+        'D' => 'H',
+        'H' => 'D',
+        _ => return base,
+    };
+    if base.is_ascii_lowercase() {
+        complemented.to_ascii_lowercase()
+    } else {
+        complemented
+    }
+}
+
+/// Records of a FASTA or FASTQ file, told apart by whether the file starts with `>`
+/// or `@`
+pub enum ReadRecords<R> {
+    Fasta(FastaReader<R>),
+    Fastq(FastqReader<R>),
+}
+
+impl<R: BufRead> ReadRecords<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let first = reader
+            .fill_buf()?
+            .iter()
+            .find(|byte| !byte.is_ascii_whitespace())
+            .copied();
+        Ok(match first {
+            Some(b'@') => ReadRecords::Fastq(FastqReader::new(reader)),
+            _ => ReadRecords::Fasta(FastaReader::new(reader)),
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for ReadRecords<R> {
+    type Item = io::Result<ReadRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            ReadRecords::Fasta(reader) => reader.next().map(|record| record.map(Into::into)),
+            ReadRecords::Fastq(reader) => reader.next().map(|record| record.map(Into::into)),
+        }
+    }
+}
+
+/// How good an overlap must be for a pair of reads to be merged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MergeCriteria {
+    /// Fewest columns of the overlap alignment
+    pub min_overlap: usize,
+    /// Least fraction of identical columns in the overlap
+    pub min_identity: f64,
+}
+
+/// Two reads of a pair merged into one sequence across their overlap
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergedRead {
+    pub id: String,
+    pub sequence: String,
+    /// Qualities of the merged bases, when both reads have them
+    pub quality: Option<Vec<u8>>,
+    /// Columns of the overlap alignment
+    pub overlap: usize,
+    /// Fraction of identical columns in the overlap
+    pub identity: f64,
+    pub score: f64,
+}
+
+/// Why a pair of reads was not merged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotMerged {
+    /// The best overlap alignment does not run from the end of the first read into the
+    /// start of the reverse complement of the second
+    NoOverlap,
+    /// The overlap has fewer columns than `min_overlap`
+    ShortOverlap(usize),
+    /// The overlap has an identity below `min_identity`
+    LowIdentity(f64),
+}
+
+impl fmt::Display for NotMerged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotMerged::NoOverlap => write!(f, "no suffix-prefix overlap"),
+            NotMerged::ShortOverlap(columns) => write!(f, "overlap of {} columns", columns),
+            NotMerged::LowIdentity(identity) => {
+                write!(f, "overlap identity {:.3}", identity)
+            }
+        }
+    }
+}
+
+/// Merge a pair of paired-end reads into the fragment they were read from.
+///
+/// The second read is reverse complemented and aligned against the first with free end
+/// gaps in both, using the match scores and gap penalties of `template` whatever its
+/// alignment type. Of the co-optimal alignments, in canonical order, the first that
+/// runs from the end of the first read into the start of the second is the overlap,
+/// less any bases of the second read it opens with against gaps; without one, or when
+/// it fails `criteria`, the pair is not merged. The merged read is the first read up to
+/// the overlap, the consensus of the overlap and the rest of the second read. Where the reads disagree, the base with the higher quality is taken,
+/// the first read's on ties or without qualities; each base of the overlap gets the
+/// higher quality of the two. Returns why the pair was not merged as the inner error.
+pub fn merge_pair(
+    read_1: &ReadRecord,
+    read_2: &ReadRecord,
+    template: &AlignmentParameters<f64>,
+    criteria: &MergeCriteria,
+) -> Result<Result<MergedRead, NotMerged>, AlignmentError> {
+    let read_2 = read_2.reverse_complement();
+    let mut parameters = AlignmentParameters {
+        sequences: Sequences::from_string(read_1.sequence.clone(), read_2.sequence.clone()),
+        ..template.clone()
+    };
+    parameters.global_alignment = true;
+    parameters.gap_penalties = parameters
+        .gap_penalties
+        .with_end_gaps(EndGaps::Free, EndGaps::Free);
+    let (len_a, len_b) = (parameters.len_a(), parameters.len_b());
+    if len_a == 0 || len_b == 0 {
+        return Ok(Err(NotMerged::NoOverlap));
+    }
+
+    let mut grid = AlignGrid::new(len_a, len_b);
+    let result = grid
+        .populate_score_matrices(&parameters)
+        .and_then(|_| traceback(&grid, &parameters, &TokenFormat::default()))
+        .map_err(|e| {
+            AlignmentError::InvalidInput(format!("{} vs {}: {}", read_1.id, read_2.id, e))
+        })?;
+    let Some(overlap) = result
+        .alignments
+        .iter()
+        .find(|alignment| alignment.start_b == 0 && alignment.end_a() == len_a)
+    else {
+        return Ok(Err(NotMerged::NoOverlap));
+    };
+    // With free end gaps in both, bases of the second read may open the overlap against
+    // gaps at no cost; the first read covers those positions of the fragment already
+    let leading = overlap
+        .ops
+        .iter()
+        .take_while(|op| **op == AlignmentOp::Insertion)
+        .count();
+    let overlap = &overlap.slice(leading..overlap.len());
+
+    let summary = AlignmentSummary::from_alignment(overlap);
+    if summary.columns < criteria.min_overlap {
+        return Ok(Err(NotMerged::ShortOverlap(summary.columns)));
+    }
+    let identity = summary.identity().unwrap_or_default();
+    if identity < criteria.min_identity {
+        return Ok(Err(NotMerged::LowIdentity(identity)));
+    }
+
+    let qualities = read_1.quality.as_deref().zip(read_2.quality.as_deref());
+    let rule = ConsensusRule {
+        mismatch: match qualities {
+            Some((quality_a, quality_b)) => MismatchRule::Quality {
+                quality_a: quality_a.to_vec(),
+                quality_b: quality_b.to_vec(),
+            },
+            None => MismatchRule::PreferA,
+        },
+        gaps: GapPolicy::Keep,
+    };
+    let sequence = read_1
+        .sequence
+        .chars()
+        .take(overlap.start_a)
+        .collect::<String>()
+        + &consensus(overlap, &rule)
+        + &read_2
+            .sequence
+            .chars()
+            .skip(overlap.end_b())
+            .collect::<String>();
+    let quality = qualities.map(|(quality_a, quality_b)| {
+        let mut quality = quality_a[..overlap.start_a].to_vec();
+        quality.extend(overlap_qualities(overlap, quality_a, quality_b));
+        quality.extend_from_slice(&quality_b[overlap.end_b()..]);
+        quality
+    });
+
+    Ok(Ok(MergedRead {
+        id: read_1.id.clone(),
+        sequence,
+        quality,
+        overlap: summary.columns,
+        identity,
+        score: result.score,
+    }))
+}
+
+/// Quality of each consensus base of the overlap: the higher of the two reads' in an
+/// aligned column, and that of the read with the base in a gap column
+fn overlap_qualities<'a>(
+    overlap: &'a Alignment,
+    quality_a: &'a [u8],
+    quality_b: &'a [u8],
+) -> impl Iterator<Item = u8> + 'a {
+    overlap.columns().map(|column| {
+        let q_a = column.a_pos.map_or(0, |pos| quality_a[pos]);
+        let q_b = column.b_pos.map_or(0, |pos| quality_b[pos]);
+        match column.kind {
+            ColumnKind::InsertA => q_a,
+            ColumnKind::InsertB => q_b,
+            ColumnKind::Match | ColumnKind::Mismatch => q_a.max(q_b),
+        }
+    })
+}
+
+/// Merge the pairs of reads `reads_1` and `reads_2` hold in the same order, passing each
+/// outcome to `sink` with the two reads as read.
+///
+/// Pairs are read and merged in parallel a chunk at a time, so only one chunk of reads
+/// is held in memory; `sink` sees the outcomes in file order. Fails after passing on
+/// every complete pair if either file has reads left over.
+pub fn for_each_read_pair<I, J, F>(
+    reads_1: I,
+    reads_2: J,
+    template: &AlignmentParameters<f64>,
+    criteria: &MergeCriteria,
+    mut sink: F,
+) -> Result<(), AlignmentError>
+where
+    I: IntoIterator<Item = io::Result<ReadRecord>>,
+    J: IntoIterator<Item = io::Result<ReadRecord>>,
+    F: FnMut(&ReadRecord, &ReadRecord, Result<MergedRead, NotMerged>) -> io::Result<()>,
+{
+    let (mut reads_1, mut reads_2) = (reads_1.into_iter(), reads_2.into_iter());
+    let mut chunk: Vec<(ReadRecord, ReadRecord)> = Vec::with_capacity(MERGE_CHUNK);
+    loop {
+        chunk.clear();
+        let mut unpaired = None;
+        while chunk.len() < MERGE_CHUNK {
+            match (reads_1.next().transpose()?, reads_2.next().transpose()?) {
+                (Some(read_1), Some(read_2)) => chunk.push((read_1, read_2)),
+                (None, None) => break,
+                (read_1, read_2) => {
+                    unpaired = read_1
+                        .map(|read| (read, 1))
+                        .or(read_2.map(|read| (read, 2)));
+                    break;
+                }
+            }
+        }
+        let outcomes = chunk
+            .par_iter()
+            .map(|(read_1, read_2)| merge_pair(read_1, read_2, template, criteria))
+            .collect::<Result<Vec<_>, _>>()?;
+        for ((read_1, read_2), outcome) in chunk.iter().zip(outcomes) {
+            sink(read_1, read_2, outcome)?;
+        }
+        if let Some((read, file)) = unpaired {
+            return Err(AlignmentError::InvalidInput(format!(
+                "the files of reads differ in length; {} of file {} has no mate",
+                read.id, file
+            )));
+        }
+        if chunk.len() < MERGE_CHUNK {
+            return Ok(());
+        }
+    }
+}
+
+/// Write a merged read as a FASTQ record when it has qualities and as FASTA otherwise,
+/// with the length and identity of the overlap in the header
+pub fn write_merged_read<W: Write>(writer: &mut W, read: &MergedRead) -> io::Result<()> {
+    let description = format!("overlap={} identity={:.4}", read.overlap, read.identity);
+    match &read.quality {
+        Some(quality) => writeln!(
+            writer,
+            "@{} {}\n{}\n+\n{}",
+            read.id,
+            description,
+            read.sequence,
+            encode_quality(quality)
+        ),
+        None => writeln!(writer, ">{} {}\n{}", read.id, description, read.sequence),
+    }
+}
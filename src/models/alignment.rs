@@ -0,0 +1,584 @@
+use crate::error::AlignmentError;
+use crate::models::{Columns, GapPenalties, MatchMatrix};
+use crate::verify;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt::Write;
+use std::io;
+use std::ops::{Deref, Range};
+
+/// One column of a pairwise alignment, taking sequence A as the reference
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum AlignmentOp {
+    /// A residue from each sequence aligned together (match or mismatch)
+    Match,
+    /// A residue from A aligned to a gap in B
+    Deletion,
+    /// A gap in A aligned to a residue from B
+    Insertion,
+}
+
+impl AlignmentOp {
+    pub fn consumes_a(&self) -> bool {
+        !matches!(self, AlignmentOp::Insertion)
+    }
+
+    pub fn consumes_b(&self) -> bool {
+        !matches!(self, AlignmentOp::Deletion)
+    }
+
+    pub fn cigar_char(&self) -> char {
+        match self {
+            AlignmentOp::Match => 'M',
+            AlignmentOp::Deletion => 'D',
+            AlignmentOp::Insertion => 'I',
+        }
+    }
+}
+
+/// A single aligned pair with its column operations and start coordinates.
+///
+/// `start_a` and `start_b` are 0-based indices of the first residue of each
+/// sequence covered by the alignment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alignment {
+    pub align_a: String,
+    pub align_b: String,
+    pub start_a: usize,
+    pub start_b: usize,
+    pub ops: Vec<AlignmentOp>,
+    /// Residues of A soft-clipped before and after the aligned region, written as `S`
+    /// at the ends of the CIGAR string
+    pub soft_clip_a: (usize, usize),
+    /// Range covered before the alignment was trimmed, if it was
+    pub untrimmed: Option<CoveredRange>,
+}
+
+/// An alignment with the columns where each of its rows has a gap, as an
+/// [`AlignmentResult`](crate::models::AlignmentResult) keeps it.
+///
+/// The gap columns are listed once, when the pair is built, so callers can read them
+/// without walking the rows; everything else of the alignment is reached through
+/// `Deref`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlignedPair {
+    alignment: Alignment,
+    gaps_a: Vec<usize>,
+    gaps_b: Vec<usize>,
+}
+
+impl AlignedPair {
+    /// Aligned row A, with gap characters
+    pub fn seq_a(&self) -> &str {
+        &self.alignment.align_a
+    }
+
+    /// Aligned row B, with gap characters
+    pub fn seq_b(&self) -> &str {
+        &self.alignment.align_b
+    }
+
+    /// Columns, 0-based, where row A has a gap
+    pub fn gaps_a(&self) -> &[usize] {
+        &self.gaps_a
+    }
+
+    /// Columns, 0-based, where row B has a gap
+    pub fn gaps_b(&self) -> &[usize] {
+        &self.gaps_b
+    }
+
+    pub fn alignment(&self) -> &Alignment {
+        &self.alignment
+    }
+
+    /// Change the alignment with `f`, listing its gap columns again afterwards
+    pub fn modify<R>(&mut self, f: impl FnOnce(&mut Alignment) -> R) -> R {
+        let value = f(&mut self.alignment);
+        self.gaps_a = self.alignment.gaps_a();
+        self.gaps_b = self.alignment.gaps_b();
+        value
+    }
+
+    pub fn into_alignment(self) -> Alignment {
+        self.alignment
+    }
+
+    /// The rows and gap columns, as `(seq_a, gaps_a, seq_b, gaps_b)`
+    pub fn into_parts(self) -> (String, Vec<usize>, String, Vec<usize>) {
+        (
+            self.alignment.align_a,
+            self.gaps_a,
+            self.alignment.align_b,
+            self.gaps_b,
+        )
+    }
+}
+
+impl From<Alignment> for AlignedPair {
+    fn from(alignment: Alignment) -> Self {
+        Self {
+            gaps_a: alignment.gaps_a(),
+            gaps_b: alignment.gaps_b(),
+            alignment,
+        }
+    }
+}
+
+impl Deref for AlignedPair {
+    type Target = Alignment;
+
+    fn deref(&self) -> &Alignment {
+        &self.alignment
+    }
+}
+
+impl Serialize for AlignedPair {
+    fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+        let mut pair = serializer.serialize_struct("AlignedPair", 4)?;
+        pair.serialize_field("seq_a", self.seq_a())?;
+        pair.serialize_field("seq_b", self.seq_b())?;
+        pair.serialize_field("gaps_a", &self.gaps_a)?;
+        pair.serialize_field("gaps_b", &self.gaps_b)?;
+        pair.end()
+    }
+}
+
+/// Residues of each sequence covered by an alignment, as 0-based indices with exclusive
+/// ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoveredRange {
+    pub start_a: usize,
+    pub end_a: usize,
+    pub start_b: usize,
+    pub end_b: usize,
+}
+
+impl Alignment {
+    pub fn new(
+        align_a: String,
+        align_b: String,
+        start_a: usize,
+        start_b: usize,
+        ops: Vec<AlignmentOp>,
+    ) -> Self {
+        Self {
+            align_a,
+            align_b,
+            start_a,
+            start_b,
+            ops,
+            soft_clip_a: (0, 0),
+            untrimmed: None,
+        }
+    }
+
+    /// Build an alignment from two gapped character rows starting at the origin
+    pub fn from_rows(align_a: String, align_b: String, gap: char) -> io::Result<Self> {
+        let mut ops = Vec::with_capacity(align_a.len());
+        let mut chars_b = align_b.chars();
+        for a in align_a.chars() {
+            let b = chars_b.next().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "Aligned rows differ in length")
+            })?;
+            let op = match (a == gap, b == gap) {
+                (false, false) => AlignmentOp::Match,
+                (false, true) => AlignmentOp::Deletion,
+                (true, false) => AlignmentOp::Insertion,
+                (true, true) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Aligned rows contain a gap-only column",
+                    ))
+                }
+            };
+            ops.push(op);
+        }
+        if chars_b.next().is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Aligned rows differ in length",
+            ));
+        }
+        Ok(Self::new(align_a, align_b, 0, 0, ops))
+    }
+
+    /// Build an alignment of `seq_a` and `seq_b` from a CIGAR string produced elsewhere,
+    /// starting at the 0-based positions `start_a` and `start_b`, with gaps written as
+    /// `gap` ([`GAP_CHAR`](crate::models::GAP_CHAR) in the default output).
+    ///
+    /// `M`, `=` and `X` align a residue of each sequence, `D` a residue of A against a gap
+    /// and `I` a residue of B against a gap, as [`Self::cigar`] writes them. `S` at either
+    /// end soft-clips residues of A; the clips must then account for all of A, the leading
+    /// one being `start_a`. Fails on any other operation, or if the CIGAR runs past the end
+    /// of either sequence.
+    pub fn from_cigar(
+        seq_a: &[char],
+        seq_b: &[char],
+        start_a: usize,
+        start_b: usize,
+        cigar: &str,
+        gap: char,
+    ) -> Result<Self, AlignmentError> {
+        let invalid =
+            |reason: String| AlignmentError::InvalidInput(format!("CIGAR {}: {}", cigar, reason));
+        let mut runs = Vec::new();
+        let mut length = String::new();
+        for symbol in cigar.chars() {
+            if symbol.is_ascii_digit() {
+                length.push(symbol);
+                continue;
+            }
+            let run = match length.parse::<usize>() {
+                Ok(run) if run > 0 => run,
+                _ => return Err(invalid(format!("{} has no length", symbol))),
+            };
+            runs.push((symbol, run));
+            length.clear();
+        }
+        if !length.is_empty() {
+            return Err(invalid(format!("{} has no operation", length)));
+        }
+
+        let clip = |run: Option<&(char, usize)>| match run {
+            Some(&('S', length)) => Some(length),
+            _ => None,
+        };
+        let leading_clip = clip(runs.first());
+        let trailing_clip = clip(runs.last()).filter(|_| runs.len() > 1);
+        let unclipped = &runs[usize::from(leading_clip.is_some())
+            ..runs.len() - usize::from(trailing_clip.is_some())];
+        let mut ops = Vec::new();
+        for &(symbol, run) in unclipped {
+            let op = match symbol {
+                'M' | '=' | 'X' => AlignmentOp::Match,
+                'D' => AlignmentOp::Deletion,
+                'I' => AlignmentOp::Insertion,
+                'S' => return Err(invalid("S is only allowed at either end".to_string())),
+                _ => return Err(invalid(format!("unsupported operation {}", symbol))),
+            };
+            ops.extend(std::iter::repeat_n(op, run));
+        }
+
+        let consumed_a = ops.iter().filter(|op| op.consumes_a()).count();
+        let consumed_b = ops.iter().filter(|op| op.consumes_b()).count();
+        for (name, start, consumed, len) in [
+            ("A", start_a, consumed_a, seq_a.len()),
+            ("B", start_b, consumed_b, seq_b.len()),
+        ] {
+            if start + consumed > len {
+                return Err(invalid(format!(
+                    "covers {} up to position {}, but {} has {} residues",
+                    name,
+                    start + consumed,
+                    name,
+                    len
+                )));
+            }
+        }
+        let soft_clip_a = if leading_clip.is_some() || trailing_clip.is_some() {
+            let clips = (leading_clip.unwrap_or(0), trailing_clip.unwrap_or(0));
+            if clips != (start_a, seq_a.len() - start_a - consumed_a) {
+                return Err(invalid(format!(
+                    "soft clips of {} and {} residues do not account for the {} residues of \
+                     A around positions {} to {}",
+                    clips.0,
+                    clips.1,
+                    seq_a.len(),
+                    start_a + 1,
+                    start_a + consumed_a
+                )));
+            }
+            clips
+        } else {
+            (0, 0)
+        };
+
+        let (mut pos_a, mut pos_b) = (start_a, start_b);
+        let (mut align_a, mut align_b) = (String::new(), String::new());
+        for op in &ops {
+            align_a.push(if op.consumes_a() { seq_a[pos_a] } else { gap });
+            align_b.push(if op.consumes_b() { seq_b[pos_b] } else { gap });
+            pos_a += usize::from(op.consumes_a());
+            pos_b += usize::from(op.consumes_b());
+        }
+        let mut alignment = Self::new(align_a, align_b, start_a, start_b, ops);
+        alignment.soft_clip_a = soft_clip_a;
+        Ok(alignment)
+    }
+
+    /// Number of alignment columns
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Exclusive end of the covered range in sequence A
+    pub fn end_a(&self) -> usize {
+        self.start_a + self.ops.iter().filter(|op| op.consumes_a()).count()
+    }
+
+    /// Exclusive end of the covered range in sequence B
+    pub fn end_b(&self) -> usize {
+        self.start_b + self.ops.iter().filter(|op| op.consumes_b()).count()
+    }
+
+    /// Run-length encoded operations, e.g. `5M1D3M`, with soft clips as `S`, e.g.
+    /// `2S5M1D3M4S`
+    pub fn cigar(&self) -> String {
+        let mut cigar = String::new();
+        let (leading_clip, trailing_clip) = self.soft_clip_a;
+        if leading_clip > 0 {
+            let _ = write!(cigar, "{}S", leading_clip);
+        }
+        let mut ops = self.ops.iter().peekable();
+        while let Some(op) = ops.next() {
+            let mut run = 1;
+            while ops.next_if_eq(&op).is_some() {
+                run += 1;
+            }
+            let _ = write!(cigar, "{}{}", run, op.cigar_char());
+        }
+        if trailing_clip > 0 {
+            let _ = write!(cigar, "{}S", trailing_clip);
+        }
+        cigar
+    }
+
+    /// Score of the alignment under `match_matrix` and an affine gap model, charging
+    /// each gap column as [`verify::column_scores`] does with the internal penalties of
+    /// `gap_penalties`: the first column of a gap in B opens with `dy` and later ones
+    /// extend with `ey`, and likewise `dx` and `ex` for a gap in A. Soft clips and the
+    /// residues outside the alignment cost nothing, as in a local alignment; use
+    /// [`verify::rescore`] for the end charges of a global one.
+    pub fn rescore(
+        &self,
+        match_matrix: &MatchMatrix<f64>,
+        gap_penalties: &GapPenalties<f64>,
+    ) -> f64 {
+        verify::affine_column_scores(self, match_matrix, gap_penalties, None)
+            .iter()
+            .sum()
+    }
+
+    /// For each covered position of A, the position of B aligned to it (`None` for a gap)
+    pub fn project_onto_a(&self) -> Vec<(usize, Option<usize>)> {
+        let (mut pos_a, mut pos_b) = (self.start_a, self.start_b);
+        let mut projection = Vec::with_capacity(self.ops.len());
+        for op in &self.ops {
+            match op {
+                AlignmentOp::Match => projection.push((pos_a, Some(pos_b))),
+                AlignmentOp::Deletion => projection.push((pos_a, None)),
+                AlignmentOp::Insertion => {}
+            }
+            if op.consumes_a() {
+                pos_a += 1;
+            }
+            if op.consumes_b() {
+                pos_b += 1;
+            }
+        }
+        projection
+    }
+
+    /// Columns, 0-based, where row A has a gap. For character alignments these are also
+    /// the indices of the gap symbols in `align_a`.
+    pub fn gaps_a(&self) -> Vec<usize> {
+        self.gap_columns(AlignmentOp::Insertion)
+    }
+
+    /// Columns, 0-based, where row B has a gap; see [`Self::gaps_a`]
+    pub fn gaps_b(&self) -> Vec<usize> {
+        self.gap_columns(AlignmentOp::Deletion)
+    }
+
+    fn gap_columns(&self, gap_op: AlignmentOp) -> Vec<usize> {
+        self.ops
+            .iter()
+            .enumerate()
+            .filter(|&(_, &op)| op == gap_op)
+            .map(|(col, _)| col)
+            .collect()
+    }
+
+    /// The columns of the alignment in order, each with its residues, their positions
+    /// and whether it is a match, a mismatch or a gap in either row. The iterator runs
+    /// from either end.
+    ///
+    /// Built on the operations, so it needs no parsing of the rows beyond reading one
+    /// character per column. Rows that do not have one character per column, such as
+    /// those of token alignments, give no residues, and every aligned column of theirs is
+    /// a [`ColumnKind::Match`](crate::models::ColumnKind::Match).
+    pub fn columns(&self) -> Columns<'_> {
+        Columns::new(self)
+    }
+
+    /// The alignment of columns `range` alone, starting at the residues of each sequence
+    /// in its first column. Residues of A before and after the slice are soft-clipped if
+    /// the alignment was.
+    ///
+    /// Expects one character per column in each row. Panics if `range` is out of bounds.
+    pub fn slice(&self, range: Range<usize>) -> Alignment {
+        let before = &self.ops[..range.start];
+        let ops = self.ops[range.clone()].to_vec();
+        let row = |row: &str| -> String {
+            row.chars()
+                .skip(range.start)
+                .take(range.end - range.start)
+                .collect()
+        };
+        let mut slice = Alignment::new(
+            row(&self.align_a),
+            row(&self.align_b),
+            self.start_a + before.iter().filter(|op| op.consumes_a()).count(),
+            self.start_b + before.iter().filter(|op| op.consumes_b()).count(),
+            ops,
+        );
+        if self.soft_clip_a != (0, 0) {
+            let len_a = self.end_a() + self.soft_clip_a.1;
+            slice.soft_clip_a = (slice.start_a, len_a - slice.end_a());
+        }
+        slice
+    }
+
+    /// Residues of each sequence covered by the alignment
+    pub fn covered_range(&self) -> CoveredRange {
+        CoveredRange {
+            start_a: self.start_a,
+            end_a: self.end_a(),
+            start_b: self.start_b,
+            end_b: self.end_b(),
+        }
+    }
+
+    /// Fractions of sequences of `len_a` and `len_b` residues spanned by the alignment,
+    /// `(end - start) / len` with the exclusive ends of [`Self::covered_range`]; `None` for
+    /// an empty sequence.
+    ///
+    /// Only the columns written out count, so the overhang a global alignment skips with
+    /// free end gaps is left out of the span, as the aligned region of a local alignment
+    /// is all that counts there.
+    pub fn coverage(&self, len_a: usize, len_b: usize) -> (Option<f64>, Option<f64>) {
+        let range = self.covered_range();
+        let fraction = |start: usize, end: usize, len: usize| {
+            (len > 0).then(|| (end - start) as f64 / len as f64)
+        };
+        (
+            fraction(range.start_a, range.end_a, len_a),
+            fraction(range.start_b, range.end_b, len_b),
+        )
+    }
+
+    /// Drop the leading and trailing columns that have a gap in either row, so that the
+    /// alignment starts and ends on aligned residues. `gap` is the symbol the rows write
+    /// for a gap.
+    pub fn trim_end_gaps(&mut self, gap: &str) {
+        let is_gap = |op: &&AlignmentOp| **op != AlignmentOp::Match;
+        let leading = self.ops.iter().take_while(is_gap).count();
+        let trailing = self.ops[leading..].iter().rev().take_while(is_gap).count();
+        self.trim_columns(leading, trailing, gap);
+    }
+
+    /// Drop columns from each end while the `window` columns at that end have an
+    /// identity below `min_identity`. No end is trimmed to fewer than `window` columns.
+    pub fn trim_below_identity(&mut self, min_identity: f64, window: usize, gap: &str) {
+        if window == 0 {
+            return;
+        }
+        let identical: Vec<bool> = self
+            .column_tokens(gap)
+            .into_iter()
+            .map(|(a, b, op)| op == AlignmentOp::Match && a == b)
+            .collect();
+        let low = |columns: &[bool]| {
+            let same = columns.iter().filter(|&&same| same).count();
+            (same as f64) < min_identity * window as f64
+        };
+        let len = identical.len();
+        let mut leading = 0;
+        while len - leading >= window && low(&identical[leading..leading + window]) {
+            leading += 1;
+        }
+        let mut trailing = 0;
+        while len - leading - trailing >= window
+            && low(&identical[len - trailing - window..len - trailing])
+        {
+            trailing += 1;
+        }
+        self.trim_columns(leading, trailing, gap);
+    }
+
+    /// The residue (or gap symbol) of each row in every column, with its operation
+    fn column_tokens(&self, gap: &str) -> Vec<(&str, &str, AlignmentOp)> {
+        let gap_width = gap.chars().count();
+        let mut rest_a = self.align_a.as_str();
+        let mut rest_b = self.align_b.as_str();
+        let width = |consumes: bool| if consumes { 1 } else { gap_width };
+        self.ops
+            .iter()
+            .map(|&op| {
+                let a = split_chars(&mut rest_a, width(op.consumes_a()));
+                let b = split_chars(&mut rest_b, width(op.consumes_b()));
+                (a, b, op)
+            })
+            .collect()
+    }
+
+    /// Remove `leading` columns from the start and `trailing` from the end, moving the
+    /// start coordinates past the residues removed and keeping the range covered before
+    /// the first trim in [`Self::untrimmed`]
+    fn trim_columns(&mut self, leading: usize, trailing: usize, gap: &str) {
+        if leading + trailing == 0 {
+            return;
+        }
+        let untrimmed = self.untrimmed.unwrap_or_else(|| self.covered_range());
+        let kept = self.ops.len().saturating_sub(leading + trailing);
+        let columns = self.column_tokens(gap);
+        let (mut align_a, mut align_b) = (String::new(), String::new());
+        for &(a, b, _) in columns.iter().skip(leading).take(kept) {
+            align_a.push_str(a);
+            align_b.push_str(b);
+        }
+        let removed = &self.ops[..leading];
+        self.start_a += removed.iter().filter(|op| op.consumes_a()).count();
+        self.start_b += removed.iter().filter(|op| op.consumes_b()).count();
+        self.ops = self.ops[leading..leading + kept].to_vec();
+        self.align_a = align_a;
+        self.align_b = align_b;
+        self.untrimmed = Some(untrimmed);
+    }
+
+    /// Set the start coordinates by finding the ungapped rows in the original sequences.
+    ///
+    /// Returns `false` if either row does not occur in its sequence.
+    pub fn locate(&mut self, seq_a: &[char], seq_b: &[char], gap: char) -> bool {
+        let residues_a: Vec<char> = self.align_a.chars().filter(|c| *c != gap).collect();
+        let residues_b: Vec<char> = self.align_b.chars().filter(|c| *c != gap).collect();
+        match (find(seq_a, &residues_a), find(seq_b, &residues_b)) {
+            (Some(start_a), Some(start_b)) => {
+                self.start_a = start_a;
+                self.start_b = start_b;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Split the first `n` characters off `rest`
+fn split_chars<'a>(rest: &mut &'a str, n: usize) -> &'a str {
+    let split = rest.char_indices().nth(n).map_or(rest.len(), |(i, _)| i);
+    let (head, tail) = rest.split_at(split);
+    *rest = tail;
+    head
+}
+
+fn find(haystack: &[char], needle: &[char]) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(0);
+    }
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
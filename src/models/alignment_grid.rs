@@ -1,100 +1,390 @@
+use crate::error::AlignmentError;
 use crate::io::parameters::AlignmentParameters;
+use crate::models::frequency_profile::ScoringSource;
 use crate::models::score_matrix::MatrixType::{Ix, Iy, M};
-use crate::models::score_matrix::{MatrixType, ScoreMatrix};
+use crate::models::score_matrix::{MatrixType, Pointer, ScoreMatrix};
+#[cfg(feature = "mmap")]
+use crate::models::score_store::{DiskElement, DiskStore, ScoreStorage};
+use crate::models::{CodonAwareGapPenalties, FrequencyProfile, GapConstraints, Residue};
+use crate::trace_logger::StepTracer;
 use crate::utils::{clamp_to_zero, max, Epsilon};
+use ndarray::{Array2, ArrayView2};
 use num_traits::Zero;
 use std::error::Error;
 use std::fmt::Display;
+use std::io;
 use std::ops::Sub;
+#[cfg(feature = "mmap")]
+use std::path::Path;
 use std::str::FromStr;
 
-/// Main alignment object
+/// Main alignment object.
+///
+/// A clone of a filled grid is a snapshot for reading, e.g. to trace back on another
+/// thread; nothing refills it, and refilling the original leaves it unchanged.
+#[derive(Clone)]
 pub struct AlignGrid<T> {
     pub(crate) m_matrix: ScoreMatrix<T>,
     pub(crate) ix_matrix: ScoreMatrix<T>,
     pub(crate) iy_matrix: ScoreMatrix<T>,
 }
 
+impl<T> AlignGrid<T> {
+    /// Number of cells in each of the M, Ix and Iy matrices, the boundary included
+    pub fn cells(&self) -> usize {
+        self.m_matrix.nrow * self.m_matrix.ncol
+    }
+
+    /// Approximate heap usage of the three matrices, in bytes
+    pub fn memory_bytes(&self) -> usize {
+        self.m_matrix.memory_bytes() + self.ix_matrix.memory_bytes() + self.iy_matrix.memory_bytes()
+    }
+
+    /// Heap usage [`Self::memory_bytes`] will report for sequences of `rows` and
+    /// `columns` residues, computed without allocating the grid
+    pub fn estimate_memory_bytes(rows: usize, columns: usize) -> usize {
+        3 * ScoreMatrix::<T>::estimate_memory_bytes(rows + 1, columns + 1)
+    }
+}
+
+#[cfg(feature = "numpy")]
+impl<T: Copy + ndarray_npy::WritableElement> AlignGrid<T> {
+    /// Write the M, Ix and Iy scores as NumPy `.npy` arrays, boundary row and column
+    /// included, so that cell `[r, c]` of each aligns residue `r - 1` of A with `c - 1` of B
+    pub fn export_npy(&self, m_path: &str, ix_path: &str, iy_path: &str) -> io::Result<()> {
+        self.m_matrix.write_npy(m_path)?;
+        self.ix_matrix.write_npy(ix_path)?;
+        self.iy_matrix.write_npy(iy_path)
+    }
+}
+
 impl<T: Copy + Display + Epsilon + FromStr + PartialEq + PartialOrd + Sub<Output = T> + Zero>
     AlignGrid<T>
 {
+    /// An empty grid for sequences of `rows` and `columns` residues: `(rows + 1) x
+    /// (columns + 1)` cells, row and column 0 being the boundary before the first residue
+    /// of each sequence. Cell `(r, c)` aligns `a_(r-1)` with `b_(c-1)`.
     pub fn new(rows: usize, columns: usize) -> Self {
         Self {
-            m_matrix: ScoreMatrix::new(M, rows, columns),
-            ix_matrix: ScoreMatrix::new(Ix, rows, columns),
-            iy_matrix: ScoreMatrix::new(Iy, rows, columns),
+            m_matrix: ScoreMatrix::new(M, rows + 1, columns + 1),
+            ix_matrix: ScoreMatrix::new(Ix, rows + 1, columns + 1),
+            iy_matrix: ScoreMatrix::new(Iy, rows + 1, columns + 1),
+        }
+    }
+
+    /// An empty grid as [`Self::new`] lays it out, with the scores and pointers of each
+    /// matrix in a memory-mapped temporary file in `dir` rather than on the heap, for
+    /// grids larger than RAM. The files are removed when the grid is dropped, or however
+    /// the process ends.
+    #[cfg(feature = "mmap")]
+    pub fn new_on_disk(rows: usize, columns: usize, dir: &Path) -> io::Result<Self>
+    where
+        T: DiskElement,
+    {
+        let matrix = |matrix_type| -> io::Result<ScoreMatrix<T>> {
+            let (nrow, ncol) = (rows + 1, columns + 1);
+            Ok(ScoreMatrix {
+                matrix_type,
+                nrow,
+                ncol,
+                scores: ScoreStorage::Disk(DiskStore::zeros(nrow, ncol, dir)?),
+                pointers: ScoreStorage::Disk(DiskStore::zeros(nrow, ncol, dir)?),
+            })
+        };
+        Ok(Self {
+            m_matrix: matrix(M)?,
+            ix_matrix: matrix(Ix)?,
+            iy_matrix: matrix(Iy)?,
+        })
+    }
+
+    /// Build a grid from precomputed M, Ix and Iy scores, with no traceback pointers. The
+    /// arrays include the boundary row and column, as [`Self::new`] lays them out.
+    ///
+    /// Panics if the three arrays do not have the same shape.
+    pub fn from_arrays(m: Array2<T>, ix: Array2<T>, iy: Array2<T>) -> Self {
+        assert!(
+            m.dim() == ix.dim() && m.dim() == iy.dim(),
+            "M, Ix and Iy score arrays must have the same shape"
+        );
+        Self {
+            m_matrix: ScoreMatrix::from_array2(M, m),
+            ix_matrix: ScoreMatrix::from_array2(Ix, ix),
+            iy_matrix: ScoreMatrix::from_array2(Iy, iy),
         }
     }
 
-    /// Populate the score matrices
-    pub fn populate_score_matrices(
+    pub fn m_scores(&self) -> ArrayView2<'_, T> {
+        self.m_matrix.as_array2()
+    }
+
+    pub fn ix_scores(&self) -> ArrayView2<'_, T> {
+        self.ix_matrix.as_array2()
+    }
+
+    pub fn iy_scores(&self) -> ArrayView2<'_, T> {
+        self.iy_matrix.as_array2()
+    }
+
+    /// Populate the score matrices.
+    ///
+    /// Every cell of M takes its diagonal predecessor, the boundary row and column
+    /// included, so residues left before the first aligned pair are charged through the
+    /// boundary. Gaps have no transition from the other gap matrix, so the first row of Ix
+    /// and the first column of Iy are set from the end gap rules instead.
+    pub fn populate_score_matrices<S: Residue>(
         &mut self,
-        alignment_parameters: &AlignmentParameters<T>,
+        alignment_parameters: &AlignmentParameters<T, S>,
     ) -> Result<(), Box<dyn Error>> {
+        let source = ScoringSource::Matrix(&alignment_parameters.match_matrix);
+        self.fill(alignment_parameters, &source, cfg!(debug_assertions), None)?;
+        Ok(())
+    }
+
+    /// Populate the score matrices as [`Self::populate_score_matrices`] does, failing at
+    /// the first cell whose score is NaN or infinite, as NaN or infinite match scores or
+    /// penalties produce. Debug builds check every fill this way.
+    pub fn populate_score_matrices_checked<S: Residue>(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<T, S>,
+    ) -> Result<(), AlignmentError> {
+        let source = ScoringSource::Matrix(&alignment_parameters.match_matrix);
+        self.fill(alignment_parameters, &source, true, None)
+    }
+
+    /// Populate the score matrices as [`Self::populate_score_matrices`] does for aligning
+    /// `profile` against sequence B, scoring each row by its profile position rather than
+    /// by a residue of A.
+    ///
+    /// The parameters' sequence A stands in for the profile in the traceback, so it must
+    /// have one residue per position; the profile's consensus is the natural choice. A
+    /// profile of sequence A alone gives the same grid as [`Self::populate_score_matrices`].
+    pub fn populate_from_profile<S: Residue>(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<T, S>,
+        profile: &FrequencyProfile<T, S>,
+    ) -> Result<(), AlignmentError> {
+        let len_a = alignment_parameters.sequences.len_a();
+        if profile.len() != len_a {
+            return Err(AlignmentError::InvalidInput(format!(
+                "profile has {} positions but sequence A has {} residues",
+                profile.len(),
+                len_a
+            )));
+        }
+        let source = ScoringSource::Profile(profile);
+        self.fill(alignment_parameters, &source, cfg!(debug_assertions), None)
+    }
+
+    /// Fill the grid row by row, handing each cell to `tracer` once it is computed
+    fn fill<S: Residue>(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<T, S>,
+        source: &ScoringSource<T, S>,
+        check_scores: bool,
+        tracer: Option<&mut StepTracer<T>>,
+    ) -> Result<(), AlignmentError> {
+        let sequences = &alignment_parameters.sequences;
+        self.reset(sequences.len_a(), sequences.len_b())?;
+        self.init_borders(alignment_parameters)?;
+        self.fill_cells(alignment_parameters, source, check_scores, tracer)
+    }
+
+    /// Fill every cell past the boundary row and column, which must already be set
+    fn fill_cells<S: Residue>(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<T, S>,
+        source: &ScoringSource<T, S>,
+        check_scores: bool,
+        mut tracer: Option<&mut StepTracer<T>>,
+    ) -> Result<(), AlignmentError> {
         let sequences = &alignment_parameters.sequences;
-        let match_matrix = &alignment_parameters.match_matrix;
         let (rows, columns) = (sequences.len_a(), sequences.len_b());
+        for r in 1..=rows {
+            for c in 1..=columns {
+                let m = self.update_m(alignment_parameters, source, r, c);
+                let ix = (r > 1).then(|| self.update_ix(alignment_parameters, r, c));
+                let iy = (c > 1).then(|| self.update_iy(alignment_parameters, r, c));
+                if check_scores {
+                    check_score(M, r, c, Some(m))?;
+                    check_score(Ix, r, c, ix)?;
+                    check_score(Iy, r, c, iy)?;
+                }
+                if let Some(tracer) = tracer.as_deref_mut() {
+                    tracer.record_cell(self, alignment_parameters, source, r, c);
+                }
+            }
+        }
+        Ok(())
+    }
 
-        self.m_matrix = ScoreMatrix::new(MatrixType::M, rows, columns);
-        self.ix_matrix = ScoreMatrix::new(MatrixType::Ix, rows, columns);
-        self.iy_matrix = ScoreMatrix::new(MatrixType::Iy, rows, columns);
+    /// Clear the grid for sequences of `rows` and `columns` residues, as [`Self::new`]
+    /// lays it out, keeping it on disk if it was there
+    fn reset(&mut self, rows: usize, columns: usize) -> io::Result<()> {
+        for matrix in [&mut self.m_matrix, &mut self.ix_matrix, &mut self.iy_matrix] {
+            matrix.reset(rows + 1, columns + 1)?;
+        }
+        Ok(())
+    }
 
-        let seq_a_chars = &sequences.seq_a;
-        let seq_b_chars = &sequences.seq_b;
+    /// Set the boundary row and column to minus the leading gap charged before each
+    /// residue, at the penalties for the ends of that sequence, and the first row of Ix and
+    /// first column of Iy to the cost of opening a gap there after the other sequence's
+    /// leading gap. That gap is internal to the sequence it is in, so it is charged even
+    /// when both sequences have free ends, unless it runs to the end of the other one.
+    /// Everything stays zero for local alignments. Fails for an end-gap bonus the
+    /// parameters cannot take.
+    fn init_borders<S: Residue>(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<T, S>,
+    ) -> Result<(), AlignmentError> {
         let is_global = alignment_parameters.global_alignment;
+        let gaps = &alignment_parameters.gap_penalties;
+        gaps.check_end_gap_bonus(is_global)?;
+        let (rows, columns) = (self.m_matrix.nrow - 1, self.m_matrix.ncol - 1);
+        let sequences = &alignment_parameters.sequences;
+        let (nrow, ncol) = (sequences.orig_len_a() + 1, sequences.orig_len_b() + 1);
+        // Residues of A before the first aligned one are charged as a gap in B, and those
+        // of B as a gap in A, unless that sequence's ends are free
+        let a_ends = gaps.a_end_penalties().filter(|_| is_global);
+        let b_ends = gaps.b_end_penalties().filter(|_| is_global);
 
-        // Initialize first column
-        for r in 0..rows {
-            let score = match_matrix.get_score(seq_a_chars[r], seq_b_chars[0]);
-            let score = if !is_global {
-                clamp_to_zero(score)
-            } else {
-                score
-            };
-            self.m_matrix.set_score(r, 0, score);
-            if r > 0 {
-                self.update_ix(&alignment_parameters, r, 0);
+        let mut leading_gap = T::zero();
+        for r in 0..=rows {
+            self.set_border(r, 0, T::zero() - leading_gap);
+            if let Some((open, extend)) = a_ends {
+                leading_gap = leading_gap + if r == 0 { open } else { extend };
             }
-        }
-
-        // Initialize first row
-        for c in 0..columns {
-            let score = match_matrix.get_score(seq_a_chars[0], seq_b_chars[c]);
-            let score = if !is_global {
-                clamp_to_zero(score)
-            } else {
-                score
-            };
-            self.m_matrix.set_score(0, c, score);
-            if c > 0 {
-                self.update_iy(&alignment_parameters, 0, c);
+            if r == rows {
+                break;
+            }
+            if is_global {
+                // b_0 in a gap in A after a_0..=a_r, themselves a gap in B
+                let (open, _) = gaps.x_penalties(r + 1, nrow);
+                self.iy_matrix
+                    .set_score(r + 1, 1, T::zero() - leading_gap - open);
             }
         }
 
-        // Fill the rest of the matrix
-        for r in 1..rows {
-            for c in 1..columns {
-                self.update(&alignment_parameters, r, c);
+        let mut leading_gap = T::zero();
+        for c in 0..=columns {
+            self.set_border(0, c, T::zero() - leading_gap);
+            if let Some((open, extend)) = b_ends {
+                leading_gap = leading_gap + if c == 0 { open } else { extend };
+            }
+            if c == columns {
+                break;
+            }
+            if is_global {
+                // a_0 in a gap in B after b_0..=b_c, themselves a gap in A
+                let (open, _) = gaps.y_penalties(c + 1, ncol);
+                self.ix_matrix
+                    .set_score(1, c + 1, T::zero() - leading_gap - open);
             }
         }
         Ok(())
     }
 
-    /// Update all matrices at a given position
-    fn update(&mut self, alignment_parameters: &AlignmentParameters<T>, row: usize, col: usize) {
-        self.update_m(alignment_parameters, row, col);
-        self.update_ix(alignment_parameters, row, col);
-        self.update_iy(alignment_parameters, row, col);
+    /// Give a boundary cell the same score in all three matrices, so that the diagonal
+    /// step out of it takes that score whichever matrix it comes from
+    fn set_border(&mut self, row: usize, col: usize, score: T) {
+        self.m_matrix.set_score(row, col, score);
+        self.ix_matrix.set_score(row, col, score);
+        self.iy_matrix.set_score(row, col, score);
+    }
+
+    /// Fill the grid as [`Self::populate_score_matrices`] does, recording every cell in
+    /// `tracer` as it is computed
+    pub fn populate_score_matrices_traced<S: Residue>(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<T, S>,
+        tracer: &mut StepTracer<T>,
+    ) -> Result<(), Box<dyn Error>> {
+        let source = ScoringSource::Matrix(&alignment_parameters.match_matrix);
+        let (rows, columns) = (
+            alignment_parameters.sequences.len_a(),
+            alignment_parameters.sequences.len_b(),
+        );
+        tracer.updates.reserve(3 * rows * columns);
+        self.fill(
+            alignment_parameters,
+            &source,
+            cfg!(debug_assertions),
+            Some(tracer),
+        )?;
+        Ok(())
+    }
+
+    /// Populate the score matrices for semi-global alignment of all of A against part of
+    /// B, as when placing a query within a longer subject sequence.
+    ///
+    /// B's residues before and after the aligned part cost nothing, while gaps at either end
+    /// of A are charged. The first row is left at zero, so the alignment may start past
+    /// any prefix of B for free, and a gap in A in the last row, after all of A, opens and
+    /// extends for nothing, so it may end before any suffix of B. Residues of A left before
+    /// the first aligned column are charged as a gap through the boundary column, and
+    /// reported through the alignment's start rather than as gap columns. The parameters
+    /// must come from [`AlignmentParameters::set_semi_global`], which the traceback relies
+    /// on to start anywhere in the last row. The grid is that of
+    /// [`Self::populate_score_matrices`] for those parameters.
+    pub fn populate_semi_global_free_b_end<S: Residue>(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<T, S>,
+    ) -> Result<(), Box<dyn Error>> {
+        if !alignment_parameters.is_semi_global() {
+            return Err(AlignmentError::IncompatibleParameters(
+                "semi-global alignment needs parameters from set_semi_global".to_string(),
+            )
+            .into());
+        }
+        let gaps = &alignment_parameters.gap_penalties;
+        gaps.check_end_gap_bonus(true)?;
+        let sequences = &alignment_parameters.sequences;
+        let (rows, columns) = (sequences.len_a(), sequences.len_b());
+        let last_row = sequences.orig_len_a();
+        self.reset(rows, columns)?;
+
+        // The first row stays zero: residues of B before the aligned part are free
+        let (open, extend) = gaps.a_end_penalties().unwrap_or((T::zero(), T::zero()));
+        let mut leading_gap = T::zero();
+        for r in 1..=rows {
+            // a_0..a_r-1 charged as a gap in B, then b_0 in a gap in A, which is free only
+            // after all of A
+            leading_gap = leading_gap + if r == 1 { open } else { extend };
+            self.set_border(r, 0, T::zero() - leading_gap);
+            let gap_open = if r == last_row { T::zero() } else { gaps.dx };
+            self.iy_matrix
+                .set_score(r, 1, T::zero() - leading_gap - gap_open);
+        }
+        for c in 1..=columns {
+            // a_0 in a gap in B after the free b_0..b_c-1
+            let (open, _) = gaps.y_penalties(c, sequences.orig_len_b() + 1);
+            self.ix_matrix.set_score(1, c, T::zero() - open);
+        }
+
+        let source = ScoringSource::Matrix(&alignment_parameters.match_matrix);
+        self.fill_cells(alignment_parameters, &source, cfg!(debug_assertions), None)?;
+        Ok(())
     }
 
     /// Update M matrix at position
-    fn update_m(&mut self, alignment_parameters: &AlignmentParameters<T>, row: usize, col: usize) {
+    fn update_m<S: Residue>(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<T, S>,
+        source: &ScoringSource<T, S>,
+        row: usize,
+        col: usize,
+    ) -> T {
         let sequences = &alignment_parameters.sequences;
-        let seq_a_chars = &sequences.seq_a;
-        let seq_b_chars = &sequences.seq_b;
-        let score = alignment_parameters
-            .match_matrix
-            .get_score(seq_a_chars[row], seq_b_chars[col]);
+        let score = if sequences.is_padding(row - 1, col - 1) {
+            T::zero()
+        } else {
+            source.score(
+                row - 1,
+                &sequences.seq_a[row - 1],
+                &sequences.seq_b[col - 1],
+            )
+        };
 
         let m = self.m_matrix.get_score(row - 1, col - 1);
         let ix = self.ix_matrix.get_score(row - 1, col - 1);
@@ -130,18 +420,41 @@ impl<T: Copy + Display + Epsilon + FromStr + PartialEq + PartialOrd + Sub<Output
             }
         }
 
+        // The boundary ends every path, so nothing points into it
+        if row == 1 || col == 1 {
+            pointers.clear();
+        }
+        #[cfg(feature = "trace")]
+        trace_update(
+            M,
+            row,
+            col,
+            &[
+                (M, m, m + score),
+                (Ix, ix, ix + score),
+                (Iy, iy, iy + score),
+            ],
+            new_score,
+            &pointers,
+        );
         self.m_matrix.set_score(row, col, new_score);
         self.m_matrix.set_pointers(row, col, pointers);
+        new_score
     }
 
     /// Update Ix matrix at position
-    fn update_ix(&mut self, alignment_parameters: &AlignmentParameters<T>, row: usize, col: usize) {
+    fn update_ix<S: Residue>(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<T, S>,
+        row: usize,
+        col: usize,
+    ) -> T {
         let mut pointers = Vec::new();
-        let new_score;
+        let (new_score, m, ix);
 
         if !alignment_parameters.global_alignment {
-            let m = self.m_matrix.get_score(row - 1, col) - alignment_parameters.gap_penalties.dy;
-            let ix = self.ix_matrix.get_score(row - 1, col) - alignment_parameters.gap_penalties.ey;
+            m = self.m_matrix.get_score(row - 1, col) - alignment_parameters.gap_penalties.dy;
+            ix = self.ix_matrix.get_score(row - 1, col) - alignment_parameters.gap_penalties.ey;
             new_score = clamp_to_zero(max(m, ix));
 
             if new_score > T::epsilon() {
@@ -153,20 +466,13 @@ impl<T: Copy + Display + Epsilon + FromStr + PartialEq + PartialOrd + Sub<Output
                 }
             }
         } else {
-            let ncol = self.m_matrix.ncol;
-            let dy = if col < ncol - 1 {
-                alignment_parameters.gap_penalties.dy
-            } else {
-                T::zero()
-            };
-            let ey = if col < ncol - 1 {
-                alignment_parameters.gap_penalties.ey
-            } else {
-                T::zero()
-            };
+            // The end of B is that of its residues, before any padding
+            let (dy, ey) = alignment_parameters
+                .gap_penalties
+                .y_penalties(col, alignment_parameters.sequences.orig_len_b() + 1);
 
-            let m = self.m_matrix.get_score(row - 1, col) - dy;
-            let ix = self.ix_matrix.get_score(row - 1, col) - ey;
+            m = self.m_matrix.get_score(row - 1, col) - dy;
+            ix = self.ix_matrix.get_score(row - 1, col) - ey;
             new_score = max(m, ix);
 
             if T::fuzzy_equals(new_score, m) {
@@ -177,18 +483,37 @@ impl<T: Copy + Display + Epsilon + FromStr + PartialEq + PartialOrd + Sub<Output
             }
         }
 
+        #[cfg(feature = "trace")]
+        trace_update(
+            Ix,
+            row,
+            col,
+            &[
+                (M, self.m_matrix.get_score(row - 1, col), m),
+                (Ix, self.ix_matrix.get_score(row - 1, col), ix),
+            ],
+            new_score,
+            &pointers,
+        );
+
         self.ix_matrix.set_score(row, col, new_score);
         self.ix_matrix.set_pointers(row, col, pointers);
+        new_score
     }
 
     /// Update Iy matrix at position
-    fn update_iy(&mut self, alignment_parameters: &AlignmentParameters<T>, row: usize, col: usize) {
+    fn update_iy<S: Residue>(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<T, S>,
+        row: usize,
+        col: usize,
+    ) -> T {
         let mut pointers = Vec::new();
-        let new_score;
+        let (new_score, m, iy);
 
         if !alignment_parameters.global_alignment {
-            let m = self.m_matrix.get_score(row, col - 1) - alignment_parameters.gap_penalties.dx;
-            let iy = self.iy_matrix.get_score(row, col - 1) - alignment_parameters.gap_penalties.ex;
+            m = self.m_matrix.get_score(row, col - 1) - alignment_parameters.gap_penalties.dx;
+            iy = self.iy_matrix.get_score(row, col - 1) - alignment_parameters.gap_penalties.ex;
             new_score = clamp_to_zero(max(m, iy));
 
             if new_score > T::epsilon() {
@@ -200,20 +525,12 @@ impl<T: Copy + Display + Epsilon + FromStr + PartialEq + PartialOrd + Sub<Output
                 }
             }
         } else {
-            let nrow = self.m_matrix.nrow;
-            let dx = if row < nrow - 1 {
-                alignment_parameters.gap_penalties.dx
-            } else {
-                T::zero()
-            };
-            let ex = if row < nrow - 1 {
-                alignment_parameters.gap_penalties.ex
-            } else {
-                T::zero()
-            };
+            let (dx, ex) = alignment_parameters
+                .gap_penalties
+                .x_penalties(row, alignment_parameters.sequences.orig_len_a() + 1);
 
-            let m = self.m_matrix.get_score(row, col - 1) - dx;
-            let iy = self.iy_matrix.get_score(row, col - 1) - ex;
+            m = self.m_matrix.get_score(row, col - 1) - dx;
+            iy = self.iy_matrix.get_score(row, col - 1) - ex;
             new_score = max(m, iy);
 
             if T::fuzzy_equals(new_score, m) {
@@ -224,7 +541,572 @@ impl<T: Copy + Display + Epsilon + FromStr + PartialEq + PartialOrd + Sub<Output
             }
         }
 
+        #[cfg(feature = "trace")]
+        trace_update(
+            Iy,
+            row,
+            col,
+            &[
+                (M, self.m_matrix.get_score(row, col - 1), m),
+                (Iy, self.iy_matrix.get_score(row, col - 1), iy),
+            ],
+            new_score,
+            &pointers,
+        );
+
         self.iy_matrix.set_score(row, col, new_score);
         self.iy_matrix.set_pointers(row, col, pointers);
+        new_score
+    }
+}
+
+/// Log one cell update of the fill at trace level: each candidate as the score of the
+/// cell it steps from and its value after the step, and whether it ties the chosen score
+/// within epsilon, then the pointers kept. A local alignment also drops pointers from
+/// cells not above epsilon, which is logged alongside.
+#[cfg(feature = "trace")]
+fn trace_update<T: Copy + Display + Epsilon>(
+    matrix: MatrixType,
+    row: usize,
+    col: usize,
+    candidates: &[(MatrixType, T, T)],
+    new_score: T,
+    pointers: &[Pointer],
+) {
+    use std::fmt::Write;
+
+    let mut line = format!(
+        "fill {}[{},{}] = {} (eps {}) from",
+        matrix.as_str(),
+        row,
+        col,
+        new_score,
+        T::epsilon()
+    );
+    for (i, &(from, previous, candidate)) in candidates.iter().enumerate() {
+        let _ = write!(
+            line,
+            "{} {} {} -> {}{}",
+            if i == 0 { "" } else { "," },
+            from.as_str(),
+            previous,
+            candidate,
+            if T::fuzzy_equals(new_score, candidate) {
+                " tie"
+            } else {
+                ""
+            }
+        );
+    }
+    let _ = write!(line, "; pointers {:?}", pointers);
+    log::trace!("{}", line);
+}
+
+impl AlignGrid<f64> {
+    /// Populate the score matrices as [`Self::populate_score_matrices`] does, allowing
+    /// only the gaps `constraints` allows.
+    ///
+    /// A gap cell the constraints rule out scores minus infinity, zero in a local
+    /// alignment, and has no pointers, so no path enters or extends a gap through it.
+    /// Each gap cell keeps the length of the longest gap its pointers lead back through,
+    /// and may only extend the gap above it while that is shorter than the maximum. As
+    /// only one gap is kept per cell, a shorter gap that scores less there is not taken
+    /// up once the best one reaches the maximum, so an alignment that could only keep to
+    /// the limit through such a gap is missed. Without binding constraints the grid is
+    /// the same as the unconstrained one.
+    pub fn populate_constrained<S: Residue>(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<f64, S>,
+        constraints: &GapConstraints,
+    ) -> Result<(), AlignmentError> {
+        let sequences = &alignment_parameters.sequences;
+        let (rows, columns) = (sequences.len_a(), sequences.len_b());
+        let (nrow, ncol) = (sequences.orig_len_a() + 1, sequences.orig_len_b() + 1);
+        constraints.check_region(sequences.orig_len_a())?;
+
+        self.reset(rows, columns)?;
+        self.init_borders(alignment_parameters)?;
+        let blocked = if alignment_parameters.global_alignment {
+            f64::NEG_INFINITY
+        } else {
+            0.0
+        };
+        // Length of the longest gap leading into each cell of Ix and of Iy. Free end gaps
+        // are neither limited nor counted.
+        let source = ScoringSource::Matrix(&alignment_parameters.match_matrix);
+        let gaps = &alignment_parameters.gap_penalties;
+        let global = alignment_parameters.global_alignment;
+        let mut ix_lengths = Array2::<usize>::zeros((rows + 1, columns + 1));
+        let mut iy_lengths = Array2::<usize>::zeros((rows + 1, columns + 1));
+        for c in 1..=columns {
+            if global && gaps.is_free_a_end(c, ncol) {
+                continue;
+            }
+            if constraints.allows_gap_in_b(1) {
+                ix_lengths[[1, c]] = 1;
+            } else {
+                self.ix_matrix.set_score(1, c, blocked);
+            }
+        }
+        for r in 1..=rows {
+            if global && gaps.is_free_b_end(r, nrow) {
+                continue;
+            }
+            if constraints.allows_gap_in_a(r) {
+                iy_lengths[[r, 1]] = 1;
+            } else {
+                self.iy_matrix.set_score(r, 1, blocked);
+            }
+        }
+
+        for r in 1..=rows {
+            for c in 1..=columns {
+                self.update_m(alignment_parameters, &source, r, c);
+                if r > 1 {
+                    self.update_constrained_gap(
+                        alignment_parameters,
+                        constraints,
+                        Ix,
+                        r,
+                        c,
+                        &mut ix_lengths,
+                    );
+                }
+                if c > 1 {
+                    self.update_constrained_gap(
+                        alignment_parameters,
+                        constraints,
+                        Iy,
+                        r,
+                        c,
+                        &mut iy_lengths,
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Update Ix or Iy at position under `constraints`, recording the length of the
+    /// longest gap into the cell in `lengths`. Free end gaps are neither limited nor
+    /// counted.
+    fn update_constrained_gap<S: Residue>(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<f64, S>,
+        constraints: &GapConstraints,
+        matrix: MatrixType,
+        row: usize,
+        col: usize,
+        lengths: &mut Array2<usize>,
+    ) {
+        let local = !alignment_parameters.global_alignment;
+        let gaps = &alignment_parameters.gap_penalties;
+        let sequences = &alignment_parameters.sequences;
+        let (nrow, ncol) = (sequences.orig_len_a() + 1, sequences.orig_len_b() + 1);
+        let (from_row, from_col, allowed, (open, extend), free) = match matrix {
+            Ix => (
+                row - 1,
+                col,
+                constraints.allows_gap_in_b(row),
+                if local {
+                    (gaps.dy, gaps.ey)
+                } else {
+                    gaps.y_penalties(col, ncol)
+                },
+                !local && gaps.is_free_a_end(col, ncol),
+            ),
+            Iy => (
+                row,
+                col - 1,
+                constraints.allows_gap_in_a(row),
+                if local {
+                    (gaps.dx, gaps.ex)
+                } else {
+                    gaps.x_penalties(row, nrow)
+                },
+                !local && gaps.is_free_b_end(row, nrow),
+            ),
+            M => unreachable!("M has no gap constraints"),
+        };
+        let allowed = allowed || free;
+        let gap_matrix = if matrix == Ix {
+            &mut self.ix_matrix
+        } else {
+            &mut self.iy_matrix
+        };
+
+        let from_length = lengths[[from_row, from_col]];
+        let opened = self.m_matrix.get_score(from_row, from_col) - open;
+        let extended = if free || constraints.allows_extension(from_length) {
+            gap_matrix.get_score(from_row, from_col) - extend
+        } else {
+            f64::NEG_INFINITY
+        };
+        let mut new_score = if allowed {
+            opened.max(extended)
+        } else {
+            f64::NEG_INFINITY
+        };
+        if local {
+            new_score = clamp_to_zero(new_score);
+        }
+
+        let attains =
+            |score: f64| f64::fuzzy_equals(new_score, score) && (!local || score > f64::epsilon());
+        let mut pointers = Vec::new();
+        let mut length = 0;
+        if allowed && (!local || new_score > f64::epsilon()) {
+            if attains(opened) {
+                pointers.push((M, from_row, from_col));
+                length = 1;
+            }
+            if attains(extended) {
+                pointers.push((matrix, from_row, from_col));
+                length = from_length + 1;
+            }
+        }
+        gap_matrix.set_score(row, col, new_score);
+        gap_matrix.set_pointers(row, col, pointers);
+        lengths[[row, col]] = if free { 0 } else { length };
+    }
+
+    /// Populate the score matrices as [`Self::populate_score_matrices`] does, charging
+    /// internal gaps with `codon_gaps` rather than the parameters' gap penalties.
+    ///
+    /// A gap opens in frame when it starts on a codon boundary of A, as placed by the
+    /// parameters' `frame_offset`: a gap in B before residue `r` of A, counted from 0, or
+    /// a gap in A after it, when `r + frame_offset` or `r + 1 + frame_offset` is a
+    /// multiple of 3. The ends of a global alignment are charged as the parameters' gap
+    /// penalties say, except that a gap opening after a leading gap takes the codon-aware
+    /// open penalty unless it runs to the end of the other sequence. With equal open
+    /// penalties the grid is that of
+    /// [`Self::populate_score_matrices`] with gap penalties of that open and `extend`.
+    pub fn populate_codon_aware<S: Residue>(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<f64, S>,
+        codon_gaps: &CodonAwareGapPenalties,
+    ) -> Result<(), AlignmentError> {
+        self.fill_codon_aware(alignment_parameters, codon_gaps, cfg!(debug_assertions))
+    }
+
+    /// Populate the score matrices as [`Self::populate_codon_aware`] does, failing at the
+    /// first cell whose score is NaN or infinite, as
+    /// [`Self::populate_score_matrices_checked`] does for the plain fill
+    pub fn populate_codon_aware_checked<S: Residue>(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<f64, S>,
+        codon_gaps: &CodonAwareGapPenalties,
+    ) -> Result<(), AlignmentError> {
+        self.fill_codon_aware(alignment_parameters, codon_gaps, true)
+    }
+
+    fn fill_codon_aware<S: Residue>(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<f64, S>,
+        codon_gaps: &CodonAwareGapPenalties,
+        check_scores: bool,
+    ) -> Result<(), AlignmentError> {
+        let source = ScoringSource::Matrix(&alignment_parameters.match_matrix);
+        let frame_offset = alignment_parameters.frame_offset;
+        if frame_offset > 2 {
+            return Err(AlignmentError::InvalidInput(format!(
+                "frame offset {} is not 0, 1 or 2",
+                frame_offset
+            )));
+        }
+        let sequences = &alignment_parameters.sequences;
+        let (rows, columns) = (sequences.len_a(), sequences.len_b());
+
+        self.reset(rows, columns)?;
+        self.init_borders(alignment_parameters)?;
+        if alignment_parameters.global_alignment {
+            // Reopen the first gap after each leading gap at the codon-aware penalty,
+            // unless it runs to the end of the other sequence
+            for c in 1..sequences.orig_len_b() {
+                let open = codon_gaps.open_after(0, frame_offset);
+                self.ix_matrix
+                    .set_score(1, c, self.m_matrix.get_score(0, c) - open);
+            }
+            for r in 1..sequences.orig_len_a() {
+                let open = codon_gaps.open_after(r, frame_offset);
+                self.iy_matrix
+                    .set_score(r, 1, self.m_matrix.get_score(r, 0) - open);
+            }
+        }
+
+        for r in 1..=rows {
+            for c in 1..=columns {
+                let m = self.update_m(alignment_parameters, &source, r, c);
+                let ix = (r > 1)
+                    .then(|| self.update_codon_gap(alignment_parameters, codon_gaps, Ix, r, c));
+                let iy = (c > 1)
+                    .then(|| self.update_codon_gap(alignment_parameters, codon_gaps, Iy, r, c));
+                if check_scores {
+                    check_score(M, r, c, Some(m))?;
+                    check_score(Ix, r, c, ix)?;
+                    check_score(Iy, r, c, iy)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Update Ix or Iy at position with the codon-aware penalties, or with the penalties
+    /// for the ends of the sequences in the last column or row of a global alignment
+    fn update_codon_gap<S: Residue>(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<f64, S>,
+        codon_gaps: &CodonAwareGapPenalties,
+        matrix: MatrixType,
+        row: usize,
+        col: usize,
+    ) -> f64 {
+        let local = !alignment_parameters.global_alignment;
+        let gaps = &alignment_parameters.gap_penalties;
+        let frame_offset = alignment_parameters.frame_offset;
+        // The ends of the sequences are those of their residues, before any padding
+        let sequences = &alignment_parameters.sequences;
+        let (nrow, ncol) = (sequences.orig_len_a() + 1, sequences.orig_len_b() + 1);
+        let (from_row, from_col, (open, extend)) = match matrix {
+            Ix if !local && col + 1 == ncol => (row - 1, col, gaps.y_penalties(col, ncol)),
+            Ix => (
+                row - 1,
+                col,
+                (
+                    codon_gaps.open_after(row - 1, frame_offset),
+                    codon_gaps.extend,
+                ),
+            ),
+            Iy if !local && row + 1 == nrow => (row, col - 1, gaps.x_penalties(row, nrow)),
+            Iy => (
+                row,
+                col - 1,
+                (codon_gaps.open_after(row, frame_offset), codon_gaps.extend),
+            ),
+            M => unreachable!("M has no gap penalties"),
+        };
+        let gap_matrix = if matrix == Ix {
+            &mut self.ix_matrix
+        } else {
+            &mut self.iy_matrix
+        };
+
+        let opened = self.m_matrix.get_score(from_row, from_col) - open;
+        let extended = gap_matrix.get_score(from_row, from_col) - extend;
+        let mut new_score = opened.max(extended);
+        if local {
+            new_score = clamp_to_zero(new_score);
+        }
+
+        let attains =
+            |score: f64| f64::fuzzy_equals(new_score, score) && (!local || score > f64::epsilon());
+        let mut pointers = Vec::new();
+        if !local || new_score > f64::epsilon() {
+            if attains(opened) {
+                pointers.push((M, from_row, from_col));
+            }
+            if attains(extended) {
+                pointers.push((matrix, from_row, from_col));
+            }
+        }
+        gap_matrix.set_score(row, col, new_score);
+        gap_matrix.set_pointers(row, col, pointers);
+        new_score
+    }
+}
+
+/// Fail if a score just computed at `matrix[row, col]` is NaN or infinite
+fn check_score<T: Copy + Display + Epsilon>(
+    matrix: MatrixType,
+    row: usize,
+    col: usize,
+    score: Option<T>,
+) -> Result<(), AlignmentError> {
+    match score {
+        Some(score) if !T::is_finite(score) => Err(AlignmentError::InvalidInput(format!(
+            "{} score at {}[{},{}]; check the match scores and gap penalties for NaN or \
+             infinite values",
+            score,
+            matrix.as_str(),
+            row,
+            col
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Numerically stable `ln(exp(a) + exp(b))`
+pub fn log_sum_exp(a: f64, b: f64) -> f64 {
+    let (hi, lo) = if a >= b { (a, b) } else { (b, a) };
+    if hi == f64::NEG_INFINITY {
+        hi
+    } else {
+        hi + (lo - hi).exp().ln_1p()
+    }
+}
+
+/// How log-space scores from different predecessors are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogSpaceMode {
+    /// Strict maximum: the log-probability of the best alignment, with traceback
+    #[default]
+    Max,
+    /// Soft maximum via [`log_sum_exp`]: the log-probability summed over all alignments.
+    /// No traceback pointers are recorded.
+    SumExp,
+}
+
+/// Global alignment grid over log-probabilities.
+///
+/// Expects a match matrix from [`MatchMatrix::to_log_probabilities`] and gap costs from
+/// [`GapPenalties::to_log_costs`], so that sums of scores are products of probabilities
+/// and long sequences do not underflow.
+///
+/// [`MatchMatrix::to_log_probabilities`]: crate::models::MatchMatrix::to_log_probabilities
+/// [`GapPenalties::to_log_costs`]: crate::models::GapPenalties::to_log_costs
+pub struct LogSpaceAlignGrid {
+    grid: AlignGrid<f64>,
+    mode: LogSpaceMode,
+    /// Cell after the last residues of both sequences, short of any padding
+    end: (usize, usize),
+}
+
+impl LogSpaceAlignGrid {
+    pub fn new(rows: usize, columns: usize, mode: LogSpaceMode) -> Self {
+        Self {
+            grid: AlignGrid::new(rows, columns),
+            mode,
+            end: (rows, columns),
+        }
+    }
+
+    /// Underlying grid, for traceback in [`LogSpaceMode::Max`]
+    pub fn grid(&self) -> &AlignGrid<f64> {
+        &self.grid
+    }
+
+    pub fn into_grid(self) -> AlignGrid<f64> {
+        self.grid
+    }
+
+    pub fn populate_score_matrices<S: Residue>(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<f64, S>,
+    ) -> Result<(), Box<dyn Error>> {
+        Self::check_parameters(alignment_parameters)?;
+        self.populate(alignment_parameters);
+        Ok(())
+    }
+
+    /// Fail for parameters log-space scoring does not support: local alignment
+    pub fn check_parameters<S: Residue>(
+        alignment_parameters: &AlignmentParameters<f64, S>,
+    ) -> Result<(), AlignmentError> {
+        if alignment_parameters.global_alignment {
+            Ok(())
+        } else {
+            Err(AlignmentError::IncompatibleParameters(
+                "log-space scoring is only supported for global alignments".to_string(),
+            ))
+        }
+    }
+
+    /// Combine predecessor scores, returning the combined score and which ones attain it
+    fn combine(&self, scores: &[(f64, Pointer)]) -> (f64, Vec<Pointer>) {
+        let best = scores
+            .iter()
+            .map(|&(score, _)| score)
+            .fold(f64::NEG_INFINITY, f64::max);
+        match self.mode {
+            LogSpaceMode::Max => {
+                let pointers = scores
+                    .iter()
+                    .filter(|&&(score, _)| {
+                        best > f64::NEG_INFINITY && f64::fuzzy_equals(score, best)
+                    })
+                    .map(|&(_, pointer)| pointer)
+                    .collect();
+                (best, pointers)
+            }
+            LogSpaceMode::SumExp => (
+                scores.iter().fold(f64::NEG_INFINITY, |total, &(score, _)| {
+                    log_sum_exp(total, score)
+                }),
+                Vec::new(),
+            ),
+        }
+    }
+
+    /// Fill all three matrices. Cells that no alignment can reach hold negative infinity
+    /// (probability zero) rather than the zero used by [`AlignGrid`]. A padded residue
+    /// scores 0, probability one, against everything.
+    fn populate<S: Residue>(&mut self, alignment_parameters: &AlignmentParameters<f64, S>) {
+        let sequences = &alignment_parameters.sequences;
+        let match_matrix = &alignment_parameters.match_matrix;
+        let gap_penalties = &alignment_parameters.gap_penalties;
+        let (rows, columns) = (sequences.len_a(), sequences.len_b());
+        let end = (sequences.orig_len_a(), sequences.orig_len_b());
+
+        let shape = (rows + 1, columns + 1);
+        let mut grid = AlignGrid::from_arrays(
+            Array2::from_elem(shape, f64::NEG_INFINITY),
+            Array2::from_elem(shape, f64::NEG_INFINITY),
+            Array2::from_elem(shape, f64::NEG_INFINITY),
+        );
+
+        for (r, a) in (1..).zip(&sequences.seq_a) {
+            for (c, b) in (1..).zip(&sequences.seq_b) {
+                let score = if sequences.is_padding(r - 1, c - 1) {
+                    0.0
+                } else {
+                    match_matrix.get_score(a, b)
+                };
+                if r == 1 || c == 1 {
+                    grid.m_matrix.set_score(r, c, score);
+                } else {
+                    let (total, pointers) = self.combine(&[
+                        (grid.m_matrix.get_score(r - 1, c - 1), (M, r - 1, c - 1)),
+                        (grid.ix_matrix.get_score(r - 1, c - 1), (Ix, r - 1, c - 1)),
+                        (grid.iy_matrix.get_score(r - 1, c - 1), (Iy, r - 1, c - 1)),
+                    ]);
+                    grid.m_matrix.set_score(r, c, total + score);
+                    grid.m_matrix.set_pointers(r, c, pointers);
+                }
+                if r > 1 {
+                    let (dy, ey) = gap_penalties.y_penalties(c, end.1 + 1);
+                    let (total, pointers) = self.combine(&[
+                        (grid.m_matrix.get_score(r - 1, c) - dy, (M, r - 1, c)),
+                        (grid.ix_matrix.get_score(r - 1, c) - ey, (Ix, r - 1, c)),
+                    ]);
+                    grid.ix_matrix.set_score(r, c, total);
+                    grid.ix_matrix.set_pointers(r, c, pointers);
+                }
+                if c > 1 {
+                    let (dx, ex) = gap_penalties.x_penalties(r, end.0 + 1);
+                    let (total, pointers) = self.combine(&[
+                        (grid.m_matrix.get_score(r, c - 1) - dx, (M, r, c - 1)),
+                        (grid.iy_matrix.get_score(r, c - 1) - ex, (Iy, r, c - 1)),
+                    ]);
+                    grid.iy_matrix.set_score(r, c, total);
+                    grid.iy_matrix.set_pointers(r, c, pointers);
+                }
+            }
+        }
+        self.grid = grid;
+        self.end = end;
+    }
+
+    /// Final score: the best (`Max`) or total (`SumExp`) log-probability
+    pub fn score(&self) -> f64 {
+        let (row, col) = self.end;
+        let (m_end, ix_end, iy_end) = (
+            self.grid.m_matrix.get_score(row, col),
+            self.grid.ix_matrix.get_score(row, col),
+            self.grid.iy_matrix.get_score(row, col),
+        );
+        match self.mode {
+            LogSpaceMode::Max => m_end.max(ix_end).max(iy_end),
+            LogSpaceMode::SumExp => log_sum_exp(log_sum_exp(m_end, ix_end), iy_end),
+        }
     }
 }
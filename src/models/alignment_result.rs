@@ -0,0 +1,565 @@
+use crate::io::diffs::write_diffs_to;
+use crate::io::format::OutputFormat;
+use crate::io::interleaved::{write_interleaved_to, DEFAULT_INTERLEAVED_WIDTH};
+use crate::models::score_matrix::{MatrixType, Pointer};
+use crate::models::{AlignedPair, Alignment, Alphabet, CoveredRange};
+use crate::stats::{DnaStats, ShuffleSignificance, Significance};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Display;
+use std::io;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+/// Score and co-optimal alignments produced by a traceback
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignmentResult<T = f64> {
+    pub score: T,
+    pub alignments: Vec<AlignedPair>,
+    /// Score of each alignment, when they do not all share `score`, as for the top local
+    /// hits of [`crate::alignment::traceback_top_hits`]
+    pub alignment_scores: Option<Vec<T>>,
+    /// Score relative to the better self-alignment score, when requested
+    pub score_ratio: Option<f64>,
+    /// Bit score and E-value shared by every alignment, for local alignments
+    pub significance: Option<Significance>,
+    /// Empirical significance against shuffles of sequence B, for local alignments
+    pub shuffle_significance: Option<ShuffleSignificance>,
+    /// Substitution spectrum of each alignment, for nucleotide alignments
+    pub dna_stats: Option<Vec<DnaStats>>,
+    /// Limits that cut the traceback short, in the order they fired
+    pub truncated: Vec<Truncation>,
+    /// Every cell attaining the optimal score that the traceback could start from,
+    /// including any `--max-starts` dropped, sorted by row, column and matrix. Empty for
+    /// results not traced back from co-optimal cells, such as the top local hits.
+    pub starts: Vec<StartInfo>,
+}
+
+/// A cell a traceback starts from, in grid coordinates, row and column 0 being the
+/// boundary: the last cell of a global alignment in whichever of M, Ix and Iy attain the
+/// optimal score, or a cell of M attaining it in a local alignment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StartInfo {
+    pub matrix: MatrixType,
+    pub row: usize,
+    pub col: usize,
+}
+
+impl From<Pointer> for StartInfo {
+    fn from((matrix, row, col): Pointer) -> Self {
+        Self { matrix, row, col }
+    }
+}
+
+/// Traceback limit set on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TracebackLimit {
+    MaxStarts,
+    MaxPathsPerStart,
+    MaxAlignments,
+}
+
+impl TracebackLimit {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TracebackLimit::MaxStarts => "max-starts",
+            TracebackLimit::MaxPathsPerStart => "max-paths-per-start",
+            TracebackLimit::MaxAlignments => "max-alignments",
+        }
+    }
+}
+
+/// A limit that dropped part of the traceback: `kept` of `total` start cells for
+/// [`TracebackLimit::MaxStarts`], of `total` alignments for the others. Totals
+/// saturate at `u128::MAX`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Truncation {
+    pub limit: TracebackLimit,
+    pub kept: u128,
+    pub total: u128,
+}
+
+impl fmt::Display for Truncation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match self.limit {
+            TracebackLimit::MaxStarts => "start cells",
+            _ => "alignments",
+        };
+        write!(
+            f,
+            "--{} kept {} of {} {}",
+            self.limit.as_str(),
+            self.kept,
+            self.total,
+            unit
+        )
+    }
+}
+
+/// One alignment as written to the TSV and JSON formats, with 1-based coordinates
+#[derive(Serialize)]
+struct AlignmentRecord<'a, T> {
+    index: usize,
+    score: T,
+    start_a: usize,
+    end_a: usize,
+    start_b: usize,
+    end_b: usize,
+    cigar: String,
+    align_a: &'a str,
+    align_b: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bit_score: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    evalue: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    z_score: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    p_value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dna_stats: Option<&'a DnaStats>,
+    /// Coordinates before trimming, for trimmed alignments
+    #[serde(skip_serializing_if = "Option::is_none")]
+    untrimmed: Option<RangeRecord>,
+}
+
+/// 1-based, inclusive coordinates of a covered range
+#[derive(Serialize)]
+struct RangeRecord {
+    start_a: usize,
+    end_a: usize,
+    start_b: usize,
+    end_b: usize,
+}
+
+impl From<CoveredRange> for RangeRecord {
+    fn from(range: CoveredRange) -> Self {
+        Self {
+            start_a: range.start_a + 1,
+            end_a: range.end_a,
+            start_b: range.start_b + 1,
+            end_b: range.end_b,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ResultRecord<'a, T> {
+    score: T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score_ratio: Option<f64>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    truncated: &'a [Truncation],
+    alignments: Vec<AlignmentRecord<'a, T>>,
+}
+
+/// The two FASTA records of the `index`th alignment (1-based) of a result
+pub(crate) fn write_fasta_alignment<W: Write, T: Display>(
+    writer: &mut W,
+    index: usize,
+    score: T,
+    alignment: &Alignment,
+) -> io::Result<()> {
+    let untrimmed = alignment.untrimmed.map(RangeRecord::from);
+    write!(
+        writer,
+        ">alignment_{}_a score={} start={} end={}",
+        index,
+        score,
+        alignment.start_a + 1,
+        alignment.end_a()
+    )?;
+    if let Some(range) = &untrimmed {
+        write!(writer, " untrimmed={}-{}", range.start_a, range.end_a)?;
+    }
+    writeln!(writer)?;
+    writeln!(writer, "{}", alignment.align_a)?;
+    write!(
+        writer,
+        ">alignment_{}_b score={} start={} end={}",
+        index,
+        score,
+        alignment.start_b + 1,
+        alignment.end_b()
+    )?;
+    if let Some(range) = &untrimmed {
+        write!(writer, " untrimmed={}-{}", range.start_b, range.end_b)?;
+    }
+    writeln!(writer)?;
+    writeln!(writer, "{}", alignment.align_b)
+}
+
+const TSV_HEADER: &str = "index\tscore\tstart_a\tend_a\tstart_b\tend_b\tcigar\talign_a\talign_b";
+
+impl<T: Copy> AlignmentResult<T> {
+    /// Score of the `index`th alignment (0-based)
+    pub fn alignment_score(&self, index: usize) -> T {
+        self.alignment_scores
+            .as_ref()
+            .and_then(|scores| scores.get(index).copied())
+            .unwrap_or(self.score)
+    }
+}
+
+impl<T: Copy + PartialOrd> AlignmentResult<T> {
+    /// Put the alignments in canonical order: by descending score when they have scores
+    /// of their own, then by the start in A, the start in B and the CIGAR string, compared
+    /// lexicographically. Their scores and DNA statistics move with them.
+    ///
+    /// The tracebacks return their alignments in this order, so the order depends only on
+    /// the alignments found, not on the platform, the number of threads or hash seeds.
+    pub fn sort_canonical(&mut self) {
+        let keys: Vec<(usize, usize, String)> = self
+            .alignments
+            .iter()
+            .map(|alignment| (alignment.start_a, alignment.start_b, alignment.cigar()))
+            .collect();
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        order.sort_by(|&i, &j| {
+            let by_score = self
+                .alignment_score(j)
+                .partial_cmp(&self.alignment_score(i))
+                .unwrap_or(Ordering::Equal);
+            by_score.then_with(|| keys[i].cmp(&keys[j]))
+        });
+        if order.iter().enumerate().all(|(position, &i)| position == i) {
+            return;
+        }
+        self.alignments = permute(std::mem::take(&mut self.alignments), &order);
+        if let Some(scores) = self.alignment_scores.take() {
+            self.alignment_scores = Some(permute(scores, &order));
+        }
+        if let Some(dna_stats) = self.dna_stats.take() {
+            self.dna_stats = Some(permute(dna_stats, &order));
+        }
+    }
+}
+
+/// `items` rearranged so that the `k`th is the `order[k]`th of `items`
+fn permute<U>(items: Vec<U>, order: &[usize]) -> Vec<U> {
+    let mut slots: Vec<Option<U>> = items.into_iter().map(Some).collect();
+    order
+        .iter()
+        .filter_map(|&i| slots.get_mut(i).and_then(Option::take))
+        .collect()
+}
+
+impl<T: Copy + Display + Serialize> AlignmentResult<T> {
+    fn records(&self) -> Vec<AlignmentRecord<'_, T>> {
+        self.alignments
+            .iter()
+            .enumerate()
+            .map(|(i, alignment)| AlignmentRecord {
+                index: i + 1,
+                score: self.alignment_score(i),
+                start_a: alignment.start_a + 1,
+                end_a: alignment.end_a(),
+                start_b: alignment.start_b + 1,
+                end_b: alignment.end_b(),
+                cigar: alignment.cigar(),
+                align_a: &alignment.align_a,
+                align_b: &alignment.align_b,
+                bit_score: self.significance.map(|s| s.bit_score),
+                evalue: self.significance.map(|s| s.evalue),
+                z_score: self.shuffle_significance.map(|s| s.z_score),
+                p_value: self.shuffle_significance.map(|s| s.p_value),
+                dna_stats: self.dna_stats.as_ref().and_then(|stats| stats.get(i)),
+                untrimmed: alignment.untrimmed.map(RangeRecord::from),
+            })
+            .collect()
+    }
+
+    /// Write the result in the requested format
+    pub fn write_to<W: Write>(&self, writer: &mut W, format: OutputFormat) -> io::Result<()> {
+        match format {
+            OutputFormat::Text => {
+                self.write_truncation_comments(writer)?;
+                if self.starts.len() > 1 {
+                    let [m, ix, iy] = self.starts_by_matrix();
+                    writeln!(
+                        writer,
+                        "# starts: {} co-optimal cells (M {}, Ix {}, Iy {})",
+                        self.starts.len(),
+                        m,
+                        ix,
+                        iy
+                    )?;
+                }
+                for (i, stats) in self.dna_stats.iter().flatten().enumerate() {
+                    writeln!(
+                        writer,
+                        "# dna-stats alignment {}: {}",
+                        i + 1,
+                        stats.summary()
+                    )?;
+                }
+                for (i, score) in self.alignment_scores.iter().flatten().enumerate() {
+                    writeln!(writer, "# score alignment {}: {}", i + 1, score)?;
+                }
+                for (i, alignment) in self.alignments.iter().enumerate() {
+                    if let Some(range) = alignment.untrimmed.map(RangeRecord::from) {
+                        writeln!(
+                            writer,
+                            "# trimmed alignment {}: A {}-{} of {}-{}, B {}-{} of {}-{}",
+                            i + 1,
+                            alignment.start_a + 1,
+                            alignment.end_a(),
+                            range.start_a,
+                            range.end_a,
+                            alignment.start_b + 1,
+                            alignment.end_b(),
+                            range.start_b,
+                            range.end_b
+                        )?;
+                    }
+                }
+                match self.score_ratio {
+                    Some(ratio) => writeln!(writer, "{} {}", self.score, ratio)?,
+                    None => writeln!(writer, "{}", self.score)?,
+                }
+                for alignment in &self.alignments {
+                    writer.write_all(b"\n")?;
+                    writer.write_all(alignment.align_a.as_bytes())?;
+                    writer.write_all(b"\n")?;
+                    writer.write_all(alignment.align_b.as_bytes())?;
+                    writer.write_all(b"\n")?;
+                }
+            }
+            OutputFormat::Fasta => {
+                for (i, alignment) in self.alignments.iter().enumerate() {
+                    write_fasta_alignment(writer, i + 1, self.alignment_score(i), alignment)?;
+                }
+            }
+            OutputFormat::Tsv => {
+                self.write_truncation_comments(writer)?;
+                write!(writer, "{}", TSV_HEADER)?;
+                if self.significance.is_some() {
+                    write!(writer, "\tbit_score\tevalue")?;
+                }
+                if self.shuffle_significance.is_some() {
+                    write!(writer, "\tz_score\tp_value")?;
+                }
+                let trimmed = self.alignments.iter().any(|a| a.untrimmed.is_some());
+                if trimmed {
+                    write!(writer, "\tuntrimmed_a\tuntrimmed_b")?;
+                }
+                writeln!(writer)?;
+                for record in self.records() {
+                    write!(
+                        writer,
+                        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                        record.index,
+                        record.score,
+                        record.start_a,
+                        record.end_a,
+                        record.start_b,
+                        record.end_b,
+                        record.cigar,
+                        record.align_a,
+                        record.align_b
+                    )?;
+                    if let (Some(bit_score), Some(evalue)) = (record.bit_score, record.evalue) {
+                        write!(writer, "\t{:.2}\t{:.3e}", bit_score, evalue)?;
+                    }
+                    if let (Some(z_score), Some(p_value)) = (record.z_score, record.p_value) {
+                        write!(writer, "\t{:.2}\t{:.3e}", z_score, p_value)?;
+                    }
+                    if trimmed {
+                        // An alignment that needed no trimming covers the same range
+                        let range = record.untrimmed.unwrap_or(RangeRecord {
+                            start_a: record.start_a,
+                            end_a: record.end_a,
+                            start_b: record.start_b,
+                            end_b: record.end_b,
+                        });
+                        write!(
+                            writer,
+                            "\t{}-{}\t{}-{}",
+                            range.start_a, range.end_a, range.start_b, range.end_b
+                        )?;
+                    }
+                    writeln!(writer)?;
+                }
+            }
+            OutputFormat::Json => {
+                let result = ResultRecord {
+                    score: self.score,
+                    score_ratio: self.score_ratio,
+                    truncated: &self.truncated,
+                    alignments: self.records(),
+                };
+                serde_json::to_writer_pretty(&mut *writer, &result)?;
+                writeln!(writer)?;
+            }
+            OutputFormat::Interleaved => {
+                write_interleaved_to(writer, self, DEFAULT_INTERLEAVED_WIDTH)?;
+            }
+            OutputFormat::Diffs => {
+                write_diffs_to(writer, self)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// One `# truncated:` header line per limit that fired
+    fn write_truncation_comments<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for truncation in &self.truncated {
+            writeln!(writer, "# truncated: {}", truncation)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Copy + Display + FromStr + Serialize> AlignmentResult<T>
+where
+    <T as FromStr>::Err: Display,
+{
+    /// Read a result previously written in the text format, its rows writing gaps as
+    /// `gap`.
+    ///
+    /// Lines starting with `#` are treated as header comments and skipped. The text
+    /// format does not record coordinates, so every alignment starts at the origin
+    /// until it is placed with [`Alignment::locate`].
+    pub fn read_from<R: BufRead>(reader: R, gap: char) -> io::Result<Self> {
+        let mut lines = reader
+            .lines()
+            .filter(|line| !matches!(line, Ok(l) if l.starts_with('#')));
+
+        let score_line = lines
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing score"))??;
+        let mut score_fields = score_line.split_whitespace();
+        let score = score_fields
+            .next()
+            .unwrap_or_default()
+            .parse::<T>()
+            .map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("Invalid score: {}", e))
+            })?;
+        let score_ratio = score_fields
+            .next()
+            .map(|field| {
+                field.parse::<f64>().map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Invalid score ratio: {}", e),
+                    )
+                })
+            })
+            .transpose()?;
+
+        let mut alignments = Vec::new();
+        let mut block: Vec<String> = Vec::with_capacity(2);
+        for line in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                Self::close_block(&mut block, &mut alignments, gap)?;
+            } else {
+                block.push(line.trim_end().to_string());
+            }
+        }
+        Self::close_block(&mut block, &mut alignments, gap)?;
+
+        Ok(Self {
+            score_ratio,
+            ..Self::new(score, alignments)
+        })
+    }
+
+    fn close_block(
+        block: &mut Vec<String>,
+        alignments: &mut Vec<Alignment>,
+        gap: char,
+    ) -> io::Result<()> {
+        match block.len() {
+            0 => return Ok(()),
+            2 => {}
+            n => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Alignment {} has {} lines, expected 2",
+                        alignments.len() + 1,
+                        n
+                    ),
+                ))
+            }
+        }
+
+        let align_b = block.pop().unwrap_or_default();
+        let align_a = block.pop().unwrap_or_default();
+        let alignment = Alignment::from_rows(align_a, align_b, gap).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Alignment {}: {}", alignments.len() + 1, e),
+            )
+        })?;
+        alignments.push(alignment);
+        Ok(())
+    }
+}
+
+impl<T> AlignmentResult<T> {
+    pub fn new(score: T, alignments: Vec<Alignment>) -> Self {
+        Self {
+            score,
+            alignments: alignments.into_iter().map(AlignedPair::from).collect(),
+            alignment_scores: None,
+            score_ratio: None,
+            significance: None,
+            shuffle_significance: None,
+            dna_stats: None,
+            truncated: Vec::new(),
+            starts: Vec::new(),
+        }
+    }
+
+    /// Number of start cells in each of M, Ix and Iy
+    pub fn starts_by_matrix(&self) -> [usize; 3] {
+        let mut counts = [0; 3];
+        for start in &self.starts {
+            counts[start.matrix as usize] += 1;
+        }
+        counts
+    }
+
+    /// Whether the traceback found no alignment
+    pub fn is_empty(&self) -> bool {
+        self.alignments.is_empty()
+    }
+
+    /// Number of co-optimal alignments kept
+    pub fn n_alignments(&self) -> usize {
+        self.alignments.len()
+    }
+
+    /// The rows and gap columns of each alignment, in order
+    pub fn aligned_pairs(&self) -> &[AlignedPair] {
+        &self.alignments
+    }
+
+    /// Check that every aligned row only contains alphabet or gap characters
+    pub fn validate_symbols(
+        &self,
+        alphabet_a: &Alphabet,
+        alphabet_b: &Alphabet,
+        gap: char,
+    ) -> io::Result<()> {
+        for (i, alignment) in self.alignments.iter().enumerate() {
+            for (row, alphabet) in [
+                (&alignment.align_a, alphabet_a),
+                (&alignment.align_b, alphabet_b),
+            ] {
+                if let Some(c) = row.chars().find(|c| *c != gap && !alphabet.contains(c)) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Alignment {} contains unknown character '{}'", i + 1, c),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,23 +1,72 @@
-use std::fs::File;
+use crate::models::residue::{lower_case, upper_case};
+use crate::models::Residue;
+use std::collections::BTreeSet;
 use std::io;
-use std::io::{BufReader, Lines};
 
 #[derive(Clone, Debug)]
-pub struct Alphabet {
-    alphabet: String,
-    aliphabet_size: usize,
+pub struct Alphabet<S = char> {
+    symbols: Vec<S>,
 }
 
-impl Alphabet {
-    pub fn new(alphabet: String) -> Self {
-        let size = alphabet.len();
-        Self {
-            alphabet,
-            aliphabet_size: size,
+impl Alphabet<char> {
+    pub fn from_string(alphabet: &str) -> Self {
+        Self::new(alphabet.chars().collect())
+    }
+}
+
+impl<S: Residue> Alphabet<S> {
+    pub fn new(symbols: Vec<S>) -> Self {
+        Self { symbols }
+    }
+
+    /// The distinct residues of a sequence, in sorted order
+    pub fn of_residues(sequence: &[S]) -> Self {
+        let symbols: BTreeSet<&S> = sequence.iter().collect();
+        Self::new(symbols.into_iter().cloned().collect())
+    }
+
+    pub fn symbols(&self) -> &[S] {
+        &self.symbols
+    }
+
+    /// The alphabet with each symbol written in upper case, dropping the duplicates this
+    /// makes and keeping the first-seen order
+    pub fn upper_case(&self) -> Self {
+        let mut symbols: Vec<S> = Vec::with_capacity(self.symbols.len());
+        for symbol in &self.symbols {
+            let symbol = upper_case(symbol).unwrap_or_else(|| symbol.clone());
+            if !symbols.contains(&symbol) {
+                symbols.push(symbol);
+            }
         }
+        Self::new(symbols)
+    }
+
+    /// The alphabet followed by the lower-case forms of its upper-case symbols that it
+    /// does not already hold
+    pub fn with_lower_case(&self) -> Self {
+        let mut symbols = self.symbols.clone();
+        for symbol in self.symbols.iter().filter_map(lower_case) {
+            if !symbols.contains(&symbol) {
+                symbols.push(symbol);
+            }
+        }
+        Self::new(symbols)
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn contains(&self, symbol: &S) -> bool {
+        self.symbols.contains(symbol)
     }
 
-    pub fn read_alphabet(lines: &mut Lines<BufReader<File>>) -> io::Result<Self> {
+    pub fn read_alphabet(lines: &mut impl Iterator<Item = io::Result<String>>) -> io::Result<Self> {
         let len_alphabet = lines
             .next()
             .ok_or_else(|| {
@@ -33,19 +82,20 @@ impl Alphabet {
         let alphabet = lines
             .next()
             .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing alphabet A"))??;
+        let symbols = S::tokenize(&alphabet);
 
         // Check if len_alphabet matches the actual alphabet size
-        if len_alphabet != alphabet.len() {
+        if len_alphabet != symbols.len() {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 format!(
                     "Alphabet length mismatch: expected {}, got {}",
                     len_alphabet,
-                    alphabet.len()
+                    symbols.len()
                 ),
             ));
         }
 
-        Ok(Alphabet::new(alphabet))
+        Ok(Alphabet::new(symbols))
     }
 }
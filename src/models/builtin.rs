@@ -0,0 +1,207 @@
+use crate::models::{MatchMatrix, Residue};
+use num_traits::Zero;
+use std::str::FromStr;
+
+/// Residue order of the rows and columns of the builtin protein matrices
+pub const PROTEIN_RESIDUES: &str = "ARNDCQEGHILKMFPSTWYVBZX*";
+
+/// Names accepted by [`MatchMatrix::builtin`]
+pub const BUILTIN_MATRICES: [&str; 5] = ["blosum62", "pam40", "pam120", "pam200", "pam250"];
+
+#[rustfmt::skip]
+const BLOSUM62: [[i8; 24]; 24] = [
+    [  4,  -1,  -2,  -2,   0,  -1,  -1,   0,  -2,  -1,  -1,  -1,  -1,  -2,  -1,   1,   0,  -3,  -2,   0,  -2,  -1,   0,  -4],
+    [ -1,   5,   0,  -2,  -3,   1,   0,  -2,   0,  -3,  -2,   2,  -1,  -3,  -2,  -1,  -1,  -3,  -2,  -3,  -1,   0,  -1,  -4],
+    [ -2,   0,   6,   1,  -3,   0,   0,   0,   1,  -3,  -3,   0,  -2,  -3,  -2,   1,   0,  -4,  -2,  -3,   3,   0,  -1,  -4],
+    [ -2,  -2,   1,   6,  -3,   0,   2,  -1,  -1,  -3,  -4,  -1,  -3,  -3,  -1,   0,  -1,  -4,  -3,  -3,   4,   1,  -1,  -4],
+    [  0,  -3,  -3,  -3,   9,  -3,  -4,  -3,  -3,  -1,  -1,  -3,  -1,  -2,  -3,  -1,  -1,  -2,  -2,  -1,  -3,  -3,  -2,  -4],
+    [ -1,   1,   0,   0,  -3,   5,   2,  -2,   0,  -3,  -2,   1,   0,  -3,  -1,   0,  -1,  -2,  -1,  -2,   0,   3,  -1,  -4],
+    [ -1,   0,   0,   2,  -4,   2,   5,  -2,   0,  -3,  -3,   1,  -2,  -3,  -1,   0,  -1,  -3,  -2,  -2,   1,   4,  -1,  -4],
+    [  0,  -2,   0,  -1,  -3,  -2,  -2,   6,  -2,  -4,  -4,  -2,  -3,  -3,  -2,   0,  -2,  -2,  -3,  -3,  -1,  -2,  -1,  -4],
+    [ -2,   0,   1,  -1,  -3,   0,   0,  -2,   8,  -3,  -3,  -1,  -2,  -1,  -2,  -1,  -2,  -2,   2,  -3,   0,   0,  -1,  -4],
+    [ -1,  -3,  -3,  -3,  -1,  -3,  -3,  -4,  -3,   4,   2,  -3,   1,   0,  -3,  -2,  -1,  -3,  -1,   3,  -3,  -3,  -1,  -4],
+    [ -1,  -2,  -3,  -4,  -1,  -2,  -3,  -4,  -3,   2,   4,  -2,   2,   0,  -3,  -2,  -1,  -2,  -1,   1,  -4,  -3,  -1,  -4],
+    [ -1,   2,   0,  -1,  -3,   1,   1,  -2,  -1,  -3,  -2,   5,  -1,  -3,  -1,   0,  -1,  -3,  -2,  -2,   0,   1,  -1,  -4],
+    [ -1,  -1,  -2,  -3,  -1,   0,  -2,  -3,  -2,   1,   2,  -1,   5,   0,  -2,  -1,  -1,  -1,  -1,   1,  -3,  -1,  -1,  -4],
+    [ -2,  -3,  -3,  -3,  -2,  -3,  -3,  -3,  -1,   0,   0,  -3,   0,   6,  -4,  -2,  -2,   1,   3,  -1,  -3,  -3,  -1,  -4],
+    [ -1,  -2,  -2,  -1,  -3,  -1,  -1,  -2,  -2,  -3,  -3,  -1,  -2,  -4,   7,  -1,  -1,  -4,  -3,  -2,  -2,  -1,  -2,  -4],
+    [  1,  -1,   1,   0,  -1,   0,   0,   0,  -1,  -2,  -2,   0,  -1,  -2,  -1,   4,   1,  -3,  -2,  -2,   0,   0,   0,  -4],
+    [  0,  -1,   0,  -1,  -1,  -1,  -1,  -2,  -2,  -1,  -1,  -1,  -1,  -2,  -1,   1,   5,  -2,  -2,   0,  -1,  -1,   0,  -4],
+    [ -3,  -3,  -4,  -4,  -2,  -2,  -3,  -2,  -2,  -3,  -2,  -3,  -1,   1,  -4,  -3,  -2,  11,   2,  -3,  -4,  -3,  -2,  -4],
+    [ -2,  -2,  -2,  -3,  -2,  -1,  -2,  -3,   2,  -1,  -1,  -2,  -1,   3,  -3,  -2,  -2,   2,   7,  -1,  -3,  -2,  -1,  -4],
+    [  0,  -3,  -3,  -3,  -1,  -2,  -2,  -3,  -3,   3,   1,  -2,   1,  -1,  -2,  -2,   0,  -3,  -1,   4,  -3,  -2,  -1,  -4],
+    [ -2,  -1,   3,   4,  -3,   0,   1,  -1,   0,  -3,  -4,   0,  -3,  -3,  -2,   0,  -1,  -4,  -3,  -3,   4,   1,  -1,  -4],
+    [ -1,   0,   0,   1,  -3,   3,   4,  -2,   0,  -3,  -3,   1,  -1,  -3,  -1,   0,  -1,  -3,  -2,  -2,   1,   4,  -1,  -4],
+    [  0,  -1,  -1,  -1,  -2,  -1,  -1,  -1,  -1,  -1,  -1,  -1,  -1,  -1,  -2,   0,   0,  -2,  -1,  -1,  -1,  -1,  -1,  -4],
+    [ -4,  -4,  -4,  -4,  -4,  -4,  -4,  -4,  -4,  -4,  -4,  -4,  -4,  -4,  -4,  -4,  -4,  -4,  -4,  -4,  -4,  -4,  -4,   1],
+];
+
+#[rustfmt::skip]
+const PAM40: [[i8; 24]; 24] = [
+    [  6,  -6,  -3,  -3,  -6,  -3,  -2,  -1,  -6,  -4,  -5,  -6,  -4,  -7,  -1,   0,   0, -12,  -7,  -2,  -3,  -2,  -3, -15],
+    [ -6,   8,  -5,  -9,  -7,  -1,  -8,  -8,  -1,  -5,  -8,   1,  -3,  -8,  -3,  -2,  -5,  -1,  -9,  -7,  -6,  -3,  -5, -15],
+    [ -3,  -5,   7,   2,  -9,  -3,  -1,  -2,   1,  -4,  -6,   0,  -7,  -8,  -5,   0,  -1,  -7,  -4,  -7,   6,  -2,  -3, -15],
+    [ -3,  -9,   2,   7, -12,  -2,   3,  -3,  -3,  -6, -11,  -4,  -9, -13,  -7,  -3,  -4, -13, -10,  -7,   6,   2,  -5, -15],
+    [ -6,  -7,  -9, -12,   9, -12, -12,  -8,  -7,  -5, -13, -12, -12, -11,  -7,  -2,  -7, -14,  -3,  -5, -11, -12,  -8, -15],
+    [ -3,  -1,  -3,  -2, -12,   8,   2,  -6,   1,  -7,  -4,  -2,  -3, -11,  -2,  -4,  -5, -11, -10,  -6,  -2,   6,  -4, -15],
+    [ -2,  -8,  -1,   3, -12,   2,   7,  -3,  -4,  -5,  -8,  -4,  -6, -12,  -5,  -4,  -5, -15,  -8,  -6,   2,   6,  -4, -15],
+    [ -1,  -8,  -2,  -3,  -8,  -6,  -3,   6,  -8,  -9,  -9,  -6,  -7,  -8,  -5,  -1,  -5, -13, -12,  -5,  -2,  -4,  -4, -15],
+    [ -6,  -1,   1,  -3,  -7,   1,  -4,  -8,   9,  -8,  -5,  -5,  -9,  -5,  -3,  -5,  -6,  -6,  -3,  -6,  -1,   0,  -4, -15],
+    [ -4,  -5,  -4,  -6,  -5,  -7,  -5,  -9,  -8,   8,  -1,  -5,   0,  -2,  -7,  -6,  -2, -12,  -5,   2,  -5,  -5,  -4, -15],
+    [ -5,  -8,  -6, -11, -13,  -4,  -8,  -9,  -5,  -1,   7,  -7,   1,  -2,  -6,  -7,  -6,  -5,  -6,  -2,  -8,  -6,  -5, -15],
+    [ -6,   1,   0,  -4, -12,  -2,  -4,  -6,  -5,  -5,  -7,   6,  -1, -12,  -6,  -3,  -2, -10,  -8,  -8,  -2,  -3,  -4, -15],
+    [ -4,  -3,  -7,  -9, -12,  -3,  -6,  -7,  -9,   0,   1,  -1,  11,  -3,  -7,  -5,  -3, -11, -10,  -1,  -8,  -4,  -4, -15],
+    [ -7,  -8,  -8, -13, -11, -11, -12,  -8,  -5,  -2,  -2, -12,  -3,   9,  -9,  -6,  -8,  -4,   2,  -7,  -9, -12,  -7, -15],
+    [ -1,  -3,  -5,  -7,  -7,  -2,  -5,  -5,  -3,  -7,  -6,  -6,  -7,  -9,   8,  -1,  -3, -12, -12,  -5,  -6,  -3,  -4, -15],
+    [  0,  -2,   0,  -3,  -2,  -4,  -4,  -1,  -5,  -6,  -7,  -3,  -5,  -6,  -1,   6,   1,  -4,  -6,  -5,  -1,  -4,  -2, -15],
+    [  0,  -5,  -1,  -4,  -7,  -5,  -5,  -5,  -6,  -2,  -6,  -2,  -3,  -8,  -3,   1,   7, -11,  -6,  -2,  -2,  -5,  -3, -15],
+    [-12,  -1,  -7, -13, -14, -11, -15, -13,  -6, -12,  -5, -10, -11,  -4, -12,  -4, -11,  13,  -4, -14,  -9, -13,  -9, -15],
+    [ -7,  -9,  -4, -10,  -3, -10,  -8, -12,  -3,  -5,  -6,  -8, -10,   2, -12,  -6,  -6,  -4,  10,  -6,  -6,  -8,  -7, -15],
+    [ -2,  -7,  -7,  -7,  -5,  -6,  -6,  -5,  -6,   2,  -2,  -8,  -1,  -7,  -5,  -5,  -2, -14,  -6,   7,  -7,  -6,  -4, -15],
+    [ -3,  -6,   6,   6, -11,  -2,   2,  -2,  -1,  -5,  -8,  -2,  -8,  -9,  -6,  -1,  -2,  -9,  -6,  -7,   6,   1,  -4, -15],
+    [ -2,  -3,  -2,   2, -12,   6,   6,  -4,   0,  -5,  -6,  -3,  -4, -12,  -3,  -4,  -5, -13,  -8,  -6,   1,   6,  -4, -15],
+    [ -3,  -5,  -3,  -5,  -8,  -4,  -4,  -4,  -4,  -4,  -5,  -4,  -4,  -7,  -4,  -2,  -3,  -9,  -7,  -4,  -4,  -4,  -4, -15],
+    [-15, -15, -15, -15, -15, -15, -15, -15, -15, -15, -15, -15, -15, -15, -15, -15, -15, -15, -15, -15, -15, -15, -15,   1],
+];
+
+#[rustfmt::skip]
+const PAM120: [[i8; 24]; 24] = [
+    [  3,  -3,  -1,   0,  -3,  -1,   0,   1,  -3,  -1,  -3,  -2,  -2,  -4,   1,   1,   1,  -7,  -4,   0,   0,  -1,  -1,  -8],
+    [ -3,   6,  -1,  -3,  -4,   1,  -3,  -4,   1,  -2,  -4,   2,  -1,  -5,  -1,  -1,  -2,   1,  -5,  -3,  -2,  -1,  -2,  -8],
+    [ -1,  -1,   4,   2,  -5,   0,   1,   0,   2,  -2,  -4,   1,  -3,  -4,  -2,   1,   0,  -4,  -2,  -3,   3,   0,  -1,  -8],
+    [  0,  -3,   2,   5,  -7,   1,   3,   0,   0,  -3,  -5,  -1,  -4,  -7,  -3,   0,  -1,  -8,  -5,  -3,   4,   3,  -2,  -8],
+    [ -3,  -4,  -5,  -7,   9,  -7,  -7,  -4,  -4,  -3,  -7,  -7,  -6,  -6,  -4,   0,  -3,  -8,  -1,  -3,  -6,  -7,  -4,  -8],
+    [ -1,   1,   0,   1,  -7,   6,   2,  -3,   3,  -3,  -2,   0,  -1,  -6,   0,  -2,  -2,  -6,  -5,  -3,   0,   4,  -1,  -8],
+    [  0,  -3,   1,   3,  -7,   2,   5,  -1,  -1,  -3,  -4,  -1,  -3,  -7,  -2,  -1,  -2,  -8,  -5,  -3,   3,   4,  -1,  -8],
+    [  1,  -4,   0,   0,  -4,  -3,  -1,   5,  -4,  -4,  -5,  -3,  -4,  -5,  -2,   1,  -1,  -8,  -6,  -2,   0,  -2,  -2,  -8],
+    [ -3,   1,   2,   0,  -4,   3,  -1,  -4,   7,  -4,  -3,  -2,  -4,  -3,  -1,  -2,  -3,  -3,  -1,  -3,   1,   1,  -2,  -8],
+    [ -1,  -2,  -2,  -3,  -3,  -3,  -3,  -4,  -4,   6,   1,  -3,   1,   0,  -3,  -2,   0,  -6,  -2,   3,  -3,  -3,  -1,  -8],
+    [ -3,  -4,  -4,  -5,  -7,  -2,  -4,  -5,  -3,   1,   5,  -4,   3,   0,  -3,  -4,  -3,  -3,  -2,   1,  -4,  -3,  -2,  -8],
+    [ -2,   2,   1,  -1,  -7,   0,  -1,  -3,  -2,  -3,  -4,   5,   0,  -7,  -2,  -1,  -1,  -5,  -5,  -4,   0,  -1,  -2,  -8],
+    [ -2,  -1,  -3,  -4,  -6,  -1,  -3,  -4,  -4,   1,   3,   0,   8,  -1,  -3,  -2,  -1,  -6,  -4,   1,  -4,  -2,  -2,  -8],
+    [ -4,  -5,  -4,  -7,  -6,  -6,  -7,  -5,  -3,   0,   0,  -7,  -1,   8,  -5,  -3,  -4,  -1,   4,  -3,  -5,  -6,  -3,  -8],
+    [  1,  -1,  -2,  -3,  -4,   0,  -2,  -2,  -1,  -3,  -3,  -2,  -3,  -5,   6,   1,  -1,  -7,  -6,  -2,  -2,  -1,  -2,  -8],
+    [  1,  -1,   1,   0,   0,  -2,  -1,   1,  -2,  -2,  -4,  -1,  -2,  -3,   1,   3,   2,  -2,  -3,  -2,   0,  -1,  -1,  -8],
+    [  1,  -2,   0,  -1,  -3,  -2,  -2,  -1,  -3,   0,  -3,  -1,  -1,  -4,  -1,   2,   4,  -6,  -3,   0,   0,  -2,  -1,  -8],
+    [ -7,   1,  -4,  -8,  -8,  -6,  -8,  -8,  -3,  -6,  -3,  -5,  -6,  -1,  -7,  -2,  -6,  12,  -2,  -8,  -6,  -7,  -5,  -8],
+    [ -4,  -5,  -2,  -5,  -1,  -5,  -5,  -6,  -1,  -2,  -2,  -5,  -4,   4,  -6,  -3,  -3,  -2,   8,  -3,  -3,  -5,  -3,  -8],
+    [  0,  -3,  -3,  -3,  -3,  -3,  -3,  -2,  -3,   3,   1,  -4,   1,  -3,  -2,  -2,   0,  -8,  -3,   5,  -3,  -3,  -1,  -8],
+    [  0,  -2,   3,   4,  -6,   0,   3,   0,   1,  -3,  -4,   0,  -4,  -5,  -2,   0,   0,  -6,  -3,  -3,   4,   2,  -1,  -8],
+    [ -1,  -1,   0,   3,  -7,   4,   4,  -2,   1,  -3,  -3,  -1,  -2,  -6,  -1,  -1,  -2,  -7,  -5,  -3,   2,   4,  -1,  -8],
+    [ -1,  -2,  -1,  -2,  -4,  -1,  -1,  -2,  -2,  -1,  -2,  -2,  -2,  -3,  -2,  -1,  -1,  -5,  -3,  -1,  -1,  -1,  -2,  -8],
+    [ -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,   1],
+];
+
+#[rustfmt::skip]
+const PAM200: [[i8; 24]; 24] = [
+    [  3,  -2,   0,   0,  -3,  -1,   0,   1,  -2,  -1,  -2,  -2,  -2,  -4,   1,   1,   1,  -7,  -4,   0,   0,   0,   0,  -9],
+    [ -2,   7,   0,  -2,  -4,   1,  -2,  -4,   2,  -2,  -4,   4,  -1,  -5,   0,  -1,  -1,   2,  -5,  -3,  -1,   0,  -1,  -9],
+    [  0,   0,   3,   3,  -5,   1,   2,   0,   2,  -2,  -4,   1,  -2,  -4,  -1,   1,   0,  -5,  -2,  -2,   3,   1,   0,  -9],
+    [  0,  -2,   3,   5,  -6,   2,   4,   0,   0,  -3,  -5,   0,  -4,  -7,  -2,   0,   0,  -8,  -5,  -3,   4,   3,  -1,  -9],
+    [ -3,  -4,  -5,  -6,  12,  -7,  -7,  -4,  -4,  -3,  -7,  -7,  -6,  -6,  -4,   0,  -3,  -9,   0,  -2,  -5,  -7,  -4,  -9],
+    [ -1,   1,   1,   2,  -7,   5,   3,  -2,   3,  -3,  -2,   1,  -1,  -6,   0,  -1,  -1,  -6,  -5,  -3,   1,   4,  -1,  -9],
+    [  0,  -2,   2,   4,  -7,   3,   5,   0,   0,  -3,  -4,   0,  -3,  -7,  -1,   0,  -1,  -9,  -5,  -2,   3,   4,  -1,  -9],
+    [  1,  -4,   0,   0,  -4,  -2,   0,   6,  -3,  -3,  -5,  -2,  -4,  -6,  -1,   1,   0,  -8,  -6,  -2,   0,  -1,  -1,  -9],
+    [ -2,   2,   2,   0,  -4,   3,   0,  -3,   8,  -3,  -3,  -1,  -3,  -2,  -1,  -1,  -2,  -3,   0,  -3,   1,   2,  -1,  -9],
+    [ -1,  -2,  -2,  -3,  -3,  -3,  -3,  -3,  -3,   6,   2,  -2,   2,   1,  -3,  -2,   0,  -6,  -2,   4,  -3,  -3,  -1,  -9],
+    [ -2,  -4,  -4,  -5,  -7,  -2,  -4,  -5,  -3,   2,   7,  -4,   4,   2,  -3,  -4,  -2,  -2,  -2,   2,  -4,  -3,  -2,  -9],
+    [ -2,   4,   1,   0,  -7,   1,   0,  -2,  -1,  -2,  -4,   6,   1,  -7,  -2,   0,   0,  -4,  -5,  -3,   0,   0,  -1,  -9],
+    [ -2,  -1,  -2,  -4,  -6,  -1,  -3,  -4,  -3,   2,   4,   1,   8,   0,  -3,  -2,  -1,  -5,  -3,   2,  -3,  -2,  -1,  -9],
+    [ -4,  -5,  -4,  -7,  -6,  -6,  -7,  -6,  -2,   1,   2,  -7,   0,  10,  -6,  -4,  -4,   0,   7,  -2,  -6,  -6,  -3,  -9],
+    [  1,   0,  -1,  -2,  -4,   0,  -1,  -1,  -1,  -3,  -3,  -2,  -3,  -6,   7,   1,   0,  -7,  -6,  -2,  -1,  -1,  -1,  -9],
+    [  1,  -1,   1,   0,   0,  -1,   0,   1,  -1,  -2,  -4,   0,  -2,  -4,   1,   2,   2,  -3,  -3,  -1,   1,  -1,   0,  -9],
+    [  1,  -1,   0,   0,  -3,  -1,  -1,   0,  -2,   0,  -2,   0,  -1,  -4,   0,   2,   4,  -6,  -3,   0,   0,  -1,   0,  -9],
+    [ -7,   2,  -5,  -8,  -9,  -6,  -9,  -8,  -3,  -6,  -2,  -4,  -5,   0,  -7,  -3,  -6,  18,  -1,  -8,  -6,  -7,  -5,  -9],
+    [ -4,  -5,  -2,  -5,   0,  -5,  -5,  -6,   0,  -2,  -2,  -5,  -3,   7,  -6,  -3,  -3,  -1,  11,  -3,  -4,  -5,  -3,  -9],
+    [  0,  -3,  -2,  -3,  -2,  -3,  -2,  -2,  -3,   4,   2,  -3,   2,  -2,  -2,  -1,   0,  -8,  -3,   5,  -3,  -2,  -1,  -9],
+    [  0,  -1,   3,   4,  -5,   1,   3,   0,   1,  -3,  -4,   0,  -3,  -6,  -1,   1,   0,  -6,  -4,  -3,   3,   2,  -1,  -9],
+    [  0,   0,   1,   3,  -7,   4,   4,  -1,   2,  -3,  -3,   0,  -2,  -6,  -1,  -1,  -1,  -7,  -5,  -2,   2,   4,  -1,  -9],
+    [  0,  -1,   0,  -1,  -4,  -1,  -1,  -1,  -1,  -1,  -2,  -1,  -1,  -3,  -1,   0,   0,  -5,  -3,  -1,  -1,  -1,  -1,  -9],
+    [ -9,  -9,  -9,  -9,  -9,  -9,  -9,  -9,  -9,  -9,  -9,  -9,  -9,  -9,  -9,  -9,  -9,  -9,  -9,  -9,  -9,  -9,  -9,   1],
+];
+
+#[rustfmt::skip]
+const PAM250: [[i8; 24]; 24] = [
+    [  2,  -2,   0,   0,  -2,   0,   0,   1,  -1,  -1,  -2,  -1,  -1,  -3,   1,   1,   1,  -6,  -3,   0,   0,   0,   0,  -8],
+    [ -2,   6,   0,  -1,  -4,   1,  -1,  -3,   2,  -2,  -3,   3,   0,  -4,   0,   0,  -1,   2,  -4,  -2,  -1,   0,  -1,  -8],
+    [  0,   0,   2,   2,  -4,   1,   1,   0,   2,  -2,  -3,   1,  -2,  -3,   0,   1,   0,  -4,  -2,  -2,   2,   1,   0,  -8],
+    [  0,  -1,   2,   4,  -5,   2,   3,   1,   1,  -2,  -4,   0,  -3,  -6,  -1,   0,   0,  -7,  -4,  -2,   3,   3,  -1,  -8],
+    [ -2,  -4,  -4,  -5,  12,  -5,  -5,  -3,  -3,  -2,  -6,  -5,  -5,  -4,  -3,   0,  -2,  -8,   0,  -2,  -4,  -5,  -3,  -8],
+    [  0,   1,   1,   2,  -5,   4,   2,  -1,   3,  -2,  -2,   1,  -1,  -5,   0,  -1,  -1,  -5,  -4,  -2,   1,   3,  -1,  -8],
+    [  0,  -1,   1,   3,  -5,   2,   4,   0,   1,  -2,  -3,   0,  -2,  -5,  -1,   0,   0,  -7,  -4,  -2,   3,   3,  -1,  -8],
+    [  1,  -3,   0,   1,  -3,  -1,   0,   5,  -2,  -3,  -4,  -2,  -3,  -5,   0,   1,   0,  -7,  -5,  -1,   0,   0,  -1,  -8],
+    [ -1,   2,   2,   1,  -3,   3,   1,  -2,   6,  -2,  -2,   0,  -2,  -2,   0,  -1,  -1,  -3,   0,  -2,   1,   2,  -1,  -8],
+    [ -1,  -2,  -2,  -2,  -2,  -2,  -2,  -3,  -2,   5,   2,  -2,   2,   1,  -2,  -1,   0,  -5,  -1,   4,  -2,  -2,  -1,  -8],
+    [ -2,  -3,  -3,  -4,  -6,  -2,  -3,  -4,  -2,   2,   6,  -3,   4,   2,  -3,  -3,  -2,  -2,  -1,   2,  -3,  -3,  -1,  -8],
+    [ -1,   3,   1,   0,  -5,   1,   0,  -2,   0,  -2,  -3,   5,   0,  -5,  -1,   0,   0,  -3,  -4,  -2,   1,   0,  -1,  -8],
+    [ -1,   0,  -2,  -3,  -5,  -1,  -2,  -3,  -2,   2,   4,   0,   6,   0,  -2,  -2,  -1,  -4,  -2,   2,  -2,  -2,  -1,  -8],
+    [ -3,  -4,  -3,  -6,  -4,  -5,  -5,  -5,  -2,   1,   2,  -5,   0,   9,  -5,  -3,  -3,   0,   7,  -1,  -4,  -5,  -2,  -8],
+    [  1,   0,   0,  -1,  -3,   0,  -1,   0,   0,  -2,  -3,  -1,  -2,  -5,   6,   1,   0,  -6,  -5,  -1,  -1,   0,  -1,  -8],
+    [  1,   0,   1,   0,   0,  -1,   0,   1,  -1,  -1,  -3,   0,  -2,  -3,   1,   2,   1,  -2,  -3,  -1,   0,   0,   0,  -8],
+    [  1,  -1,   0,   0,  -2,  -1,   0,   0,  -1,   0,  -2,   0,  -1,  -3,   0,   1,   3,  -5,  -3,   0,   0,  -1,   0,  -8],
+    [ -6,   2,  -4,  -7,  -8,  -5,  -7,  -7,  -3,  -5,  -2,  -3,  -4,   0,  -6,  -2,  -5,  17,   0,  -6,  -5,  -6,  -4,  -8],
+    [ -3,  -4,  -2,  -4,   0,  -4,  -4,  -5,   0,  -1,  -1,  -4,  -2,   7,  -5,  -3,  -3,   0,  10,  -2,  -3,  -4,  -2,  -8],
+    [  0,  -2,  -2,  -2,  -2,  -2,  -2,  -1,  -2,   4,   2,  -2,   2,  -1,  -1,  -1,   0,  -6,  -2,   4,  -2,  -2,  -1,  -8],
+    [  0,  -1,   2,   3,  -4,   1,   3,   0,   1,  -2,  -3,   1,  -2,  -4,  -1,   0,   0,  -5,  -3,  -2,   3,   2,  -1,  -8],
+    [  0,   0,   1,   3,  -5,   3,   3,   0,   2,  -2,  -3,   0,  -2,  -5,   0,   0,  -1,  -6,  -4,  -2,   2,   3,  -1,  -8],
+    [  0,  -1,   0,  -1,  -3,  -1,  -1,  -1,  -1,  -1,  -1,  -1,  -1,  -2,  -1,   0,   0,  -4,  -2,  -1,  -1,  -1,  -1,  -8],
+    [ -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,  -8,   1],
+];
+
+fn table(name: &str) -> Option<&'static [[i8; 24]; 24]> {
+    match name.to_ascii_lowercase().as_str() {
+        "blosum62" => Some(&BLOSUM62),
+        "pam40" => Some(&PAM40),
+        "pam120" => Some(&PAM120),
+        "pam200" => Some(&PAM200),
+        "pam250" => Some(&PAM250),
+        _ => None,
+    }
+}
+
+impl<T: Copy + From<i8> + FromStr + Zero, S: Residue> MatchMatrix<T, S> {
+    /// Load a builtin substitution matrix by name (case-insensitive), e.g. `blosum62`
+    pub fn builtin(name: &str) -> Option<Self> {
+        table(name).map(Self::from_table)
+    }
+
+    fn from_table(table: &[[i8; 24]; 24]) -> Self {
+        let residues: Vec<S> = PROTEIN_RESIDUES
+            .chars()
+            .filter_map(|c| S::parse_token(c.encode_utf8(&mut [0; 4])))
+            .collect();
+        let mut match_matrix = Self::new();
+        // Only the lower triangle is read, so the matrix is symmetric by construction
+        for (i, (a, row)) in residues.iter().zip(table).enumerate() {
+            for (b, &score) in residues.iter().zip(row).take(i + 1) {
+                match_matrix.set_score(a.clone(), b.clone(), T::from(score));
+                match_matrix.set_score(b.clone(), a.clone(), T::from(score));
+            }
+        }
+        match_matrix
+    }
+}
+
+impl<S: Residue> MatchMatrix<i8, S> {
+    /// BLOSUM62 for an `i8` grid. Its half-bit scores, -4 to 11, already fit in `i8`, so
+    /// they are kept as they are rather than rescaled.
+    pub fn blosum62_int() -> Self {
+        Self::from_table(&BLOSUM62)
+    }
+}
+
+impl<T: Copy + From<i8> + FromStr + PartialEq + Zero, S: Residue> MatchMatrix<T, S> {
+    /// Name of the builtin matrix that agrees with every pair defined here
+    pub fn builtin_name(&self) -> Option<&'static str> {
+        if self.is_empty() {
+            return None;
+        }
+        BUILTIN_MATRICES.into_iter().find(|name| {
+            Self::builtin(name).is_some_and(|builtin| {
+                self.iter().all(|(a, b, score)| {
+                    builtin.has_score(&a, &b) && builtin.get_score(&a, &b) == score
+                })
+            })
+        })
+    }
+}
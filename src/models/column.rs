@@ -0,0 +1,138 @@
+use crate::models::{Alignment, AlignmentOp};
+use std::iter::FusedIterator;
+
+/// How the two rows of an alignment column relate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColumnKind {
+    /// The same residue in both rows
+    Match,
+    /// Different residues in the two rows
+    Mismatch,
+    /// A residue of A against a gap in B, a [`AlignmentOp::Deletion`]
+    InsertA,
+    /// A residue of B against a gap in A, an [`AlignmentOp::Insertion`]
+    InsertB,
+}
+
+impl ColumnKind {
+    /// Whether either row has a gap in the column
+    pub fn is_gap(&self) -> bool {
+        matches!(self, ColumnKind::InsertA | ColumnKind::InsertB)
+    }
+}
+
+/// One column of an alignment, with the residue of each row and its 0-based position in
+/// its sequence; both are `None` for a gap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Column {
+    pub a: Option<char>,
+    pub b: Option<char>,
+    pub a_pos: Option<usize>,
+    pub b_pos: Option<usize>,
+    pub kind: ColumnKind,
+}
+
+/// Iterator over the columns of an alignment, from [`Alignment::columns`]
+#[derive(Debug, Clone)]
+pub struct Columns<'a> {
+    ops: &'a [AlignmentOp],
+    /// The rows one character per column, or empty when they are not
+    row_a: Vec<char>,
+    row_b: Vec<char>,
+    /// Columns `front..back` are left to yield
+    front: usize,
+    back: usize,
+    /// Positions of the next residue of each sequence at the front, and just past the
+    /// last at the back
+    front_a: usize,
+    front_b: usize,
+    back_a: usize,
+    back_b: usize,
+}
+
+impl<'a> Columns<'a> {
+    pub(crate) fn new(alignment: &'a Alignment) -> Self {
+        let row = |row: &str| {
+            let chars: Vec<char> = row.chars().collect();
+            if chars.len() == alignment.len() {
+                chars
+            } else {
+                Vec::new()
+            }
+        };
+        Self {
+            ops: &alignment.ops,
+            row_a: row(&alignment.align_a),
+            row_b: row(&alignment.align_b),
+            front: 0,
+            back: alignment.len(),
+            front_a: alignment.start_a,
+            front_b: alignment.start_b,
+            back_a: alignment.end_a(),
+            back_b: alignment.end_b(),
+        }
+    }
+
+    fn column(&self, col: usize, a_pos: usize, b_pos: usize) -> Column {
+        let op = self.ops[col];
+        let a = op
+            .consumes_a()
+            .then(|| self.row_a.get(col).copied())
+            .flatten();
+        let b = op
+            .consumes_b()
+            .then(|| self.row_b.get(col).copied())
+            .flatten();
+        let kind = match op {
+            AlignmentOp::Match if a == b => ColumnKind::Match,
+            AlignmentOp::Match => ColumnKind::Mismatch,
+            AlignmentOp::Deletion => ColumnKind::InsertA,
+            AlignmentOp::Insertion => ColumnKind::InsertB,
+        };
+        Column {
+            a,
+            b,
+            a_pos: op.consumes_a().then_some(a_pos),
+            b_pos: op.consumes_b().then_some(b_pos),
+            kind,
+        }
+    }
+}
+
+impl Iterator for Columns<'_> {
+    type Item = Column;
+
+    fn next(&mut self) -> Option<Column> {
+        if self.front == self.back {
+            return None;
+        }
+        let column = self.column(self.front, self.front_a, self.front_b);
+        let op = self.ops[self.front];
+        self.front += 1;
+        self.front_a += usize::from(op.consumes_a());
+        self.front_b += usize::from(op.consumes_b());
+        Some(column)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+impl DoubleEndedIterator for Columns<'_> {
+    fn next_back(&mut self) -> Option<Column> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        let op = self.ops[self.back];
+        self.back_a -= usize::from(op.consumes_a());
+        self.back_b -= usize::from(op.consumes_b());
+        Some(self.column(self.back, self.back_a, self.back_b))
+    }
+}
+
+impl ExactSizeIterator for Columns<'_> {}
+
+impl FusedIterator for Columns<'_> {}
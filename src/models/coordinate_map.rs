@@ -0,0 +1,192 @@
+use crate::models::{Alignment, AlignmentOp};
+
+/// Where a position of sequence A lands on sequence B
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappedPos {
+    /// Aligned to this position of B
+    Exact(usize),
+    /// Aligned to a gap in B. `left` and `right` are the nearest B positions on either
+    /// side of the gap, or `None` when the gap is at an end of the alignment.
+    InDeletion {
+        left: Option<usize>,
+        right: Option<usize>,
+    },
+    /// Outside the part of A covered by the alignment
+    Unaligned,
+}
+
+/// Projection of a half-open interval of A onto B
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MappedInterval {
+    /// First B position aligned to a residue in the interval
+    pub start: usize,
+    /// One past the last B position aligned to a residue in the interval
+    pub end: usize,
+    /// Residues of the interval aligned to gaps in B
+    pub deleted: usize,
+    /// Residues of the interval outside the alignment
+    pub unaligned: usize,
+}
+
+/// A run of columns that consume sequence A, all with the same op
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    op: AlignmentOp,
+    start_a: usize,
+    start_b: usize,
+    len: usize,
+    /// A residues in deletion runs before this one
+    deleted_before: usize,
+}
+
+/// Index over an alignment's ops for mapping A coordinates to B.
+///
+/// Building the map is linear in the alignment length; each query is a binary search
+/// over runs of columns. All coordinates are 0-based.
+#[derive(Debug, Clone)]
+pub struct CoordinateMap {
+    runs: Vec<Run>,
+    start_a: usize,
+    end_a: usize,
+    start_b: usize,
+    end_b: usize,
+    /// Index of the first match run at or after each run
+    next_match: Vec<Option<usize>>,
+    /// Index of the last match run at or before each run
+    prev_match: Vec<Option<usize>>,
+}
+
+impl CoordinateMap {
+    pub fn new(alignment: &Alignment) -> Self {
+        let mut runs: Vec<Run> = Vec::new();
+        let (mut pos_a, mut pos_b, mut deleted) = (alignment.start_a, alignment.start_b, 0);
+        let mut previous = None;
+
+        for &op in &alignment.ops {
+            if op.consumes_a() {
+                match runs.last_mut() {
+                    Some(run) if previous == Some(op) => run.len += 1,
+                    _ => runs.push(Run {
+                        op,
+                        start_a: pos_a,
+                        start_b: pos_b,
+                        len: 1,
+                        deleted_before: deleted,
+                    }),
+                }
+                deleted += usize::from(op == AlignmentOp::Deletion);
+            }
+            pos_a += usize::from(op.consumes_a());
+            pos_b += usize::from(op.consumes_b());
+            previous = Some(op);
+        }
+
+        let is_match = |run: &Run| run.op == AlignmentOp::Match;
+        let mut next_match = vec![None; runs.len()];
+        let mut prev_match = vec![None; runs.len()];
+        let mut last = None;
+        for (i, run) in runs.iter().enumerate() {
+            if is_match(run) {
+                last = Some(i);
+            }
+            prev_match[i] = last;
+        }
+        last = None;
+        for (i, run) in runs.iter().enumerate().rev() {
+            if is_match(run) {
+                last = Some(i);
+            }
+            next_match[i] = last;
+        }
+
+        Self {
+            runs,
+            start_a: alignment.start_a,
+            end_a: pos_a,
+            start_b: alignment.start_b,
+            end_b: pos_b,
+            next_match,
+            prev_match,
+        }
+    }
+
+    /// Index of the run covering an A position inside the alignment
+    fn run_index(&self, pos: usize) -> usize {
+        self.runs
+            .partition_point(|run| run.start_a + run.len <= pos)
+    }
+
+    pub fn map_a_to_b(&self, pos: usize) -> MappedPos {
+        if pos < self.start_a || pos >= self.end_a {
+            return MappedPos::Unaligned;
+        }
+        let run = &self.runs[self.run_index(pos)];
+        match run.op {
+            AlignmentOp::Deletion => MappedPos::InDeletion {
+                left: (run.start_b > self.start_b).then(|| run.start_b - 1),
+                right: (run.start_b < self.end_b).then_some(run.start_b),
+            },
+            _ => MappedPos::Exact(run.start_b + (pos - run.start_a)),
+        }
+    }
+
+    /// A residues in deletion runs before `pos`, for `pos` within the alignment
+    fn deleted_before(&self, pos: usize) -> usize {
+        if pos >= self.end_a {
+            return self.runs.last().map_or(0, |run| {
+                run.deleted_before
+                    + if run.op == AlignmentOp::Deletion {
+                        run.len
+                    } else {
+                        0
+                    }
+            });
+        }
+        let run = &self.runs[self.run_index(pos)];
+        run.deleted_before
+            + if run.op == AlignmentOp::Deletion {
+                pos - run.start_a
+            } else {
+                0
+            }
+    }
+
+    /// Project the half-open interval `start..end` of A onto B.
+    ///
+    /// Returns `None` if no residue of the interval is aligned to a residue of B.
+    pub fn map_interval(&self, start: usize, end: usize) -> Option<MappedInterval> {
+        let clip_start = start.max(self.start_a);
+        let clip_end = end.min(self.end_a);
+        if clip_start >= clip_end {
+            return None;
+        }
+
+        let first_run = self.run_index(clip_start);
+        let last_run = self.run_index(clip_end - 1);
+        let first = self.next_match[first_run].filter(|&i| i <= last_run)?;
+        let last = self.prev_match[last_run].filter(|&i| i >= first_run)?;
+
+        let (first, last) = (&self.runs[first], &self.runs[last]);
+        let first_a = clip_start.max(first.start_a);
+        let last_a = (clip_end - 1).min(last.start_a + last.len - 1);
+
+        Some(MappedInterval {
+            start: first.start_b + (first_a - first.start_a),
+            end: last.start_b + (last_a - last.start_a) + 1,
+            deleted: self.deleted_before(clip_end) - self.deleted_before(clip_start),
+            unaligned: (end - start) - (clip_end - clip_start),
+        })
+    }
+}
+
+impl Alignment {
+    /// Map a position of A to B; see [`CoordinateMap`] for repeated queries
+    pub fn map_a_to_b(&self, pos: usize) -> MappedPos {
+        CoordinateMap::new(self).map_a_to_b(pos)
+    }
+
+    /// Project a half-open interval of A onto B; see [`CoordinateMap::map_interval`]
+    pub fn map_interval_a_to_b(&self, start: usize, end: usize) -> Option<MappedInterval> {
+        CoordinateMap::new(self).map_interval(start, end)
+    }
+}
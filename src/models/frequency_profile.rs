@@ -0,0 +1,150 @@
+use crate::error::AlignmentError;
+use crate::models::{MatchMatrix, Residue};
+use num_traits::Zero;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Residue frequencies at each position of a profile, such as the columns of a multiple
+/// alignment, standing in for sequence A in
+/// [`AlignGrid::populate_from_profile`](crate::models::AlignGrid::populate_from_profile).
+///
+/// A position scores against a residue of B the mean of the match scores of its
+/// residues, weighted by their frequencies, so a profile of a single sequence scores
+/// exactly as that sequence does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrequencyProfile<T = f64, S: Residue = char> {
+    /// Score of each residue of B against each position
+    scores: Vec<HashMap<S, T>>,
+    /// Most frequent residue of each position, the first listed on ties
+    consensus: Vec<S>,
+}
+
+impl<S: Residue> FrequencyProfile<f64, S> {
+    /// A profile from the residues of each position with their frequencies or counts,
+    /// scored against B by `match_matrix`. The weights of a position need not sum to 1.
+    ///
+    /// Fails for a position without residues or with a negative, non-finite or zero total
+    /// weight, or for a residue the match matrix has no scores for.
+    pub fn new(
+        frequencies: &[Vec<(S, f64)>],
+        match_matrix: &MatchMatrix<f64, S>,
+    ) -> Result<Self, AlignmentError> {
+        let residues_b: Vec<S> = match_matrix.chars_b().collect();
+        let mut scores = Vec::with_capacity(frequencies.len());
+        let mut consensus = Vec::with_capacity(frequencies.len());
+        for (position, weights) in frequencies.iter().enumerate() {
+            let invalid = |reason: String| {
+                AlignmentError::InvalidInput(format!(
+                    "profile position {}: {}",
+                    position + 1,
+                    reason
+                ))
+            };
+            if let Some((residue, weight)) = weights
+                .iter()
+                .find(|(_, weight)| !weight.is_finite() || *weight < 0.0)
+            {
+                return Err(invalid(format!("{} has weight {}", residue, weight)));
+            }
+            let total: f64 = weights.iter().map(|(_, weight)| weight).sum();
+            let Some((top, _)) = weights
+                .iter()
+                .reduce(|best, next| if next.1 > best.1 { next } else { best })
+                .filter(|_| total > 0.0)
+            else {
+                return Err(invalid("no residue has a positive weight".to_string()));
+            };
+            if let Some((residue, _)) = weights.iter().find(|(residue, _)| {
+                !residues_b
+                    .iter()
+                    .any(|b| match_matrix.has_score(residue, b))
+            }) {
+                return Err(invalid(format!("{} has no match scores", residue)));
+            }
+            let position_scores = residues_b
+                .iter()
+                .map(|b| {
+                    let score = weights
+                        .iter()
+                        .map(|(a, weight)| weight * match_matrix.get_score(a, b))
+                        .sum::<f64>();
+                    (b.clone(), score / total)
+                })
+                .collect();
+            scores.push(position_scores);
+            consensus.push(top.clone());
+        }
+        Ok(Self { scores, consensus })
+    }
+
+    /// The profile of equal-length, ungapped sequences, each position counting the
+    /// residues the sequences have there
+    pub fn from_sequences(
+        sequences: &[&[S]],
+        match_matrix: &MatchMatrix<f64, S>,
+    ) -> Result<Self, AlignmentError> {
+        let len = sequences.first().map_or(0, |sequence| sequence.len());
+        if sequences.iter().any(|sequence| sequence.len() != len) {
+            return Err(AlignmentError::InvalidInput(
+                "profile sequences differ in length".to_string(),
+            ));
+        }
+        let frequencies: Vec<Vec<(S, f64)>> = (0..len)
+            .map(|position| {
+                let mut counts: Vec<(S, f64)> = Vec::new();
+                for sequence in sequences {
+                    let residue = &sequence[position];
+                    match counts.iter_mut().find(|(seen, _)| seen == residue) {
+                        Some((_, count)) => *count += 1.0,
+                        None => counts.push((residue.clone(), 1.0)),
+                    }
+                }
+                counts
+            })
+            .collect();
+        Self::new(&frequencies, match_matrix)
+    }
+}
+
+impl<T: Copy + FromStr + Zero, S: Residue> FrequencyProfile<T, S> {
+    /// Number of positions
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    /// Score of residue `b` against `position` (0-based); zero for a residue the match
+    /// matrix had no scores for, as [`MatchMatrix::get_score`] gives
+    pub fn score_at(&self, position: usize, b: &S) -> T {
+        self.scores[position]
+            .get(b)
+            .copied()
+            .unwrap_or_else(T::zero)
+    }
+
+    /// Most frequent residue of each position, to stand in for the profile as sequence A
+    pub fn consensus(&self) -> &[S] {
+        &self.consensus
+    }
+}
+
+/// Where the fill takes the score of aligning a position of A with a residue of B
+pub(crate) enum ScoringSource<'a, T, S: Residue> {
+    /// The match matrix, scoring the residues of sequence A
+    Matrix(&'a MatchMatrix<T, S>),
+    /// A profile in place of sequence A
+    Profile(&'a FrequencyProfile<T, S>),
+}
+
+impl<T: Copy + FromStr + Zero, S: Residue> ScoringSource<'_, T, S> {
+    /// Score of position `row` (0-based) of A, residue `a` of sequence A, against `b`
+    pub(crate) fn score(&self, row: usize, a: &S, b: &S) -> T {
+        match self {
+            ScoringSource::Matrix(match_matrix) => match_matrix.get_score(a, b),
+            ScoringSource::Profile(profile) => profile.score_at(row, b),
+        }
+    }
+}
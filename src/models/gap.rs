@@ -1,49 +1,476 @@
+use crate::error::AlignmentError;
+use num_traits::Zero;
 use std::fmt::Display;
-use std::fs::File;
 use std::io;
-use std::io::{BufReader, Lines};
+use std::ops::{Range, Sub};
 use std::str::FromStr;
 
-#[derive(Debug, Clone)]
+/// How residues of a sequence left unaligned at either end of a global alignment are
+/// charged. A's overhang is a gap in B before B's first residue or after its last, and
+/// B's a gap in A.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EndGaps<T> {
+    /// As any other gap in the other sequence
+    Charged,
+    /// Not at all; the overhang is left out of the aligned rows
+    Free,
+    /// With their own open and extend penalties, usually cheaper than internal gaps
+    Terminal { open: T, extend: T },
+}
+
+impl<T: Copy> EndGaps<T> {
+    /// `self`, unless `current` has terminal penalties, which are kept instead
+    pub fn unless_terminal(self, current: EndGaps<T>) -> Self {
+        match current {
+            EndGaps::Terminal { .. } => current,
+            _ => self,
+        }
+    }
+
+    /// Open and extend penalties, `internal` when charged as any other gap, or `None`
+    /// when free
+    pub fn penalties(&self, internal: (T, T)) -> Option<(T, T)> {
+        match *self {
+            EndGaps::Charged => Some(internal),
+            EndGaps::Free => None,
+            EndGaps::Terminal { open, extend } => Some((open, extend)),
+        }
+    }
+
+    fn map<U>(&self, mut f: impl FnMut(T) -> U) -> EndGaps<U> {
+        match *self {
+            EndGaps::Charged => EndGaps::Charged,
+            EndGaps::Free => EndGaps::Free,
+            EndGaps::Terminal { open, extend } => EndGaps::Terminal {
+                open: f(open),
+                extend: f(extend),
+            },
+        }
+    }
+
+    fn try_map<U, E>(&self, mut f: impl FnMut(T) -> Result<U, E>) -> Result<EndGaps<U>, E> {
+        Ok(match *self {
+            EndGaps::Charged => EndGaps::Charged,
+            EndGaps::Free => EndGaps::Free,
+            EndGaps::Terminal { open, extend } => EndGaps::Terminal {
+                open: f(open)?,
+                extend: f(extend)?,
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct GapPenalties<T> {
     pub dx: T,
     pub ex: T,
     pub dy: T,
     pub ey: T,
+    /// How residues at the ends of A left unaligned are charged
+    a_ends: EndGaps<T>,
+    /// How residues at the ends of B left unaligned are charged
+    b_ends: EndGaps<T>,
+    /// Taken off the open penalty of each end gap of a global alignment, leading or
+    /// trailing, in either sequence. When non-zero, free ends are charged as any other
+    /// gap less the bonus, so that unaligned ends cost something. Must not be negative.
+    pub end_gap_bonus: T,
 }
 
-impl<T: FromStr + Copy> GapPenalties<T> {
+impl<T: FromStr + Copy + Zero> GapPenalties<T> {
     pub fn new(dx: T, ex: T, dy: T, ey: T) -> Self {
-        GapPenalties { dx, ex, dy, ey }
+        GapPenalties {
+            dx,
+            ex,
+            dy,
+            ey,
+            a_ends: EndGaps::Charged,
+            b_ends: EndGaps::Charged,
+            end_gap_bonus: T::zero(),
+        }
+    }
+
+    /// Penalties applied to internal gaps only; gaps at the end of either sequence are free
+    pub fn free_end_gaps(internal_dx: T, internal_ex: T, internal_dy: T, internal_ey: T) -> Self {
+        Self::new(internal_dx, internal_ex, internal_dy, internal_ey)
+            .with_end_gaps(EndGaps::Free, EndGaps::Free)
+    }
+
+    /// Penalties for semi-global alignment of all of A against part of B: B may overhang
+    /// A at either end for free, while gaps at the ends of A are charged
+    pub fn free_b_end_gaps(dx: T, ex: T, dy: T, ey: T) -> Self {
+        Self::new(dx, ex, dy, ey).with_end_gaps(EndGaps::Charged, EndGaps::Free)
+    }
+
+    /// Penalties for soft-clipping A: A may overhang B at either end for free, while gaps
+    /// at the ends of B are charged
+    pub fn free_a_end_gaps(dx: T, ex: T, dy: T, ey: T) -> Self {
+        Self::new(dx, ex, dy, ey).with_end_gaps(EndGaps::Free, EndGaps::Charged)
+    }
+}
+
+impl<T: FromStr + Copy> GapPenalties<T> {
+    /// Replace the penalties, keeping how end gaps are charged
+    pub fn with_penalties(&self, dx: T, ex: T, dy: T, ey: T) -> Self {
+        GapPenalties {
+            dx,
+            ex,
+            dy,
+            ey,
+            ..*self
+        }
+    }
+
+    /// Replace how the ends of A and of B are charged, keeping the penalties
+    pub fn with_end_gaps(&self, a_ends: EndGaps<T>, b_ends: EndGaps<T>) -> Self {
+        GapPenalties {
+            a_ends,
+            b_ends,
+            ..*self
+        }
+    }
+
+    /// Replace the bonus taken off the open penalty of end gaps
+    pub fn with_end_gap_bonus(&self, end_gap_bonus: T) -> Self {
+        GapPenalties {
+            end_gap_bonus,
+            ..*self
+        }
+    }
+
+    /// The penalties with the roles of A and B exchanged: `dx` and `ex` with `dy` and
+    /// `ey`, and how the ends of A are charged with how those of B are
+    pub fn swapped(&self) -> Self {
+        GapPenalties {
+            dx: self.dy,
+            ex: self.ey,
+            dy: self.dx,
+            ey: self.ex,
+            a_ends: self.b_ends,
+            b_ends: self.a_ends,
+            end_gap_bonus: self.end_gap_bonus,
+        }
+    }
+
+    /// Convert each penalty with `f`, keeping the end-gap treatment
+    pub fn try_map<U, E>(
+        &self,
+        mut f: impl FnMut(T) -> Result<U, E>,
+    ) -> Result<GapPenalties<U>, E> {
+        Ok(GapPenalties {
+            dx: f(self.dx)?,
+            ex: f(self.ex)?,
+            dy: f(self.dy)?,
+            ey: f(self.ey)?,
+            a_ends: self.a_ends.try_map(&mut f)?,
+            b_ends: self.b_ends.try_map(&mut f)?,
+            end_gap_bonus: f(self.end_gap_bonus)?,
+        })
+    }
+}
+
+impl<T: Copy + Into<f64>> GapPenalties<T> {
+    /// Divide the penalties for gaps in A (`dx`, `ex`) by `sqrt(len_a)` and those for gaps
+    /// in B (`dy`, `ey`) by `sqrt(len_b)`, terminal penalties included. An empty sequence
+    /// leaves its penalties unchanged.
+    pub fn scale_by_length(&self, len_a: usize, len_b: usize) -> GapPenalties<f64> {
+        let factor = |len: usize| if len > 0 { (len as f64).sqrt() } else { 1.0 };
+        let (factor_a, factor_b) = (factor(len_a), factor(len_b));
+        GapPenalties {
+            dx: self.dx.into() / factor_a,
+            ex: self.ex.into() / factor_a,
+            dy: self.dy.into() / factor_b,
+            ey: self.ey.into() / factor_b,
+            // A's overhang is a gap in B, and B's a gap in A
+            a_ends: self.a_ends.map(|penalty| penalty.into() / factor_b),
+            b_ends: self.b_ends.map(|penalty| penalty.into() / factor_a),
+            // Discounts penalties of either sequence, so it is left as it is
+            end_gap_bonus: self.end_gap_bonus.into(),
+        }
+    }
+}
+
+impl GapPenalties<f64> {
+    /// Treat each penalty as a probability and convert it to the cost `-ln(p)`
+    pub fn to_log_costs(&self) -> Self {
+        GapPenalties {
+            dx: -self.dx.ln(),
+            ex: -self.ex.ln(),
+            dy: -self.dy.ln(),
+            ey: -self.ey.ln(),
+            a_ends: self.a_ends.map(|p| -p.ln()),
+            b_ends: self.b_ends.map(|p| -p.ln()),
+            end_gap_bonus: self.end_gap_bonus,
+        }
     }
 }
 
-impl<T: FromStr + Copy> GapPenalties<T>
+impl<T: Copy + Zero> GapPenalties<T> {
+    /// How the ends of A are charged: as set, except that an end-gap bonus charges free
+    /// ends as any other gap
+    pub fn a_end_gaps(&self) -> EndGaps<T> {
+        self.with_bonus(self.a_ends)
+    }
+
+    /// How the ends of B are charged, as for [`Self::a_end_gaps`]
+    pub fn b_end_gaps(&self) -> EndGaps<T> {
+        self.with_bonus(self.b_ends)
+    }
+
+    fn with_bonus(&self, ends: EndGaps<T>) -> EndGaps<T> {
+        match ends {
+            EndGaps::Free if !self.end_gap_bonus.is_zero() => EndGaps::Charged,
+            ends => ends,
+        }
+    }
+
+    pub fn has_free_end_gaps(&self) -> bool {
+        self.has_free_a_ends() || self.has_free_b_ends()
+    }
+
+    pub fn has_free_a_ends(&self) -> bool {
+        matches!(self.a_end_gaps(), EndGaps::Free)
+    }
+
+    pub fn has_free_b_ends(&self) -> bool {
+        matches!(self.b_end_gaps(), EndGaps::Free)
+    }
+
+    /// Whether a gap in B (Ix) at column `col` of `ncol` leaves the end of A unaligned
+    /// for free
+    pub fn is_free_a_end(&self, col: usize, ncol: usize) -> bool {
+        self.has_free_a_ends() && col + 1 == ncol
+    }
+
+    /// Whether a gap in A (Iy) at row `row` of `nrow` leaves the end of B unaligned for
+    /// free
+    pub fn is_free_b_end(&self, row: usize, nrow: usize) -> bool {
+        self.has_free_b_ends() && row + 1 == nrow
+    }
+}
+
+impl<T: Copy + Zero + Sub<Output = T>> GapPenalties<T> {
+    /// Open and extend penalties for residues of A left unaligned at its ends, a gap in
+    /// B, less the end-gap bonus; `None` when they are free
+    pub fn a_end_penalties(&self) -> Option<(T, T)> {
+        self.a_end_gaps()
+            .penalties((self.dy, self.ey))
+            .map(|(open, extend)| (open - self.end_gap_bonus, extend))
+    }
+
+    /// Open and extend penalties for residues of B left unaligned at its ends, a gap in
+    /// A, less the end-gap bonus; `None` when they are free
+    pub fn b_end_penalties(&self) -> Option<(T, T)> {
+        self.b_end_gaps()
+            .penalties((self.dx, self.ex))
+            .map(|(open, extend)| (open - self.end_gap_bonus, extend))
+    }
+
+    /// Open and extend penalties for a gap in B (Ix) at column `col` of `ncol`: those for
+    /// the ends of A in the last column, where the gap follows all of B
+    pub fn y_penalties(&self, col: usize, ncol: usize) -> (T, T) {
+        if col + 1 != ncol {
+            return (self.dy, self.ey);
+        }
+        self.a_end_penalties().unwrap_or((T::zero(), T::zero()))
+    }
+
+    /// Open and extend penalties for a gap in A (Iy) at row `row` of `nrow`: those for the
+    /// ends of B in the last row, where the gap follows all of A
+    pub fn x_penalties(&self, row: usize, nrow: usize) -> (T, T) {
+        if row + 1 != nrow {
+            return (self.dx, self.ex);
+        }
+        self.b_end_penalties().unwrap_or((T::zero(), T::zero()))
+    }
+}
+
+impl<T: Copy + Display + PartialOrd + Zero> GapPenalties<T> {
+    /// Fail for a negative end-gap bonus, for one in a local alignment, which has no end
+    /// gaps, or for one larger than the open penalty of the end gaps of either sequence,
+    /// which would score leaving residues unaligned above aligning them for nothing
+    pub fn check_end_gap_bonus(&self, global: bool) -> Result<(), AlignmentError> {
+        if self.end_gap_bonus.is_zero() {
+            return Ok(());
+        }
+        if self.end_gap_bonus < T::zero() {
+            return Err(AlignmentError::InvalidInput(format!(
+                "end-gap bonus {} must not be negative",
+                self.end_gap_bonus
+            )));
+        }
+        if !global {
+            return Err(AlignmentError::IncompatibleParameters(
+                "an end-gap bonus needs a global alignment".to_string(),
+            ));
+        }
+        let ends = [
+            ("A", self.a_end_gaps().penalties((self.dy, self.ey))),
+            ("B", self.b_end_gaps().penalties((self.dx, self.ex))),
+        ];
+        for (sequence, penalties) in ends {
+            if let Some((open, _)) = penalties.filter(|(open, _)| self.end_gap_bonus > *open) {
+                return Err(AlignmentError::InvalidInput(format!(
+                    "end-gap bonus {} exceeds the open penalty {} of end gaps in {}",
+                    self.end_gap_bonus, open, sequence
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: FromStr + Copy + Zero> FromStr for GapPenalties<T>
 where
     <T as FromStr>::Err: Display,
 {
-    pub fn read_gap_penalties(lines: &mut Lines<BufReader<File>>) -> io::Result<Self> {
-        let gaps_line = lines
-            .next()
-            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing gap penalties"))??;
-        let gaps = gaps_line
-            .split_whitespace()
-            .map(|s| s.parse::<T>())
+    type Err = AlignmentError;
+
+    /// Parse `dx ex dy ey`, separated by whitespace or commas, e.g. `"2 1 2 1"`, optionally
+    /// followed by terminal open and extend penalties for the ends of A and then of B,
+    /// e.g. `"2 1 2 1 1 0.5 1 0.5"`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let gaps = s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|value| !value.is_empty())
+            .map(|value| value.parse::<T>())
             .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| {
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("Invalid gap values: {}", e),
-                )
-            })?;
+            .map_err(|e| AlignmentError::InvalidInput(format!("Invalid gap values: {}", e)))?;
 
         if gaps.len() < 4 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Not enough gap penalty values",
+            return Err(AlignmentError::InvalidInput(
+                "Not enough gap penalty values".to_string(),
             ));
         }
 
-        Ok(GapPenalties::new(gaps[0], gaps[1], gaps[2], gaps[3]))
+        let penalties = GapPenalties::new(gaps[0], gaps[1], gaps[2], gaps[3]);
+        Ok(match gaps[..] {
+            [_, _, _, _, a_open, a_extend, b_open, b_extend, ..] => penalties.with_end_gaps(
+                EndGaps::Terminal {
+                    open: a_open,
+                    extend: a_extend,
+                },
+                EndGaps::Terminal {
+                    open: b_open,
+                    extend: b_extend,
+                },
+            ),
+            _ => penalties,
+        })
+    }
+}
+
+impl<T: FromStr + Copy + Zero> GapPenalties<T>
+where
+    <T as FromStr>::Err: Display,
+{
+    pub fn read_gap_penalties(
+        lines: &mut impl Iterator<Item = io::Result<String>>,
+    ) -> io::Result<Self> {
+        let gaps_line = lines
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing gap penalties"))??;
+        gaps_line.parse().map_err(|e| match e {
+            AlignmentError::InvalidInput(message) => {
+                io::Error::new(io::ErrorKind::InvalidData, message)
+            }
+            e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+        })
+    }
+}
+
+/// Hard limits on where gaps may go, enforced by
+/// [`AlignGrid::populate_constrained`](crate::models::AlignGrid::populate_constrained).
+///
+/// Both apply to the gap columns of an alignment. Residues left unaligned at the ends, as
+/// leading gaps charged through the boundary, free end gaps or soft clips, are not gap
+/// columns and are not limited.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GapConstraints {
+    /// Longest gap allowed, in columns; `Some(0)` allows no gap at all
+    pub max_gap_length: Option<usize>,
+    /// Residues of A, 0-based with an exclusive end, that may not be aligned to a gap
+    /// nor have a gap in A between two of them
+    pub no_gap_region_a: Option<Range<usize>>,
+}
+
+impl GapConstraints {
+    /// Whether neither constraint is set
+    pub fn is_empty(&self) -> bool {
+        self.max_gap_length.is_none() && self.no_gap_region_a.is_none()
+    }
+
+    /// Fail for a no-gap region that is empty or runs past the end of a sequence A of
+    /// `len_a` residues
+    pub fn check_region(&self, len_a: usize) -> Result<(), AlignmentError> {
+        match &self.no_gap_region_a {
+            Some(region) if region.is_empty() || region.end > len_a => {
+                Err(AlignmentError::InvalidInput(format!(
+                    "no-gap region {}:{} does not fit sequence A of length {}",
+                    region.start + 1,
+                    region.end,
+                    len_a
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether residue `row` of A (1-based, as a grid row) may be aligned to a gap in B
+    pub(crate) fn allows_gap_in_b(&self, row: usize) -> bool {
+        self.max_gap_length != Some(0)
+            && !self
+                .no_gap_region_a
+                .as_ref()
+                .is_some_and(|region| region.contains(&(row - 1)))
+    }
+
+    /// Whether a gap in A may follow the first `row` residues of A
+    pub(crate) fn allows_gap_in_a(&self, row: usize) -> bool {
+        self.max_gap_length != Some(0)
+            && !self
+                .no_gap_region_a
+                .as_ref()
+                .is_some_and(|region| region.start < row && row < region.end)
+    }
+
+    /// Whether a gap that already runs `length` columns may be extended by one more
+    pub(crate) fn allows_extension(&self, length: usize) -> bool {
+        self.max_gap_length.is_none_or(|max| length < max)
+    }
+}
+
+/// Gap penalties for coding sequences, used by
+/// [`AlignGrid::populate_codon_aware`](crate::models::AlignGrid::populate_codon_aware).
+///
+/// A gap that opens on a codon boundary of A, where it can remove or insert whole codons
+/// without shifting the reading frame, is usually less costly than one that opens within
+/// a codon, so the two are charged apart. The same penalties apply to gaps in either
+/// sequence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CodonAwareGapPenalties {
+    /// Penalty for opening a gap on a codon boundary
+    pub in_frame_open: f64,
+    /// Penalty for opening a gap within a codon
+    pub out_of_frame_open: f64,
+    pub extend: f64,
+}
+
+impl CodonAwareGapPenalties {
+    pub fn new(in_frame_open: f64, out_of_frame_open: f64, extend: f64) -> Self {
+        Self {
+            in_frame_open,
+            out_of_frame_open,
+            extend,
+        }
+    }
+
+    /// Penalty for opening a gap after the first `residues_a` residues of A, whose first
+    /// residue is at position `frame_offset` (0, 1 or 2) of its codon
+    pub fn open_after(&self, residues_a: usize, frame_offset: u8) -> f64 {
+        if (residues_a + usize::from(frame_offset)).is_multiple_of(3) {
+            self.in_frame_open
+        } else {
+            self.out_of_frame_open
+        }
     }
 }
@@ -1,34 +1,123 @@
+use crate::models::residue::{lower_case, upper_case};
+use crate::models::{Alphabet, GapPenalties, Residue};
 use num_traits::Zero;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Display;
 use std::fs::File;
 use std::io;
-use std::io::{BufReader, Lines};
+use std::io::{BufRead, BufReader};
 use std::str::FromStr;
 
 /// Match matrix stores the scores of matches between characters
 #[derive(Debug, Clone)]
-pub struct MatchMatrix<T> {
-    scores: HashMap<char, HashMap<char, T>>,
+pub struct MatchMatrix<T, S = char> {
+    scores: HashMap<S, HashMap<S, T>>,
 }
 
-impl<T: Copy + FromStr + Zero> MatchMatrix<T> {
+impl<T: PartialEq, S: Residue> PartialEq for MatchMatrix<T, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.scores == other.scores
+    }
+}
+
+impl<T: Copy + FromStr + Zero, S: Residue> Default for MatchMatrix<T, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy + FromStr + Zero, S: Residue> MatchMatrix<T, S> {
     pub fn new() -> Self {
         Self {
             scores: HashMap::new(),
         }
     }
 
+    /// Convert every score with `f`
+    pub fn try_map<U, E>(
+        &self,
+        mut f: impl FnMut(T) -> Result<U, E>,
+    ) -> Result<MatchMatrix<U, S>, E> {
+        let mut scores = HashMap::with_capacity(self.scores.len());
+        for (a, row) in &self.scores {
+            let row = row
+                .iter()
+                .map(|(b, &score)| Ok((b.clone(), f(score)?)))
+                .collect::<Result<_, E>>()?;
+            scores.insert(a.clone(), row);
+        }
+        Ok(MatchMatrix { scores })
+    }
+
+    /// Scores `match_score` for identical residues and `mismatch_score` for every other
+    /// pair drawn from the two alphabets
+    pub fn simple(alphabet_a: &[S], alphabet_b: &[S], match_score: T, mismatch_score: T) -> Self {
+        let mut match_matrix = Self::new();
+        for a in alphabet_a {
+            for b in alphabet_b {
+                let score = if a == b { match_score } else { mismatch_score };
+                match_matrix.set_score(a.clone(), b.clone(), score);
+            }
+        }
+        match_matrix
+    }
+
+    /// Scores `f(a, b)` for every pair of `a` from `alphabet_a` and `b` from `alphabet_b`,
+    /// for building a matrix in code rather than reading one
+    pub fn from_fn(
+        alphabet_a: &Alphabet<S>,
+        alphabet_b: &Alphabet<S>,
+        f: impl Fn(&S, &S) -> T,
+    ) -> Self {
+        let mut match_matrix = Self::new();
+        for a in alphabet_a.symbols() {
+            for b in alphabet_b.symbols() {
+                match_matrix.set_score(a.clone(), b.clone(), f(a, b));
+            }
+        }
+        match_matrix
+    }
+
+    /// The score for a pair, computing it with `f` and storing it if the pair has none
+    /// yet, so that an expensive scoring function runs only for the pairs looked up
+    pub fn get_or_insert_with<F: FnOnce(&S, &S) -> T>(&mut self, a: &S, b: &S, f: F) -> T {
+        *self
+            .scores
+            .entry(a.clone())
+            .or_default()
+            .entry(b.clone())
+            .or_insert_with(|| f(a, b))
+    }
+
     /// Updates or adds a score for a specified match
-    fn set_score(&mut self, a: char, b: char, score: T) {
-        self.scores
-            .entry(a)
-            .or_insert_with(HashMap::new)
-            .insert(b, score);
+    pub(crate) fn set_score(&mut self, a: S, b: S, score: T) {
+        self.scores.entry(a).or_default().insert(b, score);
+    }
+
+    fn parse_score(field: &str) -> io::Result<T>
+    where
+        <T as FromStr>::Err: Display,
+    {
+        field.parse().map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("Invalid score: {}", e))
+        })
+    }
+
+    pub fn read_match_matrix(
+        lines: &mut impl Iterator<Item = io::Result<String>>,
+    ) -> io::Result<Self>
+    where
+        <T as FromStr>::Err: Display,
+    {
+        Ok(Self::read_match_matrix_until(lines)?.0)
     }
 
-    pub fn read_match_matrix(lines: &mut Lines<BufReader<File>>) -> io::Result<Self>
+    /// Read match scores, also returning the line that ended the matrix, if any
+    pub fn read_match_matrix_until(
+        lines: &mut impl Iterator<Item = io::Result<String>>,
+    ) -> io::Result<(Self, Option<String>)>
     where
-        <T as FromStr>::Err: std::fmt::Display,
+        <T as FromStr>::Err: Display,
     {
         let mut match_matrix = Self::new();
         for line in lines {
@@ -36,21 +125,47 @@ impl<T: Copy + FromStr + Zero> MatchMatrix<T> {
             let parts: Vec<&str> = line.split_whitespace().collect();
 
             if parts.len() < 5 {
-                break;
+                return Ok((match_matrix, Some(line)));
             }
 
-            // Format: "match" "(" a b score ")"
-            let a = parts[2]
-                .chars()
-                .next()
+            // Format: index_a index_b a b score
+            let a = S::parse_token(parts[2])
                 .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid character A"))?;
-            let b = parts[3]
-                .chars()
-                .next()
+            let b = S::parse_token(parts[3])
                 .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid character B"))?;
-            let score = parts[4].parse().map_err(|e| {
-                io::Error::new(io::ErrorKind::InvalidData, format!("Invalid score: {}", e))
-            })?;
+            let score = Self::parse_score(parts[4])?;
+
+            match_matrix.set_score(a, b, score);
+        }
+        Ok((match_matrix, None))
+    }
+
+    /// Load a score file with one `a b score` triple per line
+    pub fn load_pair_scores(path: &str) -> io::Result<Self>
+    where
+        <T as FromStr>::Err: Display,
+    {
+        let reader = BufReader::new(File::open(path)?);
+        let mut match_matrix = Self::new();
+        for line in reader.lines() {
+            let line = line?;
+            let parts: Vec<&str> = line.split_whitespace().collect();
+
+            if parts.is_empty() {
+                continue;
+            }
+            if parts.len() != 3 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Expected `a b score`, got: {}", line),
+                ));
+            }
+
+            let a = S::parse_token(parts[0])
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid token A"))?;
+            let b = S::parse_token(parts[1])
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid token B"))?;
+            let score = Self::parse_score(parts[2])?;
 
             match_matrix.set_score(a, b, score);
         }
@@ -58,11 +173,214 @@ impl<T: Copy + FromStr + Zero> MatchMatrix<T> {
     }
 
     /// Returns the score for a particular match
-    pub fn get_score(&self, a: char, b: char) -> T {
+    pub fn get_score(&self, a: &S, b: &S) -> T {
         *self
             .scores
-            .get(&a)
-            .and_then(|m| m.get(&b))
+            .get(a)
+            .and_then(|m| m.get(b))
             .unwrap_or(&T::zero())
     }
+
+    /// Whether a score is defined for the pair
+    pub fn has_score(&self, a: &S, b: &S) -> bool {
+        self.scores.get(a).is_some_and(|m| m.contains_key(b))
+    }
+
+    /// Iterates over all defined (a, b, score) triples, sorted by `a` and then `b` so
+    /// that the order does not depend on the hash seed
+    pub fn iter(&self) -> impl Iterator<Item = (S, S, T)> + '_ {
+        let mut triples: Vec<(S, S, T)> = self
+            .scores
+            .iter()
+            .flat_map(|(a, row)| {
+                row.iter()
+                    .map(move |(b, &score)| (a.clone(), b.clone(), score))
+            })
+            .collect();
+        triples.sort_by(|x, y| (&x.0, &x.1).cmp(&(&y.0, &y.1)));
+        triples.into_iter()
+    }
+
+    /// Number of defined pairs
+    pub fn len(&self) -> usize {
+        self.scores.values().map(HashMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Mirror every defined pair, so that `(b, a)` scores the same as `(a, b)`.
+    ///
+    /// Where both orders are defined with different scores, the pair whose first residue
+    /// sorts first wins.
+    pub fn symmetrize(&mut self) {
+        let mut pairs: Vec<(S, S)> = self.iter().map(|(a, b, _)| (a, b)).collect();
+        pairs.sort();
+        for (a, b) in pairs {
+            let score = self.get_score(&a, &b);
+            self.set_score(b, a, score);
+        }
+    }
+
+    /// The matrix with the roles of the residues exchanged, `(b, a)` scoring what
+    /// `(a, b)` scores here, for aligning the sequences the other way round
+    pub fn transpose(&self) -> Self {
+        let mut transposed = Self::new();
+        for (a, b, score) in self.iter() {
+            transposed.set_score(b, a, score);
+        }
+        transposed
+    }
+
+    /// Check that `score(a, b) == score(b, a)` for every defined pair, an undefined pair
+    /// scoring zero. On failure, returns each offending pair once, with `a` sorting
+    /// before `b`, together with `score(a, b)` and `score(b, a)`.
+    pub fn validate_symmetry(&self) -> Result<(), Vec<(S, S, T, T)>>
+    where
+        T: PartialEq,
+    {
+        let pairs: BTreeSet<(S, S)> = self
+            .iter()
+            .map(|(a, b, _)| if a <= b { (a, b) } else { (b, a) })
+            .collect();
+        let asymmetric: Vec<_> = pairs
+            .into_iter()
+            .map(|(a, b)| {
+                let (forward, reverse) = (self.get_score(&a, &b), self.get_score(&b, &a));
+                (a, b, forward, reverse)
+            })
+            .filter(|(_, _, forward, reverse)| forward != reverse)
+            .collect();
+        if asymmetric.is_empty() {
+            Ok(())
+        } else {
+            Err(asymmetric)
+        }
+    }
+
+    /// Check that a score is defined for every pair of a residue of `alpha_a` with one of
+    /// `alpha_b`. On failure, returns the missing pairs, which [`Self::get_score`] scores
+    /// zero, in the order of the alphabets.
+    pub fn validate_completeness(
+        &self,
+        alpha_a: &Alphabet<S>,
+        alpha_b: &Alphabet<S>,
+    ) -> Result<(), Vec<(S, S)>> {
+        let missing: Vec<(S, S)> = alpha_a
+            .symbols()
+            .iter()
+            .flat_map(|a| alpha_b.symbols().iter().map(move |b| (a, b)))
+            .filter(|(a, b)| !self.has_score(a, b))
+            .map(|(a, b)| (a.clone(), b.clone()))
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(missing)
+        }
+    }
+
+    /// Characters that appear as the first element of a defined pair, in sorted order
+    pub fn chars_a(&self) -> impl Iterator<Item = S> + '_ {
+        self.scores
+            .keys()
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+    }
+
+    /// Characters that appear as the second element of a defined pair, without repeats
+    /// and in sorted order
+    pub fn chars_b(&self) -> impl Iterator<Item = S> + '_ {
+        self.scores
+            .values()
+            .flat_map(HashMap::keys)
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+    }
+}
+
+/// Down-weighting of soft-masked residues of sequence A, written in lower case as in
+/// repeat-masked genome assemblies
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SoftMaskMode {
+    /// Factor on the score of a lower-case residue of A against any residue of B: 0
+    /// masks it fully, 1 ignores the masking
+    pub penalty_factor: f64,
+}
+
+impl<S: Residue> MatchMatrix<f64, S> {
+    /// These scores extended to the lower-case forms of their residues for `mode`: a
+    /// lower-case residue of A scores `penalty_factor` times its upper-case form, and a
+    /// lower-case residue of B the same as its upper-case form. Scores of pairs with a
+    /// lower-case residue are derived only from the upper-case ones, replacing any the
+    /// matrix gives them itself.
+    pub fn with_soft_mask(&self, mode: SoftMaskMode) -> Self {
+        let mut masked = self.clone();
+        let has_lower_case = |residue: &S| upper_case(residue).is_some();
+        for (a, b, score) in self.iter() {
+            if has_lower_case(&a) || has_lower_case(&b) {
+                continue;
+            }
+            let (lower_a, lower_b) = (lower_case(&a), lower_case(&b));
+            for b in [Some(b), lower_b].into_iter().flatten() {
+                if let Some(lower_a) = &lower_a {
+                    masked.set_score(lower_a.clone(), b.clone(), mode.penalty_factor * score);
+                }
+                masked.set_score(a.clone(), b, score);
+            }
+        }
+        masked
+    }
+
+    /// Convert match probabilities to log-probabilities; zero becomes negative infinity
+    pub fn to_log_probabilities(&self) -> MatchMatrix<f64, S> {
+        MatchMatrix {
+            scores: self
+                .scores
+                .iter()
+                .map(|(a, row)| {
+                    let row = row.iter().map(|(b, p)| (b.clone(), p.ln())).collect();
+                    (a.clone(), row)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl MatchMatrix<f64> {
+    /// Scoring for DNA/DNA alignment with G-T wobble pairs as near-matches.
+    ///
+    /// Watson-Crick pairs (A-T, G-C) score `match_score`, the near-complementary pairs
+    /// G-T and A-C score `wobble_score`, and every other pair of `ACGT` scores
+    /// `mismatch_score`, in either order. The gap penalties use `gap_open` for `dx` and
+    /// `dy` and `gap_ext` for `ex` and `ey`.
+    pub fn dna_with_wobble(
+        match_score: f64,
+        wobble_score: f64,
+        mismatch_score: f64,
+        gap_open: f64,
+        gap_ext: f64,
+    ) -> (MatchMatrix<f64>, GapPenalties<f64>) {
+        let pair = |a: char, b: char, x: char, y: char| (a == x && b == y) || (a == y && b == x);
+        let mut match_matrix = Self::new();
+        for a in "ACGT".chars() {
+            for b in "ACGT".chars() {
+                let score = if pair(a, b, 'A', 'T') || pair(a, b, 'G', 'C') {
+                    match_score
+                } else if pair(a, b, 'G', 'T') || pair(a, b, 'A', 'C') {
+                    wobble_score
+                } else {
+                    mismatch_score
+                };
+                match_matrix.set_score(a, b, score);
+            }
+        }
+        (
+            match_matrix,
+            GapPenalties::new(gap_open, gap_ext, gap_open, gap_ext),
+        )
+    }
 }
@@ -1,12 +1,28 @@
+mod alignment;
 mod alignment_grid;
+mod alignment_result;
 mod alphabet;
+pub mod builtin;
+mod column;
+mod coordinate_map;
+mod frequency_profile;
 mod gap;
 mod match_matrix;
+mod residue;
 pub mod score_matrix;
+pub mod score_store;
 mod sequences;
 
-pub use alignment_grid::AlignGrid;
+pub use alignment::{AlignedPair, Alignment, AlignmentOp, CoveredRange};
+pub use alignment_grid::{log_sum_exp, AlignGrid, LogSpaceAlignGrid, LogSpaceMode};
+pub(crate) use alignment_result::write_fasta_alignment;
+pub use alignment_result::{AlignmentResult, StartInfo, TracebackLimit, Truncation};
 pub use alphabet::Alphabet;
-pub use gap::GapPenalties;
-pub use match_matrix::MatchMatrix;
+pub use column::{Column, ColumnKind, Columns};
+pub use coordinate_map::{CoordinateMap, MappedInterval, MappedPos};
+pub use frequency_profile::FrequencyProfile;
+pub(crate) use frequency_profile::ScoringSource;
+pub use gap::{CodonAwareGapPenalties, EndGaps, GapConstraints, GapPenalties};
+pub use match_matrix::{MatchMatrix, SoftMaskMode};
+pub use residue::{Residue, TokenFormat, GAP_CHAR};
 pub use sequences::Sequences;
@@ -0,0 +1,104 @@
+use std::fmt::{Display, Write};
+use std::hash::Hash;
+
+/// A single position in a sequence: a `char` by default, or a multi-character token
+pub trait Residue: Clone + Display + Eq + Hash + Ord + Send + Sync {
+    /// Split one input line into residues
+    fn tokenize(line: &str) -> Vec<Self>;
+
+    /// Parse a single residue from a whitespace-delimited field
+    fn parse_token(field: &str) -> Option<Self>;
+
+    /// Append this residue to an output string
+    fn push_to(&self, out: &mut String) {
+        let _ = write!(out, "{}", self);
+    }
+}
+
+impl Residue for char {
+    fn tokenize(line: &str) -> Vec<Self> {
+        line.chars().collect()
+    }
+
+    fn parse_token(field: &str) -> Option<Self> {
+        field.chars().next()
+    }
+
+    fn push_to(&self, out: &mut String) {
+        out.push(*self);
+    }
+}
+
+impl Residue for String {
+    fn tokenize(line: &str) -> Vec<Self> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn parse_token(field: &str) -> Option<Self> {
+        Some(field.to_string())
+    }
+}
+
+/// The residue written in upper case, if that differs from how it is written
+pub(crate) fn upper_case<S: Residue>(residue: &S) -> Option<S> {
+    let text = residue.to_string();
+    let upper = text.to_uppercase();
+    (upper != text).then(|| S::parse_token(&upper)).flatten()
+}
+
+/// The lower-case form of a residue written without lower-case letters, if it has one
+pub(crate) fn lower_case<S: Residue>(residue: &S) -> Option<S> {
+    let text = residue.to_string();
+    let lower = text.to_lowercase();
+    (lower != text && text.to_uppercase() == text)
+        .then(|| S::parse_token(&lower))
+        .flatten()
+}
+
+/// Gap character used in character alignments
+pub const GAP_CHAR: char = '_';
+
+/// How aligned residues are joined and how gaps are written in the output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenFormat {
+    pub separator: String,
+    pub gap: String,
+}
+
+impl TokenFormat {
+    pub fn new(separator: &str, gap: &str) -> Self {
+        Self {
+            separator: separator.to_string(),
+            gap: gap.to_string(),
+        }
+    }
+
+    /// Default format for whitespace-separated token sequences
+    pub fn tokens() -> Self {
+        Self::new(" ", "-")
+    }
+
+    /// Join a row of aligned residues (`None` marks a gap) into an output string
+    pub fn render<S: Residue>(&self, row: &[Option<&S>]) -> String {
+        let mut out = String::with_capacity(row.len() * (1 + self.separator.len()));
+        for (i, residue) in row.iter().enumerate() {
+            if i > 0 {
+                out.push_str(&self.separator);
+            }
+            match residue {
+                Some(r) => r.push_to(&mut out),
+                None => out.push_str(&self.gap),
+            }
+        }
+        out
+    }
+}
+
+impl Default for TokenFormat {
+    fn default() -> Self {
+        Self {
+            separator: String::new(),
+            gap: GAP_CHAR.to_string(),
+        }
+    }
+}
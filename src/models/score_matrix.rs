@@ -1,9 +1,13 @@
-use ndarray::Array2;
+use crate::models::score_store::{ScoreStorage, ScoreStore, VecStore};
+use log::trace;
+use ndarray::{Array2, ArrayView2};
 use num_traits::Zero;
+use smallvec::SmallVec;
 use std::fmt::Display;
+use std::io;
 
 /// Matrix type identifier
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum MatrixType {
     M,
     Ix,
@@ -23,66 +27,219 @@ impl MatrixType {
 /// Type alias for pointer entries (matrix_type, row, col)
 pub type Pointer = (MatrixType, usize, usize);
 
+/// Traceback pointers of one cell packed into a byte.
+///
+/// Every pointer of a cell steps back by the same offset: diagonally for M, up for Ix
+/// and left for Iy. Bits 0-2 record which of M, Ix and Iy the cell points into, and
+/// bits 3-4 the step, so the pointers are recovered from the byte and the cell's own
+/// coordinates. A cell without pointers is `0`.
+pub struct PointerByte;
+
+const STEP_SHIFT: u8 = 3;
+
+impl PointerByte {
+    fn matrix_bit(matrix: MatrixType) -> u8 {
+        match matrix {
+            MatrixType::M => 1,
+            MatrixType::Ix => 2,
+            MatrixType::Iy => 4,
+        }
+    }
+
+    /// Pack the pointers of cell `(cur_row, cur_col)`.
+    ///
+    /// Each pointer must step back to `(cur_row - 1, cur_col - 1)`, `(cur_row - 1,
+    /// cur_col)` or `(cur_row, cur_col - 1)`, all by the same step.
+    pub fn encode(ptrs: &[Pointer], cur_row: usize, cur_col: usize) -> u8 {
+        let Some(&(_, row, col)) = ptrs.first() else {
+            return 0;
+        };
+        let step = match (cur_row - row, cur_col - col) {
+            (1, 1) => 0,
+            (1, 0) => 1,
+            (0, 1) => 2,
+            offset => panic!("Pointer offset {:?} is not a unit step", offset),
+        };
+        ptrs.iter()
+            .fold(step << STEP_SHIFT, |byte, &(matrix, r, c)| {
+                debug_assert!(
+                    (r, c) == (row, col),
+                    "Pointers of one cell must share a step"
+                );
+                byte | Self::matrix_bit(matrix)
+            })
+    }
+
+    /// Unpack the pointers of cell `(cur_row, cur_col)`, in M, Ix, Iy order
+    pub fn decode(b: u8, cur_row: usize, cur_col: usize) -> SmallVec<[Pointer; 3]> {
+        let (row, col) = match b >> STEP_SHIFT {
+            0 => (cur_row.wrapping_sub(1), cur_col.wrapping_sub(1)),
+            1 => (cur_row.wrapping_sub(1), cur_col),
+            _ => (cur_row, cur_col.wrapping_sub(1)),
+        };
+        [MatrixType::M, MatrixType::Ix, MatrixType::Iy]
+            .into_iter()
+            .filter(|&matrix| b & Self::matrix_bit(matrix) != 0)
+            .map(|matrix| (matrix, row, col))
+            .collect()
+    }
+}
+
 /// Score matrix used during the alignment process
+#[derive(Clone)]
 pub struct ScoreMatrix<T> {
     pub matrix_type: MatrixType,
     pub nrow: usize,
     pub ncol: usize,
-    pub scores: Array2<T>,
-    pub pointers: Vec<Vec<Vec<Pointer>>>,
+    pub scores: ScoreStorage<T>,
+    /// Traceback pointers of each cell, packed by [`PointerByte`]
+    pub pointers: ScoreStorage<u8>,
+}
+
+impl<T> ScoreMatrix<T> {
+    /// Approximate heap usage of the scores and pointers, in bytes; none for a matrix
+    /// stored on disk
+    pub fn memory_bytes(&self) -> usize {
+        self.scores.heap_bytes() + self.pointers.heap_bytes()
+    }
+
+    /// Heap usage of the scores and pointers of an `nrow` x `ncol` matrix, in bytes
+    pub fn estimate_memory_bytes(nrow: usize, ncol: usize) -> usize {
+        nrow * ncol * (size_of::<T>() + size_of::<u8>())
+    }
 }
 
 impl<T: Zero + Copy + Clone + Display> ScoreMatrix<T> {
     pub fn new(matrix_type: MatrixType, nrow: usize, ncol: usize) -> Self {
-        let scores = Array2::zeros((nrow, ncol));
-        let pointers = vec![vec![Vec::new(); ncol]; nrow];
+        Self {
+            matrix_type,
+            nrow,
+            ncol,
+            scores: ScoreStorage::Heap(VecStore::zeros(nrow, ncol)),
+            pointers: ScoreStorage::Heap(VecStore::zeros(nrow, ncol)),
+        }
+    }
+
+    /// Wrap an existing score array; pointers start out empty
+    pub fn from_array2(matrix_type: MatrixType, scores: Array2<T>) -> Self {
+        let (nrow, ncol) = scores.dim();
+        Self {
+            matrix_type,
+            nrow,
+            ncol,
+            scores: ScoreStorage::Heap(VecStore::from_array2(&scores)),
+            pointers: ScoreStorage::Heap(VecStore::zeros(nrow, ncol)),
+        }
+    }
 
+    /// Wrap `nrow` x `ncol` scores given row by row; pointers start out empty.
+    ///
+    /// Panics if `data` does not hold `nrow * ncol` scores.
+    pub fn from_vec(matrix_type: MatrixType, nrow: usize, ncol: usize, data: Vec<T>) -> Self {
+        assert_eq!(
+            data.len(),
+            nrow * ncol,
+            "a {} x {} score matrix needs {} scores",
+            nrow,
+            ncol,
+            nrow * ncol
+        );
         Self {
             matrix_type,
             nrow,
             ncol,
-            scores,
-            pointers,
+            scores: ScoreStorage::Heap(VecStore::from_vec(data, ncol)),
+            pointers: ScoreStorage::Heap(VecStore::zeros(nrow, ncol)),
         }
     }
 
+    /// The scores as an `nrow` x `ncol` array
+    pub fn as_array2(&self) -> ArrayView2<'_, T> {
+        self.scores.view(self.nrow, self.ncol)
+    }
+
+    pub fn into_array2(self) -> Array2<T> {
+        self.as_array2().to_owned()
+    }
+
     pub fn get_score(&self, row: usize, col: usize) -> T {
-        self.scores[[row, col]]
+        self.scores.get(row, col)
     }
 
     pub fn set_score(&mut self, row: usize, col: usize, score: T) {
-        self.scores[[row, col]] = score;
+        self.scores.set(row, col, score);
     }
 
-    pub fn get_pointers(&self, row: usize, col: usize) -> &[Pointer] {
-        &self.pointers[row][col]
+    /// Clear the scores and pointers for an `nrow` x `ncol` matrix, in place when it
+    /// already has that shape, and keeping them on disk if they were there
+    pub fn reset(&mut self, nrow: usize, ncol: usize) -> io::Result<()> {
+        if (self.nrow, self.ncol) == (nrow, ncol) {
+            self.zeros();
+            self.pointers.fill(0);
+            return Ok(());
+        }
+        self.scores.reallocate(nrow, ncol)?;
+        self.pointers.reallocate(nrow, ncol)?;
+        (self.nrow, self.ncol) = (nrow, ncol);
+        Ok(())
+    }
+
+    /// Set every score to `value` without reallocating, leaving the pointers as they are
+    pub fn fill(&mut self, value: T) {
+        self.scores.fill(value);
+    }
+
+    /// Set every score to zero without reallocating, as [`Self::fill`] does
+    pub fn zeros(&mut self) {
+        self.fill(T::zero());
+    }
+
+    /// Scores of `row`, in column order
+    pub fn row_scores(&self, row: usize) -> &[T] {
+        self.scores.row_slice(row)
+    }
+
+    pub fn get_pointers(&self, row: usize, col: usize) -> SmallVec<[Pointer; 3]> {
+        PointerByte::decode(self.pointers.get(row, col), row, col)
     }
 
     pub fn set_pointers(&mut self, row: usize, col: usize, pointers: Vec<Pointer>) {
-        self.pointers[row][col] = pointers;
+        self.pointers
+            .set(row, col, PointerByte::encode(&pointers, row, col));
     }
 
-    /// Print scores for debugging
+    /// Log scores at trace level for debugging
     #[allow(dead_code)]
     pub fn print_scores(&self) {
-        println!("{}=", self.matrix_type.as_str());
+        trace!("{}=", self.matrix_type.as_str());
         for r in 0..self.nrow {
-            for c in 0..self.ncol {
-                print!("{:.1} ", self.scores[[r, c]]);
-            }
-            println!();
+            let row: Vec<String> = (0..self.ncol)
+                .map(|c| format!("{:.1}", self.get_score(r, c)))
+                .collect();
+            trace!("{}", row.join(" "));
         }
     }
 
-    /// Print pointers for debugging
+    /// Log pointers at trace level for debugging
     #[allow(dead_code)]
     pub fn print_pointers(&self) {
-        println!("{} Pointers=", self.matrix_type.as_str());
+        trace!("{} Pointers=", self.matrix_type.as_str());
         for r in 0..self.nrow {
-            for c in 0..self.ncol {
-                print!("{:?} ", self.pointers[r][c]);
-            }
-            println!();
+            let row: Vec<String> = (0..self.ncol)
+                .map(|c| format!("{:?}", self.get_pointers(r, c)))
+                .collect();
+            trace!("{}", row.join(" "));
         }
     }
 }
+
+#[cfg(feature = "numpy")]
+impl<T: Copy + ndarray_npy::WritableElement> ScoreMatrix<T> {
+    /// Write the scores as an `nrow` x `ncol` NumPy `.npy` array, for `np.load`
+    pub fn write_npy(&self, path: &str) -> io::Result<()> {
+        ndarray_npy::write_npy(path, &self.scores.view(self.nrow, self.ncol)).map_err(|e| match e {
+            ndarray_npy::WriteNpyError::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        })
+    }
+}
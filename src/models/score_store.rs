@@ -0,0 +1,312 @@
+use ndarray::{Array2, ArrayView2};
+use num_traits::Zero;
+#[cfg(feature = "mmap")]
+use std::fmt;
+use std::io;
+#[cfg(feature = "mmap")]
+use std::marker::PhantomData;
+#[cfg(feature = "mmap")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "mmap")]
+use std::str::FromStr;
+
+/// Row-major storage of the cells of a score matrix.
+///
+/// The fill writes the cells a row at a time and reads back only the row it is on and
+/// the one before, so a store that pages its cells in and out sees sequential access.
+pub trait ScoreStore<T> {
+    fn get(&self, row: usize, col: usize) -> T;
+    fn set(&mut self, row: usize, col: usize, value: T);
+    /// The cells of `row`, in column order
+    fn row_slice(&self, row: usize) -> &[T];
+}
+
+/// Where the DP matrices of a grid are kept
+#[cfg(feature = "mmap")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatrixStorage {
+    #[default]
+    Heap,
+    /// Memory-mapped temporary files; see [`DiskStore`]
+    Disk,
+}
+
+#[cfg(feature = "mmap")]
+impl MatrixStorage {
+    pub const ALL: [MatrixStorage; 2] = [MatrixStorage::Heap, MatrixStorage::Disk];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatrixStorage::Heap => "heap",
+            MatrixStorage::Disk => "disk",
+        }
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl fmt::Display for MatrixStorage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl FromStr for MatrixStorage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "heap" => Ok(MatrixStorage::Heap),
+            "disk" => Ok(MatrixStorage::Disk),
+            _ => Err(format!("Unknown matrix storage: {}", s)),
+        }
+    }
+}
+
+/// Cells held in a heap `Vec`
+#[derive(Debug, Clone)]
+pub struct VecStore<T> {
+    cells: Vec<T>,
+    ncol: usize,
+}
+
+impl<T: Copy + Zero> VecStore<T> {
+    pub fn zeros(nrow: usize, ncol: usize) -> Self {
+        Self {
+            cells: vec![T::zero(); nrow * ncol],
+            ncol,
+        }
+    }
+}
+
+impl<T: Copy> VecStore<T> {
+    /// Cells from `cells`, row by row, `ncol` to a row
+    pub fn from_vec(cells: Vec<T>, ncol: usize) -> Self {
+        Self { cells, ncol }
+    }
+
+    pub fn from_array2(array: &Array2<T>) -> Self {
+        Self {
+            cells: array.iter().copied().collect(),
+            ncol: array.ncols(),
+        }
+    }
+}
+
+impl<T: Copy> ScoreStore<T> for VecStore<T> {
+    fn get(&self, row: usize, col: usize) -> T {
+        self.cells[row * self.ncol + col]
+    }
+
+    fn set(&mut self, row: usize, col: usize, value: T) {
+        self.cells[row * self.ncol + col] = value;
+    }
+
+    fn row_slice(&self, row: usize) -> &[T] {
+        &self.cells[row * self.ncol..(row + 1) * self.ncol]
+    }
+}
+
+/// Scores that may be stored in a memory-mapped file.
+///
+/// # Safety
+///
+/// Every bit pattern must be a valid value, all zero bits being zero, as the cells are
+/// read straight from the bytes of a zero-filled file.
+#[cfg(feature = "mmap")]
+pub unsafe trait DiskElement: Copy {}
+
+#[cfg(feature = "mmap")]
+unsafe impl DiskElement for f64 {}
+#[cfg(feature = "mmap")]
+unsafe impl DiskElement for f32 {}
+#[cfg(feature = "mmap")]
+unsafe impl DiskElement for i32 {}
+#[cfg(feature = "mmap")]
+unsafe impl DiskElement for i16 {}
+#[cfg(feature = "mmap")]
+unsafe impl DiskElement for i8 {}
+#[cfg(feature = "mmap")]
+unsafe impl DiskElement for u8 {}
+
+/// Cells held in a memory-mapped temporary file, which the OS pages in and out so that a
+/// grid may be larger than RAM.
+///
+/// The file is unlinked as soon as it is created, so it is removed when the store is
+/// dropped or the process exits, panics or is killed, and never appears in the directory.
+#[cfg(feature = "mmap")]
+#[derive(Debug)]
+pub struct DiskStore<T> {
+    map: memmap2::MmapMut,
+    dir: PathBuf,
+    nrow: usize,
+    ncol: usize,
+    _cells: PhantomData<T>,
+}
+
+#[cfg(feature = "mmap")]
+impl<T: DiskElement> DiskStore<T> {
+    /// A zero-filled store backed by a temporary file in `dir`
+    pub fn zeros(nrow: usize, ncol: usize, dir: &Path) -> io::Result<Self> {
+        Self::create(nrow, ncol, dir)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<T> DiskStore<T> {
+    /// Map a new zero-filled file; only for `DiskElement` types, or the types of an
+    /// existing store
+    fn create(nrow: usize, ncol: usize, dir: &Path) -> io::Result<Self> {
+        let file = tempfile::tempfile_in(dir)?;
+        file.set_len((nrow * ncol * size_of::<T>()).max(1) as u64)?;
+        // SAFETY: the file is private to this store, having been unlinked on creation, so
+        // nothing else can change or truncate it while it is mapped
+        let map = unsafe { memmap2::MmapMut::map_mut(&file)? };
+        Ok(Self {
+            map,
+            dir: dir.to_path_buf(),
+            nrow,
+            ncol,
+            _cells: PhantomData,
+        })
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<T: Copy> DiskStore<T> {
+    fn cells(&self) -> &[T] {
+        // SAFETY: stores are only created for `DiskElement` types, the map is page
+        // aligned and holds at least `nrow * ncol` cells
+        unsafe { std::slice::from_raw_parts(self.map.as_ptr().cast(), self.nrow * self.ncol) }
+    }
+
+    fn cells_mut(&mut self) -> &mut [T] {
+        // SAFETY: as for `cells`
+        unsafe {
+            std::slice::from_raw_parts_mut(self.map.as_mut_ptr().cast(), self.nrow * self.ncol)
+        }
+    }
+}
+
+/// A copy in a new temporary file of the same directory.
+///
+/// Panics if the file cannot be created, e.g. when the disk is full.
+#[cfg(feature = "mmap")]
+impl<T> Clone for DiskStore<T> {
+    fn clone(&self) -> Self {
+        let mut copy = Self::create(self.nrow, self.ncol, &self.dir).unwrap_or_else(|e| {
+            panic!(
+                "cannot copy a score matrix to {}: {}",
+                self.dir.display(),
+                e
+            )
+        });
+        copy.map.copy_from_slice(&self.map);
+        copy
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<T: Copy> ScoreStore<T> for DiskStore<T> {
+    fn get(&self, row: usize, col: usize) -> T {
+        self.cells()[row * self.ncol + col]
+    }
+
+    fn set(&mut self, row: usize, col: usize, value: T) {
+        let ncol = self.ncol;
+        self.cells_mut()[row * ncol + col] = value;
+    }
+
+    fn row_slice(&self, row: usize) -> &[T] {
+        &self.cells()[row * self.ncol..(row + 1) * self.ncol]
+    }
+}
+
+/// Where a score matrix keeps its cells
+#[derive(Debug, Clone)]
+pub enum ScoreStorage<T> {
+    Heap(VecStore<T>),
+    #[cfg(feature = "mmap")]
+    Disk(DiskStore<T>),
+}
+
+impl<T> ScoreStorage<T> {
+    /// Heap bytes the cells take, none for a store on disk
+    pub fn heap_bytes(&self) -> usize {
+        match self {
+            ScoreStorage::Heap(store) => store.cells.len() * size_of::<T>(),
+            #[cfg(feature = "mmap")]
+            ScoreStorage::Disk(_) => 0,
+        }
+    }
+}
+
+impl<T: Copy> ScoreStorage<T> {
+    /// Every cell, row by row
+    pub fn as_slice(&self) -> &[T] {
+        match self {
+            ScoreStorage::Heap(store) => &store.cells,
+            #[cfg(feature = "mmap")]
+            ScoreStorage::Disk(store) => store.cells(),
+        }
+    }
+
+    /// Every cell, row by row, for writing
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        match self {
+            ScoreStorage::Heap(store) => &mut store.cells,
+            #[cfg(feature = "mmap")]
+            ScoreStorage::Disk(store) => store.cells_mut(),
+        }
+    }
+
+    /// Set every cell to `value`, in place
+    pub fn fill(&mut self, value: T) {
+        self.as_mut_slice().fill(value);
+    }
+
+    /// The cells as an `nrow` x `ncol` array view
+    pub fn view(&self, nrow: usize, ncol: usize) -> ArrayView2<'_, T> {
+        ArrayView2::from_shape((nrow, ncol), self.as_slice())
+            .expect("a store holds nrow x ncol cells")
+    }
+}
+
+impl<T: Copy + Zero> ScoreStorage<T> {
+    /// Replace the cells with zeroed `nrow` x `ncol` ones, in a new file in the same
+    /// directory for a store on disk
+    pub fn reallocate(&mut self, nrow: usize, ncol: usize) -> io::Result<()> {
+        match self {
+            ScoreStorage::Heap(store) => *store = VecStore::zeros(nrow, ncol),
+            #[cfg(feature = "mmap")]
+            ScoreStorage::Disk(store) => *store = DiskStore::create(nrow, ncol, &store.dir)?,
+        }
+        Ok(())
+    }
+}
+
+impl<T: Copy> ScoreStore<T> for ScoreStorage<T> {
+    fn get(&self, row: usize, col: usize) -> T {
+        match self {
+            ScoreStorage::Heap(store) => store.get(row, col),
+            #[cfg(feature = "mmap")]
+            ScoreStorage::Disk(store) => store.get(row, col),
+        }
+    }
+
+    fn set(&mut self, row: usize, col: usize, value: T) {
+        match self {
+            ScoreStorage::Heap(store) => store.set(row, col, value),
+            #[cfg(feature = "mmap")]
+            ScoreStorage::Disk(store) => store.set(row, col, value),
+        }
+    }
+
+    fn row_slice(&self, row: usize) -> &[T] {
+        match self {
+            ScoreStorage::Heap(store) => store.row_slice(row),
+            #[cfg(feature = "mmap")]
+            ScoreStorage::Disk(store) => store.row_slice(row),
+        }
+    }
+}
@@ -1,29 +1,192 @@
-use std::fs::File;
+use crate::error::AlignmentError;
+use crate::io::fasta::FastaReader;
+use crate::models::Residue;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use std::io;
-use std::io::{BufReader, Lines};
 
 #[derive(Clone, Debug)]
-pub struct Sequences {
-    pub seq_a: Vec<char>,
-    pub seq_b: Vec<char>,
+pub struct Sequences<S = char> {
+    pub seq_a: Vec<S>,
+    pub seq_b: Vec<S>,
+    orig_len_a: usize,
+    orig_len_b: usize,
 }
 
-impl Sequences {
+impl Sequences<char> {
     pub fn from_string(seq_a: String, seq_b: String) -> Self {
+        Self::new(seq_a.chars().collect(), seq_b.chars().collect())
+    }
+
+    /// Parse an in-memory FASTA string into pairs of sequences, records 1 and 2 giving the
+    /// first pair, 3 and 4 the second and so on, as `batch --pairs-fasta` pairs them.
+    ///
+    /// Sequence lines may be wrapped and end in `\r\n`. A header followed directly by
+    /// another header, or by the end of a complete input, gives an empty sequence. Fails
+    /// if the input ends on a header line without a newline, as a record cut off before
+    /// its sequence, or if the last record has no partner.
+    pub fn from_fasta_str(s: &str) -> Result<Vec<Self>, AlignmentError> {
+        let records = FastaReader::new(s.as_bytes())
+            .collect::<io::Result<Vec<_>>>()
+            .map_err(|e| AlignmentError::InvalidInput(e.to_string()))?;
+        let last_line = s.rsplit('\n').next().unwrap_or_default();
+        if last_line.trim_start().starts_with('>') {
+            return Err(AlignmentError::InvalidInput(format!(
+                "FASTA record {} has a header but no sequence; the input looks truncated",
+                records.len()
+            )));
+        }
+        if records.len() % 2 == 1 {
+            return Err(AlignmentError::InvalidInput(format!(
+                "the FASTA input has an odd number of records; {} (record {}) has no partner",
+                records[records.len() - 1].id,
+                records.len()
+            )));
+        }
+        Ok(records
+            .chunks_exact(2)
+            .map(|pair| Self::from_string(pair[0].sequence.clone(), pair[1].sequence.clone()))
+            .collect())
+    }
+
+    /// Build sequences from two base64-encoded ASCII strings
+    pub fn from_base64(seq_a: &str, seq_b: &str) -> Result<Self, AlignmentError> {
+        Ok(Self::from_string(
+            decode_base64(seq_a)?,
+            decode_base64(seq_b)?,
+        ))
+    }
+}
+
+/// Prefix marking a base64-encoded sequence line in a parameter file
+pub const BASE64_PREFIX: &str = "base64:";
+
+/// Decode a base64 string whose bytes must be ASCII
+pub fn decode_base64(encoded: &str) -> Result<String, AlignmentError> {
+    let bytes = STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| AlignmentError::InvalidInput(format!("Invalid base64 sequence: {}", e)))?;
+    if !bytes.is_ascii() {
+        return Err(AlignmentError::InvalidInput(
+            "Base64 sequence does not decode to ASCII".to_string(),
+        ));
+    }
+    Ok(bytes.into_iter().map(char::from).collect())
+}
+
+impl Sequences<String> {
+    /// Build token sequences from whitespace-separated strings
+    pub fn from_tokens(seq_a: &str, seq_b: &str) -> Self {
+        Self::new(String::tokenize(seq_a), String::tokenize(seq_b))
+    }
+}
+
+impl<S: Residue> Sequences<S> {
+    pub fn new(seq_a: Vec<S>, seq_b: Vec<S>) -> Self {
         Self {
-            seq_a: seq_a.chars().collect(),
-            seq_b: seq_b.chars().collect(),
+            orig_len_a: seq_a.len(),
+            orig_len_b: seq_b.len(),
+            seq_a,
+            seq_b,
         }
     }
 
-    pub fn load_sequences(lines: &mut Lines<BufReader<File>>) -> io::Result<Self> {
+    /// Both sequences with `pad_char` appended until each length is a multiple of `n`,
+    /// keeping their unpadded lengths as [`Self::orig_len_a`] and [`Self::orig_len_b`].
+    ///
+    /// Every grid scores a padded residue 0 against everything and ends a global
+    /// alignment at the unpadded lengths, and the traceback starts within them, so the
+    /// score and alignments stay those of the unpadded sequences. An `n` of 0 or 1 pads
+    /// nothing. Fails if `pad_char` is not a residue of this type.
+    pub fn pad_to_multiple(&self, n: usize, pad_char: char) -> Result<Self, AlignmentError> {
+        let Some(pad) = S::parse_token(pad_char.encode_utf8(&mut [0; 4])) else {
+            return Err(AlignmentError::InvalidInput(format!(
+                "padding character {:?} is not a residue",
+                pad_char
+            )));
+        };
+        let pad_seq = |seq: &[S]| {
+            let mut padded = seq.to_vec();
+            if n > 1 {
+                padded.resize(seq.len().next_multiple_of(n), pad.clone());
+            }
+            padded
+        };
+        Ok(Self {
+            seq_a: pad_seq(&self.seq_a),
+            seq_b: pad_seq(&self.seq_b),
+            orig_len_a: self.orig_len_a,
+            orig_len_b: self.orig_len_b,
+        })
+    }
+
+    /// Residues of A before [`Self::pad_to_multiple`] padded it; all of A otherwise
+    pub fn orig_len_a(&self) -> usize {
+        self.orig_len_a
+    }
+
+    /// Residues of B before [`Self::pad_to_multiple`] padded it; all of B otherwise
+    pub fn orig_len_b(&self) -> usize {
+        self.orig_len_b
+    }
+
+    /// Whether either sequence has been padded past its original length
+    pub fn is_padded(&self) -> bool {
+        self.seq_a.len() > self.orig_len_a || self.seq_b.len() > self.orig_len_b
+    }
+
+    /// Whether residue `pos_a` of A or `pos_b` of B, 0-based, is padding
+    pub fn is_padding(&self, pos_a: usize, pos_b: usize) -> bool {
+        pos_a >= self.orig_len_a || pos_b >= self.orig_len_b
+    }
+
+    pub fn load_sequences(
+        lines: &mut impl Iterator<Item = io::Result<String>>,
+    ) -> io::Result<Self> {
         let seq_a = lines
             .next()
             .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing sequence A"))??;
         let seq_b = lines
             .next()
             .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing sequence B"))??;
-        Ok(Self::from_string(seq_a, seq_b))
+        Self::from_lines(&seq_a, &seq_b)
+    }
+
+    /// Parse two sequences written as they would be in an input file
+    pub fn from_lines(seq_a: &str, seq_b: &str) -> io::Result<Self> {
+        Ok(Self::new(
+            Self::parse_line(seq_a)?,
+            Self::parse_line(seq_b)?,
+        ))
+    }
+
+    /// Tokenize a sequence line, decoding it first if it starts with `base64:`
+    fn parse_line(line: &str) -> io::Result<Vec<S>> {
+        match line.strip_prefix(BASE64_PREFIX) {
+            Some(encoded) => {
+                let decoded = decode_base64(encoded)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+                Ok(S::tokenize(&decoded))
+            }
+            None => Ok(S::tokenize(line)),
+        }
+    }
+
+    /// Remove every `gap_char` from both sequences, so that pre-aligned rows can be
+    /// realigned. Returns how many were removed from A and from B.
+    pub fn strip_gaps(&mut self, gap_char: char) -> (usize, usize) {
+        let Some(gap) = S::parse_token(gap_char.encode_utf8(&mut [0; 4])) else {
+            return (0, 0);
+        };
+        let strip = |seq: &mut Vec<S>| {
+            let len = seq.len();
+            seq.retain(|residue| *residue != gap);
+            len - seq.len()
+        };
+        let stripped = (strip(&mut self.seq_a), strip(&mut self.seq_b));
+        self.orig_len_a = self.orig_len_a.saturating_sub(stripped.0);
+        self.orig_len_b = self.orig_len_b.saturating_sub(stripped.1);
+        stripped
     }
 
     pub fn len_a(&self) -> usize {
@@ -0,0 +1,255 @@
+use crate::error::AlignmentError;
+use crate::io::parameters::AlignmentParameters;
+use crate::models::{GapPenalties, MatchMatrix, Residue};
+use std::fmt;
+
+/// Fewest letters a sequence must have for its type to be detected
+pub const MIN_DETECTION_LENGTH: usize = 20;
+
+/// Least fraction of `ACGTUN` among the letters of a nucleotide sequence
+pub const NUCLEOTIDE_FRACTION: f64 = 0.9;
+
+/// Greatest fraction of `ACGTUN` among the letters of a protein sequence. Amino acids
+/// with those letters make up about a quarter of a typical protein.
+pub const PROTEIN_FRACTION: f64 = 0.5;
+
+/// Whether sequences are DNA or RNA, or amino acids
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceType {
+    Nucleotide,
+    Protein,
+}
+
+impl fmt::Display for SequenceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SequenceType::Nucleotide => write!(f, "nucleotide"),
+            SequenceType::Protein => write!(f, "protein"),
+        }
+    }
+}
+
+/// The type of a sequence from the fraction of its letters that are `A`, `C`, `G`, `T`,
+/// `U` or `N`, in either case: nucleotide from [`NUCLEOTIDE_FRACTION`] up, protein at
+/// [`PROTEIN_FRACTION`] or below. Residues other than single letters, such as gaps and
+/// stop codons, are not counted. `None` when the sequences have fewer than
+/// [`MIN_DETECTION_LENGTH`] letters or the fraction falls in between.
+pub fn detect_sequence_type<'a, S: Residue + 'a>(
+    residues: impl IntoIterator<Item = &'a S>,
+) -> Option<SequenceType> {
+    let (mut letters, mut nucleotides) = (0usize, 0usize);
+    let mut text = String::new();
+    for residue in residues {
+        text.clear();
+        residue.push_to(&mut text);
+        let mut chars = text.chars();
+        let (Some(c), None) = (chars.next(), chars.next()) else {
+            continue;
+        };
+        if c.is_ascii_alphabetic() {
+            letters += 1;
+            if "ACGTUN".contains(c.to_ascii_uppercase()) {
+                nucleotides += 1;
+            }
+        }
+    }
+    if letters < MIN_DETECTION_LENGTH {
+        return None;
+    }
+    let fraction = nucleotides as f64 / letters as f64;
+    if fraction >= NUCLEOTIDE_FRACTION {
+        Some(SequenceType::Nucleotide)
+    } else if fraction <= PROTEIN_FRACTION {
+        Some(SequenceType::Protein)
+    } else {
+        None
+    }
+}
+
+/// Where the match scores of a preset come from
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PresetScores {
+    /// [`nucleotide_matrix`] with these match and mismatch scores
+    Nucleotide {
+        match_score: f64,
+        mismatch_score: f64,
+    },
+    /// A builtin protein matrix
+    Builtin(&'static str),
+}
+
+/// A named set of match scores and gap penalties suited to one type of sequence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Preset {
+    pub name: &'static str,
+    pub sequence_type: SequenceType,
+    pub description: &'static str,
+    scores: PresetScores,
+    /// Penalty of the first residue of a gap, in either sequence
+    pub gap_open: f64,
+    /// Penalty of each further residue of a gap
+    pub gap_extend: f64,
+}
+
+/// Names of the [`PRESETS`], in the same order
+pub const PRESET_NAMES: [&str; 4] = ["dna", "dna-sensitive", "protein", "protein-distant"];
+
+/// Every preset; the first of each sequence type is the one suggested for it
+pub const PRESETS: [Preset; 4] = [
+    Preset {
+        name: "dna",
+        sequence_type: SequenceType::Nucleotide,
+        description: "EDNAFULL-like +5/-4 with IUPAC codes, gaps 10/0.5",
+        scores: PresetScores::Nucleotide {
+            match_score: 5.0,
+            mismatch_score: -4.0,
+        },
+        gap_open: 10.0,
+        gap_extend: 0.5,
+    },
+    Preset {
+        name: "dna-sensitive",
+        sequence_type: SequenceType::Nucleotide,
+        description: "blastn-like +2/-3 with IUPAC codes, gaps 7/2",
+        scores: PresetScores::Nucleotide {
+            match_score: 2.0,
+            mismatch_score: -3.0,
+        },
+        gap_open: 7.0,
+        gap_extend: 2.0,
+    },
+    Preset {
+        name: "protein",
+        sequence_type: SequenceType::Protein,
+        description: "BLOSUM62, gaps 10/0.5",
+        scores: PresetScores::Builtin("blosum62"),
+        gap_open: 10.0,
+        gap_extend: 0.5,
+    },
+    Preset {
+        name: "protein-distant",
+        sequence_type: SequenceType::Protein,
+        description: "PAM250 for distant homologs, gaps 10/0.5",
+        scores: PresetScores::Builtin("pam250"),
+        gap_open: 10.0,
+        gap_extend: 0.5,
+    },
+];
+
+impl Preset {
+    /// The preset called `name`, ignoring case
+    pub fn find(name: &str) -> Option<&'static Preset> {
+        PRESETS
+            .iter()
+            .find(|preset| preset.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The preset suggested for sequences of type `sequence_type`
+    pub fn for_type(sequence_type: SequenceType) -> &'static Preset {
+        PRESETS
+            .iter()
+            .find(|preset| preset.sequence_type == sequence_type)
+            .expect("every sequence type has a preset")
+    }
+
+    pub fn match_matrix<S: Residue>(&self) -> MatchMatrix<f64, S> {
+        match self.scores {
+            PresetScores::Nucleotide {
+                match_score,
+                mismatch_score,
+            } => nucleotide_matrix(match_score, mismatch_score),
+            PresetScores::Builtin(name) => {
+                MatchMatrix::builtin(name).expect("presets name builtin matrices")
+            }
+        }
+    }
+
+    /// The same penalties for gaps in A and in B, every end charged
+    pub fn gap_penalties(&self) -> GapPenalties<f64> {
+        GapPenalties::new(
+            self.gap_open,
+            self.gap_extend,
+            self.gap_open,
+            self.gap_extend,
+        )
+    }
+
+    /// Replace the match scores and gap penalties of `parameters`, keeping the end-gap
+    /// treatment of their alignment type
+    pub fn apply<S: Residue>(&self, parameters: &mut AlignmentParameters<f64, S>) {
+        parameters.match_matrix = self.match_matrix();
+        parameters.set_gap_penalties(
+            self.gap_open,
+            self.gap_extend,
+            self.gap_open,
+            self.gap_extend,
+        );
+    }
+
+    /// Fail if `residues` are confidently detected as a type other than the preset's
+    pub fn check_sequences<'a, S: Residue + 'a>(
+        &self,
+        residues: impl IntoIterator<Item = &'a S>,
+    ) -> Result<(), AlignmentError> {
+        match detect_sequence_type(residues) {
+            Some(detected) if detected != self.sequence_type => {
+                Err(AlignmentError::IncompatibleParameters(format!(
+                    "the sequences look like {} but preset {} is for {} sequences",
+                    detected, self.name, self.sequence_type
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Bases each IUPAC nucleotide code stands for, `U` standing for `T`
+const IUPAC_BASES: [(char, &str); 16] = [
+    ('A', "A"),
+    ('C', "C"),
+    ('G', "G"),
+    ('T', "T"),
+    ('U', "T"),
+    ('R', "AG"),
+    ('Y', "CT"),
+    ('S', "CG"),
+    ('W', "AT"),
+    ('K', "GT"),
+    ('M', "AC"),
+    ('B', "CGT"),
+    ('D', "AGT"),
+    ('H', "ACT"),
+    ('V', "ACG"),
+    ('N', "ACGT"),
+];
+
+/// Scores for the upper-case IUPAC nucleotide codes, `U` scoring as `T`. Two bases
+/// score `match_score` when equal and `mismatch_score` otherwise; a pair involving an
+/// ambiguity code scores the mean over the bases the two codes stand for, rounded to
+/// the nearest integer, as in EDNAFULL.
+pub fn nucleotide_matrix<S: Residue>(match_score: f64, mismatch_score: f64) -> MatchMatrix<f64, S> {
+    let mut match_matrix = MatchMatrix::new();
+    for (a, bases_a) in IUPAC_BASES {
+        for (b, bases_b) in IUPAC_BASES {
+            let total: f64 = bases_a
+                .chars()
+                .flat_map(|x| {
+                    bases_b
+                        .chars()
+                        .map(move |y| if x == y { match_score } else { mismatch_score })
+                })
+                .sum();
+            let pairs = (bases_a.len() * bases_b.len()) as f64;
+            let score = if pairs == 1.0 {
+                total
+            } else {
+                (total / pairs).round()
+            };
+            let residue = |c: char| S::parse_token(c.encode_utf8(&mut [0; 4]));
+            if let (Some(a), Some(b)) = (residue(a), residue(b)) {
+                match_matrix.set_score(a, b, score);
+            }
+        }
+    }
+    match_matrix
+}
@@ -0,0 +1,109 @@
+use crate::io::parameters::AlignmentParameters;
+use crate::models::{Alignment, ColumnKind};
+use crate::verify::column_scores;
+use serde::Serialize;
+use std::io;
+use std::io::Write;
+
+/// Identity and mean score over one window of alignment columns.
+///
+/// Column bounds are 0-based and exclusive at the end; sequence bounds are the 0-based,
+/// end-exclusive range of residues covered by the window, so a window made only of gaps
+/// in one sequence has an empty range there.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ProfileWindow {
+    pub column_start: usize,
+    pub column_end: usize,
+    pub a_start: usize,
+    pub a_end: usize,
+    pub b_start: usize,
+    pub b_end: usize,
+    /// Identical residue pairs over all columns in the window, gaps included
+    pub identity: f64,
+    /// Mean per-column score, with gap columns charged their open or extend penalty
+    pub mean_score: f64,
+}
+
+/// Slide a window of `width` columns along an alignment in steps of `step` columns.
+///
+/// The last window is cut short at the end of the alignment, and no window starts after
+/// one has reached it. Returns no windows for an empty alignment or a zero width or step.
+pub fn window_profile(
+    alignment: &Alignment,
+    parameters: &AlignmentParameters<f64>,
+    width: usize,
+    step: usize,
+) -> Vec<ProfileWindow> {
+    let len = alignment.len();
+    if len == 0 || width == 0 || step == 0 {
+        return Vec::new();
+    }
+
+    let scores = column_scores(alignment, parameters);
+    let identical: Vec<bool> = alignment
+        .columns()
+        .map(|column| column.kind == ColumnKind::Match)
+        .collect();
+
+    // Residues of A and B consumed before each column, plus the totals at the end
+    let mut pos_a = Vec::with_capacity(len + 1);
+    let mut pos_b = Vec::with_capacity(len + 1);
+    let (mut a, mut b) = (alignment.start_a, alignment.start_b);
+    for op in &alignment.ops {
+        pos_a.push(a);
+        pos_b.push(b);
+        a += usize::from(op.consumes_a());
+        b += usize::from(op.consumes_b());
+    }
+    pos_a.push(a);
+    pos_b.push(b);
+
+    let mut windows = Vec::new();
+    for start in (0..len).step_by(step) {
+        let end = (start + width).min(len);
+        let columns = (end - start) as f64;
+        windows.push(ProfileWindow {
+            column_start: start,
+            column_end: end,
+            a_start: pos_a[start],
+            a_end: pos_a[end],
+            b_start: pos_b[start],
+            b_end: pos_b[end],
+            identity: identical[start..end].iter().filter(|&&same| same).count() as f64 / columns,
+            mean_score: scores[start..end].iter().sum::<f64>() / columns,
+        });
+        if end == len {
+            break;
+        }
+    }
+    windows
+}
+
+/// Write profiles as TSV with 1-based inclusive coordinates, one row per window
+pub fn write_profile_tsv<W: Write>(
+    writer: &mut W,
+    profiles: &[Vec<ProfileWindow>],
+) -> io::Result<()> {
+    writeln!(
+        writer,
+        "alignment\tcolumn_start\tcolumn_end\ta_start\ta_end\tb_start\tb_end\tidentity\tmean_score"
+    )?;
+    for (i, windows) in profiles.iter().enumerate() {
+        for window in windows {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.4}\t{:.4}",
+                i + 1,
+                window.column_start + 1,
+                window.column_end,
+                window.a_start + 1,
+                window.a_end,
+                window.b_start + 1,
+                window.b_end,
+                window.identity,
+                window.mean_score
+            )?;
+        }
+    }
+    Ok(())
+}
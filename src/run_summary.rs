@@ -0,0 +1,111 @@
+use crate::io::parameters::{AlignmentMode, AlignmentParameters};
+use crate::models::Residue;
+use num_traits::Zero;
+use std::fmt;
+use std::str::FromStr;
+
+/// DP cells past which a run counts as large and [`RunSummary::suggestion`] points to
+/// cheaper options: a grid of about 10,000 x 10,000 residues
+pub const LARGE_RUN_CELLS: usize = 300_000_000;
+
+/// How the DP computes what a run reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Fill the whole grid with traceback pointers, then trace the alignments back
+    FillAndTraceback,
+    /// Fill the grid in log space, then trace the alignments back
+    LogSpace,
+    /// Compute the optimal score keeping two rows of the grid, without alignments
+    ScoreOnly,
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Algorithm::FillAndTraceback => write!(f, "fill and traceback"),
+            Algorithm::LogSpace => write!(f, "log-space fill and traceback"),
+            Algorithm::ScoreOnly => write!(f, "score-only fill"),
+        }
+    }
+}
+
+/// Size of the DP problem a run solves and how it solves it, as reported by the dry run,
+/// the timings, the parameter echo and batch mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunSummary {
+    pub mode: AlignmentMode,
+    pub algorithm: Algorithm,
+    pub len_a: usize,
+    pub len_b: usize,
+}
+
+impl RunSummary {
+    pub fn new(mode: AlignmentMode, algorithm: Algorithm, len_a: usize, len_b: usize) -> Self {
+        Self {
+            mode,
+            algorithm,
+            len_a,
+            len_b,
+        }
+    }
+
+    pub fn for_parameters<T: Copy + FromStr + Zero, S: Residue>(
+        parameters: &AlignmentParameters<T, S>,
+        algorithm: Algorithm,
+    ) -> Self {
+        Self::new(
+            parameters.mode(),
+            algorithm,
+            parameters.sequences.len_a(),
+            parameters.sequences.len_b(),
+        )
+    }
+
+    /// Rows of each DP matrix, one per residue of A and one for the leading border
+    pub fn rows(&self) -> usize {
+        self.len_a + 1
+    }
+
+    /// Columns of each DP matrix, one per residue of B and one for the leading border
+    pub fn columns(&self) -> usize {
+        self.len_b + 1
+    }
+
+    /// Cells computed over the M, Ix and Iy matrices
+    pub fn cells(&self) -> usize {
+        self.rows().saturating_mul(self.columns()).saturating_mul(3)
+    }
+
+    pub fn is_large(&self) -> bool {
+        self.cells() > LARGE_RUN_CELLS
+    }
+
+    /// For a large run, a note naming the `cheaper` options the caller found to apply to
+    /// it; `None` for a run within [`LARGE_RUN_CELLS`] or without cheaper options
+    pub fn suggestion(&self, cheaper: &[&str]) -> Option<String> {
+        if !self.is_large() || cheaper.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "{} DP cells is a large run; consider {}",
+            self.cells(),
+            cheaper.join(" or ")
+        ))
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} alignment of {} x {} residues by {}, {} x {} grid, {} DP cells",
+            self.mode,
+            self.len_a,
+            self.len_b,
+            self.algorithm,
+            self.rows(),
+            self.columns(),
+            self.cells()
+        )
+    }
+}
@@ -0,0 +1,121 @@
+use crate::io::parameters::AlignmentParameters;
+use crate::models::{GapPenalties, MatchMatrix, Residue};
+use crate::utils::{clamp_to_zero, max};
+
+/// Best local alignment score, computed row by row without storing the full grid or
+/// traceback pointers.
+///
+/// Uses the same recurrences as [`crate::models::AlignGrid`] for local alignment, so the
+/// result matches the score reported by a full traceback.
+pub fn local_score<S: Residue>(
+    seq_a: &[S],
+    seq_b: &[S],
+    match_matrix: &MatchMatrix<f64, S>,
+    gap_penalties: &GapPenalties<f64>,
+) -> f64 {
+    let columns = seq_b.len();
+    let mut prev_m = vec![0.0; columns];
+    let mut prev_ix = vec![0.0; columns];
+    let mut prev_iy = vec![0.0; columns];
+    let mut cur_m = vec![0.0; columns];
+    let mut cur_ix = vec![0.0; columns];
+    let mut cur_iy = vec![0.0; columns];
+    let mut best = 0.0;
+
+    for (r, a) in seq_a.iter().enumerate() {
+        for (c, b) in seq_b.iter().enumerate() {
+            let score = match_matrix.get_score(a, b);
+            cur_m[c] = if r == 0 || c == 0 {
+                clamp_to_zero(score)
+            } else {
+                clamp_to_zero(max(max(prev_m[c - 1], prev_ix[c - 1]), prev_iy[c - 1]) + score)
+            };
+            cur_ix[c] = if r == 0 {
+                0.0
+            } else {
+                clamp_to_zero(max(
+                    prev_m[c] - gap_penalties.dy,
+                    prev_ix[c] - gap_penalties.ey,
+                ))
+            };
+            cur_iy[c] = if c == 0 {
+                0.0
+            } else {
+                clamp_to_zero(max(
+                    cur_m[c - 1] - gap_penalties.dx,
+                    cur_iy[c - 1] - gap_penalties.ex,
+                ))
+            };
+            best = max(best, cur_m[c]);
+        }
+        std::mem::swap(&mut prev_m, &mut cur_m);
+        std::mem::swap(&mut prev_ix, &mut cur_ix);
+        std::mem::swap(&mut prev_iy, &mut cur_iy);
+    }
+    best
+}
+
+/// Best global alignment score, computed row by row like [`local_score`].
+///
+/// Follows the global recurrences of [`crate::models::AlignGrid`], including free
+/// overhangs at the start of either sequence and free end gaps in the last row and column.
+pub fn global_score<S: Residue>(
+    seq_a: &[S],
+    seq_b: &[S],
+    match_matrix: &MatchMatrix<f64, S>,
+    gap_penalties: &GapPenalties<f64>,
+) -> f64 {
+    let (rows, columns) = (seq_a.len(), seq_b.len());
+    let mut prev_m = vec![0.0; columns];
+    let mut prev_ix = vec![0.0; columns];
+    let mut prev_iy = vec![0.0; columns];
+    let mut cur_m = vec![0.0; columns];
+    let mut cur_ix = vec![0.0; columns];
+    let mut cur_iy = vec![0.0; columns];
+
+    for (r, a) in seq_a.iter().enumerate() {
+        let (dx, ex) = gap_penalties.x_penalties(r, rows);
+        for (c, b) in seq_b.iter().enumerate() {
+            let score = match_matrix.get_score(a, b);
+            cur_m[c] = if r == 0 || c == 0 {
+                score
+            } else {
+                max(max(prev_m[c - 1], prev_ix[c - 1]), prev_iy[c - 1]) + score
+            };
+            let (dy, ey) = gap_penalties.y_penalties(c, columns);
+            // A gap opening after the other sequence's free leading residues is charged
+            cur_ix[c] = if r == 0 {
+                -dy
+            } else {
+                max(prev_m[c] - dy, prev_ix[c] - ey)
+            };
+            cur_iy[c] = if c == 0 {
+                -dx
+            } else {
+                max(cur_m[c - 1] - dx, cur_iy[c - 1] - ex)
+            };
+        }
+        std::mem::swap(&mut prev_m, &mut cur_m);
+        std::mem::swap(&mut prev_ix, &mut cur_ix);
+        std::mem::swap(&mut prev_iy, &mut cur_iy);
+    }
+
+    match (prev_m.last(), prev_ix.last(), prev_iy.last()) {
+        (Some(&m), Some(&ix), Some(&iy)) if rows > 0 => max(max(m, ix), iy),
+        _ => 0.0,
+    }
+}
+
+/// Optimal score for the alignment type of `params`, without traceback. Padding added by
+/// [`Sequences::pad_to_multiple`](crate::models::Sequences::pad_to_multiple) is left out,
+/// as the full grid leaves it out of the score.
+pub fn optimal_score<S: Residue>(params: &AlignmentParameters<f64, S>) -> f64 {
+    let sequences = &params.sequences;
+    let seq_a = &sequences.seq_a[..sequences.orig_len_a()];
+    let seq_b = &sequences.seq_b[..sequences.orig_len_b()];
+    if params.global_alignment {
+        global_score(seq_a, seq_b, &params.match_matrix, &params.gap_penalties)
+    } else {
+        local_score(seq_a, seq_b, &params.match_matrix, &params.gap_penalties)
+    }
+}
@@ -0,0 +1,383 @@
+use crate::alignment::{
+    estimate_alignments, traceback_into, traceback_per_cell, traceback_with_limits, AlignmentSink,
+    CellTraceback, TracebackLimits,
+};
+use crate::error::AlignmentError;
+use crate::io::parameters::AlignmentParameters;
+use crate::models::{AlignGrid, Alignment, AlignmentResult, Residue, TokenFormat, Truncation};
+use crate::utils::Epsilon;
+use num_traits::{NumCast, ToPrimitive, Zero};
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::io;
+use std::ops::Sub;
+use std::str::FromStr;
+
+/// Numeric type the DP grid is filled with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScoreType {
+    #[default]
+    F64,
+    /// Half the grid memory of `F64`, with about seven significant digits
+    F32,
+    /// Exact integer arithmetic; every score and penalty must be a whole number
+    I32,
+    /// As `I32`, for sequences short enough that no alignment score can leave
+    /// `-32767..=32767`
+    I16,
+    /// As `I32`, for sequences short enough that no alignment score can leave
+    /// `-127..=127`, e.g. peptides scored with BLOSUM62
+    I8,
+}
+
+impl ScoreType {
+    pub const ALL: [ScoreType; 5] = [
+        ScoreType::F64,
+        ScoreType::F32,
+        ScoreType::I32,
+        ScoreType::I16,
+        ScoreType::I8,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScoreType::F64 => "f64",
+            ScoreType::F32 => "f32",
+            ScoreType::I32 => "i32",
+            ScoreType::I16 => "i16",
+            ScoreType::I8 => "i8",
+        }
+    }
+
+    /// Largest score magnitude the type can hold in both signs, or `None` for the float
+    /// types, which do not overflow
+    fn max_magnitude(&self) -> Option<f64> {
+        match self {
+            ScoreType::F64 | ScoreType::F32 => None,
+            ScoreType::I32 => Some(i32::MAX as f64),
+            ScoreType::I16 => Some(i16::MAX as f64),
+            ScoreType::I8 => Some(i8::MAX as f64),
+        }
+    }
+
+    /// Heap usage of a grid of this type for sequences of `rows` and `columns`
+    /// residues, computed without allocating it
+    pub fn estimate_memory_bytes(&self, rows: usize, columns: usize) -> usize {
+        match self {
+            ScoreType::F64 => AlignGrid::<f64>::estimate_memory_bytes(rows, columns),
+            ScoreType::F32 => AlignGrid::<f32>::estimate_memory_bytes(rows, columns),
+            ScoreType::I32 => AlignGrid::<i32>::estimate_memory_bytes(rows, columns),
+            ScoreType::I16 => AlignGrid::<i16>::estimate_memory_bytes(rows, columns),
+            ScoreType::I8 => AlignGrid::<i8>::estimate_memory_bytes(rows, columns),
+        }
+    }
+
+    /// Fail if a score or penalty of the parameters cannot be represented in this type,
+    /// or an integer grid for their sequences could overflow
+    pub fn check_parameters<S: Residue>(
+        &self,
+        alignment_parameters: &AlignmentParameters<f64, S>,
+    ) -> Result<(), AlignmentError> {
+        let score_type = *self;
+        match score_type {
+            ScoreType::F64 => Ok(()),
+            ScoreType::F32 => alignment_parameters
+                .try_map_scores(|score| cast_score::<f32>(score, score_type))
+                .map(drop),
+            ScoreType::I32 => alignment_parameters
+                .try_map_scores(|score| cast_score::<i32>(score, score_type))
+                .map(drop),
+            ScoreType::I16 => alignment_parameters
+                .try_map_scores(|score| cast_score::<i16>(score, score_type))
+                .map(drop),
+            ScoreType::I8 => alignment_parameters
+                .try_map_scores(|score| cast_score::<i8>(score, score_type))
+                .map(drop),
+        }?;
+        self.check_range(alignment_parameters)
+    }
+
+    /// Fail unless every score the grid can hold for these parameters fits the type.
+    ///
+    /// Each column of an alignment adds one match score or gap penalty, so no cell can
+    /// exceed the largest of them in magnitude times one more than the columns of the
+    /// longest alignment. The bound is loose for local alignment, whose scores stay
+    /// near zero on the negative side.
+    fn check_range<S: Residue>(
+        &self,
+        alignment_parameters: &AlignmentParameters<f64, S>,
+    ) -> Result<(), AlignmentError> {
+        let Some(max_magnitude) = self.max_magnitude() else {
+            return Ok(());
+        };
+        let gaps = &alignment_parameters.gap_penalties;
+        let largest = alignment_parameters
+            .match_matrix
+            .iter()
+            .map(|(_, _, score)| score)
+            .chain([gaps.dx, gaps.ex, gaps.dy, gaps.ey])
+            .fold(0.0, |largest: f64, score| largest.max(score.abs()));
+        let columns = alignment_parameters.len_a() + alignment_parameters.len_b();
+        let bound = largest * (columns + 1) as f64;
+        if bound > max_magnitude {
+            return Err(AlignmentError::IncompatibleParameters(format!(
+                "alignment scores of up to {} in magnitude cannot be represented as {}",
+                bound, self
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ScoreType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ScoreType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "f64" => Ok(ScoreType::F64),
+            "f32" => Ok(ScoreType::F32),
+            "i32" => Ok(ScoreType::I32),
+            "i16" => Ok(ScoreType::I16),
+            "i8" => Ok(ScoreType::I8),
+            _ => Err(format!("Unknown score type: {}", s)),
+        }
+    }
+}
+
+/// A DP grid whose score type is chosen at run time.
+///
+/// Parameters are given and results returned with `f64` scores whatever the grid's own
+/// type, so code downstream of the traceback is shared by every score type.
+pub trait AlignGridDyn<S: Residue = char> {
+    fn score_type(&self) -> ScoreType;
+
+    /// Convert the parameters to the grid's score type and fill the grid
+    fn populate_score_matrices_dyn(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<f64, S>,
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// Trace back the optimal alignments of the last populated parameters
+    fn traceback_dyn(
+        &self,
+        token_format: &TokenFormat,
+        limits: &TracebackLimits,
+    ) -> Result<AlignmentResult, Box<dyn Error>>;
+
+    /// Trace back as [`Self::traceback_dyn`] does, handing each alignment to `sink` as it
+    /// is built. Returns the optimal score and the limits that dropped anything.
+    fn traceback_into_dyn(
+        &self,
+        token_format: &TokenFormat,
+        limits: &TracebackLimits,
+        sink: &mut dyn AlignmentSink<f64>,
+    ) -> Result<(f64, Vec<Truncation>), Box<dyn Error>>;
+
+    /// Trace back each optimal cell in a task of its own, handing its alignments to the
+    /// sink `open` creates for its row and column; see [`traceback_per_cell`]
+    fn traceback_per_cell_dyn(
+        &self,
+        token_format: &TokenFormat,
+        max_paths_per_start: Option<usize>,
+        open: &CellSinkOpener<'_>,
+    ) -> Result<(f64, Vec<CellTraceback>), Box<dyn Error>>;
+
+    /// Number of alignments [`Self::traceback_dyn`] would enumerate under `limits`,
+    /// before any `max_alignments` cap
+    fn estimate_alignments_dyn(&self, limits: &TracebackLimits) -> Result<u128, Box<dyn Error>>;
+
+    /// Approximate heap usage of the grid, in bytes
+    fn memory_bytes(&self) -> usize;
+}
+
+/// Creates the sink for the alignments of the optimal cell at a row and column
+pub type CellSinkOpener<'a> =
+    dyn Fn(usize, usize) -> io::Result<Box<dyn AlignmentSink<f64> + Send>> + Sync + 'a;
+
+/// An [`AlignGrid`] together with the parameters it was filled for, converted to `T`
+struct TypedGrid<T: Copy + FromStr + Zero, S: Residue> {
+    score_type: ScoreType,
+    grid: AlignGrid<T>,
+    parameters: Option<AlignmentParameters<T, S>>,
+}
+
+/// Passes a traceback of any score type on to a sink of `f64` scores
+struct F64Sink<K>(K);
+
+impl<T: ToPrimitive, K: AlignmentSink<f64>> AlignmentSink<T> for F64Sink<K> {
+    fn begin(&mut self, score: T) -> io::Result<()> {
+        let score = score.to_f64().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "score does not fit in f64")
+        })?;
+        self.0.begin(score)
+    }
+
+    fn push(&mut self, alignment: Alignment) -> io::Result<()> {
+        self.0.push(alignment)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.0.finish()
+    }
+}
+
+/// Relative change in a score accepted when converting it, the rounding error of `f32`
+const ROUNDING: f64 = f32::EPSILON as f64;
+
+/// Convert a score to `T`, failing if that would change its value beyond rounding
+pub(crate) fn cast_score<T: NumCast + ToPrimitive>(
+    value: f64,
+    score_type: ScoreType,
+) -> Result<T, AlignmentError> {
+    T::from(value)
+        .filter(|cast: &T| {
+            cast.to_f64()
+                .is_some_and(|back| back == value || (back - value).abs() <= value.abs() * ROUNDING)
+        })
+        .ok_or_else(|| {
+            AlignmentError::IncompatibleParameters(format!(
+                "score {} cannot be represented as {}",
+                value, score_type
+            ))
+        })
+}
+
+impl<T, S> AlignGridDyn<S> for TypedGrid<T, S>
+where
+    T: Copy
+        + Display
+        + Epsilon
+        + FromStr
+        + NumCast
+        + PartialOrd
+        + Send
+        + Sub<Output = T>
+        + Sync
+        + ToPrimitive
+        + Zero,
+    <T as FromStr>::Err: Display,
+    S: Residue,
+{
+    fn score_type(&self) -> ScoreType {
+        self.score_type
+    }
+
+    fn populate_score_matrices_dyn(
+        &mut self,
+        alignment_parameters: &AlignmentParameters<f64, S>,
+    ) -> Result<(), Box<dyn Error>> {
+        let score_type = self.score_type;
+        score_type.check_range(alignment_parameters)?;
+        let parameters =
+            alignment_parameters.try_map_scores(|score| cast_score::<T>(score, score_type))?;
+        self.grid.populate_score_matrices(&parameters)?;
+        self.parameters = Some(parameters);
+        Ok(())
+    }
+
+    fn traceback_dyn(
+        &self,
+        token_format: &TokenFormat,
+        limits: &TracebackLimits,
+    ) -> Result<AlignmentResult, Box<dyn Error>> {
+        let parameters = self
+            .parameters
+            .as_ref()
+            .ok_or("traceback_dyn called before populate_score_matrices_dyn")?;
+        let result = traceback_with_limits(&self.grid, parameters, token_format, limits)?;
+        let score = result.score.to_f64().ok_or("score does not fit in f64")?;
+        let mut converted = AlignmentResult::new(score, Vec::new());
+        converted.alignments = result.alignments;
+        converted.truncated = result.truncated;
+        converted.starts = result.starts;
+        Ok(converted)
+    }
+
+    fn traceback_into_dyn(
+        &self,
+        token_format: &TokenFormat,
+        limits: &TracebackLimits,
+        sink: &mut dyn AlignmentSink<f64>,
+    ) -> Result<(f64, Vec<Truncation>), Box<dyn Error>> {
+        let parameters = self
+            .parameters
+            .as_ref()
+            .ok_or("traceback_into_dyn called before populate_score_matrices_dyn")?;
+        let (score, truncated) = traceback_into(
+            &self.grid,
+            parameters,
+            token_format,
+            limits,
+            &mut F64Sink(sink),
+        )?;
+        let score = score.to_f64().ok_or("score does not fit in f64")?;
+        Ok((score, truncated))
+    }
+
+    fn traceback_per_cell_dyn(
+        &self,
+        token_format: &TokenFormat,
+        max_paths_per_start: Option<usize>,
+        open: &CellSinkOpener<'_>,
+    ) -> Result<(f64, Vec<CellTraceback>), Box<dyn Error>> {
+        let parameters = self
+            .parameters
+            .as_ref()
+            .ok_or("traceback_per_cell_dyn called before populate_score_matrices_dyn")?;
+        let (score, cells) = traceback_per_cell(
+            &self.grid,
+            parameters,
+            token_format,
+            max_paths_per_start,
+            |row, col| open(row, col).map(F64Sink),
+        )?;
+        let score = score.to_f64().ok_or("score does not fit in f64")?;
+        Ok((score, cells))
+    }
+
+    fn estimate_alignments_dyn(&self, limits: &TracebackLimits) -> Result<u128, Box<dyn Error>> {
+        let parameters = self
+            .parameters
+            .as_ref()
+            .ok_or("estimate_alignments_dyn called before populate_score_matrices_dyn")?;
+        Ok(estimate_alignments(&self.grid, parameters, limits))
+    }
+
+    fn memory_bytes(&self) -> usize {
+        self.grid.memory_bytes()
+    }
+}
+
+/// An empty grid of the given score type for sequences of `rows` and `columns` residues
+pub fn new_grid<S: Residue + 'static>(
+    score_type: ScoreType,
+    rows: usize,
+    columns: usize,
+) -> Box<dyn AlignGridDyn<S>> {
+    fn typed<T, S>(score_type: ScoreType, rows: usize, columns: usize) -> TypedGrid<T, S>
+    where
+        T: Copy + Display + Epsilon + FromStr + PartialOrd + Sub<Output = T> + Zero,
+        S: Residue,
+    {
+        TypedGrid {
+            score_type,
+            grid: AlignGrid::new(rows, columns),
+            parameters: None,
+        }
+    }
+
+    match score_type {
+        ScoreType::F64 => Box::new(typed::<f64, S>(score_type, rows, columns)),
+        ScoreType::F32 => Box::new(typed::<f32, S>(score_type, rows, columns)),
+        ScoreType::I32 => Box::new(typed::<i32, S>(score_type, rows, columns)),
+        ScoreType::I16 => Box::new(typed::<i16, S>(score_type, rows, columns)),
+        ScoreType::I8 => Box::new(typed::<i8, S>(score_type, rows, columns)),
+    }
+}
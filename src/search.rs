@@ -0,0 +1,265 @@
+use crate::alignment::traceback;
+use crate::error::AlignmentError;
+use crate::io::fasta::{FastaReader, FastaRecord};
+use crate::io::parameters::AlignmentParameters;
+use crate::kmer::KmerFilter;
+use crate::models::{AlignGrid, AlignmentResult, Sequences, TokenFormat};
+use crate::score_only::local_score;
+use crate::stats::{CoverageFilter, KarlinAltschul, Significance};
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::io;
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+/// Database records scored in parallel at a time during the scoring pass
+const SEARCH_CHUNK: usize = 256;
+
+/// Which database records are reported as hits.
+///
+/// A record must pass every threshold that is set; of those, only the `top` best
+/// scoring are kept. Coverage is that of the record's first alignment, the query as A
+/// and the record as B.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HitFilter {
+    pub top: Option<usize>,
+    pub min_score: Option<f64>,
+    pub max_evalue: Option<f64>,
+    pub coverage: CoverageFilter,
+}
+
+/// A database record reported by [`search`]
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    /// Position of the record in the database
+    pub index: usize,
+    pub id: String,
+    pub length: usize,
+    pub score: f64,
+    pub significance: Option<Significance>,
+    /// Fractions of the query and of the record spanned by the first alignment
+    pub coverage: (Option<f64>, Option<f64>),
+    pub result: AlignmentResult,
+}
+
+/// Hits of a search, with how many database records were looked at
+#[derive(Debug, Clone)]
+pub struct SearchResults {
+    pub hits: Vec<SearchHit>,
+    /// Records read from the database
+    pub searched: usize,
+    /// Records skipped by the k-mer prefilter without being scored
+    pub prefiltered: usize,
+}
+
+/// A record that survived the scoring pass
+struct Candidate {
+    index: usize,
+    record: FastaRecord,
+    score: f64,
+    significance: Option<Significance>,
+}
+
+/// Best score first, then database order
+fn rank(a: &Candidate, b: &Candidate) -> Ordering {
+    b.score.total_cmp(&a.score).then(a.index.cmp(&b.index))
+}
+
+/// Local search of one query against every record of a FASTA database.
+///
+/// The search runs in two passes. The scoring pass streams the database in chunks,
+/// computes each record's local score without traceback, and keeps only records that
+/// pass `filter`, so memory is bounded by the number of hits rather than the database
+/// size. With a `prefilter`, records sharing too few k-mers with the query are skipped
+/// before scoring. The traceback pass then aligns just the reported hits. E-values use
+/// the query and record lengths as the search space. Hits are returned best first.
+///
+/// Coverage needs the alignment, so it is checked in the traceback pass. With a coverage
+/// threshold, the scoring pass keeps every record that passes the other thresholds
+/// rather than only the `top` best, so that records failing on coverage do not take the
+/// place of those further down.
+pub fn search<R: BufRead>(
+    query: &FastaRecord,
+    database: FastaReader<R>,
+    template: &AlignmentParameters<f64>,
+    filter: &HitFilter,
+    karlin_altschul: Option<KarlinAltschul>,
+    prefilter: Option<&KmerFilter>,
+) -> Result<SearchResults, AlignmentError> {
+    if template.global_alignment {
+        return Err(AlignmentError::IncompatibleParameters(
+            "database search needs a local alignment type".to_string(),
+        ));
+    }
+    if filter.max_evalue.is_some() && karlin_altschul.is_none() {
+        return Err(AlignmentError::IncompatibleParameters(
+            "an E-value cutoff needs Karlin-Altschul parameters".to_string(),
+        ));
+    }
+
+    let query_seq: Vec<char> = query.sequence.chars().collect();
+    let query_kmers = prefilter.map(|prefilter| prefilter.kmers(&query.sequence));
+    let prefiltered = AtomicUsize::new(0);
+    let score_chunk = |chunk: &mut Vec<(usize, FastaRecord)>, candidates: &mut Vec<Candidate>| {
+        let scored: Vec<Candidate> = chunk
+            .par_drain(..)
+            .filter_map(|(index, record)| {
+                if let (Some(prefilter), Some(query_kmers)) = (prefilter, &query_kmers) {
+                    if !prefilter.passes(query_kmers, &prefilter.kmers(&record.sequence)) {
+                        prefiltered.fetch_add(1, AtomicOrdering::Relaxed);
+                        return None;
+                    }
+                }
+                let subject: Vec<char> = record.sequence.chars().collect();
+                let score = local_score(
+                    &query_seq,
+                    &subject,
+                    &template.match_matrix,
+                    &template.gap_penalties,
+                );
+                let significance = karlin_altschul
+                    .map(|ka| ka.significance(score, query_seq.len(), subject.len()));
+                let passes = filter.min_score.is_none_or(|min| score >= min)
+                    && filter.max_evalue.is_none_or(|max| {
+                        significance.is_some_and(|significance| significance.evalue <= max)
+                    });
+                passes.then_some(Candidate {
+                    index,
+                    record,
+                    score,
+                    significance,
+                })
+            })
+            .collect();
+        candidates.extend(scored);
+        if let Some(top) = filter.top.filter(|_| !filter.coverage.is_set()) {
+            candidates.sort_by(rank);
+            candidates.truncate(top);
+        }
+    };
+
+    let mut candidates = Vec::new();
+    let mut chunk = Vec::with_capacity(SEARCH_CHUNK);
+    let mut searched = 0;
+    for (index, record) in database.enumerate() {
+        chunk.push((index, record?));
+        searched += 1;
+        if chunk.len() == SEARCH_CHUNK {
+            score_chunk(&mut chunk, &mut candidates);
+        }
+    }
+    score_chunk(&mut chunk, &mut candidates);
+    candidates.sort_by(rank);
+
+    let hits = candidates
+        .into_par_iter()
+        .map(|candidate| {
+            let length = candidate.record.sequence.chars().count();
+            let parameters = AlignmentParameters {
+                sequences: Sequences::from_string(
+                    query.sequence.clone(),
+                    candidate.record.sequence,
+                ),
+                ..template.clone()
+            };
+            let mut grid = AlignGrid::new(parameters.len_a(), parameters.len_b());
+            grid.populate_score_matrices(&parameters)
+                .and_then(|_| traceback(&grid, &parameters, &TokenFormat::default()))
+                .map(|result| SearchHit {
+                    index: candidate.index,
+                    id: candidate.record.id.clone(),
+                    length,
+                    score: candidate.score,
+                    significance: candidate.significance,
+                    coverage: result.alignments.first().map_or((None, None), |alignment| {
+                        alignment.coverage(query_seq.len(), length)
+                    }),
+                    result,
+                })
+                .map_err(|e| {
+                    AlignmentError::InvalidInput(format!(
+                        "{} vs {}: {}",
+                        query.id, candidate.record.id, e
+                    ))
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut hits: Vec<SearchHit> = hits
+        .into_iter()
+        .filter(|hit| filter.coverage.passes(hit.coverage))
+        .collect();
+    if let Some(top) = filter.top {
+        hits.truncate(top);
+    }
+
+    Ok(SearchResults {
+        hits,
+        searched,
+        prefiltered: prefiltered.into_inner(),
+    })
+}
+
+/// Write one tab-separated row per hit, best first.
+///
+/// Coordinates and coverage are those of the first reported alignment, coordinates
+/// 1-based and inclusive; a hit without significance values has `NA` for its bit score
+/// and E-value, and one without an alignment `NA` for its coordinates and coverage.
+pub fn write_hits_tsv<W: Write>(
+    writer: &mut W,
+    query_id: &str,
+    hits: &[SearchHit],
+) -> io::Result<()> {
+    writeln!(
+        writer,
+        "rank\tquery\tsubject\tsubject_length\tscore\tbit_score\tevalue\t\
+         query_start\tquery_end\tsubject_start\tsubject_end\tcigar\t\
+         query_coverage\tsubject_coverage"
+    )?;
+    for (rank, hit) in hits.iter().enumerate() {
+        let (bit_score, evalue) = hit.significance.map_or_else(
+            || ("NA".to_string(), "NA".to_string()),
+            |s| (format!("{:.1}", s.bit_score), format!("{:.3e}", s.evalue)),
+        );
+        let (query_range, subject_range, cigar) = match hit.result.alignments.first() {
+            Some(alignment) => (
+                format!("{}\t{}", alignment.start_a + 1, alignment.end_a()),
+                format!("{}\t{}", alignment.start_b + 1, alignment.end_b()),
+                alignment.cigar(),
+            ),
+            None => ("NA\tNA".to_string(), "NA\tNA".to_string(), "NA".to_string()),
+        };
+        let format_coverage = |coverage: Option<f64>| {
+            coverage.map_or_else(|| "NA".to_string(), |c| format!("{:.4}", c))
+        };
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            rank + 1,
+            query_id,
+            hit.id,
+            hit.length,
+            hit.score,
+            bit_score,
+            evalue,
+            query_range,
+            subject_range,
+            cigar,
+            format_coverage(hit.coverage.0),
+            format_coverage(hit.coverage.1)
+        )?;
+    }
+    Ok(())
+}
+
+/// Write the first alignment of each hit as a block headed by its rank, subject and score
+pub fn write_hit_alignments<W: Write>(writer: &mut W, hits: &[SearchHit]) -> io::Result<()> {
+    for (rank, hit) in hits.iter().enumerate() {
+        writeln!(writer, "# {} {} score={}", rank + 1, hit.id, hit.score)?;
+        if let Some(alignment) = hit.result.alignments.first() {
+            writeln!(writer, "{}", alignment.align_a)?;
+            writeln!(writer, "{}", alignment.align_b)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
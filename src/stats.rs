@@ -0,0 +1,718 @@
+use crate::io::parameters::AlignmentParameters;
+use crate::models::{
+    Alignment, AlignmentResult, Alphabet, ColumnKind, GapPenalties, MatchMatrix, Residue,
+};
+use crate::score_only::{local_score, optimal_score};
+use crate::utils::Epsilon;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Write;
+
+/// Score of aligning a sequence against itself with no gaps
+pub fn self_alignment_score<S: Residue>(seq: &[S], match_matrix: &MatchMatrix<f64, S>) -> f64 {
+    seq.iter().map(|c| match_matrix.get_score(c, c)).sum()
+}
+
+impl AlignmentResult<f64> {
+    /// Score divided by the larger of the two self-alignment scores
+    pub fn score_ratio<S: Residue>(&self, params: &AlignmentParameters<f64, S>) -> f64 {
+        let self_score_a = self_alignment_score(&params.sequences.seq_a, &params.match_matrix);
+        let self_score_b = self_alignment_score(&params.sequences.seq_b, &params.match_matrix);
+        self.score / self_score_a.max(self_score_b)
+    }
+
+    /// Whether the score is strictly greater than `threshold`
+    pub fn score_above(&self, threshold: f64) -> bool {
+        self.score > threshold
+    }
+
+    /// [`infer_dot_bracket`] of the rows of each alignment, in order
+    pub fn to_dot_bracket(&self) -> Vec<String> {
+        self.alignments
+            .iter()
+            .map(|alignment| infer_dot_bracket(&alignment.align_a, &alignment.align_b))
+            .collect()
+    }
+}
+
+/// Highest-scoring result of a batch, the first one on ties. `None` for an empty batch.
+pub fn max_score(results: &[AlignmentResult]) -> Option<&AlignmentResult> {
+    results.iter().reduce(|best, r| {
+        if r.score.total_cmp(&best.score).is_gt() {
+            r
+        } else {
+            best
+        }
+    })
+}
+
+/// Lowest-scoring result of a batch, the first one on ties. `None` for an empty batch.
+pub fn min_score(results: &[AlignmentResult]) -> Option<&AlignmentResult> {
+    results.iter().reduce(|worst, r| {
+        if r.score.total_cmp(&worst.score).is_lt() {
+            r
+        } else {
+            worst
+        }
+    })
+}
+
+/// Fraction of the average match score charged for opening a gap by
+/// [`suggest_gap_penalties`]
+const SUGGESTED_OPEN_FRACTION: f64 = 0.3;
+/// Fraction of the average match score charged for each further gap position
+const SUGGESTED_EXTEND_FRACTION: f64 = 0.1;
+
+/// Starting gap penalties for a pair of sequences, scaled to their average match score.
+///
+/// The average match score weighs the score of each residue against itself by how often
+/// it occurs in the two sequences together. Opening a gap then costs 30% of it and each
+/// further position 10%, following the rule of thumb in Durbin et al., *Biological
+/// Sequence Analysis*, that gap costs be a modest fraction of a typical match. The
+/// penalties are the same for gaps in either sequence, and zero if the average match
+/// score is not positive.
+pub fn suggest_gap_penalties<S: Residue>(
+    seq_a: &[S],
+    seq_b: &[S],
+    match_matrix: &MatchMatrix<f64, S>,
+) -> GapPenalties<f64> {
+    let residues = seq_a.len() + seq_b.len();
+    let average = if residues == 0 {
+        0.0
+    } else {
+        let total: f64 = seq_a
+            .iter()
+            .chain(seq_b)
+            .map(|c| match_matrix.get_score(c, c))
+            .sum();
+        (total / residues as f64).max(0.0)
+    };
+    let open = SUGGESTED_OPEN_FRACTION * average;
+    let extend = SUGGESTED_EXTEND_FRACTION * average;
+    GapPenalties::new(open, extend, open, extend)
+}
+
+/// Score pairs present in both matrices, as `(score in a, score in b)` in residue order
+fn common_scores<S: Residue>(a: &MatchMatrix<f64, S>, b: &MatchMatrix<f64, S>) -> Vec<(f64, f64)> {
+    let mut pairs: Vec<(S, S, f64)> = a.iter().filter(|(x, y, _)| b.has_score(x, y)).collect();
+    pairs.sort_by(|p, q| (&p.0, &p.1).cmp(&(&q.0, &q.1)));
+    pairs
+        .into_iter()
+        .map(|(x, y, score)| (score, b.get_score(&x, &y)))
+        .collect()
+}
+
+/// Frobenius norm of the difference between two match matrices, over the residue pairs
+/// scored by both
+pub fn matrix_distance<S: Residue>(a: &MatchMatrix<f64, S>, b: &MatchMatrix<f64, S>) -> f64 {
+    common_scores(a, b)
+        .iter()
+        .fold(0.0, |total, (x, y)| total + (x - y).powi(2))
+        .sqrt()
+}
+
+/// Pearson correlation of the scores two match matrices give the residue pairs scored by
+/// both.
+///
+/// A low correlation between a user-supplied matrix and the builtin it is meant to
+/// reproduce usually points to a misread or transposed file. `NaN` if fewer than two
+/// pairs are shared or either matrix is constant over them.
+pub fn correlation<S: Residue>(a: &MatchMatrix<f64, S>, b: &MatchMatrix<f64, S>) -> f64 {
+    let scores = common_scores(a, b);
+    let n = scores.len() as f64;
+    if scores.len() < 2 {
+        return f64::NAN;
+    }
+    let mean_a = scores.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_b = scores.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let (covariance, var_a, var_b) =
+        scores
+            .iter()
+            .fold((0.0, 0.0, 0.0), |(cov, var_a, var_b), (x, y)| {
+                let (dx, dy) = (x - mean_a, y - mean_b);
+                (cov + dx * dy, var_a + dx * dx, var_b + dy * dy)
+            });
+    if var_a == 0.0 || var_b == 0.0 {
+        return f64::NAN;
+    }
+    covariance / (var_a * var_b).sqrt()
+}
+
+/// Karlin–Altschul statistical parameters for a scoring system
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct KarlinAltschul {
+    pub lambda: f64,
+    pub k: f64,
+}
+
+/// Bit score and E-value of a local alignment score
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Significance {
+    pub bit_score: f64,
+    pub evalue: f64,
+}
+
+/// BLAST-style gap (open, extend) costs; `None` marks ungapped alignment
+type GapCosts = Option<(u32, u32)>;
+
+/// Published (lambda, K) values keyed by matrix and gap costs
+const KARLIN_ALTSCHUL_TABLE: [(&str, GapCosts, f64, f64); 13] = [
+    ("blosum62", None, 0.3176, 0.134),
+    ("blosum62", Some((11, 2)), 0.297, 0.082),
+    ("blosum62", Some((10, 2)), 0.291, 0.075),
+    ("blosum62", Some((9, 2)), 0.279, 0.058),
+    ("blosum62", Some((8, 2)), 0.264, 0.045),
+    ("blosum62", Some((7, 2)), 0.239, 0.027),
+    ("blosum62", Some((6, 2)), 0.201, 0.012),
+    ("blosum62", Some((13, 1)), 0.292, 0.071),
+    ("blosum62", Some((12, 1)), 0.283, 0.059),
+    ("blosum62", Some((11, 1)), 0.267, 0.041),
+    ("blosum62", Some((10, 1)), 0.243, 0.024),
+    ("blosum62", Some((9, 1)), 0.206, 0.010),
+    ("pam250", None, 0.229, 0.09),
+];
+
+impl KarlinAltschul {
+    pub fn new(lambda: f64, k: f64) -> Self {
+        Self { lambda, k }
+    }
+
+    /// Precomputed parameters for a builtin matrix under the given gap penalties.
+    ///
+    /// BLAST charges `open + extend` for the first gap column, which corresponds to
+    /// `dx = dy = open + extend` and `ex = ey = extend` here. Gaps that differ between
+    /// the two sequences have no published values.
+    pub fn lookup(matrix: &str, gap_penalties: &GapPenalties<f64>) -> Option<Self> {
+        let GapPenalties { dx, ex, dy, ey, .. } = *gap_penalties;
+        if dx != dy || ex != ey {
+            return None;
+        }
+        KARLIN_ALTSCHUL_TABLE
+            .iter()
+            .find(|(name, gaps, _, _)| {
+                name.eq_ignore_ascii_case(matrix)
+                    && gaps.is_some_and(|(open, extend)| {
+                        f64::from(open + extend) == dx && f64::from(extend) == ex
+                    })
+            })
+            .map(|&(_, _, lambda, k)| Self::new(lambda, k))
+    }
+
+    /// Parameters given in the input file, or else published values for its builtin matrix
+    pub fn from_parameters<S: Residue>(params: &AlignmentParameters<f64, S>) -> Option<Self> {
+        params.karlin_altschul.or_else(|| {
+            let name = params.match_matrix.builtin_name()?;
+            Self::lookup(name, &params.gap_penalties)
+        })
+    }
+
+    /// Parameters for ungapped alignments with a builtin matrix
+    pub fn ungapped(matrix: &str) -> Option<Self> {
+        KARLIN_ALTSCHUL_TABLE
+            .iter()
+            .find(|(name, gaps, _, _)| name.eq_ignore_ascii_case(matrix) && gaps.is_none())
+            .map(|&(_, _, lambda, k)| Self::new(lambda, k))
+    }
+
+    /// Normalized score in bits: `(lambda * S - ln K) / ln 2`
+    pub fn bit_score(&self, score: f64) -> f64 {
+        (self.lambda * score - self.k.ln()) / std::f64::consts::LN_2
+    }
+
+    /// Expected number of chance hits scoring at least `score` between sequences of
+    /// lengths `m` and `n`: `K * m * n * exp(-lambda * S)`.
+    ///
+    /// The full sequence lengths are used as the effective lengths; no edge-effect
+    /// correction is applied.
+    pub fn evalue(&self, score: f64, m: usize, n: usize) -> f64 {
+        self.k * m as f64 * n as f64 * (-self.lambda * score).exp()
+    }
+
+    pub fn significance(&self, score: f64, m: usize, n: usize) -> Significance {
+        Significance {
+            bit_score: self.bit_score(score),
+            evalue: self.evalue(score, m, n),
+        }
+    }
+}
+
+/// Significance of a local score against scores of composition-preserving shuffles of B
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ShuffleSignificance {
+    pub shuffles: usize,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub z_score: f64,
+    /// `(hits + 1) / (shuffles + 1)`, where hits counts shuffles scoring at least as well
+    pub p_value: f64,
+}
+
+/// Shuffle sequence B `shuffles` times and score each shuffle against A.
+///
+/// Shuffle `i` draws from a ChaCha stream keyed by `(seed, i)`, so the null
+/// distribution is the same for a given seed however the work is split across threads.
+pub fn shuffle_significance<S: Residue>(
+    score: f64,
+    params: &AlignmentParameters<f64, S>,
+    shuffles: usize,
+    seed: u64,
+) -> ShuffleSignificance {
+    let seq_a = &params.sequences.seq_a;
+    let seq_b = &params.sequences.seq_b;
+    let null_scores: Vec<f64> = (0..shuffles)
+        .into_par_iter()
+        .map(|i| {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            rng.set_stream(i as u64);
+            let mut shuffled = seq_b.clone();
+            shuffled.shuffle(&mut rng);
+            local_score(
+                seq_a,
+                &shuffled,
+                &params.match_matrix,
+                &params.gap_penalties,
+            )
+        })
+        .collect();
+
+    let n = null_scores.len().max(1) as f64;
+    let mean = null_scores.iter().sum::<f64>() / n;
+    let variance = null_scores.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n;
+    let std_dev = variance.sqrt();
+    let hits = null_scores.iter().filter(|&&s| s >= score).count();
+
+    ShuffleSignificance {
+        shuffles,
+        mean,
+        std_dev,
+        z_score: if std_dev > 0.0 {
+            (score - mean) / std_dev
+        } else {
+            f64::NAN
+        },
+        p_value: (hits + 1) as f64 / (shuffles + 1) as f64,
+    }
+}
+
+/// Gap penalty varied by [`sensitivity_analysis`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum GapParameter {
+    Dx,
+    Ex,
+    Dy,
+    Ey,
+}
+
+impl GapParameter {
+    pub const ALL: [GapParameter; 4] = [
+        GapParameter::Dx,
+        GapParameter::Ex,
+        GapParameter::Dy,
+        GapParameter::Ey,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GapParameter::Dx => "dx",
+            GapParameter::Ex => "ex",
+            GapParameter::Dy => "dy",
+            GapParameter::Ey => "ey",
+        }
+    }
+
+    fn value_mut<'a>(&self, gap_penalties: &'a mut GapPenalties<f64>) -> &'a mut f64 {
+        match self {
+            GapParameter::Dx => &mut gap_penalties.dx,
+            GapParameter::Ex => &mut gap_penalties.ex,
+            GapParameter::Dy => &mut gap_penalties.dy,
+            GapParameter::Ey => &mut gap_penalties.ey,
+        }
+    }
+}
+
+/// Optimal score after shifting one gap penalty by `delta`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SensitivityPoint {
+    pub parameter: GapParameter,
+    pub delta: f64,
+    pub new_score: f64,
+    pub delta_score: f64,
+}
+
+/// How the optimal score responds to each gap penalty
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ParameterSensitivity {
+    pub parameter: GapParameter,
+    /// Central difference `d score / d penalty` over the smallest step
+    pub derivative: f64,
+    /// Smallest tested decrease at which the score stops changing linearly, i.e. a
+    /// different alignment becomes optimal
+    pub switch_below: Option<f64>,
+    /// Smallest tested increase at which a different alignment becomes optimal
+    pub switch_above: Option<f64>,
+}
+
+/// Score changes under independent variations of each gap penalty
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SensitivityReport {
+    pub base_score: f64,
+    pub points: Vec<SensitivityPoint>,
+    pub parameters: Vec<ParameterSensitivity>,
+}
+
+/// Vary each of `dx`, `ex`, `dy` and `ey` by up to `±delta` in `steps` equal steps and
+/// rerun the score-only DP for each value.
+///
+/// The optimal score is piecewise linear in each penalty, with slope equal to minus the
+/// number of times the optimal alignment pays it, so a change in slope between steps
+/// marks a switch to a different optimum. Boundaries are only located to within a step.
+pub fn sensitivity_analysis<S: Residue>(
+    params: &AlignmentParameters<f64, S>,
+    delta: f64,
+    steps: usize,
+) -> SensitivityReport {
+    let base_score = optimal_score(params);
+    let step = if steps > 0 { delta / steps as f64 } else { 0.0 };
+    let steps = steps as i64;
+    let offsets: Vec<f64> = (-steps..=steps)
+        .filter(|&k| k != 0)
+        .map(|k| k as f64 * step)
+        .collect();
+
+    let points: Vec<SensitivityPoint> = GapParameter::ALL
+        .iter()
+        .flat_map(|&parameter| offsets.iter().map(move |&offset| (parameter, offset)))
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|(parameter, offset)| {
+            let mut varied = params.clone();
+            *parameter.value_mut(&mut varied.gap_penalties) += offset;
+            let new_score = optimal_score(&varied);
+            SensitivityPoint {
+                parameter,
+                delta: offset,
+                new_score,
+                delta_score: new_score - base_score,
+            }
+        })
+        .collect();
+
+    let parameters = GapParameter::ALL
+        .iter()
+        .map(|&parameter| {
+            let side = |sign: f64| -> Vec<f64> {
+                let mut scores: Vec<(f64, f64)> = points
+                    .iter()
+                    .filter(|p| p.parameter == parameter && p.delta * sign > 0.0)
+                    .map(|p| (p.delta.abs(), p.new_score))
+                    .collect();
+                scores.sort_by(|x, y| x.0.total_cmp(&y.0));
+                scores.into_iter().map(|(_, score)| score).collect()
+            };
+            let (below, above) = (side(-1.0), side(1.0));
+            let derivative = match (below.first(), above.first()) {
+                (Some(low), Some(high)) => (high - low) / (2.0 * step),
+                _ => 0.0,
+            };
+            ParameterSensitivity {
+                parameter,
+                derivative,
+                switch_below: switch_offset(base_score, &below, step),
+                switch_above: switch_offset(base_score, &above, step),
+            }
+        })
+        .collect();
+
+    SensitivityReport {
+        base_score,
+        points,
+        parameters,
+    }
+}
+
+/// First offset along one side whose score leaves the line through the first step
+fn switch_offset(base_score: f64, scores: &[f64], step: f64) -> Option<f64> {
+    let slope = scores.first()? - base_score;
+    (1..scores.len())
+        .find(|&k| !f64::fuzzy_equals(scores[k] - scores[k - 1], slope))
+        .map(|k| (k + 1) as f64 * step)
+}
+
+impl SensitivityReport {
+    /// Write one CSV row per varied penalty and offset
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "parameter,delta,new_score,delta_score")?;
+        for point in &self.points {
+            writeln!(
+                writer,
+                "{},{},{},{}",
+                point.parameter.as_str(),
+                point.delta,
+                point.new_score,
+                point.delta_score
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// IUPAC nucleotide codes accepted by [`is_nucleotide_alphabet`]
+const NUCLEOTIDE_SYMBOLS: &str = "ACGTURYSWKMBDHVN";
+
+/// Whether an alphabet looks like DNA or RNA: only IUPAC nucleotide codes (in either
+/// case) and containing A, C, G and one of T or U
+pub fn is_nucleotide_alphabet(alphabet: &Alphabet) -> bool {
+    let symbols: Vec<char> = alphabet
+        .symbols()
+        .iter()
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    symbols.iter().all(|c| NUCLEOTIDE_SYMBOLS.contains(*c))
+        && ['A', 'C', 'G'].iter().all(|c| symbols.contains(c))
+        && (symbols.contains(&'T') || symbols.contains(&'U'))
+}
+
+/// Map a residue to A, C, G or T, treating U as T; `None` for ambiguity codes
+fn unambiguous_base(c: char) -> Option<char> {
+    match c.to_ascii_uppercase() {
+        'U' => Some('T'),
+        base @ ('A' | 'C' | 'G' | 'T') => Some(base),
+        _ => None,
+    }
+}
+
+/// Dot-bracket summary of where two aligned RNA rows could base pair, one character
+/// per column: `(` where A's base pairs with B's as A-U or G-C, `)` where it pairs the
+/// other way round, as U-A or C-G, and `.` for every other column, gaps included. T
+/// counts as U and case is ignored; wobble pairs and ambiguity codes count as unpaired.
+pub fn infer_dot_bracket(seq_a_aligned: &str, seq_b_aligned: &str) -> String {
+    seq_a_aligned
+        .chars()
+        .zip(seq_b_aligned.chars())
+        .map(|(a, b)| match (unambiguous_base(a), unambiguous_base(b)) {
+            (Some('A'), Some('T')) | (Some('G'), Some('C')) => '(',
+            (Some('T'), Some('A')) | (Some('C'), Some('G')) => ')',
+            _ => '.',
+        })
+        .collect()
+}
+
+/// Substitution spectrum and indel lengths of a nucleotide alignment
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DnaStats {
+    /// Aligned columns with the same base in both rows
+    pub identical: usize,
+    /// Counts per unordered substitution, keyed like `A<->G`
+    pub substitutions: BTreeMap<String, usize>,
+    /// A<->G and C<->T substitutions
+    pub transitions: usize,
+    pub transversions: usize,
+    /// Transitions over transversions; `None` without transversions
+    pub ts_tv_ratio: Option<f64>,
+    /// Aligned columns involving an ambiguity code, left out of the counts above
+    pub ambiguous: usize,
+    /// Number of gap runs of each length; a run ends when the gapped sequence changes
+    pub indel_lengths: BTreeMap<usize, usize>,
+}
+
+impl DnaStats {
+    pub fn from_alignment(alignment: &Alignment) -> Self {
+        let mut stats = Self::default();
+        let mut run: Option<(ColumnKind, usize)> = None;
+
+        for column in alignment.columns() {
+            if !column.kind.is_gap() {
+                let base = |residue: Option<char>| residue.and_then(unambiguous_base);
+                match (base(column.a), base(column.b)) {
+                    (Some(a), Some(b)) if a == b => stats.identical += 1,
+                    (Some(a), Some(b)) => stats.add_substitution(a, b),
+                    _ => stats.ambiguous += 1,
+                }
+            }
+
+            run = match run {
+                Some((previous, len)) if previous == column.kind => Some((previous, len + 1)),
+                _ => {
+                    stats.close_run(run);
+                    column.kind.is_gap().then_some((column.kind, 1))
+                }
+            };
+        }
+        stats.close_run(run);
+
+        stats.ts_tv_ratio = (stats.transversions > 0)
+            .then(|| stats.transitions as f64 / stats.transversions as f64);
+        stats
+    }
+
+    fn add_substitution(&mut self, a: char, b: char) {
+        let (low, high) = if a < b { (a, b) } else { (b, a) };
+        *self
+            .substitutions
+            .entry(format!("{}<->{}", low, high))
+            .or_default() += 1;
+        if matches!((low, high), ('A', 'G') | ('C', 'T')) {
+            self.transitions += 1;
+        } else {
+            self.transversions += 1;
+        }
+    }
+
+    fn close_run(&mut self, run: Option<(ColumnKind, usize)>) {
+        if let Some((_, len)) = run {
+            *self.indel_lengths.entry(len).or_default() += 1;
+        }
+    }
+
+    /// One-line summary for the text report
+    pub fn summary(&self) -> String {
+        let join = |items: Vec<String>| {
+            if items.is_empty() {
+                "none".to_string()
+            } else {
+                items.join(",")
+            }
+        };
+        format!(
+            "identical={} transitions={} transversions={} ts/tv={} ambiguous={} substitutions={} indel_lengths={}",
+            self.identical,
+            self.transitions,
+            self.transversions,
+            self.ts_tv_ratio
+                .map_or("NA".to_string(), |ratio| format!("{:.3}", ratio)),
+            self.ambiguous,
+            join(
+                self.substitutions
+                    .iter()
+                    .map(|(pair, count)| format!("{}:{}", pair, count))
+                    .collect()
+            ),
+            join(
+                self.indel_lengths
+                    .iter()
+                    .map(|(len, count)| format!("{}:{}", len, count))
+                    .collect()
+            ),
+        )
+    }
+}
+
+/// Identity and coverage of one alignment, recomputed from its rows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct AlignmentSummary {
+    pub columns: usize,
+    /// Aligned columns with the same residue in both rows
+    pub identical: usize,
+    /// Columns with a gap in either row
+    pub gap_columns: usize,
+    /// Residues of each sequence covered by the alignment
+    pub residues_a: usize,
+    pub residues_b: usize,
+}
+
+/// Columns of the TSV written by [`write_summaries_tsv`], before the coverage columns
+const SUMMARY_TSV_HEADER: &str =
+    "index\tcolumns\tidentical\tidentity\tgap_columns\tresidues_a\tresidues_b";
+
+impl AlignmentSummary {
+    pub fn from_alignment(alignment: &Alignment) -> Self {
+        let mut summary = Self {
+            columns: alignment.len(),
+            ..Self::default()
+        };
+        for column in alignment.columns() {
+            match column.kind {
+                ColumnKind::Match => summary.identical += 1,
+                ColumnKind::Mismatch => {}
+                ColumnKind::InsertA | ColumnKind::InsertB => summary.gap_columns += 1,
+            }
+            summary.residues_a += usize::from(column.a_pos.is_some());
+            summary.residues_b += usize::from(column.b_pos.is_some());
+        }
+        summary
+    }
+
+    /// Identical columns over all columns; `None` for an empty alignment
+    pub fn identity(&self) -> Option<f64> {
+        (self.columns > 0).then(|| self.identical as f64 / self.columns as f64)
+    }
+
+    /// Fractions of sequences of `len_a` and `len_b` residues covered by the alignment
+    pub fn coverage(&self, len_a: usize, len_b: usize) -> (Option<f64>, Option<f64>) {
+        let fraction =
+            |residues: usize, len: usize| (len > 0).then(|| residues as f64 / len as f64);
+        (
+            fraction(self.residues_a, len_a),
+            fraction(self.residues_b, len_b),
+        )
+    }
+}
+
+/// Minimum fractions of each sequence an alignment must span to be reported; see
+/// [`Alignment::coverage`]. Unset thresholds do not apply.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CoverageFilter {
+    pub min_a: Option<f64>,
+    pub min_b: Option<f64>,
+}
+
+impl CoverageFilter {
+    pub fn is_set(&self) -> bool {
+        self.min_a.is_some() || self.min_b.is_some()
+    }
+
+    /// Whether coverages `(coverage_a, coverage_b)` meet both thresholds. An undefined
+    /// coverage, of an empty sequence or of a pair without an alignment, fails any
+    /// threshold set for it.
+    pub fn passes(&self, (coverage_a, coverage_b): (Option<f64>, Option<f64>)) -> bool {
+        let meets = |coverage: Option<f64>, min: Option<f64>| {
+            min.is_none_or(|min| coverage.is_some_and(|coverage| coverage >= min))
+        };
+        meets(coverage_a, self.min_a) && meets(coverage_b, self.min_b)
+    }
+}
+
+/// Write one row per alignment, adding coverage columns when the sequence lengths are
+/// known; undefined fractions are written as `NA`
+pub fn write_summaries_tsv<W: Write>(
+    writer: &mut W,
+    summaries: &[AlignmentSummary],
+    lengths: Option<(usize, usize)>,
+) -> io::Result<()> {
+    let format_fraction =
+        |fraction: Option<f64>| fraction.map_or_else(|| "NA".to_string(), |f| format!("{:.4}", f));
+    write!(writer, "{}", SUMMARY_TSV_HEADER)?;
+    if lengths.is_some() {
+        write!(writer, "\tcoverage_a\tcoverage_b")?;
+    }
+    writeln!(writer)?;
+    for (i, summary) in summaries.iter().enumerate() {
+        write!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            i + 1,
+            summary.columns,
+            summary.identical,
+            format_fraction(summary.identity()),
+            summary.gap_columns,
+            summary.residues_a,
+            summary.residues_b
+        )?;
+        if let Some((len_a, len_b)) = lengths {
+            let (coverage_a, coverage_b) = summary.coverage(len_a, len_b);
+            write!(
+                writer,
+                "\t{}\t{}",
+                format_fraction(coverage_a),
+                format_fraction(coverage_b)
+            )?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,194 @@
+use crate::io::parameters::AlignmentParameters;
+use crate::models::score_matrix::MatrixType::{Ix, Iy, M};
+use crate::models::score_matrix::{MatrixType, Pointer, ScoreMatrix};
+use crate::models::ScoringSource;
+use crate::models::{AlignGrid, Residue};
+use num_traits::Zero;
+use serde::Serialize;
+use std::fmt::Display;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// One cell of the DP grid as the fill computed it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CellUpdate<T> {
+    /// Index of the residue of A, one less than the grid row past the boundary
+    pub row: usize,
+    /// Index of the residue of B, one less than the grid column past the boundary
+    pub col: usize,
+    #[serde(serialize_with = "serialize_matrix")]
+    pub matrix: MatrixType,
+    /// Score of the predecessor cell in M, if this cell has one there
+    pub prev_m: Option<T>,
+    /// Score of the predecessor cell in Ix, if this cell has one there
+    pub prev_ix: Option<T>,
+    /// Score of the predecessor cell in Iy, if this cell has one there
+    pub prev_iy: Option<T>,
+    /// Score of aligning the two residues, for cells of M
+    pub match_score: Option<T>,
+    pub new_score: T,
+    /// Predecessors the score was taken from, as `matrix(row,col)` with the same indices
+    /// as `row` and `col`
+    #[serde(serialize_with = "serialize_pointers")]
+    pub pointers: Vec<Pointer>,
+}
+
+fn serialize_matrix<S: serde::Serializer>(
+    matrix: &MatrixType,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(matrix.as_str())
+}
+
+fn serialize_pointers<S: serde::Serializer>(
+    pointers: &[Pointer],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(pointers.iter().map(|&pointer| format_pointer(pointer)))
+}
+
+fn format_pointer((matrix, row, col): Pointer) -> String {
+    format!("{}({},{})", matrix.as_str(), row, col)
+}
+
+const TSV_HEADER: &str =
+    "step\trow\tcol\tmatrix\tprev_m\tprev_ix\tprev_iy\tmatch_score\tnew_score\tpointers";
+
+/// Step-by-step log of a DP fill, for following the algorithm cell by cell.
+///
+/// Filled by [`AlignGrid::populate_score_matrices_traced`], which records each cell from
+/// inside the fill loop as soon as it is computed. The log holds three records per cell,
+/// so it is meant for the small examples used in teaching rather than for real sequences.
+#[derive(Debug, Clone, Default)]
+pub struct StepTracer<T> {
+    pub updates: Vec<CellUpdate<T>>,
+}
+
+impl<T: Copy + Display + FromStr + Zero> StepTracer<T> {
+    pub fn new() -> Self {
+        Self {
+            updates: Vec::new(),
+        }
+    }
+
+    /// Record M, Ix and Iy of the grid cell at `row` and `col`, called by the fill as soon
+    /// as it has computed them, so the predecessors are read as the fill left them
+    pub(crate) fn record_cell<S: Residue>(
+        &mut self,
+        grid: &AlignGrid<T>,
+        alignment_parameters: &AlignmentParameters<T, S>,
+        source: &ScoringSource<T, S>,
+        row: usize,
+        col: usize,
+    ) {
+        for matrix in [M, Ix, Iy] {
+            self.updates.push(Self::update(
+                grid,
+                alignment_parameters,
+                source,
+                matrix,
+                row,
+                col,
+            ));
+        }
+    }
+
+    fn update<S: Residue>(
+        grid: &AlignGrid<T>,
+        alignment_parameters: &AlignmentParameters<T, S>,
+        source: &ScoringSource<T, S>,
+        matrix: MatrixType,
+        row: usize,
+        col: usize,
+    ) -> CellUpdate<T> {
+        // The cell M, Ix or Iy builds on: diagonal, above or to the left. The first row of
+        // Ix and first column of Iy are set from the end gaps rather than computed.
+        let predecessor = match matrix {
+            M => Some((row - 1, col - 1)),
+            Ix => (row > 1).then(|| (row - 1, col)),
+            Iy => (col > 1).then(|| (row, col - 1)),
+        };
+        let score_at = |scores: &ScoreMatrix<T>, from: bool| {
+            predecessor
+                .filter(|_| from)
+                .map(|(r, c)| scores.get_score(r, c))
+        };
+        let sequences = &alignment_parameters.sequences;
+        let (scores, match_score) = match matrix {
+            M => (
+                &grid.m_matrix,
+                Some(if sequences.is_padding(row - 1, col - 1) {
+                    T::zero()
+                } else {
+                    source.score(
+                        row - 1,
+                        &sequences.seq_a[row - 1],
+                        &sequences.seq_b[col - 1],
+                    )
+                }),
+            ),
+            Ix => (&grid.ix_matrix, None),
+            Iy => (&grid.iy_matrix, None),
+        };
+        CellUpdate {
+            row: row - 1,
+            col: col - 1,
+            matrix,
+            prev_m: score_at(&grid.m_matrix, true),
+            prev_ix: score_at(&grid.ix_matrix, matrix != Iy),
+            prev_iy: score_at(&grid.iy_matrix, matrix != Ix),
+            match_score,
+            new_score: scores.get_score(row, col),
+            pointers: scores
+                .get_pointers(row, col)
+                .iter()
+                .map(|&(matrix, r, c)| (matrix, r - 1, c - 1))
+                .collect(),
+        }
+    }
+}
+
+impl<T: Copy + Display + FromStr + Serialize + Zero> StepTracer<T> {
+    /// Write the log to `path`: as a JSON array if it ends in `.json`, otherwise as TSV
+    /// with one numbered step per line and empty fields for missing values
+    pub fn write_trace(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        if Path::new(path)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+        {
+            serde_json::to_writer_pretty(&mut writer, &self.updates)?;
+            writeln!(writer)?;
+        } else {
+            self.write_tsv(&mut writer)?;
+        }
+        writer.flush()
+    }
+
+    fn write_tsv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let optional = |value: Option<T>| value.map_or_else(String::new, |v| v.to_string());
+        writeln!(writer, "{}", TSV_HEADER)?;
+        for (step, update) in self.updates.iter().enumerate() {
+            let pointers: Vec<String> =
+                update.pointers.iter().map(|&p| format_pointer(p)).collect();
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                step + 1,
+                update.row,
+                update.col,
+                update.matrix.as_str(),
+                optional(update.prev_m),
+                optional(update.prev_ix),
+                optional(update.prev_iy),
+                optional(update.match_score),
+                update.new_score,
+                pointers.join(",")
+            )?;
+        }
+        Ok(())
+    }
+}
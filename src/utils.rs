@@ -17,6 +17,8 @@ pub fn clamp_to_zero<T: Zero + PartialOrd>(score: T) -> T {
 pub trait Epsilon {
     fn epsilon() -> Self;
     fn fuzzy_equals(a: Self, b: Self) -> bool;
+    /// Whether a score is neither NaN nor infinite; always true for integers
+    fn is_finite(a: Self) -> bool;
 }
 
 impl Epsilon for f64 {
@@ -27,6 +29,24 @@ impl Epsilon for f64 {
     fn fuzzy_equals(a: f64, b: f64) -> bool {
         (a - b).abs() < Self::epsilon()
     }
+
+    fn is_finite(a: f64) -> bool {
+        a.is_finite()
+    }
+}
+
+impl Epsilon for f32 {
+    fn epsilon() -> Self {
+        1e-4
+    }
+
+    fn fuzzy_equals(a: f32, b: f32) -> bool {
+        (a - b).abs() < Self::epsilon()
+    }
+
+    fn is_finite(a: f32) -> bool {
+        a.is_finite()
+    }
 }
 
 impl Epsilon for i32 {
@@ -37,4 +57,36 @@ impl Epsilon for i32 {
     fn fuzzy_equals(a: i32, b: i32) -> bool {
         a == b
     }
+
+    fn is_finite(_a: i32) -> bool {
+        true
+    }
+}
+
+impl Epsilon for i16 {
+    fn epsilon() -> Self {
+        0
+    }
+
+    fn fuzzy_equals(a: i16, b: i16) -> bool {
+        a == b
+    }
+
+    fn is_finite(_a: i16) -> bool {
+        true
+    }
+}
+
+impl Epsilon for i8 {
+    fn epsilon() -> Self {
+        0
+    }
+
+    fn fuzzy_equals(a: i8, b: i8) -> bool {
+        a == b
+    }
+
+    fn is_finite(_a: i8) -> bool {
+        true
+    }
 }
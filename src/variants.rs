@@ -0,0 +1,191 @@
+use crate::models::{Alignment, AlignmentOp};
+use std::io;
+use std::io::Write;
+
+/// Kind of difference between the reference (A) and the alternate (B)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariantType {
+    Snv,
+    Insertion,
+    Deletion,
+}
+
+impl VariantType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VariantType::Snv => "SNV",
+            VariantType::Insertion => "INS",
+            VariantType::Deletion => "DEL",
+        }
+    }
+}
+
+/// A difference from sequence A, in VCF conventions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    /// 1-based position of the first reference base in `ref_allele`
+    pub pos: usize,
+    pub ref_allele: String,
+    pub alt_allele: String,
+    pub variant_type: VariantType,
+}
+
+/// Convert an alignment into variants relative to sequence A.
+///
+/// Mismatched columns become SNVs. Runs of gap columns become single indel records
+/// anchored on the preceding reference base (or the following base at the start of A),
+/// after being shifted left as far as the reference allows without overlapping the
+/// previous record. `seq_a` must be the full sequence the alignment was made against.
+pub fn variants_from_alignment(alignment: &Alignment, seq_a: &[char]) -> Vec<Variant> {
+    let mut variants = Vec::new();
+    // Reference positions before this one are covered by an earlier record
+    let mut floor = 0;
+    let (mut pos_a, mut col) = (alignment.start_a, 0);
+    let row_b: Vec<char> = alignment.align_b.chars().collect();
+
+    while col < alignment.ops.len() {
+        let op = alignment.ops[col];
+        let run = alignment.ops[col..]
+            .iter()
+            .take_while(|&&next| next == op)
+            .count();
+
+        match op {
+            AlignmentOp::Match => {
+                for offset in 0..run {
+                    let (reference, alternate) = (seq_a[pos_a + offset], row_b[col + offset]);
+                    if reference != alternate {
+                        variants.push(Variant {
+                            pos: pos_a + offset + 1,
+                            ref_allele: reference.to_string(),
+                            alt_allele: alternate.to_string(),
+                            variant_type: VariantType::Snv,
+                        });
+                        floor = pos_a + offset + 1;
+                    }
+                }
+            }
+            AlignmentOp::Deletion => {
+                let start = left_normalize_deletion(seq_a, pos_a, run, floor);
+                variants.push(indel(seq_a, start, &seq_a[start..start + run], true));
+                floor = pos_a + run;
+            }
+            AlignmentOp::Insertion => {
+                let inserted: Vec<char> = row_b[col..col + run].to_vec();
+                let (start, inserted) = left_normalize_insertion(seq_a, pos_a, inserted, floor);
+                variants.push(indel(seq_a, start, &inserted, false));
+                floor = pos_a;
+            }
+        }
+
+        if op.consumes_a() {
+            pos_a += run;
+        }
+        col += run;
+    }
+    variants
+}
+
+/// Shift a deletion of `seq_a[start..start + len]` left through repeated bases
+fn left_normalize_deletion(seq_a: &[char], mut start: usize, len: usize, floor: usize) -> usize {
+    while start > floor && seq_a[start - 1] == seq_a[start + len - 1] {
+        start -= 1;
+    }
+    start
+}
+
+/// Shift an insertion before `seq_a[start]` left, rotating the inserted bases as it moves
+fn left_normalize_insertion(
+    seq_a: &[char],
+    mut start: usize,
+    mut inserted: Vec<char>,
+    floor: usize,
+) -> (usize, Vec<char>) {
+    while start > floor && inserted.last() == Some(&seq_a[start - 1]) {
+        inserted.rotate_right(1);
+        start -= 1;
+    }
+    (start, inserted)
+}
+
+/// Build an indel record for `bases` deleted at, or inserted before, `seq_a[start]`
+fn indel(seq_a: &[char], start: usize, bases: &[char], deletion: bool) -> Variant {
+    let bases: String = bases.iter().collect();
+    let deleted_len = if deletion { bases.len() } else { 0 };
+    let variant_type = if deletion {
+        VariantType::Deletion
+    } else {
+        VariantType::Insertion
+    };
+
+    if start > 0 {
+        let anchor = seq_a[start - 1];
+        let (ref_allele, alt_allele) = if deletion {
+            (format!("{}{}", anchor, bases), anchor.to_string())
+        } else {
+            (anchor.to_string(), format!("{}{}", anchor, bases))
+        };
+        Variant {
+            pos: start,
+            ref_allele,
+            alt_allele,
+            variant_type,
+        }
+    } else {
+        // No preceding base: anchor on the first reference base after the event
+        let anchor = seq_a.get(deleted_len).copied().unwrap_or('N');
+        let (ref_allele, alt_allele) = if deletion {
+            (format!("{}{}", bases, anchor), anchor.to_string())
+        } else {
+            (anchor.to_string(), format!("{}{}", bases, anchor))
+        };
+        Variant {
+            pos: 1,
+            ref_allele,
+            alt_allele,
+            variant_type,
+        }
+    }
+}
+
+/// Write a minimal single-sample VCF 4.2 with sequence A as the reference contig
+pub fn write_vcf<W: Write>(
+    writer: &mut W,
+    reference_name: &str,
+    reference_len: usize,
+    sample_name: &str,
+    variants: &[Variant],
+) -> io::Result<()> {
+    writeln!(writer, "##fileformat=VCFv4.2")?;
+    writeln!(writer, "##source=align")?;
+    writeln!(
+        writer,
+        "##contig=<ID={},length={}>",
+        reference_name, reference_len
+    )?;
+    writeln!(
+        writer,
+        "##INFO=<ID=TYPE,Number=1,Type=String,Description=\"Variant type: SNV, INS or DEL\">"
+    )?;
+    writeln!(
+        writer,
+        "##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">"
+    )?;
+    writeln!(
+        writer,
+        "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\t{}",
+        sample_name
+    )?;
+    for variant in variants {
+        writeln!(
+            writer,
+            "{}\t{}\t.\t{}\t{}\t.\tPASS\tTYPE={}\tGT\t1",
+            reference_name,
+            variant.pos,
+            variant.ref_allele,
+            variant.alt_allele,
+            variant.variant_type.as_str()
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use crate::io::parameters::AlignmentParameters;
+use crate::models::{Alignment, AlignmentOp, AlignmentResult, GapPenalties, MatchMatrix};
+use crate::utils::Epsilon;
+use std::fmt;
+
+/// An alignment whose rescored value differs from the reported score
+#[derive(Debug, Clone, PartialEq)]
+pub struct Discrepancy {
+    /// Index of the alignment within the result
+    pub index: usize,
+    pub reported: f64,
+    pub rescored: f64,
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "alignment {} rescores to {} but the reported score is {}",
+            self.index + 1,
+            self.rescored,
+            self.reported
+        )
+    }
+}
+
+/// Score contributed by each column of an alignment under an affine gap model.
+///
+/// A gap in B (A residue against a gap) opens with `dy` and extends with `ey`; a gap
+/// in A opens with `dx` and extends with `ex`. In a global alignment, a gap in B after the
+/// last residue of B, or in A after the last of A, is charged the terminal penalties for
+/// the ends of that sequence instead, as the fill charges it. Free end gaps are never
+/// written into the aligned rows (the overhanging residues are simply left out), so every
+/// gap column that is present is charged.
+pub fn column_scores(alignment: &Alignment, parameters: &AlignmentParameters<f64>) -> Vec<f64> {
+    let ends = parameters
+        .global_alignment
+        .then(|| (parameters.len_a() + 1, parameters.len_b() + 1));
+    affine_column_scores(
+        alignment,
+        &parameters.match_matrix,
+        &parameters.gap_penalties,
+        ends,
+    )
+}
+
+/// [`column_scores`] under `match_matrix` and `gaps`, with the terminal penalties charged
+/// at the ends of a grid of `ends` rows and columns when given, and the internal
+/// penalties everywhere otherwise
+pub(crate) fn affine_column_scores(
+    alignment: &Alignment,
+    match_matrix: &MatchMatrix<f64>,
+    gaps: &GapPenalties<f64>,
+    ends: Option<(usize, usize)>,
+) -> Vec<f64> {
+    let (mut pos_a, mut pos_b) = (alignment.start_a, alignment.start_b);
+    let mut previous = None;
+    alignment
+        .ops
+        .iter()
+        .zip(alignment.align_a.chars())
+        .zip(alignment.align_b.chars())
+        .map(|((op, a), b)| {
+            pos_a += usize::from(op.consumes_a());
+            pos_b += usize::from(op.consumes_b());
+            let (open, extend) = match (op, ends) {
+                (AlignmentOp::Deletion, Some((_, ncol))) => gaps.y_penalties(pos_b, ncol),
+                (AlignmentOp::Deletion, None) => (gaps.dy, gaps.ey),
+                (AlignmentOp::Insertion, Some((nrow, _))) => gaps.x_penalties(pos_a, nrow),
+                _ => (gaps.dx, gaps.ex),
+            };
+            let score = match op {
+                AlignmentOp::Match => match_matrix.get_score(&a, &b),
+                _ if previous == Some(*op) => -extend,
+                _ => -open,
+            };
+            previous = Some(*op);
+            score
+        })
+        .collect()
+}
+
+/// Score an alignment under an affine gap model; see [`column_scores`]. A global
+/// alignment is also charged for the residues of each sequence left before its start,
+/// unless that sequence's ends are free, as the fill charges them through the boundary.
+pub fn rescore(alignment: &Alignment, parameters: &AlignmentParameters<f64>) -> f64 {
+    let gaps = &parameters.gap_penalties;
+    let leading_gap = |penalties: Option<(f64, f64)>, residues: usize| match penalties {
+        Some((open, extend)) if parameters.global_alignment && residues > 0 => {
+            open + (residues - 1) as f64 * extend
+        }
+        _ => 0.0,
+    };
+    column_scores(alignment, parameters).iter().sum::<f64>()
+        - leading_gap(gaps.a_end_penalties(), alignment.start_a)
+        - leading_gap(gaps.b_end_penalties(), alignment.start_b)
+}
+
+/// Rescore every alignment in a result and report those that disagree with its
+/// reported score
+pub fn verify(
+    result: &AlignmentResult<f64>,
+    parameters: &AlignmentParameters<f64>,
+) -> Vec<Discrepancy> {
+    result
+        .alignments
+        .iter()
+        .enumerate()
+        .filter_map(|(index, alignment)| {
+            let rescored = rescore(alignment, parameters);
+            let reported = result.alignment_score(index);
+            (!f64::fuzzy_equals(rescored, reported)).then_some(Discrepancy {
+                index,
+                reported,
+                rescored,
+            })
+        })
+        .collect()
+}
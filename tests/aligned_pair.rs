@@ -0,0 +1,48 @@
+mod common;
+
+use sequence_alignment::alignment::{traceback, AlignmentEngine};
+use sequence_alignment::io::parameters::AlignmentParameters;
+use sequence_alignment::models::score_matrix::MatrixType::{Ix, M};
+use sequence_alignment::models::{AlignGrid, TokenFormat};
+
+#[test]
+fn result_alignments_carry_the_gap_columns_of_their_rows() {
+    let dir = common::scratch_dir("aligned-pair-result");
+    let input = common::write_file(
+        &dir,
+        "input.txt",
+        &common::dna_input("ACGTTACGTA", "ACGACGA", 0, "2 1 2 1", 2.0, -1.0),
+    );
+    let parameters = AlignmentParameters::<f64>::load_from_file(&input).unwrap();
+    let mut grid = AlignGrid::new(parameters.len_a(), parameters.len_b());
+    grid.populate_score_matrices(&parameters).unwrap();
+    let result = traceback(&grid, &parameters, &TokenFormat::default()).unwrap();
+
+    let pair = &result.alignments[0];
+    assert_eq!((pair.seq_a(), pair.seq_b()), ("ACGTTACGTA", "ACG__ACG_A"));
+    assert!(pair.gaps_a().is_empty());
+    assert_eq!(pair.gaps_b(), [3, 4, 8]);
+    assert_eq!(result.aligned_pairs(), result.alignments.as_slice());
+}
+
+#[test]
+fn the_engine_lists_the_gaps_along_a_path() {
+    let dir = common::scratch_dir("aligned-pair-engine");
+    let input = common::write_file(
+        &dir,
+        "input.txt",
+        &common::dna_input("ACGT", "AGT", 0, "2 1 2 1", 2.0, -1.0),
+    );
+    let parameters = AlignmentParameters::<f64>::load_from_file(&input).unwrap();
+    let token_format = TokenFormat::default();
+    let engine = AlignmentEngine::new(&parameters, &token_format);
+
+    let path = vec![(M, 1, 1), (Ix, 2, 1), (M, 3, 2), (M, 4, 3)];
+    let (seq_a, gaps_a, seq_b, gaps_b) = engine.path_to_alignment_with_gaps(path.clone());
+    assert_eq!((seq_a.as_str(), seq_b.as_str()), ("ACGT", "A_GT"));
+    assert_eq!((gaps_a, gaps_b), (vec![], vec![1]));
+
+    let pair = engine.path_to_aligned_pair(path);
+    assert_eq!((pair.start_a, pair.start_b), (0, 0));
+    assert_eq!(pair.cigar(), "1M1D2M");
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use sequence_alignment::alignment::traceback;
+use sequence_alignment::generate::{
+    generate_pair, generated_parameters, AlphabetKind, MutationRates,
+};
+use sequence_alignment::io::diffs::apply_alignment;
+use sequence_alignment::models::{AlignGrid, AlignmentResult, TokenFormat};
+use std::fs;
+
+fn align(kind: AlphabetKind, seq_a: &str, seq_b: &str, global: bool) -> AlignmentResult<f64> {
+    let parameters = generated_parameters(kind, seq_a.to_string(), seq_b.to_string(), global);
+    let mut grid = AlignGrid::new(parameters.len_a(), parameters.len_b());
+    grid.populate_score_matrices(&parameters).unwrap();
+    traceback(&grid, &parameters, &TokenFormat::default()).unwrap()
+}
+
+#[test]
+fn applying_each_alignment_of_random_pairs_gives_back_b() {
+    let mut rng = ChaCha8Rng::seed_from_u64(404);
+    for seed in 0..150 {
+        let kind = if seed % 2 == 0 {
+            AlphabetKind::Dna
+        } else {
+            AlphabetKind::Protein
+        };
+        let rates = MutationRates {
+            identity: rng.random_range(0.5..1.0),
+            indel_rate: rng.random_range(0.0..0.15),
+        };
+        let (seq_a, seq_b) = generate_pair(kind, rng.random_range(1..60), rates, seed);
+        let residues_a: Vec<char> = seq_a.chars().collect();
+        let residues_b: Vec<char> = seq_b.chars().collect();
+
+        for global in [true, false] {
+            for alignment in align(kind, &seq_a, &seq_b, global).alignments {
+                let covered_b: String = residues_b[alignment.start_b..alignment.end_b()]
+                    .iter()
+                    .collect();
+                assert_eq!(
+                    apply_alignment(&residues_a, &alignment).unwrap(),
+                    covered_b,
+                    "{} against {}",
+                    seq_a,
+                    seq_b
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn applying_to_a_shorter_or_different_a_is_an_error() {
+    let (seq_a, seq_b) = ("ACGTACGTTA", "ACGACGTTA");
+    let result = align(AlphabetKind::Dna, seq_a, seq_b, true);
+    let alignment = &result.alignments[0];
+
+    let shorter: Vec<char> = seq_a[..5].chars().collect();
+    let error = apply_alignment(&shorter, alignment).unwrap_err();
+    assert!(error.to_string().contains("but A has 5 residues"));
+
+    let different: Vec<char> = "TTTTTTTTTT".chars().collect();
+    let error = apply_alignment(&different, alignment).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("row A of the alignment does not match A"));
+}
+
+#[test]
+fn convert_apply_rebuilds_b_from_a_diffs_listing() {
+    let dir = common::scratch_dir("apply-cli");
+    let rates = MutationRates {
+        identity: 0.9,
+        indel_rate: 0.05,
+    };
+    let (seq_a, seq_b) = generate_pair(AlphabetKind::Dna, 80, rates, 7);
+    let input = common::write_file(
+        &dir,
+        "input.txt",
+        &common::dna_input(&seq_a, &seq_b, 0, "3 1 3 1", 1.0, -1.0),
+    );
+    let fasta = common::write_file(&dir, "a.fa", &format!(">a\n{}\n", seq_a));
+    let diffs = dir.join("diffs.txt").to_string_lossy().into_owned();
+    let rebuilt = dir.join("b.fa").to_string_lossy().into_owned();
+
+    common::align_ok(&["--format", "diffs", "align", &input, &diffs]);
+    common::align_ok(&["convert", "--apply", &fasta, &diffs, &rebuilt]);
+
+    let rebuilt = fs::read_to_string(&rebuilt).unwrap();
+    let mut records = rebuilt.lines();
+    while let Some(header) = records.next() {
+        let range = |key: &str| -> usize {
+            let field = header.split(' ').find_map(|f| f.strip_prefix(key)).unwrap();
+            field.parse().unwrap()
+        };
+        let (start, end) = (range("start="), range("end="));
+        assert_eq!(
+            records.next().unwrap(),
+            &seq_b[start - 1..end],
+            "{}",
+            header
+        );
+    }
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use sequence_alignment::io::parameters::AlignmentParameters;
+use sequence_alignment::models::Sequences;
+
+#[test]
+fn random_sequences_survive_a_base64_round_trip_through_a_file() {
+    let mut rng = ChaCha8Rng::seed_from_u64(379);
+    let dir = common::scratch_dir("base64");
+    for case in 0..50 {
+        let mut random_seq = || -> String {
+            let len = rng.random_range(1..200);
+            (0..len)
+                .map(|_| b"ACGT"[rng.random_range(0..4)] as char)
+                .collect()
+        };
+        let (seq_a, seq_b) = (random_seq(), random_seq());
+        let input = common::dna_input(
+            &format!("base64:{}", STANDARD.encode(&seq_a)),
+            &format!("base64:{}", STANDARD.encode(&seq_b)),
+            0,
+            "2 1 2 1",
+            1.0,
+            -1.0,
+        );
+        let path = common::write_file(&dir, &format!("case{}.txt", case), &input);
+
+        let parameters = AlignmentParameters::<f64>::load_from_file(&path).unwrap();
+        let chars = |seq: &str| seq.chars().collect::<Vec<_>>();
+        assert_eq!(parameters.sequences.seq_a, chars(&seq_a));
+        assert_eq!(parameters.sequences.seq_b, chars(&seq_b));
+    }
+}
+
+#[test]
+fn from_base64_decodes_both_sequences() {
+    let sequences =
+        Sequences::from_base64(&STANDARD.encode("GATTACA"), &STANDARD.encode("T>A\nC")).unwrap();
+    assert_eq!(sequences.seq_a.iter().collect::<String>(), "GATTACA");
+    assert_eq!(sequences.seq_b.iter().collect::<String>(), "T>A\nC");
+}
+
+#[test]
+fn from_base64_rejects_invalid_and_non_ascii_input() {
+    assert!(Sequences::from_base64("not base64!", &STANDARD.encode("A")).is_err());
+    assert!(Sequences::from_base64(&STANDARD.encode("A"), &STANDARD.encode("αβ")).is_err());
+}
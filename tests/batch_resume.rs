@@ -0,0 +1,79 @@
+mod common;
+
+use sequence_alignment::generate::{generate_pair, AlphabetKind, MutationRates};
+use std::collections::HashSet;
+use std::fs;
+
+const RECORDS: usize = 7;
+
+fn sorted_rows(tsv: &str) -> Vec<String> {
+    let mut rows: Vec<String> = tsv.lines().map(str::to_string).collect();
+    rows.sort();
+    rows
+}
+
+#[test]
+fn resume_after_a_row_cut_off_mid_write_neither_duplicates_nor_skips_pairs() {
+    let dir = common::scratch_dir("batch-resume");
+    let rates = MutationRates {
+        identity: 0.9,
+        indel_rate: 0.05,
+    };
+    let fasta: String = (0..RECORDS)
+        .map(|i| {
+            let (seq, _) = generate_pair(AlphabetKind::Dna, 40 + 5 * i, rates, i as u64);
+            format!(">r{}\n{}\n", i, seq)
+        })
+        .collect();
+    let fasta = common::write_file(&dir, "records.fa", &fasta);
+    let input = common::write_file(
+        &dir,
+        "global.txt",
+        &common::dna_input("A", "A", 0, "3 1 3 1", 1.0, -1.0),
+    );
+    let complete = dir.join("complete.tsv").to_string_lossy().into_owned();
+    common::align_ok(&["batch", &input, &fasta, &complete]);
+    let expected = fs::read_to_string(&complete).unwrap();
+    assert_eq!(expected.lines().count(), 1 + RECORDS * (RECORDS - 1) / 2);
+
+    // Cut the file every 23 bytes through the rows, most cuts landing mid-row
+    let header_len = expected.find('\n').unwrap() + 1;
+    for cut in (header_len..expected.len()).step_by(23) {
+        let partial = common::write_file(&dir, "partial.tsv", &expected[..cut]);
+        common::align_ok(&["batch", "--resume", &input, &fasta, &partial]);
+
+        let resumed = fs::read_to_string(&partial).unwrap();
+        assert_eq!(
+            sorted_rows(&resumed),
+            sorted_rows(&expected),
+            "cut at {}",
+            cut
+        );
+        let pairs: HashSet<(&str, &str)> = resumed
+            .lines()
+            .skip(1)
+            .map(|row| {
+                let mut fields = row.split('\t');
+                (fields.next().unwrap(), fields.next().unwrap())
+            })
+            .collect();
+        assert_eq!(pairs.len(), RECORDS * (RECORDS - 1) / 2, "cut at {}", cut);
+    }
+}
+
+#[test]
+fn resume_of_a_complete_run_aligns_nothing_more() {
+    let dir = common::scratch_dir("batch-resume-complete");
+    let fasta = common::write_file(&dir, "records.fa", ">x\nACGTAC\n>y\nACGAC\n>z\nTTGCA\n");
+    let input = common::write_file(
+        &dir,
+        "global.txt",
+        &common::dna_input("A", "A", 0, "3 1 3 1", 1.0, -1.0),
+    );
+    let pairs = dir.join("pairs.tsv").to_string_lossy().into_owned();
+    common::align_ok(&["batch", &input, &fasta, &pairs]);
+    let complete = fs::read_to_string(&pairs).unwrap();
+
+    common::align_ok(&["batch", "--resume", &input, &fasta, &pairs]);
+    assert_eq!(fs::read_to_string(&pairs).unwrap(), complete);
+}
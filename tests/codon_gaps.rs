@@ -0,0 +1,56 @@
+mod common;
+
+use sequence_alignment::io::parameters::AlignmentParameters;
+use sequence_alignment::models::{AlignGrid, CodonAwareGapPenalties};
+
+fn input() -> String {
+    let dir = common::scratch_dir("codon-gaps");
+    common::write_file(
+        &dir,
+        "input.txt",
+        &common::dna_input("ACGTAC", "ACGAC", 0, "3 1 3 1", 2.0, -1.0),
+    )
+}
+
+#[test]
+fn the_checked_codon_fill_names_the_first_nan_cell() {
+    let parameters = AlignmentParameters::<f64>::load_from_file(&input()).unwrap();
+    let mut grid = AlignGrid::new(parameters.len_a(), parameters.len_b());
+    let codon_gaps = CodonAwareGapPenalties::new(f64::NAN, f64::NAN, 1.0);
+    let error = grid
+        .populate_codon_aware_checked(&parameters, &codon_gaps)
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("NaN score at Iy[1,2]"),
+        "{}",
+        error
+    );
+
+    let codon_gaps = CodonAwareGapPenalties::new(3.0, 5.0, 1.0);
+    grid.populate_codon_aware_checked(&parameters, &codon_gaps)
+        .unwrap();
+}
+
+#[test]
+fn check_scores_applies_to_the_codon_fill() {
+    let input = input();
+    let output = common::align(&[
+        "align",
+        "--codon-gaps",
+        "NaN,NaN,1",
+        "--check-scores",
+        &input,
+        "-",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("NaN score at Iy[1,2]"));
+
+    common::align_ok(&[
+        "align",
+        "--codon-gaps",
+        "3,5,1",
+        "--check-scores",
+        &input,
+        "-",
+    ]);
+}
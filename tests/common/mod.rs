@@ -0,0 +1,92 @@
+#![allow(dead_code)]
+
+use sequence_alignment::consistency::check_consistency;
+use sequence_alignment::io::parameters::AlignmentParameters;
+use sequence_alignment::models::AlignGrid;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A fresh, empty directory under the target directory for one test's files
+pub fn scratch_dir(name: &str) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!(
+        "{}-{}-{}",
+        name,
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// An input file aligning `seq_a` and `seq_b` over ACGT, scoring `matched` for identical
+/// residues and `mismatched` otherwise
+pub fn dna_input(
+    seq_a: &str,
+    seq_b: &str,
+    alignment_type: u8,
+    gaps: &str,
+    matched: f64,
+    mismatched: f64,
+) -> String {
+    let mut input = format!(
+        "{}\n{}\n{}\n{}\n4\nACGT\n4\nACGT\n",
+        seq_a, seq_b, alignment_type, gaps
+    );
+    for (i, a) in "ACGT".chars().enumerate() {
+        for (j, b) in "ACGT".chars().enumerate() {
+            let score = if a == b { matched } else { mismatched };
+            input += &format!("{} {} {} {} {}\n", i + 1, j + 1, a, b, score);
+        }
+    }
+    input
+}
+
+/// Write `contents` to `name` in `dir`, returning the path as a string
+pub fn write_file(dir: &Path, name: &str, contents: &str) -> String {
+    let path = dir.join(name);
+    fs::write(&path, contents).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+/// Run the `align` binary with `args`
+pub fn align(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_align"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Run the `align` binary, failing the test unless it exits successfully
+pub fn align_ok(args: &[&str]) -> Output {
+    let output = align(args);
+    assert!(
+        output.status.success(),
+        "align {:?} failed with {:?}: {}",
+        args,
+        output.status.code(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+/// Fail the test, naming the first few cells, unless every score and pointer of a filled
+/// grid agrees with the recurrence
+pub fn assert_consistent(grid: &AlignGrid<f64>, parameters: &AlignmentParameters<f64>) {
+    let report = check_consistency(grid, parameters, 3);
+    assert!(
+        report.is_consistent(),
+        "{} of {} cells inconsistent: {}",
+        report.mismatched,
+        report.checked,
+        report
+            .first
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+}
@@ -0,0 +1,40 @@
+mod common;
+
+/// The completion script `align completions <shell>` writes to stdout
+fn completions(shell: &str) -> String {
+    String::from_utf8(common::align_ok(&["completions", shell]).stdout).unwrap()
+}
+
+#[test]
+fn bash_and_zsh_completions_list_known_flags() {
+    for shell in ["bash", "zsh"] {
+        let script = completions(shell);
+        for flag in ["--matrix", "--local", "--gap-open", "--format"] {
+            assert!(script.contains(flag), "{} lacks {}", shell, flag);
+        }
+    }
+}
+
+#[test]
+fn fish_completions_list_known_flags() {
+    let script = completions("fish");
+    for flag in ["-l matrix", "-l local", "-l gap-open", "-l format"] {
+        assert!(script.contains(flag), "fish lacks {}", flag);
+    }
+}
+
+#[test]
+fn completions_offer_the_builtin_matrices_and_formats() {
+    for shell in ["bash", "zsh", "fish"] {
+        let script = completions(shell);
+        for value in ["blosum62", "pam250", "interleaved"] {
+            assert!(script.contains(value), "{} lacks {}", shell, value);
+        }
+    }
+}
+
+#[test]
+fn completions_are_hidden_from_help() {
+    let help = String::from_utf8(common::align_ok(&["--help"]).stdout).unwrap();
+    assert!(!help.contains("completions"));
+}
@@ -0,0 +1,53 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use sequence_alignment::consistency::scores_agree_int_vs_float;
+
+const RESIDUES: [char; 4] = ['A', 'C', 'G', 'T'];
+
+/// A parameter file with random sequences, alignment type, gap penalties and integer
+/// match scores
+fn random_parameters(rng: &mut ChaCha8Rng) -> String {
+    let sequence = |rng: &mut ChaCha8Rng| -> String {
+        (0..rng.random_range(1..25))
+            .map(|_| RESIDUES[rng.random_range(0..RESIDUES.len())])
+            .collect()
+    };
+    let (seq_a, seq_b) = (sequence(rng), sequence(rng));
+    let alignment_type = rng.random_range(0..2);
+    let gaps: Vec<i32> = (0..4).map(|_| rng.random_range(0..6)).collect();
+    let mut params = format!(
+        "{}\n{}\n{}\n{} {} {} {}\n4\nACGT\n4\nACGT\n",
+        seq_a, seq_b, alignment_type, gaps[0], gaps[1], gaps[2], gaps[3]
+    );
+    for (i, a) in RESIDUES.iter().enumerate() {
+        for (j, b) in RESIDUES.iter().enumerate() {
+            let score = if i == j {
+                rng.random_range(1..8)
+            } else {
+                rng.random_range(-6..3)
+            };
+            params += &format!("{} {} {} {} {}\n", i + 1, j + 1, a, b, score);
+        }
+    }
+    params
+}
+
+#[test]
+fn random_integer_parameters_align_the_same_with_i32_and_f64_scores() {
+    let mut rng = ChaCha8Rng::seed_from_u64(411);
+    for _ in 0..300 {
+        let params = random_parameters(&mut rng);
+        assert!(
+            scores_agree_int_vs_float(&params, 1e-9).unwrap(),
+            "{}",
+            params
+        );
+    }
+}
+
+#[test]
+fn non_integer_scores_cannot_be_compared() {
+    let params = "AC\nAC\n0\n2 1 2 1\n2\nAC\n2\nAC\n\
+                  1 1 A A 1.5\n1 2 A C -1\n2 1 C A -1\n2 2 C C 1\n";
+    assert!(scores_agree_int_vs_float(params, 1e-9).is_err());
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use sequence_alignment::alignment::traceback;
+use sequence_alignment::generate::{
+    generate_pair, generated_parameters, AlphabetKind, MutationRates,
+};
+use sequence_alignment::io::parameters::AlignmentParameters;
+use sequence_alignment::models::{
+    AlignGrid, AlignmentOp, AlignmentResult, EndGaps, GapConstraints, TokenFormat,
+};
+
+const RATES: MutationRates = MutationRates {
+    identity: 0.8,
+    indel_rate: 0.15,
+};
+
+fn align(
+    parameters: &AlignmentParameters<f64>,
+    constraints: Option<&GapConstraints>,
+) -> AlignmentResult {
+    let mut grid = AlignGrid::new(parameters.len_a(), parameters.len_b());
+    match constraints {
+        Some(constraints) => grid.populate_constrained(parameters, constraints).unwrap(),
+        None => grid.populate_score_matrices(parameters).unwrap(),
+    }
+    traceback(&grid, parameters, &TokenFormat::default()).unwrap()
+}
+
+/// Length of the longest run of gap columns in any of the alignments
+fn longest_gap(result: &AlignmentResult) -> usize {
+    let mut longest = 0;
+    for alignment in &result.alignments {
+        let mut run = (AlignmentOp::Match, 0);
+        for &op in &alignment.ops {
+            run = if op == run.0 {
+                (op, run.1 + 1)
+            } else {
+                (op, 1)
+            };
+            if op != AlignmentOp::Match {
+                longest = longest.max(run.1);
+            }
+        }
+    }
+    longest
+}
+
+#[test]
+fn constraints_no_alignment_runs_into_give_the_unconstrained_result() {
+    for seed in 0..60 {
+        let len = 5 + seed as usize % 25;
+        let (seq_a, seq_b) = generate_pair(AlphabetKind::Dna, len, RATES, seed);
+        for global in [true, false] {
+            let mut parameters =
+                generated_parameters(AlphabetKind::Dna, seq_a.clone(), seq_b.clone(), global);
+            if seed % 3 == 0 {
+                parameters.gap_penalties = parameters
+                    .gap_penalties
+                    .with_end_gaps(EndGaps::Charged, EndGaps::Charged);
+            }
+            let unconstrained = align(&parameters, None);
+
+            // A limit longer than either sequence, and one just long enough for every
+            // co-optimal alignment
+            for max_gap_length in [seq_a.len() + seq_b.len(), longest_gap(&unconstrained)] {
+                let constraints = GapConstraints {
+                    max_gap_length: Some(max_gap_length),
+                    no_gap_region_a: None,
+                };
+                assert_eq!(
+                    align(&parameters, Some(&constraints)),
+                    unconstrained,
+                    "{} against {}, global {}, max gap {}",
+                    seq_a,
+                    seq_b,
+                    global,
+                    max_gap_length
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn a_no_gap_region_the_alignments_do_not_gap_keeps_the_optimum() {
+    for seed in 0..60 {
+        let len = 5 + seed as usize % 25;
+        let (seq_a, seq_b) = generate_pair(AlphabetKind::Dna, len, RATES, seed);
+        let parameters =
+            generated_parameters(AlphabetKind::Dna, seq_a.clone(), seq_b.clone(), false);
+        let unconstrained = align(&parameters, None);
+        let Some(first) = unconstrained.alignments.first() else {
+            continue;
+        };
+        // The residues of A in the first alignment's longest gap-free stretch
+        let mut best = 0..0;
+        let mut start_a = first.start_a;
+        let mut run = first.start_a..first.start_a;
+        for op in &first.ops {
+            match op {
+                AlignmentOp::Match => run.end += 1,
+                _ => run = start_a + 1..start_a + 1,
+            }
+            if op != &AlignmentOp::Insertion {
+                start_a += 1;
+            }
+            if run.len() > best.len() {
+                best = run.clone();
+            }
+        }
+        if best.len() < 3 {
+            continue;
+        }
+        // Leave out the residues at either end of the stretch, which a gap may touch
+        let region = best.start + 1..best.end - 1;
+        let constraints = GapConstraints {
+            max_gap_length: None,
+            no_gap_region_a: Some(region.clone()),
+        };
+        let constrained = align(&parameters, Some(&constraints));
+        assert_eq!(
+            constrained.score, unconstrained.score,
+            "{} against {}, {:?}",
+            seq_a, seq_b, region
+        );
+        assert!(constrained
+            .alignments
+            .contains(&unconstrained.alignments[0]));
+        assert!(constrained
+            .alignments
+            .iter()
+            .all(|alignment| unconstrained.alignments.contains(alignment)));
+    }
+}
@@ -0,0 +1,176 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use sequence_alignment::models::{
+    Alignment, AlignmentOp, CoordinateMap, MappedInterval, MappedPos, GAP_CHAR,
+};
+
+fn alignment(row_a: &str, row_b: &str, start_a: usize, start_b: usize) -> Alignment {
+    let mut alignment =
+        Alignment::from_rows(row_a.to_string(), row_b.to_string(), GAP_CHAR).unwrap();
+    alignment.start_a = start_a;
+    alignment.start_b = start_b;
+    alignment
+}
+
+/// A3..A9 against B10..B15 with an insertion after A4 and a deletion of A6 and A7
+fn gapped() -> Alignment {
+    alignment("AC_GTTC", "ACAG__C", 3, 10)
+}
+
+#[test]
+fn positions_outside_the_alignment_are_unaligned() {
+    let alignment = gapped();
+    for pos in [0, 2, 9, 100] {
+        assert_eq!(alignment.map_a_to_b(pos), MappedPos::Unaligned, "{}", pos);
+    }
+}
+
+#[test]
+fn aligned_positions_map_exactly_on_either_side_of_an_insertion() {
+    let alignment = gapped();
+    assert_eq!(alignment.map_a_to_b(3), MappedPos::Exact(10));
+    assert_eq!(alignment.map_a_to_b(4), MappedPos::Exact(11));
+    assert_eq!(alignment.map_a_to_b(5), MappedPos::Exact(13));
+    assert_eq!(alignment.map_a_to_b(8), MappedPos::Exact(14));
+}
+
+#[test]
+fn deleted_positions_give_the_flanking_b_positions() {
+    let alignment = gapped();
+    let flanked = MappedPos::InDeletion {
+        left: Some(13),
+        right: Some(14),
+    };
+    assert_eq!(alignment.map_a_to_b(6), flanked);
+    assert_eq!(alignment.map_a_to_b(7), flanked);
+}
+
+#[test]
+fn deletions_at_the_ends_of_the_alignment_have_one_flank() {
+    let leading = alignment("AC", "_C", 0, 0);
+    assert_eq!(
+        leading.map_a_to_b(0),
+        MappedPos::InDeletion {
+            left: None,
+            right: Some(0)
+        }
+    );
+    assert_eq!(leading.map_a_to_b(1), MappedPos::Exact(0));
+
+    let trailing = alignment("AC", "A_", 4, 7);
+    assert_eq!(trailing.map_a_to_b(4), MappedPos::Exact(7));
+    assert_eq!(
+        trailing.map_a_to_b(5),
+        MappedPos::InDeletion {
+            left: Some(7),
+            right: None
+        }
+    );
+}
+
+#[test]
+fn intervals_count_the_residues_lost_to_gaps_and_to_the_ends() {
+    let alignment = gapped();
+    let interval = |start, end, deleted, unaligned| MappedInterval {
+        start,
+        end,
+        deleted,
+        unaligned,
+    };
+    assert_eq!(
+        alignment.map_interval_a_to_b(3, 9),
+        Some(interval(10, 15, 2, 0))
+    );
+    assert_eq!(
+        alignment.map_interval_a_to_b(0, 20),
+        Some(interval(10, 15, 2, 14))
+    );
+    assert_eq!(
+        alignment.map_interval_a_to_b(5, 7),
+        Some(interval(13, 14, 1, 0))
+    );
+    assert_eq!(
+        alignment.map_interval_a_to_b(7, 9),
+        Some(interval(14, 15, 1, 0))
+    );
+    // Nothing of these is aligned to a residue of B
+    assert_eq!(alignment.map_interval_a_to_b(6, 8), None);
+    assert_eq!(alignment.map_interval_a_to_b(0, 3), None);
+    assert_eq!(alignment.map_interval_a_to_b(9, 12), None);
+    assert_eq!(alignment.map_interval_a_to_b(4, 4), None);
+}
+
+/// What a walk over the columns says `pos` maps to
+fn naive_map(alignment: &Alignment, pos: usize) -> MappedPos {
+    let (mut pos_a, mut pos_b) = (alignment.start_a, alignment.start_b);
+    for (col, &op) in alignment.ops.iter().enumerate() {
+        if op.consumes_a() && pos_a == pos {
+            if op == AlignmentOp::Match {
+                return MappedPos::Exact(pos_b);
+            }
+            let before = &alignment.ops[..col];
+            let after = &alignment.ops[col..];
+            return MappedPos::InDeletion {
+                left: before.iter().any(|op| op.consumes_b()).then(|| pos_b - 1),
+                right: after.iter().any(|op| op.consumes_b()).then_some(pos_b),
+            };
+        }
+        pos_a += usize::from(op.consumes_a());
+        pos_b += usize::from(op.consumes_b());
+    }
+    MappedPos::Unaligned
+}
+
+#[test]
+fn random_alignments_map_as_a_walk_over_their_columns_does() {
+    let mut rng = ChaCha8Rng::seed_from_u64(383);
+    for _ in 0..500 {
+        let ops: Vec<AlignmentOp> = (0..rng.random_range(1..30))
+            .map(|_| match rng.random_range(0..4) {
+                0 => AlignmentOp::Deletion,
+                1 => AlignmentOp::Insertion,
+                _ => AlignmentOp::Match,
+            })
+            .collect();
+        let row = |consumes: fn(&AlignmentOp) -> bool| -> String {
+            ops.iter()
+                .map(|op| if consumes(op) { 'A' } else { GAP_CHAR })
+                .collect()
+        };
+        let mut alignment = alignment(
+            &row(AlignmentOp::consumes_a),
+            &row(AlignmentOp::consumes_b),
+            rng.random_range(0..5),
+            rng.random_range(0..5),
+        );
+        alignment.ops = ops;
+        let map = CoordinateMap::new(&alignment);
+        let end_a = alignment.start_a + alignment.ops.iter().filter(|op| op.consumes_a()).count();
+
+        for pos in 0..end_a + 3 {
+            assert_eq!(map.map_a_to_b(pos), naive_map(&alignment, pos), "{}", pos);
+        }
+        for start in 0..end_a + 2 {
+            for end in start..end_a + 3 {
+                let mapped: Vec<usize> = (start..end)
+                    .filter_map(|pos| match naive_map(&alignment, pos) {
+                        MappedPos::Exact(b) => Some(b),
+                        _ => None,
+                    })
+                    .collect();
+                let count = |kind: fn(&MappedPos) -> bool| {
+                    (start..end)
+                        .filter(|&pos| kind(&naive_map(&alignment, pos)))
+                        .count()
+                };
+                let expected = (!mapped.is_empty()).then(|| MappedInterval {
+                    start: mapped[0],
+                    end: mapped[mapped.len() - 1] + 1,
+                    deleted: count(|mapped| matches!(mapped, MappedPos::InDeletion { .. })),
+                    unaligned: count(|mapped| *mapped == MappedPos::Unaligned),
+                });
+                assert_eq!(map.map_interval(start, end), expected, "{}..{}", start, end);
+            }
+        }
+    }
+}
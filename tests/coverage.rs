@@ -0,0 +1,105 @@
+mod common;
+
+use sequence_alignment::alignment::traceback;
+use sequence_alignment::io::parameters::AlignmentParameters;
+use sequence_alignment::models::{AlignGrid, TokenFormat};
+use std::fs;
+use std::path::Path;
+
+const QUERY: &str = "CCCCACGTTACGTCCCC";
+const SUBJECT: &str = "GGGACGTACGTGGG";
+
+fn coverages(
+    dir: &Path,
+    seq_a: &str,
+    seq_b: &str,
+    alignment_type: u8,
+    semi_global: bool,
+) -> Vec<(f64, f64)> {
+    let input = common::write_file(
+        dir,
+        "input.txt",
+        &common::dna_input(seq_a, seq_b, alignment_type, "2 1 2 1", 3.0, -3.0),
+    );
+    let mut parameters = AlignmentParameters::<f64>::load_from_file(&input).unwrap();
+    let mut grid = AlignGrid::new(parameters.len_a(), parameters.len_b());
+    if semi_global {
+        parameters.set_semi_global();
+        grid.populate_semi_global_free_b_end(&parameters).unwrap();
+    } else {
+        grid.populate_score_matrices(&parameters).unwrap();
+    }
+    traceback(&grid, &parameters, &TokenFormat::default())
+        .unwrap()
+        .alignments
+        .iter()
+        .map(|alignment| {
+            let (a, b) = alignment.coverage(seq_a.len(), seq_b.len());
+            (a.unwrap(), b.unwrap())
+        })
+        .collect()
+}
+
+#[test]
+fn a_local_alignment_covers_its_aligned_region_gaps_included() {
+    let dir = common::scratch_dir("coverage-local");
+    // ACGTTACGT against ACGT_ACGT or ACG_TACGT: the gap in B leaves nine residues of A
+    // and eight of B within the span
+    let coverages = coverages(&dir, QUERY, SUBJECT, 1, false);
+    assert_eq!(coverages.len(), 2);
+    for (a, b) in coverages {
+        assert_eq!((a, b), (9.0 / 17.0, 8.0 / 14.0));
+    }
+}
+
+#[test]
+fn free_end_gaps_leave_the_overhang_out_of_the_span() {
+    let dir = common::scratch_dir("coverage-free-ends");
+    assert_eq!(
+        coverages(&dir, "ATCG", "GGGATCGGG", 0, true),
+        [(1.0, 4.0 / 9.0)]
+    );
+    // The default global alignment leaves each sequence's overhang out the same way
+    assert_eq!(
+        coverages(&dir, "ATCG", "GGGATCGGG", 0, false),
+        [(1.0, 4.0 / 9.0)]
+    );
+}
+
+#[test]
+fn stats_and_batch_report_the_coverage_of_the_alignment() {
+    let dir = common::scratch_dir("coverage-cli");
+    let input = common::write_file(
+        &dir,
+        "input.txt",
+        &common::dna_input(QUERY, SUBJECT, 1, "2 1 2 1", 3.0, -3.0),
+    );
+    let result = dir.join("result.txt").to_string_lossy().into_owned();
+    common::align_ok(&["align", &input, &result]);
+    let stats = common::align_ok(&["stats", "--parameters", &input, &result]);
+    for row in String::from_utf8_lossy(&stats.stdout).lines().skip(1) {
+        assert!(row.ends_with("\t0.5294\t0.5714"), "{}", row);
+    }
+
+    let fasta = common::write_file(
+        &dir,
+        "records.fa",
+        &format!(">q\n{}\n>s\n{}\n>t\nACGTTACGT\n", QUERY, SUBJECT),
+    );
+    let pairs = dir.join("pairs.tsv").to_string_lossy().into_owned();
+    let run = common::align_ok(&["batch", "--min-coverage-a", "0.55", &input, &fasta, &pairs]);
+    assert!(String::from_utf8_lossy(&run.stderr).contains("dropped 2 of 3 pairs"));
+    let rows: Vec<Vec<String>> = fs::read_to_string(&pairs)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .map(|row| row.split('\t').map(str::to_string).collect())
+        .collect();
+    // q against s and t spans 9 of its 17 residues; s against t spans 8 of its 14
+    let (scores, coverages_a): (Vec<&str>, Vec<&str>) = rows
+        .iter()
+        .map(|row| (row[2].as_str(), row[8].as_str()))
+        .unzip();
+    assert_eq!(scores, ["NA", "NA", "22"]);
+    assert_eq!(coverages_a, ["0.5294", "0.5294", "0.5714"]);
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use sequence_alignment::generate::{generate_pair, AlphabetKind, MutationRates};
+use std::fs;
+use std::path::Path;
+
+const RUNS: usize = 4;
+
+const RATES: MutationRates = MutationRates {
+    identity: 0.85,
+    indel_rate: 0.05,
+};
+
+/// Run `align` with `args` followed by an output file, once per run and thread count,
+/// checking that every run writes the same bytes
+fn assert_deterministic(dir: &Path, args: &[&str]) {
+    let mut outputs = Vec::new();
+    for threads in ["1", "4"] {
+        for run in 0..RUNS {
+            let output_file = dir
+                .join(format!("out-{}-{}.txt", threads, run))
+                .to_string_lossy()
+                .into_owned();
+            let mut command = vec!["--threads", threads];
+            command.extend_from_slice(args);
+            command.push(&output_file);
+            common::align_ok(&command);
+            outputs.push((threads, fs::read(&output_file).unwrap()));
+        }
+    }
+    let (_, first) = &outputs[0];
+    assert!(!first.is_empty());
+    for (threads, output) in &outputs {
+        assert!(output == first, "{:?} with --threads {}", args, threads);
+    }
+}
+
+fn records(count: usize) -> String {
+    (0..count)
+        .map(|i| {
+            let (seq, _) = generate_pair(AlphabetKind::Dna, 30 + 7 * i, RATES, i as u64);
+            format!(">r{}\n{}\n", i, seq)
+        })
+        .collect()
+}
+
+#[test]
+fn co_optimal_alignments_come_out_the_same_on_every_run() {
+    let dir = common::scratch_dir("determinism-align");
+    // Repeats give many co-optimal alignments and several optimal cells
+    let input = common::write_file(
+        &dir,
+        "input.txt",
+        &common::dna_input("ACACACGTGTACAC", "ACACGTACACAC", 1, "1 1 1 1", 2.0, -1.0),
+    );
+    for format in ["text", "fasta", "json", "interleaved"] {
+        assert_deterministic(&dir, &["--format", format, "align", &input]);
+    }
+    assert_deterministic(&dir, &["align", "--canonical", "left", &input]);
+}
+
+/// `(index, start_a, end_a, start_b, end_b)` of every alignment written in `format`
+fn numbered_ranges(input: &str, format: &str) -> Vec<[usize; 5]> {
+    let output = common::align_ok(&["--format", format, "align", input, "-"]);
+    let output = String::from_utf8_lossy(&output.stdout);
+    let numbers = |fields: &[&str]| -> [usize; 5] {
+        fields
+            .iter()
+            .map(|field| field.parse().unwrap())
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap()
+    };
+    match format {
+        "tsv" => output
+            .lines()
+            .skip(1)
+            .map(|row| numbers(&[0, 2, 3, 4, 5].map(|i| row.split('\t').nth(i).unwrap())))
+            .collect(),
+        "json" => {
+            let json: serde_json::Value = serde_json::from_str(&output).unwrap();
+            json["alignments"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|alignment| {
+                    ["index", "start_a", "end_a", "start_b", "end_b"]
+                        .map(|key| alignment[key].as_u64().unwrap() as usize)
+                })
+                .collect()
+        }
+        _ => {
+            // `>alignment_<index>_<a|b> score=<s> start=<start> end=<end>`, A then B
+            let headers: Vec<[&str; 3]> = output
+                .lines()
+                .filter_map(|line| line.strip_prefix(">alignment_"))
+                .map(|header| {
+                    let field = |key: &str| {
+                        header
+                            .split(' ')
+                            .find_map(|field| field.strip_prefix(key))
+                            .unwrap()
+                    };
+                    [
+                        header.split('_').next().unwrap(),
+                        field("start="),
+                        field("end="),
+                    ]
+                })
+                .collect();
+            headers
+                .chunks(2)
+                .map(|pair| numbers(&[pair[0][0], pair[0][1], pair[0][2], pair[1][1], pair[1][2]]))
+                .collect()
+        }
+    }
+}
+
+#[test]
+fn every_format_numbers_the_alignments_alike() {
+    let dir = common::scratch_dir("determinism-formats");
+    let input = common::write_file(
+        &dir,
+        "input.txt",
+        &common::dna_input("ACACACGTGTACAC", "ACACGTACACAC", 1, "1 1 1 1", 2.0, -1.0),
+    );
+    let tsv = numbered_ranges(&input, "tsv");
+    assert!(tsv.len() > 1);
+    assert_eq!(numbered_ranges(&input, "json"), tsv);
+    assert_eq!(numbered_ranges(&input, "fasta"), tsv);
+}
+
+#[test]
+fn batch_rows_come_out_the_same_on_every_run() {
+    let dir = common::scratch_dir("determinism-batch");
+    let fasta = common::write_file(&dir, "records.fa", &records(8));
+    let input = common::write_file(
+        &dir,
+        "input.txt",
+        &common::dna_input("A", "A", 0, "3 1 3 1", 1.0, -1.0),
+    );
+    assert_deterministic(&dir, &["batch", &input, &fasta]);
+}
+
+#[test]
+fn search_hits_come_out_the_same_on_every_run() {
+    let dir = common::scratch_dir("determinism-search");
+    let database = common::write_file(&dir, "database.fa", &records(10));
+    let (query, _) = generate_pair(AlphabetKind::Dna, 40, RATES, 3);
+    let query = common::write_file(&dir, "query.fa", &format!(">q\n{}\n", query));
+    let input = common::write_file(
+        &dir,
+        "input.txt",
+        &common::dna_input("A", "A", 1, "3 1 3 1", 1.0, -1.0),
+    );
+    assert_deterministic(&dir, &["search", &input, &query, &database]);
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use sequence_alignment::distance::{write_phylip, PairDistances, UNDEFINED_DISTANCE};
+use sequence_alignment::models::{Alignment, GAP_CHAR};
+use std::fs;
+
+fn distances(row_a: &str, row_b: &str) -> PairDistances {
+    let alignment = Alignment::from_rows(row_a.to_string(), row_b.to_string(), GAP_CHAR).unwrap();
+    PairDistances::from_alignment(&alignment, 0.5)
+}
+
+#[test]
+fn columns_with_a_gap_in_either_row_are_not_compared() {
+    let pair = distances("ACG_TTA", "AC_ATGA");
+    assert_eq!((pair.compared, pair.mismatches), (5, 1));
+    assert_eq!(pair.p_distance, Some(0.2));
+    // The same residues without the gap columns give the same distances
+    assert_eq!(pair, distances("ACTTA", "ACATA"));
+}
+
+#[test]
+fn an_alignment_of_gaps_only_has_no_p_or_jc_distance() {
+    let pair = distances("AC__", "__GT");
+    assert_eq!(pair.compared, 0);
+    assert_eq!(pair.p_distance, None);
+    assert_eq!(pair.jukes_cantor, None);
+    assert_eq!(pair.score_distance, 0.5);
+}
+
+#[test]
+fn jukes_cantor_is_undefined_from_three_quarters_mismatched() {
+    let below = distances("AAAAAAAAAAA", "CCCCCCCCAAA");
+    let expected = 0.75 * (3.0_f64 / (3.0 - 4.0 * 8.0 / 11.0)).ln();
+    assert!((below.jukes_cantor.unwrap() - expected).abs() < 1e-12);
+    assert_eq!(distances("AAAA", "CCCA").jukes_cantor, None);
+    assert_eq!(distances("AAAA", "CCCC").jukes_cantor, None);
+}
+
+#[test]
+fn undefined_distances_are_written_as_the_sentinel() {
+    let mut out = Vec::new();
+    let names = ["x".to_string(), "y".to_string()];
+    write_phylip(&mut out, &names, &[vec![None, None], vec![None, None]]).unwrap();
+    assert_eq!(UNDEFINED_DISTANCE, -1.0);
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "2\nx          0.000000 -1.000000\ny          -1.000000 0.000000\n"
+    );
+}
+
+#[test]
+fn batch_marks_saturated_pairs_in_the_table_and_the_matrix() {
+    let dir = common::scratch_dir("distance");
+    let fasta = common::write_file(&dir, "records.fa", ">x\nAAAAC\n>y\nCCCCC\n>z\nAAGAC\n");
+    let input = common::write_file(
+        &dir,
+        "input.txt",
+        &common::dna_input("A", "A", 0, "9 9 9 9", 1.0, 0.5),
+    );
+    let pairs = dir.join("pairs.tsv").to_string_lossy().into_owned();
+    let phylip = dir.join("distances.phy").to_string_lossy().into_owned();
+    common::align_ok(&[
+        "batch", "--phylip", &phylip, "--metric", "jc", &input, &fasta, &pairs,
+    ]);
+
+    let rows = fs::read_to_string(&pairs).unwrap();
+    let rows: Vec<&str> = rows.lines().collect();
+    assert_eq!(
+        rows[1],
+        "x\ty\t3\t5\t4\t0.800000\tNA\t0.400000\t1.0000\t1.0000"
+    );
+    assert_eq!(
+        rows[2],
+        "x\tz\t4.5\t5\t1\t0.200000\t0.232616\t0.100000\t1.0000\t1.0000"
+    );
+    assert_eq!(
+        fs::read_to_string(&phylip).unwrap(),
+        "3\n\
+         x          0.000000 -1.000000 0.232616\n\
+         y          -1.000000 0.000000 -1.000000\n\
+         z          0.232616 -1.000000 0.000000\n"
+    );
+}
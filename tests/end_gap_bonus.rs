@@ -0,0 +1,68 @@
+mod common;
+
+const SEQ_A: &str = "CATGGTACGG";
+const SEQ_B: &str = "TGACGAAG";
+
+/// Align the two sequences globally with gaps 4 1 4 1 and `flags`
+fn run(flags: &[&str]) -> std::process::Output {
+    let dir = common::scratch_dir("end-gap-bonus");
+    let input = common::write_file(
+        &dir,
+        "input.txt",
+        &common::dna_input(SEQ_A, SEQ_B, 0, "4 1 4 1", 2.0, -1.0),
+    );
+    let mut args = vec!["--format", "tsv", "align"];
+    args.extend_from_slice(flags);
+    args.extend([input.as_str(), "-"]);
+    common::align(&args)
+}
+
+/// The score and CIGAR string of the first alignment
+fn optimum(flags: &[&str]) -> (String, String) {
+    let output = run(flags);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let row: Vec<&str> = stdout
+        .lines()
+        .find(|line| line.starts_with("1\t"))
+        .unwrap()
+        .split('\t')
+        .collect();
+    (row[1].to_string(), row[6].to_string())
+}
+
+#[test]
+fn a_bonus_charges_the_free_ends_of_the_default_alignment() {
+    // Free: the last two residues of B hang over for nothing
+    let free = ("4".to_string(), "2M2D4M".to_string());
+    assert_eq!(optimum(&[]), free);
+    assert_eq!(optimum(&["--end-gap-bonus", "0"]), free);
+    // A bonus of 2 charges each end gap as an internal one less 2, which the charged
+    // optimum of -3 gains on its single leading end gap
+    assert_eq!(
+        optimum(&["--end-gap-bonus", "2"]),
+        ("-1".to_string(), "2M2D3M2I1M".to_string())
+    );
+    // A bonus of the whole open penalty leaves the ends charged by extension alone
+    assert_eq!(
+        optimum(&["--end-gap-bonus", "4"]),
+        ("2".to_string(), "2M2D4M2I".to_string())
+    );
+}
+
+#[test]
+fn a_negative_or_oversized_bonus_is_rejected() {
+    for (bonus, message) in [
+        ("-1", "end-gap bonus -1 must not be negative"),
+        ("5", "end-gap bonus 5 exceeds the open penalty 4"),
+    ] {
+        let output = run(&["--end-gap-bonus", bonus]);
+        assert_eq!(output.status.code(), Some(2));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(message), "{}", stderr);
+    }
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use std::path::Path;
+use std::process::Output;
+
+fn dna_file(dir: &Path) -> String {
+    common::write_file(
+        dir,
+        "input.txt",
+        &common::dna_input("ACGTTACGTA", "ACGACGA", 0, "2 1 2 1", 2.0, -1.0),
+    )
+}
+
+/// Check that `output` exited with `code`, kept stdout clean and said `message` on stderr
+fn assert_failed(output: &Output, code: i32, message: &str) {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(code), "{}", stderr);
+    assert!(stderr.contains(message), "{}", stderr);
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn an_unknown_flag_is_a_usage_error() {
+    let dir = common::scratch_dir("exit-usage");
+    let output = common::align(&["align", "--bogus", &dna_file(&dir), "out.txt"]);
+    assert_failed(&output, 1, "unexpected argument '--bogus'");
+}
+
+#[test]
+fn conflicting_flags_are_a_usage_error() {
+    let dir = common::scratch_dir("exit-conflict");
+    let output = common::align(&["align", "--global", "--local", &dna_file(&dir), "out.txt"]);
+    assert_failed(&output, 1, "cannot be used with");
+}
+
+#[test]
+fn a_truncated_input_file_is_an_input_error() {
+    let dir = common::scratch_dir("exit-truncated");
+    let input = common::write_file(&dir, "input.txt", "ACGT\n");
+    let output_file = dir.join("out.txt").to_string_lossy().into_owned();
+    let output = common::align(&["align", &input, &output_file]);
+    assert_failed(&output, 2, "Missing sequence B");
+}
+
+#[test]
+fn a_missing_input_file_is_an_input_error() {
+    let dir = common::scratch_dir("exit-missing");
+    let input = dir.join("missing.txt").to_string_lossy().into_owned();
+    let output_file = dir.join("out.txt").to_string_lossy().into_owned();
+    let output = common::align(&["align", &input, &output_file]);
+    assert_failed(&output, 2, "No such file or directory");
+}
+
+#[test]
+fn a_grid_over_the_memory_limit_is_a_limit_error() {
+    let dir = common::scratch_dir("exit-limit");
+    let output_file = dir.join("out.txt").to_string_lossy().into_owned();
+    let output = common::align(&["align", "--max-memory", "10", &dna_file(&dir), &output_file]);
+    assert_failed(&output, 3, "over --max-memory of 10 bytes");
+}
+
+#[test]
+fn no_alignment_passing_the_evalue_threshold_is_no_hits() {
+    let dir = common::scratch_dir("exit-no-hits");
+    let output_file = dir.join("out.txt").to_string_lossy().into_owned();
+    let output = common::align(&[
+        "align",
+        "--local",
+        "--max-evalue",
+        "1e-30",
+        "--lambda",
+        "1.0",
+        "--karlin-k",
+        "0.1",
+        &dna_file(&dir),
+        &output_file,
+    ]);
+    assert_failed(&output, 4, "no hits passed --max-evalue");
+}
+
+#[test]
+fn quiet_success_writes_nothing_to_stdout_or_stderr() {
+    let dir = common::scratch_dir("exit-quiet");
+    let output_file = dir.join("out.txt").to_string_lossy().into_owned();
+    let output = common::align_ok(&["-q", "align", "--local", &dna_file(&dir), &output_file]);
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+}
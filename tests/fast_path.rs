@@ -0,0 +1,71 @@
+mod common;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use sequence_alignment::alignment::traceback;
+use sequence_alignment::fast_path::trivial_alignment;
+use sequence_alignment::generate::{
+    generate_pair, generated_parameters, AlphabetKind, MutationRates,
+};
+use sequence_alignment::models::{AlignGrid, TokenFormat};
+
+const RATES: MutationRates = MutationRates {
+    identity: 0.9,
+    indel_rate: 0.0,
+};
+
+/// Check the fast path against the full DP for `seq_a` and `seq_b`, returning whether it
+/// applied
+fn agrees_with_the_dp(kind: AlphabetKind, seq_a: &str, seq_b: &str, global: bool) -> bool {
+    let parameters = generated_parameters(kind, seq_a.to_string(), seq_b.to_string(), global);
+    let token_format = TokenFormat::default();
+    let Some(fast) = trivial_alignment(&parameters, &token_format) else {
+        return false;
+    };
+    let mut grid = AlignGrid::new(parameters.len_a(), parameters.len_b());
+    grid.populate_score_matrices(&parameters).unwrap();
+    common::assert_consistent(&grid, &parameters);
+    let full = traceback(&grid, &parameters, &token_format).unwrap();
+    assert_eq!(fast, full, "{} against {}, global {}", seq_a, seq_b, global);
+    true
+}
+
+#[test]
+fn identical_sequences_align_as_the_dp_aligns_them() {
+    let mut applied = 0;
+    for seed in 0..100 {
+        for kind in [AlphabetKind::Dna, AlphabetKind::Protein] {
+            let (seq, _) = generate_pair(kind, 1 + seed as usize % 70, RATES, seed);
+            for global in [true, false] {
+                applied += usize::from(agrees_with_the_dp(kind, &seq, &seq, global));
+            }
+        }
+    }
+    assert_eq!(applied, 400);
+}
+
+#[test]
+fn a_prefix_aligns_as_the_dp_aligns_it() {
+    let mut rng = ChaCha8Rng::seed_from_u64(421);
+    let mut applied = 0;
+    for seed in 0..200 {
+        let kind = if seed % 2 == 0 {
+            AlphabetKind::Dna
+        } else {
+            AlphabetKind::Protein
+        };
+        let (longer, _) = generate_pair(kind, rng.random_range(2..80), RATES, seed);
+        let prefix = &longer[..rng.random_range(1..longer.len())];
+        for global in [true, false] {
+            applied += usize::from(agrees_with_the_dp(kind, prefix, &longer, global));
+            applied += usize::from(agrees_with_the_dp(kind, &longer, prefix, global));
+        }
+    }
+    // Only global alignments of a prefix that occurs nowhere else in the longer sequence
+    // take the shortcut
+    assert!(
+        applied > 300,
+        "the fast path applied to only {} pairs",
+        applied
+    );
+}
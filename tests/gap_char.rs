@@ -0,0 +1,68 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+/// An input file whose alphabet and sequences contain `_` as a residue
+fn underscore_input(dir: &Path) -> String {
+    let residues = "ACGT_";
+    let mut contents = format!("AC_GT\nAC_T\n0\n2 1 2 1\n5\n{0}\n5\n{0}\n", residues);
+    for (i, a) in residues.chars().enumerate() {
+        for (j, b) in residues.chars().enumerate() {
+            let score = if i == j { 2 } else { -1 };
+            contents += &format!("{} {} {} {} {}\n", i + 1, j + 1, a, b, score);
+        }
+    }
+    common::write_file(dir, "input.txt", &contents)
+}
+
+#[test]
+fn a_residue_colliding_with_the_default_gap_is_an_error() {
+    let dir = common::scratch_dir("gap-char-collision");
+    let output_file = dir.join("out.txt").to_string_lossy().into_owned();
+    let output = common::align(&["align", &underscore_input(&dir), &output_file]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "gap symbol '_' is also a residue of sequence A; choose another gap symbol with --gap-char"
+    ));
+}
+
+#[test]
+fn another_gap_char_aligns_and_writes_gaps_with_it() {
+    let dir = common::scratch_dir("gap-char-dash");
+    let output_file = dir.join("out.txt").to_string_lossy().into_owned();
+    common::align_ok(&[
+        "align",
+        "--gap-char",
+        "-",
+        &underscore_input(&dir),
+        &output_file,
+    ]);
+    assert_eq!(
+        fs::read_to_string(&output_file).unwrap(),
+        "6\n\nAC_GT\nAC_-T\n"
+    );
+
+    // Reading the result back needs the same gap
+    let stats = common::align_ok(&["stats", "--gap", "-", &output_file]);
+    assert!(String::from_utf8_lossy(&stats.stdout).contains("\n1\t5\t4\t0.8000\t1\t5\t4\n"));
+}
+
+#[test]
+fn json_output_gives_gaps_through_the_cigar() {
+    let dir = common::scratch_dir("gap-char-json");
+    let output_file = dir.join("out.json").to_string_lossy().into_owned();
+    common::align_ok(&[
+        "--format",
+        "json",
+        "align",
+        "--gap-char",
+        "-",
+        &underscore_input(&dir),
+        &output_file,
+    ]);
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&output_file).unwrap()).unwrap();
+    // The `_` of both sequences is a match, the `G` of A a deletion
+    assert_eq!(json["alignments"][0]["cigar"], "3M1D1M");
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use sequence_alignment::alignment::traceback;
+use sequence_alignment::io::parameters::AlignmentParameters;
+use sequence_alignment::models::{AlignGrid, TokenFormat};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// A local input file scoring 1 for identical residues of A and T and 0 otherwise
+fn match_only_input(dir: &Path, seq_a: &str, seq_b: &str) -> String {
+    common::write_file(
+        dir,
+        "input.txt",
+        &format!(
+            "{}\n{}\n1\n2 1 2 1\n2\nAT\n2\nAT\n1 1 A A 1\n1 2 A T 0\n2 1 T A 0\n2 2 T T 1\n",
+            seq_a, seq_b
+        ),
+    )
+}
+
+#[test]
+fn sequences_sharing_no_residue_have_no_local_alignment() {
+    let dir = common::scratch_dir("local-zero");
+    let parameters =
+        AlignmentParameters::<f64>::load_from_file(&match_only_input(&dir, "AAAA", "TTTT"))
+            .unwrap();
+    let mut grid = AlignGrid::new(parameters.len_a(), parameters.len_b());
+    grid.populate_score_matrices(&parameters).unwrap();
+    common::assert_consistent(&grid, &parameters);
+    let result = traceback(&grid, &parameters, &TokenFormat::default()).unwrap();
+    assert_eq!(result.score, 0.0);
+    assert!(result.alignments.is_empty());
+}
+
+#[test]
+fn a_large_zero_scoring_pair_completes_quickly_with_no_alignments() {
+    let dir = common::scratch_dir("local-zero-large");
+    let input = match_only_input(&dir, &"A".repeat(1000), &"T".repeat(1000));
+    let output_file = dir.join("out.txt").to_string_lossy().into_owned();
+
+    let start = Instant::now();
+    let output = common::align_ok(&["align", &input, &output_file]);
+    // Enumerating the million zero-scoring cells as starts would take far longer
+    assert!(start.elapsed() < Duration::from_secs(20));
+
+    assert_eq!(fs::read_to_string(&output_file).unwrap(), "0\n");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("no local alignment found: no pair of residues scores above zero"));
+}
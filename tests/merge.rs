@@ -0,0 +1,153 @@
+mod common;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use sequence_alignment::merge::ReadRecord;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+const READ_LENGTH: usize = 100;
+const PAIRS: usize = 40;
+
+/// A read pair sequenced from both ends of a fragment, and the overlap of its mates
+struct Pair {
+    fragment: String,
+    read_1: ReadRecord,
+    read_2: ReadRecord,
+    overlap: usize,
+}
+
+/// A fragment of 110 to 190 bases read from both ends, so that its mates overlap by 10
+/// to 90 bases. The first read gets a low-quality error inside the overlap, which the
+/// merge must correct from the second.
+fn synthetic_pair(rng: &mut ChaCha8Rng, i: usize) -> Pair {
+    let length = rng.random_range(READ_LENGTH + 10..2 * READ_LENGTH - 10);
+    let fragment: String = (0..length)
+        .map(|_| ['A', 'C', 'G', 'T'][rng.random_range(0..4)])
+        .collect();
+    let overlap = 2 * READ_LENGTH - length;
+
+    let mut bases_1: Vec<char> = fragment[..READ_LENGTH].chars().collect();
+    let mut quality_1 = vec![40; READ_LENGTH];
+    let error = rng.random_range(READ_LENGTH - overlap + 3..READ_LENGTH - 3);
+    bases_1[error] = if bases_1[error] == 'A' { 'C' } else { 'A' };
+    quality_1[error] = 2;
+    let read_1 = ReadRecord {
+        id: format!("pair{}", i),
+        sequence: bases_1.into_iter().collect(),
+        quality: Some(quality_1),
+    };
+    let read_2 = ReadRecord {
+        id: format!("pair{}", i),
+        sequence: fragment[length - READ_LENGTH..].to_string(),
+        quality: Some(vec![30; READ_LENGTH]),
+    }
+    .reverse_complement();
+    Pair {
+        fragment,
+        read_1,
+        read_2,
+        overlap,
+    }
+}
+
+fn fastq(reads: impl Iterator<Item = ReadRecord>) -> String {
+    reads
+        .map(|read| {
+            let quality: String = read
+                .quality
+                .unwrap()
+                .iter()
+                .map(|&q| char::from(q + 33))
+                .collect();
+            format!("@{}\n{}\n+\n{}\n", read.id, read.sequence, quality)
+        })
+        .collect()
+}
+
+/// The parameter file and the FASTQ file of each mate
+fn write_inputs(dir: &Path, pairs: &[Pair]) -> [String; 3] {
+    [
+        common::write_file(
+            dir,
+            "params.txt",
+            &common::dna_input("A", "A", 0, "5 2 5 2", 1.0, -2.0),
+        ),
+        common::write_file(
+            dir,
+            "reads_1.fq",
+            &fastq(pairs.iter().map(|pair| pair.read_1.clone())),
+        ),
+        common::write_file(
+            dir,
+            "reads_2.fq",
+            &fastq(pairs.iter().map(|pair| pair.read_2.clone())),
+        ),
+    ]
+}
+
+#[test]
+fn synthetic_pairs_merge_back_into_their_fragments() {
+    let dir = common::scratch_dir("merge");
+    let mut rng = ChaCha8Rng::seed_from_u64(419);
+    let pairs: Vec<Pair> = (0..PAIRS).map(|i| synthetic_pair(&mut rng, i)).collect();
+    let [params, reads_1, reads_2] = write_inputs(&dir, &pairs);
+    let merged = dir.join("merged.fq").to_string_lossy().into_owned();
+    common::align_ok(&["merge", &params, &reads_1, &reads_2, &merged]);
+
+    // `@<id> overlap=<n> identity=<f>`, sequence, `+`, qualities
+    let output = fs::read_to_string(&merged).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    let records: HashMap<&str, (&str, &str)> = lines
+        .chunks(4)
+        .map(|record| {
+            let (id, description) = record[0][1..].split_once(' ').unwrap();
+            (id, (description, record[1]))
+        })
+        .collect();
+    assert_eq!(records.len(), PAIRS);
+    for pair in &pairs {
+        let (description, sequence) = records[pair.read_1.id.as_str()];
+        assert_eq!(sequence, pair.fragment, "{}", pair.read_1.id);
+        assert!(
+            description.starts_with(&format!("overlap={} ", pair.overlap)),
+            "{}: {} for an overlap of {}",
+            pair.read_1.id,
+            description,
+            pair.overlap
+        );
+    }
+}
+
+#[test]
+fn pairs_below_the_overlap_criteria_are_not_merged() {
+    let dir = common::scratch_dir("merge-criteria");
+    let mut rng = ChaCha8Rng::seed_from_u64(420);
+    let pairs: Vec<Pair> = (0..PAIRS).map(|i| synthetic_pair(&mut rng, i)).collect();
+    let [params, reads_1, reads_2] = write_inputs(&dir, &pairs);
+    let merged = dir.join("merged.fq").to_string_lossy().into_owned();
+    common::align_ok(&[
+        "merge",
+        "--min-overlap",
+        "50",
+        &params,
+        &reads_1,
+        &reads_2,
+        &merged,
+    ]);
+
+    let output = fs::read_to_string(&merged).unwrap();
+    let merged_ids: Vec<&str> = output
+        .lines()
+        .step_by(4)
+        .map(|header| header[1..].split(' ').next().unwrap())
+        .collect();
+    let expected: Vec<&str> = pairs
+        .iter()
+        .filter(|pair| pair.overlap >= 50)
+        .map(|pair| pair.read_1.id.as_str())
+        .collect();
+    assert!(!expected.is_empty() && expected.len() < PAIRS);
+    assert_eq!(merged_ids, expected);
+}
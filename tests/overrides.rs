@@ -0,0 +1,118 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+/// Align `input` with `flags`, returning the score line and the `# parameters:` line
+fn run(dir: &Path, input: &str, flags: &[&str]) -> (String, String) {
+    let output_file = dir.join("out.txt").to_string_lossy().into_owned();
+    let mut args = vec!["-v", "align"];
+    args.extend_from_slice(flags);
+    args.extend([input, output_file.as_str()]);
+    let output = common::align_ok(&args);
+
+    let result = fs::read_to_string(&output_file).unwrap();
+    let score = result
+        .lines()
+        .find(|line| !line.starts_with('#'))
+        .unwrap_or_default()
+        .to_string();
+    let parameters = String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .find(|line| line.starts_with("# parameters:"))
+        .unwrap()
+        .to_string();
+    (score, parameters)
+}
+
+fn dna_file(dir: &Path, alignment_type: u8) -> String {
+    common::write_file(
+        dir,
+        &format!("input{}.txt", alignment_type),
+        &common::dna_input(
+            "ACGTTACGTA",
+            "ACGACGA",
+            alignment_type,
+            "2 1 2 1",
+            2.0,
+            -1.0,
+        ),
+    )
+}
+
+#[test]
+fn without_flags_the_input_file_values_apply() {
+    let dir = common::scratch_dir("overrides-none");
+    let (score, parameters) = run(&dir, &dna_file(&dir, 0), &[]);
+    assert_eq!(score, "9");
+    assert!(parameters.starts_with("# parameters: global alignment"));
+    assert!(parameters.ends_with("gaps dx=2 ex=1 dy=2 ey=1"));
+}
+
+#[test]
+fn alignment_type_flags_take_precedence_over_the_file() {
+    let dir = common::scratch_dir("overrides-type");
+    let (_, parameters) = run(&dir, &dna_file(&dir, 0), &["--local"]);
+    assert!(parameters.starts_with("# parameters: local alignment"));
+    let (_, parameters) = run(&dir, &dna_file(&dir, 1), &["--global"]);
+    assert!(parameters.starts_with("# parameters: global alignment"));
+}
+
+#[test]
+fn gap_flags_take_precedence_over_the_file() {
+    let dir = common::scratch_dir("overrides-gaps");
+    let input = dna_file(&dir, 0);
+
+    let (score, parameters) = run(&dir, &input, &["--gap-open", "0.5", "--gap-extend", "0.5"]);
+    assert_eq!(score, "12.5");
+    assert!(parameters.ends_with("gaps dx=0.5 ex=0.5 dy=0.5 ey=0.5"));
+
+    // A flag for one penalty keeps the file's value for the others
+    let (_, parameters) = run(&dir, &input, &["--gap-open-a", "9"]);
+    assert!(parameters.ends_with("gaps dx=9 ex=1 dy=2 ey=1"));
+}
+
+#[test]
+fn a_builtin_matrix_takes_precedence_over_the_file_scores() {
+    let dir = common::scratch_dir("overrides-matrix");
+    let input = common::write_file(
+        &dir,
+        "protein.txt",
+        "WCW\nWCW\n0\n2 1 2 1\n2\nCW\n2\nCW\n1 1 C C 1\n1 2 C W -1\n2 1 W C -1\n2 2 W W 1\n",
+    );
+    assert_eq!(run(&dir, &input, &[]).0, "3");
+    // BLOSUM62 scores W against W 11 and C against C 9
+    assert_eq!(run(&dir, &input, &["--matrix", "blosum62"]).0, "31");
+}
+
+#[test]
+fn one_file_gives_a_different_result_under_each_override() {
+    let dir = common::scratch_dir("overrides-sweep");
+    let input = dna_file(&dir, 0);
+    let output_file = dir.join("out.txt").to_string_lossy().into_owned();
+    let body = |flags: &[&str]| {
+        let mut args = vec!["align"];
+        args.extend_from_slice(flags);
+        args.extend([input.as_str(), output_file.as_str()]);
+        common::align_ok(&args);
+        let result = fs::read_to_string(&output_file).unwrap();
+        result
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let runs = [
+        (vec![], "9\n\nACGTTACGTA\nACG__ACG_A"),
+        (
+            vec!["--local"],
+            "9\n\nACGTTACG\nACG__ACG\n\nACGTTACGTA\nACG__ACG_A",
+        ),
+        (vec!["--gaps", "0,1,0,1"], "13\n\nACGTTACGTA\nACG__ACG_A"),
+        (vec!["--gaps", "5,2,5,2"], "5\n\nACGT\nACGA"),
+    ];
+    for (flags, expected) in runs {
+        assert_eq!(body(&flags), expected, "{:?}", flags);
+    }
+}
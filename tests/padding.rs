@@ -0,0 +1,93 @@
+mod common;
+
+use sequence_alignment::io::parameters::AlignmentParameters;
+use sequence_alignment::models::{AlignGrid, Sequences};
+
+#[test]
+fn pad_to_multiple_keeps_the_unpadded_lengths() {
+    let sequences = Sequences::from_string("ACGTA".to_string(), "AC".to_string());
+    let padded = sequences.pad_to_multiple(4, 'N').unwrap();
+    assert_eq!(padded.seq_a.iter().collect::<String>(), "ACGTANNN");
+    assert_eq!(padded.seq_b.iter().collect::<String>(), "ACNN");
+    assert_eq!((padded.orig_len_a(), padded.orig_len_b()), (5, 2));
+    assert!(padded.is_padded());
+    assert!(padded.is_padding(5, 0) && padded.is_padding(0, 2));
+    assert!(!padded.is_padding(4, 1));
+
+    let unpadded = sequences.pad_to_multiple(1, 'N').unwrap();
+    assert_eq!((unpadded.len_a(), unpadded.len_b()), (5, 2));
+    assert!(!unpadded.is_padded());
+}
+
+#[test]
+fn a_padded_grid_is_consistent() {
+    let dir = common::scratch_dir("padding-grid");
+    for alignment_type in [0, 1] {
+        let input = common::write_file(
+            &dir,
+            "input.txt",
+            &common::dna_input("GGACGT", "ACGTCC", alignment_type, "4 1 4 1", 2.0, -1.0),
+        );
+        let mut parameters = AlignmentParameters::<f64>::load_from_file(&input).unwrap();
+        parameters.sequences = parameters.sequences.pad_to_multiple(4, 'N').unwrap();
+        let mut grid = AlignGrid::new(parameters.len_a(), parameters.len_b());
+        grid.populate_score_matrices(&parameters).unwrap();
+        common::assert_consistent(&grid, &parameters);
+    }
+}
+
+/// Standard output of aligning `input` with `flags`
+fn stdout(input: &str, flags: &[&str]) -> String {
+    let mut args = vec!["align"];
+    args.extend_from_slice(flags);
+    args.extend([input, "-"]);
+    String::from_utf8(common::align_ok(&args).stdout).unwrap()
+}
+
+#[test]
+fn padding_leaves_every_grid_with_the_same_results() {
+    let dir = common::scratch_dir("padding-cli");
+    // The best local alignment ends on the last residue of A, where a padded row below
+    // would repeat its score
+    let input = common::write_file(
+        &dir,
+        "input.txt",
+        &common::dna_input("GGACGT", "ACGTCC", 0, "4 1 4 1", 2.0, -1.0),
+    );
+    let runs: [&[&str]; 13] = [
+        &[],
+        &["--local"],
+        &["--local", "--top-hits", "3"],
+        &["--local", "--count-paths"],
+        &["--count-paths"],
+        &["--log-space"],
+        &["--score-only"],
+        &["--score-only", "--local"],
+        &["--codon-gaps", "4,4,1"],
+        &["--max-gap-length", "1"],
+        &["--semiglobal"],
+        &["--terminal-gaps-a", "1,0.5"],
+        &["--score-type", "i32", "--local"],
+    ];
+    for flags in runs {
+        let expected = stdout(&input, flags);
+        for n in ["4", "5"] {
+            let mut padded = flags.to_vec();
+            padded.extend(["--pad-to", n]);
+            assert_eq!(stdout(&input, &padded), expected, "{:?}", padded);
+        }
+    }
+}
+
+#[test]
+fn a_local_start_in_the_padding_is_not_counted() {
+    let dir = common::scratch_dir("padding-starts");
+    let input = common::write_file(
+        &dir,
+        "input.txt",
+        &common::dna_input("GGACGT", "ACGTCC", 1, "4 1 4 1", 2.0, -1.0),
+    );
+    let output = stdout(&input, &["--pad-to", "4", "--check-consistency"]);
+    assert!(!output.contains("# starts:"), "{}", output);
+    assert_eq!(output, "8\n\nACGT\nACGT\n");
+}
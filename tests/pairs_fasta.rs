@@ -0,0 +1,87 @@
+mod common;
+
+use sequence_alignment::models::Sequences;
+use std::fs;
+use std::path::Path;
+
+fn fixture(name: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn params(dir: &Path) -> String {
+    common::write_file(
+        dir,
+        "params.txt",
+        &common::dna_input("A", "A", 0, "3 1 3 1", 1.0, -1.0),
+    )
+}
+
+#[test]
+fn wrapped_lines_blank_lines_and_crlf_endings_parse_as_the_plain_records() {
+    let contents = fs::read_to_string(fixture("pairs_mixed.fa")).unwrap();
+    assert!(contents.contains("\r\n\r\n"));
+    let pairs = Sequences::from_fasta_str(&contents).unwrap();
+
+    let rows = |sequences: &Sequences<char>| {
+        (
+            sequences.seq_a.iter().collect::<String>(),
+            sequences.seq_b.iter().collect::<String>(),
+        )
+    };
+    assert_eq!(pairs.len(), 2);
+    assert_eq!(rows(&pairs[0]), ("ACGTACGTAC".into(), "ACGTACGTAC".into()));
+    assert_eq!(
+        rows(&pairs[1]),
+        ("TTGCATTGCATT".into(), "TTGCATTGCATT".into())
+    );
+}
+
+#[test]
+fn pairs_fasta_aligns_each_pair_of_the_mixed_fixture_under_its_record_ids() {
+    let dir = common::scratch_dir("pairs-fasta-mixed");
+    let output = dir.join("pairs.tsv").to_string_lossy().into_owned();
+    common::align_ok(&[
+        "batch",
+        "--pairs-fasta",
+        &params(&dir),
+        &fixture("pairs_mixed.fa"),
+        &output,
+    ]);
+
+    let rows: Vec<Vec<String>> = fs::read_to_string(&output)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .map(|row| row.split('\t').take(3).map(str::to_string).collect())
+        .collect();
+    assert_eq!(
+        rows,
+        [["first_a", "first_b", "10"], ["second_a", "second_b", "12"]]
+    );
+}
+
+#[test]
+fn an_odd_record_count_names_the_record_without_a_partner() {
+    let dir = common::scratch_dir("pairs-fasta-odd");
+    let output = dir.join("pairs.tsv").to_string_lossy().into_owned();
+    let run = common::align(&[
+        "batch",
+        "--pairs-fasta",
+        &params(&dir),
+        &fixture("pairs_odd.fa"),
+        &output,
+    ]);
+    assert_eq!(run.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&run.stderr).contains("orphan"));
+
+    // The complete pair is still written
+    let written = fs::read_to_string(&output).unwrap();
+    assert_eq!(
+        written.lines().nth(1).unwrap().split('\t').nth(2),
+        Some("4")
+    );
+}
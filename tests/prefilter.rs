@@ -0,0 +1,91 @@
+mod common;
+
+use sequence_alignment::generate::{
+    generate_pair, generated_parameters, AlphabetKind, MutationRates,
+};
+use sequence_alignment::kmer::KmerFilter;
+use sequence_alignment::score_only::optimal_score;
+use std::fs;
+
+const STRONG: MutationRates = MutationRates {
+    identity: 0.8,
+    indel_rate: 0.02,
+};
+
+/// Generated pairs of `kind` at 80% identity whose local alignment scores at least a
+/// third of A against itself
+fn strong_pairs(kind: AlphabetKind, length: usize) -> Vec<(String, String)> {
+    let pairs: Vec<(String, String)> = (0..200)
+        .map(|seed| generate_pair(kind, length, STRONG, seed))
+        .filter(|(seq_a, seq_b)| {
+            let score = |seq_b: &str| {
+                optimal_score(&generated_parameters(
+                    kind,
+                    seq_a.clone(),
+                    seq_b.to_string(),
+                    false,
+                ))
+            };
+            score(seq_b) >= score(seq_a) / 3.0
+        })
+        .collect();
+    assert!(pairs.len() > 190, "only {} strong pairs", pairs.len());
+    pairs
+}
+
+#[test]
+fn strongly_aligned_nucleotide_pairs_pass_the_default_filter() {
+    let filter = KmerFilter::new(true);
+    for (seq_a, seq_b) in strong_pairs(AlphabetKind::Dna, 150) {
+        assert!(filter.passes(&filter.kmers(&seq_a), &filter.kmers(&seq_b)));
+    }
+}
+
+#[test]
+fn strongly_aligned_protein_pairs_pass_the_default_filter() {
+    let filter = KmerFilter::new(false);
+    for (seq_a, seq_b) in strong_pairs(AlphabetKind::Protein, 80) {
+        assert!(filter.passes(&filter.kmers(&seq_a), &filter.kmers(&seq_b)));
+    }
+}
+
+#[test]
+fn conservative_protein_substitutions_still_share_kmers() {
+    let filter = KmerFilter::new(false);
+    // L/I, K/R, E/D and S/T are in the same groups of the reduced alphabet
+    let (a, b) = (filter.kmers("MLKESW"), filter.kmers("MIRDTW"));
+    assert_eq!(a.shared(&b), a.len());
+}
+
+#[test]
+fn batch_prefilter_skips_only_the_unrelated_pairs() {
+    let dir = common::scratch_dir("prefilter");
+    let (seq_a, seq_b) = generate_pair(AlphabetKind::Dna, 120, STRONG, 3);
+    let (unrelated, _) = generate_pair(AlphabetKind::Dna, 120, STRONG, 4);
+    let fasta = format!(">a\n{}\n>b\n{}\n>u\n{}\n", seq_a, seq_b, unrelated);
+    let fasta = common::write_file(&dir, "records.fa", &fasta);
+    let input = common::write_file(
+        &dir,
+        "local.txt",
+        &common::dna_input("A", "A", 1, "3 1 3 1", 1.0, -1.0),
+    );
+    let pairs = dir.join("pairs.tsv").to_string_lossy().into_owned();
+
+    let output = common::align_ok(&["batch", "--prefilter", &input, &fasta, &pairs]);
+
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("prefilter: skipped 2 of 3 pairs sharing fewer than 1 8-mers"));
+    let rows = fs::read_to_string(&pairs).unwrap();
+    let scores: Vec<(&str, &str, &str)> = rows
+        .lines()
+        .skip(1)
+        .map(|row| {
+            let fields: Vec<&str> = row.split('\t').collect();
+            (fields[0], fields[1], fields[2])
+        })
+        .collect();
+    assert_eq!(scores.len(), 3);
+    assert!(scores[0].0 == "a" && scores[0].1 == "b" && scores[0].2 != "NA");
+    assert_eq!(scores[1], ("a", "u", "NA"));
+    assert_eq!(scores[2], ("b", "u", "NA"));
+}
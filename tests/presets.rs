@@ -0,0 +1,36 @@
+mod common;
+
+const SEQ_A: &str = "ACGTACGTACGTACGTACGTACGT";
+const SEQ_B: &str = "ACGTACGTACGTACGTACGTACGA";
+const SUGGESTION: &str = "the sequences look like nucleotide; --preset dna";
+
+fn suggests_a_preset(input: &str, flags: &[&str]) -> bool {
+    let mut args = vec!["align"];
+    args.extend_from_slice(flags);
+    args.extend([input, "-"]);
+    let output = common::align_ok(&args);
+    String::from_utf8_lossy(&output.stderr).contains(SUGGESTION)
+}
+
+#[test]
+fn a_preset_is_suggested_only_when_nothing_scores_the_residues() {
+    let dir = common::scratch_dir("presets");
+    let unscored = common::write_file(
+        &dir,
+        "unscored.txt",
+        &format!("{}\n{}\n0\n3 1 3 1\n4\nACGT\n4\nACGT\n", SEQ_A, SEQ_B),
+    );
+    let scored = common::write_file(
+        &dir,
+        "scored.txt",
+        &common::dna_input(SEQ_A, SEQ_B, 0, "3 1 3 1", 2.0, -1.0),
+    );
+
+    assert!(suggests_a_preset(&unscored, &[]));
+    assert!(!suggests_a_preset(&scored, &[]));
+    assert!(!suggests_a_preset(
+        &unscored,
+        &["--match", "2", "--mismatch", "-1"]
+    ));
+    assert!(!suggests_a_preset(&unscored, &["--preset", "dna"]));
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use sequence_alignment::alignment::traceback;
+use sequence_alignment::generate::{
+    generate_pair, generated_parameters, AlphabetKind, MutationRates,
+};
+use sequence_alignment::io::parameters::AlignmentParameters;
+use sequence_alignment::models::{AlignGrid, EndGaps, TokenFormat};
+use std::fs;
+use std::path::Path;
+
+/// An input file over ACGTN where each residue scores differently against itself
+fn input(dir: &Path, seq_a: &str, seq_b: &str) -> String {
+    let residues = "ACGTN";
+    let matched = [5.0, 3.0, 2.0, 4.0, 1.0];
+    let mut contents = format!(
+        "{}\n{}\n0\n3 1 3 1\n5\n{}\n5\n{}\n",
+        seq_a, seq_b, residues, residues
+    );
+    for (i, a) in residues.chars().enumerate() {
+        for (j, b) in residues.chars().enumerate() {
+            let score = if i == j { matched[i] } else { -2.0 };
+            contents += &format!("{} {} {} {} {}\n", i + 1, j + 1, a, b, score);
+        }
+    }
+    common::write_file(dir, "input.txt", &contents)
+}
+
+#[test]
+fn a_query_within_a_longer_subject_aligns_with_no_gap_penalty() {
+    let dir = common::scratch_dir("semi-global");
+    let mut parameters =
+        AlignmentParameters::<f64>::load_from_file(&input(&dir, "ATCG", "NNNATCGNN")).unwrap();
+    parameters.set_semi_global();
+    let mut grid = AlignGrid::new(parameters.len_a(), parameters.len_b());
+    grid.populate_semi_global_free_b_end(&parameters).unwrap();
+    let result = traceback(&grid, &parameters, &TokenFormat::default()).unwrap();
+
+    // The sum of the diagonal scores of A, T, C and G
+    assert_eq!(result.score, 14.0);
+    assert_eq!(result.alignments.len(), 1);
+    let alignment = &result.alignments[0];
+    assert_eq!(
+        (alignment.align_a.as_str(), alignment.align_b.as_str()),
+        ("ATCG", "ATCG")
+    );
+    assert_eq!((alignment.start_a, alignment.start_b), (0, 3));
+}
+
+#[test]
+fn populating_without_semi_global_parameters_is_an_error() {
+    let dir = common::scratch_dir("semi-global-unset");
+    let parameters =
+        AlignmentParameters::<f64>::load_from_file(&input(&dir, "ATCG", "NNNATCGNN")).unwrap();
+    let mut grid = AlignGrid::new(parameters.len_a(), parameters.len_b());
+    assert!(grid.populate_semi_global_free_b_end(&parameters).is_err());
+}
+
+#[test]
+fn the_semiglobal_flag_places_the_query_within_the_subject() {
+    let dir = common::scratch_dir("semi-global-cli");
+    let output_file = dir.join("out.txt").to_string_lossy().into_owned();
+    common::align_ok(&[
+        "align",
+        "--semiglobal",
+        &input(&dir, "ATCG", "NNNATCGNN"),
+        &output_file,
+    ]);
+    let result = fs::read_to_string(&output_file).unwrap();
+    let lines: Vec<&str> = result
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect();
+    assert_eq!(lines, ["14", "", "ATCG", "ATCG"]);
+}
+
+#[test]
+fn the_specialized_fill_gives_the_grid_of_the_generic_one() {
+    let rates = MutationRates {
+        identity: 0.8,
+        indel_rate: 0.1,
+    };
+    for seed in 0..100 {
+        let (seq_a, seq_b) = generate_pair(AlphabetKind::Dna, 1 + seed as usize % 30, rates, seed);
+        // A query against a longer subject, and the other way around
+        let subject = format!(
+            "{}{}{}",
+            &seq_b[seq_b.len() / 2..],
+            seq_b,
+            &seq_a[..seq_a.len() / 2]
+        );
+        for (query, subject) in [(&seq_a, &subject), (&subject, &seq_a)] {
+            let mut parameters =
+                generated_parameters(AlphabetKind::Dna, query.clone(), subject.clone(), true);
+            parameters.set_semi_global();
+            if seed % 2 == 1 {
+                let terminal = EndGaps::Terminal {
+                    open: 1.0,
+                    extend: 0.5,
+                };
+                parameters.gap_penalties = parameters
+                    .gap_penalties
+                    .with_end_gaps(terminal, EndGaps::Free);
+            }
+            let mut generic = AlignGrid::new(parameters.len_a(), parameters.len_b());
+            generic.populate_score_matrices(&parameters).unwrap();
+            let mut specialized = AlignGrid::new(parameters.len_a(), parameters.len_b());
+            specialized
+                .populate_semi_global_free_b_end(&parameters)
+                .unwrap();
+            common::assert_consistent(&generic, &parameters);
+            common::assert_consistent(&specialized, &parameters);
+
+            assert_eq!(
+                specialized.m_scores(),
+                generic.m_scores(),
+                "{} against {}",
+                query,
+                subject
+            );
+            assert_eq!(specialized.ix_scores(), generic.ix_scores());
+            assert_eq!(specialized.iy_scores(), generic.iy_scores());
+            assert_eq!(
+                traceback(&specialized, &parameters, &TokenFormat::default()).unwrap(),
+                traceback(&generic, &parameters, &TokenFormat::default()).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use std::path::Path;
+
+/// A local alignment with five co-optimal hits, which the traceback finds in a different
+/// order from the canonical one
+fn input(dir: &Path) -> String {
+    let scores = [
+        [2.0, -1.0, 0.0, -0.5],
+        [-0.5, 2.0, 0.0, 0.0],
+        [-2.0, -0.5, 3.0, -0.5],
+        [0.0, -0.5, 0.0, 3.0],
+    ];
+    let mut contents = "CCCCC\nCACGA\n1\n3 1 1.5 0.5\n4\nACGT\n4\nACGT\n".to_string();
+    for (i, a) in "ACGT".chars().enumerate() {
+        for (j, b) in "ACGT".chars().enumerate() {
+            contents += &format!("{} {} {} {} {}\n", i + 1, j + 1, a, b, scores[i][j]);
+        }
+    }
+    common::write_file(dir, "input.txt", &contents)
+}
+
+/// The `start=` and `end=` fields of each FASTA header, in order
+fn ranges(fasta: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(fasta)
+        .lines()
+        .filter(|line| line.starts_with('>'))
+        .map(|line| line.split_once(' ').unwrap().1.to_string())
+        .collect()
+}
+
+#[test]
+fn fasta_output_comes_in_canonical_order_unless_streamed() {
+    let dir = common::scratch_dir("streaming-order");
+    let input = input(&dir);
+
+    let collected = common::align_ok(&["--format", "fasta", "align", &input, "-"]);
+    let streamed = common::align_ok(&["--format", "fasta", "align", "--stream", &input, "-"]);
+    let (collected, streamed) = (ranges(&collected.stdout), ranges(&streamed.stdout));
+
+    // Sorted by the start in A, then the start in B, then the CIGAR string
+    let starts_a = |ranges: &[String]| {
+        ranges
+            .iter()
+            .step_by(2)
+            .map(|range| range.split(' ').nth(1).unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        starts_a(&collected),
+        ["start=1", "start=1", "start=2", "start=2", "start=3"]
+    );
+    assert_ne!(collected, streamed);
+
+    let (mut collected, mut streamed) = (collected, streamed);
+    collected.sort();
+    streamed.sort();
+    assert_eq!(collected, streamed);
+}
+
+#[test]
+fn streaming_needs_fasta_output() {
+    let dir = common::scratch_dir("streaming-format");
+    let output = common::align(&["align", "--stream", &input(&dir), "-"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--stream needs --format fasta"));
+}
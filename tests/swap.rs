@@ -0,0 +1,126 @@
+mod common;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use sequence_alignment::alignment::traceback;
+use sequence_alignment::io::parameters::{AlignmentMode, AlignmentParameters};
+use sequence_alignment::models::{AlignGrid, EndGaps, TokenFormat};
+
+const RESIDUES: [char; 4] = ['A', 'C', 'G', 'T'];
+
+/// A parameter file with random sequences, different gap penalties for each sequence and
+/// a match matrix that need not be symmetric
+fn random_input(rng: &mut ChaCha8Rng) -> String {
+    let sequence = |rng: &mut ChaCha8Rng| -> String {
+        (0..rng.random_range(1..12))
+            .map(|_| RESIDUES[rng.random_range(0..RESIDUES.len())])
+            .collect()
+    };
+    let (seq_a, seq_b) = (sequence(rng), sequence(rng));
+    let gaps: Vec<i32> = (0..4).map(|_| rng.random_range(0..5)).collect();
+    let mut input = format!(
+        "{}\n{}\n0\n{} {} {} {}\n4\nACGT\n4\nACGT\n",
+        seq_a, seq_b, gaps[0], gaps[1], gaps[2], gaps[3]
+    );
+    for (i, a) in RESIDUES.iter().enumerate() {
+        for (j, b) in RESIDUES.iter().enumerate() {
+            let score = if i == j {
+                rng.random_range(1..6)
+            } else {
+                rng.random_range(-4..1)
+            };
+            input += &format!("{} {} {} {} {}\n", i + 1, j + 1, a, b, score);
+        }
+    }
+    input
+}
+
+/// Score and `(start_a, start_b, CIGAR)` of every alignment, sorted. The CIGAR strings
+/// leave out soft clips, which only say how free ends of A are written: swapping free
+/// ends of B alone gives free ends of A alone, which are written as soft clips.
+fn align(parameters: &AlignmentParameters<f64>) -> (f64, Vec<(usize, usize, String)>) {
+    let mut grid = AlignGrid::new(parameters.len_a(), parameters.len_b());
+    grid.populate_score_matrices(parameters).unwrap();
+    let result = traceback(&grid, parameters, &TokenFormat::default()).unwrap();
+    let mut alignments: Vec<(usize, usize, String)> = result
+        .alignments
+        .iter()
+        .map(|alignment| {
+            let mut alignment = alignment.alignment().clone();
+            alignment.soft_clip_a = (0, 0);
+            (alignment.start_a, alignment.start_b, alignment.cigar())
+        })
+        .collect();
+    alignments.sort();
+    (result.score, alignments)
+}
+
+/// The alignments of the swapped sequences, seen from the original ones: starts
+/// exchanged and insertions read as deletions and the other way around
+fn mirrored(alignments: Vec<(usize, usize, String)>) -> Vec<(usize, usize, String)> {
+    let mut mirrored: Vec<(usize, usize, String)> = alignments
+        .into_iter()
+        .map(|(start_a, start_b, cigar)| {
+            let cigar = cigar
+                .chars()
+                .map(|op| match op {
+                    'I' => 'D',
+                    'D' => 'I',
+                    op => op,
+                })
+                .collect();
+            (start_b, start_a, cigar)
+        })
+        .collect();
+    mirrored.sort();
+    mirrored
+}
+
+fn assert_commutes(parameters: &AlignmentParameters<f64>, input: &str, mode: &str) {
+    let (score, alignments) = align(parameters);
+    let (swapped_score, swapped) = align(&parameters.swap_sequences());
+    assert_eq!(swapped_score, score, "{} under {}", input, mode);
+    assert_eq!(mirrored(swapped), alignments, "{} under {}", input, mode);
+}
+
+#[test]
+fn swapping_the_sequences_mirrors_every_alignment() {
+    let dir = common::scratch_dir("swap");
+    let mut rng = ChaCha8Rng::seed_from_u64(416);
+    for _ in 0..300 {
+        let input = random_input(&mut rng);
+        let path = common::write_file(&dir, "input.txt", &input);
+        let loaded = AlignmentParameters::<f64>::load_from_file(&path).unwrap();
+        for mode in [AlignmentMode::Global, AlignmentMode::Local] {
+            let mut parameters = loaded.clone();
+            parameters.set_mode(mode);
+            assert_commutes(&parameters, &input, &format!("{:?}", mode));
+        }
+
+        let ends = EndGaps::Terminal {
+            open: 1.0,
+            extend: 0.5,
+        };
+        for (a_ends, b_ends, mode) in [
+            (EndGaps::Charged, EndGaps::Charged, "charged ends"),
+            (ends, EndGaps::Free, "terminal ends of A"),
+            (EndGaps::Charged, ends, "charged ends of A, terminal of B"),
+        ] {
+            let mut parameters = loaded.clone();
+            parameters.gap_penalties = parameters.gap_penalties.with_end_gaps(a_ends, b_ends);
+            assert_commutes(&parameters, &input, mode);
+        }
+    }
+}
+
+#[test]
+fn swapping_twice_gives_the_original_parameters() {
+    let dir = common::scratch_dir("swap-twice");
+    let mut rng = ChaCha8Rng::seed_from_u64(417);
+    let path = common::write_file(&dir, "input.txt", &random_input(&mut rng));
+    let parameters = AlignmentParameters::<f64>::load_from_file(&path).unwrap();
+    let twice = parameters.swap_sequences().swap_sequences();
+    assert_eq!(twice.sequences.seq_a, parameters.sequences.seq_a);
+    assert_eq!(twice.sequences.seq_b, parameters.sequences.seq_b);
+    assert_eq!(align(&twice), align(&parameters));
+}
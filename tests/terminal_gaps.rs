@@ -0,0 +1,80 @@
+mod common;
+
+use sequence_alignment::alignment::traceback;
+use sequence_alignment::io::parameters::AlignmentParameters;
+use sequence_alignment::models::{AlignGrid, EndGaps, TokenFormat};
+
+const SEQ_A: &str = "CATGGTACGG";
+const SEQ_B: &str = "TGACGAAG";
+
+/// The score and CIGAR strings of the global alignments for `gaps`, a gap line of the
+/// input file
+fn optimum(gaps: &str) -> (String, Vec<String>) {
+    let dir = common::scratch_dir("terminal-gaps");
+    let input = common::write_file(
+        &dir,
+        "input.txt",
+        &common::dna_input(SEQ_A, SEQ_B, 0, gaps, 2.0, -1.0),
+    );
+    let output = common::align_ok(&["--format", "tsv", "align", &input, "-"]);
+    let rows: Vec<Vec<String>> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .skip(1)
+        .map(|row| row.split('\t').map(str::to_string).collect())
+        .collect();
+    (
+        rows[0][1].clone(),
+        rows.iter().map(|row| row[6].clone()).collect(),
+    )
+}
+
+#[test]
+fn free_cheap_and_full_terminal_gaps_each_pick_a_different_optimum() {
+    // Free: the last two residues of B hang over for nothing
+    assert_eq!(
+        optimum("4 1 4 1"),
+        ("4".to_string(), vec!["2M2D4M".to_string()])
+    );
+    // Cheap: the overhang is kept but now written and charged as a terminal gap
+    assert_eq!(
+        optimum("4 1 4 1 1 0.5 1 0.5"),
+        ("1".to_string(), vec!["2M2D4M2I".to_string()])
+    );
+    // Full: a gap that costs as much at the end as inside moves to pair the last residues
+    assert_eq!(
+        optimum("4 1 4 1 4 1 4 1"),
+        ("-3".to_string(), vec!["2M2D3M2I1M".to_string()])
+    );
+}
+
+#[test]
+fn charged_ends_score_as_terminal_penalties_equal_to_the_internal_ones() {
+    let dir = common::scratch_dir("terminal-gaps-charged");
+    let input = common::write_file(
+        &dir,
+        "input.txt",
+        &common::dna_input(SEQ_A, SEQ_B, 0, "4 1 4 1", 2.0, -1.0),
+    );
+    let loaded = AlignmentParameters::<f64>::load_from_file(&input).unwrap();
+    let align = |ends: EndGaps<f64>| {
+        let mut parameters = loaded.clone();
+        parameters.gap_penalties = parameters.gap_penalties.with_end_gaps(ends, ends);
+        let mut grid = AlignGrid::new(parameters.len_a(), parameters.len_b());
+        grid.populate_score_matrices(&parameters).unwrap();
+        let result = traceback(&grid, &parameters, &TokenFormat::default()).unwrap();
+        (result.score, result.alignments[0].cigar())
+    };
+
+    assert_eq!(align(EndGaps::Free), (4.0, "2M2D4M".to_string()));
+    let cheap = EndGaps::Terminal {
+        open: 1.0,
+        extend: 0.5,
+    };
+    assert_eq!(align(cheap), (1.0, "2M2D4M2I".to_string()));
+    let full = EndGaps::Terminal {
+        open: 4.0,
+        extend: 1.0,
+    };
+    assert_eq!(align(EndGaps::Charged), align(full));
+    assert_eq!(align(EndGaps::Charged), (-3.0, "2M2D3M2I1M".to_string()));
+}
@@ -0,0 +1,22 @@
+use std::fs;
+
+/// Pieces of the format string that logs each cell update of the fill, which no other
+/// code writes
+const TRACE_FORMAT_PIECES: [&str; 2] = [" (eps ", "; pointers "];
+
+#[test]
+fn the_binary_holds_the_fill_logging_only_with_the_trace_feature() {
+    let binary = fs::read(env!("CARGO_BIN_EXE_align")).unwrap();
+    for piece in TRACE_FORMAT_PIECES {
+        let found = binary
+            .windows(piece.len())
+            .any(|window| window == piece.as_bytes());
+        assert_eq!(
+            found,
+            cfg!(feature = "trace"),
+            "{:?} in the binary, trace feature {}",
+            piece,
+            cfg!(feature = "trace")
+        );
+    }
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+use unicode_width::UnicodeWidthStr;
+
+/// An input file over `residues`, scoring 2 for identical residues and -1 otherwise
+fn input(dir: &Path, seq_a: &str, seq_b: &str, gaps: &str, residues: &str) -> String {
+    let count = residues.chars().count();
+    let mut contents = format!(
+        "{}\n{}\n0\n{}\n{}\n{}\n{}\n{}\n",
+        seq_a, seq_b, gaps, count, residues, count, residues
+    );
+    for (i, a) in residues.chars().enumerate() {
+        for (j, b) in residues.chars().enumerate() {
+            let score = if i == j { 2 } else { -1 };
+            contents += &format!("{} {} {} {} {}\n", i + 1, j + 1, a, b, score);
+        }
+    }
+    common::write_file(dir, "input.txt", &contents)
+}
+
+/// The prefix before the row, the row and the end position of a line of interleaved
+/// output such as `A             4 δαβ 6`
+fn split_line(line: &str) -> (&str, &str, &str) {
+    let digits = line.find(|c: char| c.is_ascii_digit()).unwrap();
+    let row_start = digits + line[digits..].find(' ').unwrap() + 1;
+    let row_end = line.rfind(' ').unwrap();
+    (
+        &line[..row_start],
+        &line[row_start..row_end],
+        &line[row_end + 1..],
+    )
+}
+
+/// The rows of each block of interleaved output, checking that the rows of a block
+/// start and end at the same display column
+fn interleaved_rows(output: &str) -> Vec<(String, String)> {
+    let lines: Vec<&str> = output
+        .lines()
+        .filter(|line| line.starts_with("A ") || line.starts_with("B "))
+        .collect();
+    lines
+        .chunks(2)
+        .map(|block| {
+            let (prefix_a, row_a, _) = split_line(block[0]);
+            let (prefix_b, row_b, _) = split_line(block[1]);
+            assert_eq!(prefix_a.width(), prefix_b.width(), "{:?}", block);
+            assert_eq!(row_a.width(), row_b.width(), "{:?}", block);
+            (row_a.to_string(), row_b.to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn greek_residues_stay_in_columns_across_interleaved_blocks() {
+    let dir = common::scratch_dir("unicode-greek");
+    let input = input(&dir, "αβγδαβγ", "αγγδβγ", "2 1 2 1", "αβγδ");
+    let output_file = dir.join("out.txt").to_string_lossy().into_owned();
+    common::align_ok(&[
+        "--format",
+        "interleaved",
+        "align",
+        "--width",
+        "3",
+        &input,
+        &output_file,
+    ]);
+    let output = fs::read_to_string(&output_file).unwrap();
+    assert!(output.contains("# Alignment 1: 4M1D2M\n"));
+    assert_eq!(
+        interleaved_rows(&output),
+        [("αβγ", "αγγ"), ("δαβ", "δ_β"), ("γ", "γ")].map(|(a, b)| (a.to_string(), b.to_string()))
+    );
+    let ends: Vec<&str> = output
+        .lines()
+        .filter(|line| line.starts_with("A ") || line.starts_with("B "))
+        .map(|line| split_line(line).2)
+        .collect();
+    assert_eq!(ends, ["3", "3", "6", "5", "7", "6"]);
+}
+
+#[test]
+fn a_gap_opposite_a_double_width_residue_is_padded() {
+    let dir = common::scratch_dir("unicode-wide");
+    let input = input(&dir, "中文中", "中中", "0.5 0.5 0.5 0.5", "中文");
+    let output_file = dir.join("out.txt").to_string_lossy().into_owned();
+    common::align_ok(&["--format", "interleaved", "align", &input, &output_file]);
+    let output = fs::read_to_string(&output_file).unwrap();
+    assert_eq!(
+        interleaved_rows(&output),
+        [("中文中".to_string(), "中_ 中".to_string())]
+    );
+}
+
+#[test]
+fn greek_residues_and_gap_go_through_every_format() {
+    let dir = common::scratch_dir("unicode-formats");
+    let input = input(&dir, "αβγδαβγ", "αγγδβγ", "2 1 2 1", "αβγδ");
+    for format in ["text", "fasta", "tsv", "json", "interleaved", "diffs"] {
+        let output_file = dir
+            .join(format!("out.{}", format))
+            .to_string_lossy()
+            .into_owned();
+        common::align_ok(&[
+            "--format",
+            format,
+            "align",
+            "--gap-char",
+            "ω",
+            &input,
+            &output_file,
+        ]);
+        let output = fs::read_to_string(&output_file).unwrap();
+        if format != "diffs" {
+            assert!(output.contains("δωβ"), "{}: {}", format, output);
+        }
+    }
+    // Positions count characters, not bytes
+    let diffs = fs::read_to_string(dir.join("out.diffs")).unwrap();
+    assert!(diffs.contains("A 1-7, B 1-6, 4M1D2M"), "{}", diffs);
+}
@@ -0,0 +1,103 @@
+use sequence_alignment::models::{Alignment, GAP_CHAR};
+use sequence_alignment::variants::{variants_from_alignment, write_vcf, Variant, VariantType};
+
+/// Variants of the alignment of `row_a` and `row_b`, which cover all of A
+fn variants(row_a: &str, row_b: &str) -> Vec<Variant> {
+    let seq_a: Vec<char> = row_a.chars().filter(|&c| c != GAP_CHAR).collect();
+    let alignment = Alignment::from_rows(row_a.to_string(), row_b.to_string(), GAP_CHAR).unwrap();
+    variants_from_alignment(&alignment, &seq_a)
+}
+
+fn variant(pos: usize, ref_allele: &str, alt_allele: &str, variant_type: VariantType) -> Variant {
+    Variant {
+        pos,
+        ref_allele: ref_allele.to_string(),
+        alt_allele: alt_allele.to_string(),
+        variant_type,
+    }
+}
+
+#[test]
+fn mismatches_are_snvs_at_one_based_positions() {
+    assert_eq!(
+        variants("ACGTA", "AGGTC"),
+        vec![
+            variant(2, "C", "G", VariantType::Snv),
+            variant(5, "A", "C", VariantType::Snv),
+        ]
+    );
+}
+
+#[test]
+fn an_insertion_is_anchored_on_the_preceding_base() {
+    assert_eq!(
+        variants("AC_GT", "ACTGT"),
+        vec![variant(2, "C", "CT", VariantType::Insertion)]
+    );
+}
+
+#[test]
+fn adjacent_gap_columns_make_one_deletion() {
+    assert_eq!(
+        variants("ACGTTA", "AC__TA"),
+        vec![variant(2, "CGT", "C", VariantType::Deletion)]
+    );
+}
+
+#[test]
+fn a_deletion_at_the_start_of_a_is_anchored_on_the_following_base() {
+    assert_eq!(
+        variants("ACG", "_CG"),
+        vec![variant(1, "AC", "C", VariantType::Deletion)]
+    );
+}
+
+#[test]
+fn a_deletion_in_a_homopolymer_is_shifted_to_its_left_end() {
+    let expected = vec![variant(1, "GA", "G", VariantType::Deletion)];
+    // Wherever the aligner put the gap within the run of A, the record is the same
+    for row_b in ["G_AAT", "GA_AT", "GAA_T"] {
+        assert_eq!(variants("GAAAT", row_b), expected, "{}", row_b);
+    }
+}
+
+#[test]
+fn an_insertion_in_a_homopolymer_is_shifted_to_its_left_end() {
+    let expected = vec![variant(1, "G", "GA", VariantType::Insertion)];
+    for (row_a, row_b) in [("G_AAT", "GAAAT"), ("GA_AT", "GAAAT"), ("GAA_T", "GAAAT")] {
+        assert_eq!(variants(row_a, row_b), expected, "{}", row_a);
+    }
+}
+
+#[test]
+fn a_deletion_in_a_dinucleotide_repeat_shifts_by_whole_repeats() {
+    assert_eq!(
+        variants("TCACAG", "TCA__G"),
+        vec![variant(1, "TCA", "T", VariantType::Deletion)]
+    );
+}
+
+#[test]
+fn normalization_stops_at_the_previous_record() {
+    // The SNV at position 2 keeps the deletion from moving over it
+    assert_eq!(
+        variants("GAAAT", "GC_AT"),
+        vec![
+            variant(2, "A", "C", VariantType::Snv),
+            variant(2, "AA", "A", VariantType::Deletion),
+        ]
+    );
+}
+
+#[test]
+fn vcf_records_follow_the_header() {
+    let mut out = Vec::new();
+    write_vcf(&mut out, "ref", 5, "query", &variants("GAAAT", "GAA_T")).unwrap();
+    let vcf = String::from_utf8(out).unwrap();
+    assert!(vcf.starts_with("##fileformat=VCFv4.2\n"));
+    assert!(vcf.contains("##contig=<ID=ref,length=5>\n"));
+    assert!(vcf.ends_with(
+        "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tquery\n\
+         ref\t1\t.\tGA\tG\t.\tPASS\tTYPE=DEL\tGT\t1\n"
+    ));
+}
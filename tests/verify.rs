@@ -0,0 +1,102 @@
+mod common;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use sequence_alignment::alignment::traceback;
+use sequence_alignment::io::parameters::{AlignmentMode, AlignmentParameters};
+use sequence_alignment::models::{AlignGrid, EndGaps, TokenFormat};
+use sequence_alignment::verify::verify;
+
+const RESIDUES: [char; 4] = ['A', 'C', 'G', 'T'];
+
+/// A parameter file with random sequences, gap penalties and match scores
+fn random_input(rng: &mut ChaCha8Rng) -> String {
+    let sequence = |rng: &mut ChaCha8Rng| -> String {
+        (0..rng.random_range(1..15))
+            .map(|_| RESIDUES[rng.random_range(0..RESIDUES.len())])
+            .collect()
+    };
+    let (seq_a, seq_b) = (sequence(rng), sequence(rng));
+    let gaps: Vec<i32> = (0..4).map(|_| rng.random_range(0..5)).collect();
+    let (matched, mismatched) = (rng.random_range(1..6), rng.random_range(-4..1));
+    format!(
+        "{}\n{}\n0\n{} {} {} {}\n4\nACGT\n4\nACGT\n",
+        seq_a, seq_b, gaps[0], gaps[1], gaps[2], gaps[3]
+    ) + &RESIDUES
+        .iter()
+        .enumerate()
+        .flat_map(|(i, a)| {
+            RESIDUES.iter().enumerate().map(move |(j, b)| {
+                let score = if i == j { matched } else { mismatched };
+                format!("{} {} {} {} {}\n", i + 1, j + 1, a, b, score)
+            })
+        })
+        .collect::<String>()
+}
+
+/// Check every alignment of `parameters` rescores to the reported score
+fn assert_verifies(parameters: &AlignmentParameters<f64>, input: &str, mode: &str) {
+    let mut grid = AlignGrid::new(parameters.len_a(), parameters.len_b());
+    grid.populate_score_matrices(parameters).unwrap();
+    common::assert_consistent(&grid, parameters);
+    let result = traceback(&grid, parameters, &TokenFormat::default()).unwrap();
+    let discrepancies = verify(&result, parameters);
+    assert!(
+        discrepancies.is_empty(),
+        "{} under {}: {:?}",
+        input,
+        mode,
+        discrepancies
+    );
+}
+
+#[test]
+fn every_reported_alignment_rescores_to_its_score() {
+    let dir = common::scratch_dir("verify");
+    let mut rng = ChaCha8Rng::seed_from_u64(375);
+    for _ in 0..300 {
+        let input = random_input(&mut rng);
+        let path = common::write_file(&dir, "input.txt", &input);
+        let loaded = AlignmentParameters::<f64>::load_from_file(&path).unwrap();
+        for mode in [
+            AlignmentMode::Global,
+            AlignmentMode::Local,
+            AlignmentMode::SemiGlobal,
+            AlignmentMode::SoftClipA,
+        ] {
+            let mut parameters = loaded.clone();
+            parameters.set_mode(mode);
+            assert_verifies(&parameters, &input, &format!("{:?}", mode));
+        }
+
+        let mut charged = loaded.clone();
+        charged.gap_penalties = charged
+            .gap_penalties
+            .with_end_gaps(EndGaps::Charged, EndGaps::Charged);
+        assert_verifies(&charged, &input, "charged ends");
+
+        let mut terminal = loaded.clone();
+        let ends = EndGaps::Terminal {
+            open: 1.0,
+            extend: 0.5,
+        };
+        terminal.gap_penalties = terminal.gap_penalties.with_end_gaps(ends, ends);
+        assert_verifies(&terminal, &input, "terminal gaps");
+    }
+}
+
+#[test]
+fn the_verify_flag_passes_on_the_default_output() {
+    let dir = common::scratch_dir("verify-cli");
+    let mut rng = ChaCha8Rng::seed_from_u64(376);
+    let output_file = dir.join("out.txt").to_string_lossy().into_owned();
+    for _ in 0..30 {
+        let input = common::write_file(&dir, "input.txt", &random_input(&mut rng));
+        for mode in [None, Some("--local"), Some("--semiglobal")] {
+            let mut args = vec!["align", "--verify"];
+            args.extend(mode);
+            args.extend([input.as_str(), output_file.as_str()]);
+            common::align_ok(&args);
+        }
+    }
+}